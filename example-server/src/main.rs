@@ -13,6 +13,8 @@ async fn main() {
         .merge(modules::users::router())
         .merge(modules::products::router())
         .merge(modules::orders::router())
+        // Every event logged while handling a request carries its request_id
+        .layer(axum::middleware::from_fn(tracing_web_console::request_id))
         .merge(TracingLayer::new("/tracing").into_router());
 
     println!("🚀 Server starting on http://localhost:3000");
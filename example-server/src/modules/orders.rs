@@ -311,12 +311,8 @@ async fn fraud_detection_monitor() {
 #[tracing::instrument(name = "list_orders", skip(store))]
 async fn list_orders(State(store): State<OrderStore>) -> impl IntoResponse {
     let start = Instant::now();
-    let request_id = Uuid::new_v4();
 
-    tracing::info!(
-        request_id = %request_id,
-        "Processing list orders request"
-    );
+    tracing::info!("Processing list orders request");
 
     let orders = store.read();
     let count = orders.len();
@@ -330,7 +326,6 @@ async fn list_orders(State(store): State<OrderStore>) -> impl IntoResponse {
             });
 
     tracing::debug!(
-        request_id = %request_id,
         order_count = %count,
         status_summary = ?status_summary,
         query_duration_us = %start.elapsed().as_micros(),
@@ -340,7 +335,7 @@ async fn list_orders(State(store): State<OrderStore>) -> impl IntoResponse {
     (StatusCode::OK, Json(orders.clone()))
 }
 
-#[tracing::instrument(name = "create_order", skip(store), fields(request_id = %Uuid::new_v4()))]
+#[tracing::instrument(name = "create_order", skip(store))]
 async fn create_order(
     State(store): State<OrderStore>,
     Json(req): Json<CreateOrderRequest>,
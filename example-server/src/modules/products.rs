@@ -221,10 +221,8 @@ async fn list_products(
     State(store): State<ProductStore>,
 ) -> impl IntoResponse {
     let start = Instant::now();
-    let request_id = Uuid::new_v4();
 
     tracing::debug!(
-        request_id = %request_id,
         search = ?query.search,
         min_price = ?query.min_price,
         max_price = ?query.max_price,
@@ -250,7 +248,6 @@ async fn list_products(
                     .any(|t| t.to_lowercase().contains(&search_term.to_lowercase()))
         });
         tracing::trace!(
-            request_id = %request_id,
             filter = "search",
             search_term = %search_term,
             before_count = %before,
@@ -264,7 +261,6 @@ async fn list_products(
         let before = results.len();
         results.retain(|p| p.category.to_lowercase() == category.to_lowercase());
         tracing::trace!(
-            request_id = %request_id,
             filter = "category",
             category = %category,
             before_count = %before,
@@ -278,7 +274,6 @@ async fn list_products(
         let before = results.len();
         results.retain(|p| p.price >= min);
         tracing::trace!(
-            request_id = %request_id,
             filter = "min_price",
             min_price = %min,
             before_count = %before,
@@ -291,7 +286,6 @@ async fn list_products(
         let before = results.len();
         results.retain(|p| p.price <= max);
         tracing::trace!(
-            request_id = %request_id,
             filter = "max_price",
             max_price = %max,
             before_count = %before,
@@ -305,7 +299,6 @@ async fn list_products(
         let before = results.len();
         results.retain(|p| p.stock > 0);
         tracing::trace!(
-            request_id = %request_id,
             filter = "in_stock",
             before_count = %before,
             after_count = %results.len(),
@@ -316,7 +309,6 @@ async fn list_products(
     let query_duration = start.elapsed();
 
     tracing::info!(
-        request_id = %request_id,
         initial_count = %initial_count,
         result_count = %results.len(),
         filters_applied = %(query.search.is_some() as u8 + query.category.is_some() as u8 + query.min_price.is_some() as u8 + query.max_price.is_some() as u8 + query.in_stock.is_some() as u8),
@@ -333,10 +325,8 @@ async fn create_product(
     Json(req): Json<CreateProductRequest>,
 ) -> Response {
     let start = Instant::now();
-    let request_id = Uuid::new_v4();
 
     tracing::debug!(
-        request_id = %request_id,
         name = %req.name,
         price = %req.price,
         stock = %req.stock,
@@ -348,7 +338,6 @@ async fn create_product(
     // Validation
     if req.name.is_empty() {
         tracing::warn!(
-            request_id = %request_id,
             field = "name",
             rule = "required",
             "Product validation failed: empty name"
@@ -366,7 +355,6 @@ async fn create_product(
 
     if req.price <= 0.0 {
         tracing::warn!(
-            request_id = %request_id,
             field = "price",
             value = %req.price,
             rule = "positive",
@@ -385,7 +373,6 @@ async fn create_product(
 
     if req.stock < 0 {
         tracing::warn!(
-            request_id = %request_id,
             field = "stock",
             value = %req.stock,
             rule = "non_negative",
@@ -428,7 +415,6 @@ async fn create_product(
     };
 
     tracing::info!(
-        request_id = %request_id,
         product_id = %product_id,
         sku = %sku,
         name = %req.name,
@@ -464,10 +450,8 @@ async fn update_product(
     Json(req): Json<UpdateProductRequest>,
 ) -> Response {
     let start = Instant::now();
-    let request_id = Uuid::new_v4();
 
     tracing::debug!(
-        request_id = %request_id,
         product_id = %id,
         updates = ?req,
         "Processing product update"
@@ -481,7 +465,6 @@ async fn update_product(
 
             if let Some(new_name) = &req.name {
                 tracing::trace!(
-                    request_id = %request_id,
                     field = "name",
                     old_value = %product.name,
                     new_value = %new_name,
@@ -499,7 +482,6 @@ async fn update_product(
             if let Some(new_price) = req.price {
                 let price_change_pct = ((new_price - product.price) / product.price * 100.0).abs();
                 tracing::trace!(
-                    request_id = %request_id,
                     field = "price",
                     old_value = %product.price,
                     new_value = %new_price,
@@ -508,7 +490,6 @@ async fn update_product(
                 );
                 if price_change_pct > 20.0 {
                     tracing::warn!(
-                        request_id = %request_id,
                         product_id = %id,
                         old_price = %product.price,
                         new_price = %new_price,
@@ -523,7 +504,6 @@ async fn update_product(
             if let Some(new_stock) = req.stock {
                 let stock_delta = new_stock - product.stock;
                 tracing::trace!(
-                    request_id = %request_id,
                     field = "stock",
                     old_value = %product.stock,
                     new_value = %new_stock,
@@ -545,7 +525,6 @@ async fn update_product(
             }
 
             tracing::info!(
-                request_id = %request_id,
                 product_id = %id,
                 sku = %product.sku,
                 fields_updated = ?changes,
@@ -558,7 +537,6 @@ async fn update_product(
         }
         None => {
             tracing::warn!(
-                request_id = %request_id,
                 product_id = %id,
                 "Product not found for update"
             );
@@ -156,12 +156,8 @@ async fn user_activity_simulator(store: UserStore) {
 #[tracing::instrument(name = "list_users", skip(store))]
 async fn list_users(State(store): State<UserStore>) -> impl IntoResponse {
     let start = Instant::now();
-    let request_id = Uuid::new_v4();
 
-    tracing::info!(
-        request_id = %request_id,
-        "Processing list users request"
-    );
+    tracing::info!("Processing list users request");
 
     let users = store.read();
     let count = users.len();
@@ -175,7 +171,6 @@ async fn list_users(State(store): State<UserStore>) -> impl IntoResponse {
             });
 
     tracing::debug!(
-        request_id = %request_id,
         total_users = %count,
         roles = ?roles_breakdown,
         query_duration_us = %start.elapsed().as_micros(),
@@ -185,7 +180,7 @@ async fn list_users(State(store): State<UserStore>) -> impl IntoResponse {
     (StatusCode::OK, Json(users.clone()))
 }
 
-#[tracing::instrument(name = "create_user", skip(store), fields(request_id = %Uuid::new_v4()))]
+#[tracing::instrument(name = "create_user", skip(store))]
 async fn create_user(
     State(store): State<UserStore>,
     Json(req): Json<CreateUserRequest>,
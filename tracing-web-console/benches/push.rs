@@ -0,0 +1,59 @@
+//! Capture throughput with several threads logging at once
+//!
+//! Run with `cargo bench --bench push`. Each round logs the same number of
+//! events per thread into a fresh console and reports events per second,
+//! with combined writes and with every event taking the write lock itself.
+
+use std::time::{Duration, Instant};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_web_console::TracingLayer;
+
+const EVENTS_PER_THREAD: usize = 200_000;
+const THREADS: &[usize] = &[1, 2, 4, 8, 16];
+
+fn round(threads: usize, combined: bool) -> Duration {
+    let (capture, _console) = TracingLayer::builder("/tracing")
+        .with_capacity(10_000)
+        .with_combined_writes(combined)
+        .build_layer();
+    let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(capture));
+
+    let started = Instant::now();
+    std::thread::scope(|scope| {
+        for thread in 0..threads {
+            let dispatch = dispatch.clone();
+            scope.spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    for i in 0..EVENTS_PER_THREAD {
+                        tracing::info!(target: "bench", thread, i, "request handled");
+                    }
+                });
+            });
+        }
+    });
+    started.elapsed()
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start runtime");
+    let _runtime = runtime.enter();
+
+    // Warm up allocations and the callsite cache
+    round(1, true);
+    println!(
+        "{:>8} {:>14} {:>14} {:>8}",
+        "threads", "combined/s", "single/s", "speedup"
+    );
+    for &threads in THREADS {
+        let events = (threads * EVENTS_PER_THREAD) as f64;
+        let combined = events / round(threads, true).as_secs_f64();
+        let single = events / round(threads, false).as_secs_f64();
+        println!(
+            "{:>8} {:>14.0} {:>14.0} {:>7.2}x",
+            threads,
+            combined,
+            single,
+            combined / single
+        );
+    }
+}
@@ -1,8 +1,17 @@
+use base64::Engine;
+use sha2::{Digest, Sha384};
 use std::fs;
 use std::path::Path;
 use std::process::Command;
 
 fn main() {
+    build_frontend();
+
+    // Always generate the manifest, it is include!d by src/frontend.rs
+    write_integrity_manifest(&Path::new("frontend").join("dist"));
+}
+
+fn build_frontend() {
     let frontend_dir = Path::new("frontend");
     let dist_dir = frontend_dir.join("dist");
 
@@ -87,6 +96,61 @@ fn main() {
     }
 }
 
+/// Write subresource-integrity hashes (SHA-384) of all embedded JS/CSS files
+/// to `$OUT_DIR/asset_integrity.rs`
+fn write_integrity_manifest(dist_dir: &Path) {
+    let mut entries = Vec::new();
+    collect_integrity(dist_dir, dist_dir, &mut entries);
+    entries.sort();
+
+    let mut code = String::from("/// SRI hashes of embedded assets, generated by build.rs\n");
+    code.push_str("pub static ASSET_INTEGRITY: &[(&str, &str)] = &[\n");
+    for (path, hash) in &entries {
+        code.push_str(&format!("    ({:?}, {:?}),\n", path, hash));
+    }
+    code.push_str("];\n");
+
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    fs::write(Path::new(&out_dir).join("asset_integrity.rs"), code)
+        .expect("failed to write asset integrity manifest");
+}
+
+fn collect_integrity(root: &Path, dir: &Path, entries: &mut Vec<(String, String)>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_integrity(root, &path, entries);
+            continue;
+        }
+
+        let is_subresource = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("js") | Some("mjs") | Some("css")
+        );
+        if !is_subresource {
+            continue;
+        }
+
+        if let (Ok(contents), Ok(relative)) = (fs::read(&path), path.strip_prefix(root)) {
+            let digest = Sha384::digest(&contents);
+            let hash = format!(
+                "sha384-{}",
+                base64::engine::general_purpose::STANDARD.encode(digest)
+            );
+            let relative = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            entries.push((relative, hash));
+        }
+    }
+}
+
 fn track_directory(dir: &str) {
     let path = Path::new(dir);
     if !path.exists() {
@@ -9,6 +9,9 @@ import {
   DEFAULT_PRESET,
   loadPresets,
   generateDefaultPresetName,
+  generateCopyName,
+  fetchServerPresets,
+  syncServerPresets,
 } from '@/lib/presets';
 
 export type { FilterPreset };
@@ -36,7 +39,23 @@ export function FilterPresets({
   const [editName, setEditName] = useState(``);
   const isDefaultActive = activePresetId === DEFAULT_PRESET.id || activePresetId === null;
 
+  // Presets shared on the server replace the locally cached ones. Presets
+  // saved before the server kept any are uploaded instead.
+  useEffect(() => {
+    fetchServerPresets().then((shared) => {
+      if (!shared) return;
+      const local = loadPresets();
+      if (shared.length === 0 && local.length > 0) {
+        void syncServerPresets([], local);
+        return;
+      }
+      setPresets(shared);
+      localStorage.setItem(STORAGE_KEY, JSON.stringify(shared));
+    });
+  }, []);
+
   const savePresets = (newPresets: FilterPreset[]) => {
+    void syncServerPresets(presets, newPresets);
     setPresets(newPresets);
     localStorage.setItem(STORAGE_KEY, JSON.stringify(newPresets));
   };
@@ -82,9 +101,10 @@ export function FilterPresets({
   }, [currentFilters, activePresetId, isDefaultActive, presets]);
 
   const handleAddPreset = () => {
+    const name = generateDefaultPresetName(presets);
     const newPreset: FilterPreset = {
-      id: crypto.randomUUID(),
-      name: generateDefaultPresetName(presets),
+      id: name,
+      name,
       selectedLevels: [`trace`, `debug`, `info`, `warn`, `error`],
       targetFilter: ``,
       searchFilter: ``,
@@ -97,13 +117,15 @@ export function FilterPresets({
   };
 
   const handleDuplicate = (preset: FilterPreset) => {
+    const name = generateCopyName(preset.name, presets);
     const newPreset: FilterPreset = {
-      id: crypto.randomUUID(),
-      name: `${preset.name} (copy)`,
+      id: name,
+      name,
       selectedLevels: [...preset.selectedLevels],
       targetFilter: preset.targetFilter,
       searchFilter: preset.searchFilter,
       targetLevelFilters: preset.targetLevelFilters ? [...preset.targetLevelFilters] : [],
+      query: preset.query,
     };
 
     savePresets([...presets, newPreset]);
@@ -116,16 +138,22 @@ export function FilterPresets({
   };
 
   const handleConfirmRename = (id: string) => {
-    if (!editName.trim()) {
+    const name = editName.trim();
+    // Names identify shared presets, so they have to stay unique
+    if (!name || presets.some((p) => p.id !== id && p.name === name)) {
       setEditingId(null);
       return;
     }
 
     const updated = presets.map((p) =>
-      p.id === id ? { ...p, name: editName.trim() } : p
+      p.id === id ? { ...p, id: name, name } : p
     );
     savePresets(updated);
     setEditingId(null);
+    if (id === activePresetId) {
+      const renamed = updated.find((p) => p.id === name);
+      if (renamed) onLoadPreset(renamed);
+    }
   };
 
   const handleDelete = (id: string) => {
@@ -4,7 +4,7 @@ import { VariableSizeList } from 'react-window';
 import InfiniteLoader from 'react-window-infinite-loader';
 import AutoSizer from 'react-virtualized-auto-sizer';
 import { ChevronRight, ChevronDown } from 'lucide-react';
-import type { LogEvent } from '@/types/logs';
+import { ERROR_CHAIN_FIELD, type FieldValue, type LogEvent } from '@/types/logs';
 
 interface LogViewerProps {
   logs: LogEvent[];
@@ -47,9 +47,13 @@ function LogItem({ log, isExpanded, onToggleExpand, onTargetContextMenu }: LogIt
   };
 
   const location = formatFile(log.file, log.line);
+  const errorChain = getErrorChain(log);
+  const fields = visibleFields(log);
+  const thread = log.thread_name ?? log.thread_id?.toString();
 
   // Check if there are any fields or span to show
   const hasExpandableContent =
+    !!log.id ||
     (log.fields && Object.keys(log.fields).length > 0) ||
     (log.span && (log.span.name || Object.keys(log.span.fields).length > 0));
 
@@ -99,22 +103,51 @@ function LogItem({ log, isExpanded, onToggleExpand, onTargetContextMenu }: LogIt
           {/* Target */}
           <span
             className={`text-muted-foreground text-xs whitespace-nowrap flex-shrink-0 w-[200px] truncate hover:text-foreground cursor-pointer`}
-            title={log.target}
+            title={thread ? `${log.target} on thread ${thread}` : log.target}
             onContextMenu={handleTargetContextMenu}
           >
             {log.target}
           </span>
 
           {/* File:Line */}
-          {location && (
-            <span className={`text-muted-foreground/70 text-xs whitespace-nowrap flex-shrink-0 w-[120px] truncate`} title={log.file}>
+          {location && (log.source_url ? (
+            <a
+              className={`text-muted-foreground/70 text-xs whitespace-nowrap flex-shrink-0 w-[120px] truncate underline hover:text-blue-400`}
+              title={log.module_path ? `${log.module_path} (${log.file})` : log.file}
+              href={log.source_url}
+              target={`_blank`}
+              rel={`noreferrer`}
+              onClick={(e) => e.stopPropagation()}
+            >
+              {location}
+            </a>
+          ) : (
+            <span className={`text-muted-foreground/70 text-xs whitespace-nowrap flex-shrink-0 w-[120px] truncate`} title={log.module_path ? `${log.module_path} (${log.file})` : log.file}>
               {location}
             </span>
-          )}
+          ))}
 
           {/* Message */}
           <div className={`flex-1 min-w-0`}>
-            <div className={`text-foreground truncate`}>{log.message}</div>
+            <div className={`text-foreground truncate`}>
+              {log.repeat_count && (
+                <span
+                  className={`mr-2 px-1 rounded bg-muted text-muted-foreground text-xs`}
+                  title={`Repeated ${log.repeat_count} times`}
+                >
+                  ×{log.repeat_count}
+                </span>
+              )}
+              {log.message}
+              {log.truncated && (
+                <span
+                  className={`ml-2 px-1 rounded bg-muted text-muted-foreground text-xs`}
+                  title={`Message or field values were cut to the configured length`}
+                >
+                  truncated
+                </span>
+              )}
+            </div>
           </div>
         </div>
       </div>
@@ -126,6 +159,22 @@ function LogItem({ log, isExpanded, onToggleExpand, onTargetContextMenu }: LogIt
           <div className={`w-1 flex-shrink-0 ${levelStripeColors[level] || `bg-gray-400`}`} />
 
           <div className={`pl-8 pr-4 pt-2 pb-2 flex-1`}>
+            {/* Permalink to this single event */}
+            {log.id && (
+              <div className={`flex gap-2 text-xs font-mono h-[24px] items-center`}>
+                <span className={`text-muted-foreground`}>id:</span>
+                <a
+                  className={`text-foreground underline hover:text-blue-400`}
+                  href={`api/logs/${log.id}`}
+                  target={`_blank`}
+                  rel={`noreferrer`}
+                  onClick={(e) => e.stopPropagation()}
+                >
+                  {log.id}
+                </a>
+              </div>
+            )}
+
             {/* Span information */}
             {log.span && (
               <div className={`mb-2`}>
@@ -139,14 +188,31 @@ function LogItem({ log, isExpanded, onToggleExpand, onTargetContextMenu }: LogIt
               </div>
             )}
 
+            {/* Recorded error with its causes */}
+            {errorChain.length > 0 && (
+              <div className={`mb-2`}>
+                <div className={`text-xs font-semibold text-muted-foreground mb-1`}>Error</div>
+                {errorChain.map((cause, index) => (
+                  <div
+                    key={index}
+                    className={`flex gap-2 text-xs font-mono h-[24px] items-center`}
+                    style={{ paddingLeft: `${index * 12}px` }}
+                  >
+                    {index > 0 && <span className={`text-muted-foreground`}>caused by:</span>}
+                    <span className={`text-red-400 truncate`} title={cause}>{cause}</span>
+                  </div>
+                ))}
+              </div>
+            )}
+
             {/* Event fields */}
-            {log.fields && Object.keys(log.fields).length > 0 && (
+            {fields.length > 0 && (
               <div>
                 <div className={`text-xs font-semibold text-muted-foreground mb-1`}>Fields</div>
-                {Object.entries(log.fields).map(([key, value]) => (
+                {fields.map(([key, value]) => (
                   <div key={key} className={`flex gap-2 text-xs font-mono h-[24px] items-center`}>
                     <span className={`text-green-400`}>{key}:</span>
-                    <span className={`text-foreground`}>{value}</span>
+                    <span className={`text-foreground`}>{formatFieldValue(value)}</span>
                   </div>
                 ))}
               </div>
@@ -158,6 +224,24 @@ function LogItem({ log, isExpanded, onToggleExpand, onTargetContextMenu }: LogIt
   );
 }
 
+// Messages of a recorded error and its sources, empty without an error
+function getErrorChain(log: LogEvent): string[] {
+  const chain = log.is_error ? log.fields?.[ERROR_CHAIN_FIELD] : undefined;
+  return Array.isArray(chain) ? chain.map(formatFieldValue) : [];
+}
+
+// Fields listed in the details, the error chain gets its own section
+function visibleFields(log: LogEvent): [string, FieldValue][] {
+  return Object.entries(log.fields ?? {}).filter(
+    ([key]) => !(log.is_error && key === ERROR_CHAIN_FIELD),
+  );
+}
+
+// Strings are shown as they are, other values as JSON
+function formatFieldValue(value: FieldValue): string {
+  return typeof value === `string` ? value : JSON.stringify(value);
+}
+
 // Calculate the height of a log item based on whether it's expanded
 function calculateItemHeight(log: LogEvent, isExpanded: boolean): number {
   if (!isExpanded) {
@@ -166,16 +250,29 @@ function calculateItemHeight(log: LogEvent, isExpanded: boolean): number {
 
   let height = EXPANDED_BASE_HEIGHT;
 
+  // Add height for permalink row
+  if (log.id) {
+    height += FIELD_ROW_HEIGHT;
+  }
+
   // Add height for span section
   if (log.span) {
     height += FIELD_ROW_HEIGHT; // "Span: name" header
     height += Object.keys(log.span.fields).length * FIELD_ROW_HEIGHT;
   }
 
+  // Add height for error section
+  const errorChain = getErrorChain(log);
+  if (errorChain.length > 0) {
+    height += FIELD_ROW_HEIGHT; // "Error" header
+    height += errorChain.length * FIELD_ROW_HEIGHT;
+  }
+
   // Add height for fields section
-  if (log.fields && Object.keys(log.fields).length > 0) {
+  const fields = visibleFields(log);
+  if (fields.length > 0) {
     height += FIELD_ROW_HEIGHT; // "Fields" header
-    height += Object.keys(log.fields).length * FIELD_ROW_HEIGHT;
+    height += fields.length * FIELD_ROW_HEIGHT;
   }
 
   // Add padding (pt-2 + pb-2)
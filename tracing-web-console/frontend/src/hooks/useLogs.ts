@@ -1,5 +1,6 @@
 import { useEffect, useRef, useState, useCallback } from 'react';
 import type { LogEvent, LogLevel } from '@/types/logs';
+import { getConsoleConfig } from '@/lib/api';
 
 const BATCH_SIZE = 100; // Fetch 100 logs at a time
 
@@ -55,10 +56,13 @@ function getWebSocketUrl(): string {
     return `${wsUrl}/api/ws`;
   }
 
-  // In production, construct WebSocket URL from current location
-  // The app is served from /tracing, so WebSocket is at /tracing/api/ws
+  // In production, construct WebSocket URL from current location and the
+  // path the server injected, which accounts for reverse proxy prefixes.
+  // The page's own protocol decides between ws: and wss:, so TLS terminated
+  // by a proxy works too.
   const protocol = window.location.protocol === `https:` ? `wss:` : `ws:`;
-  return `${protocol}//${window.location.host}/tracing/api/ws`;
+  const wsPath = getConsoleConfig()?.ws_path ?? `/tracing/api/ws`;
+  return `${protocol}//${window.location.host}${wsPath}`;
 }
 
 /**
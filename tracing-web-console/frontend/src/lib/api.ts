@@ -13,3 +13,57 @@ export function getApiBaseUrl(): string {
   // In production, use relative paths (will use current host)
   return ``;
 }
+
+/**
+ * Configuration of the console, from `GET /api/config` and injected into
+ * index.html by the server
+ */
+export interface ConsoleConfig {
+  version: string;
+  /** Path the console is reached at, e.g. `/myapp/tracing` behind a proxy */
+  base_path: string;
+  ws_path: string;
+  sse_path: string;
+  /** Absolute URLs as seen by the server, TLS-aware behind proxies */
+  ws_url: string | null;
+  sse_url: string | null;
+  auth: {
+    /** `token` when access tokens are required, `none` otherwise */
+    mode: string;
+    read_only: boolean;
+    ingest_tokens_required: boolean;
+  };
+  /** Role of the presented access token, null without one */
+  role: 'reader' | 'admin' | null;
+  capabilities: {
+    persistence: boolean;
+    spill: boolean;
+    metrics: boolean;
+  };
+}
+
+declare global {
+  interface Window {
+    __TRACING_CONSOLE__?: ConsoleConfig;
+  }
+}
+
+/**
+ * Get the server-provided configuration, if the page was served by the console
+ */
+export function getConsoleConfig(): ConsoleConfig | undefined {
+  return window.__TRACING_CONSOLE__;
+}
+
+/**
+ * Fetch the configuration, for pages not served by the console such as the
+ * development server
+ */
+export async function fetchConsoleConfig(): Promise<ConsoleConfig> {
+  const baseUrl = getApiBaseUrl();
+  const response = await fetch(baseUrl ? `${baseUrl}/api/config` : `api/config`);
+  if (!response.ok) {
+    throw new Error(`Failed to fetch config: ${response.statusText}`);
+  }
+  return response.json();
+}
@@ -1,4 +1,5 @@
 import type { LogLevel } from '@/types/logs';
+import { getApiBaseUrl } from '@/lib/api';
 
 export interface TargetLevelFilter {
   id: string;
@@ -13,6 +14,20 @@ export interface FilterPreset {
   targetFilter: string;
   searchFilter: string;
   targetLevelFilters?: TargetLevelFilter[];
+  // Filter expression, only set on presets created via the API
+  query?: string;
+}
+
+/**
+ * Preset as stored by the server under `/api/filters`, shared by all users
+ */
+export interface ServerFilterPreset {
+  name: string;
+  levels: string[];
+  target_levels: Record<string, string>;
+  target: string | null;
+  search: string | null;
+  query: string | null;
 }
 
 export const STORAGE_KEY = `tracing-filter-presets`;
@@ -42,6 +57,100 @@ export function generateDefaultPresetName(existingPresets: FilterPreset[]): stri
   return name;
 }
 
+/**
+ * Generate a name for a copy of a preset that no other preset has
+ */
+export function generateCopyName(name: string, existingPresets: FilterPreset[]): string {
+  const existingNames = new Set(existingPresets.map(p => p.name));
+  let copyName = `${name} (copy)`;
+  let counter = 2;
+  while (existingNames.has(copyName)) {
+    copyName = `${name} (copy ${counter})`;
+    counter++;
+  }
+  return copyName;
+}
+
+function filtersUrl(path = ``): string {
+  const baseUrl = getApiBaseUrl();
+  return baseUrl ? `${baseUrl}/api/filters${path}` : `api/filters${path}`;
+}
+
+export function toServerPreset(preset: FilterPreset): ServerFilterPreset {
+  return {
+    name: preset.name,
+    levels: preset.selectedLevels,
+    target_levels: Object.fromEntries(
+      (preset.targetLevelFilters || []).map(f => [f.target, f.level])
+    ),
+    target: preset.targetFilter || null,
+    search: preset.searchFilter || null,
+    query: preset.query || null,
+  };
+}
+
+// Presets are identified by their name, so selections survive reloads
+export function fromServerPreset(preset: ServerFilterPreset): FilterPreset {
+  const levels = preset.levels.map(l => l.toLowerCase() as LogLevel);
+  return {
+    id: preset.name,
+    name: preset.name,
+    selectedLevels: levels.length > 0 ? levels : DEFAULT_PRESET.selectedLevels,
+    targetFilter: preset.target ?? ``,
+    searchFilter: preset.search ?? ``,
+    targetLevelFilters: Object.entries(preset.target_levels).map(([target, level]) => ({
+      id: crypto.randomUUID(),
+      target,
+      level: level.toLowerCase() as LogLevel,
+    })),
+    query: preset.query ?? undefined,
+  };
+}
+
+/**
+ * Load the presets shared on the server, null if they can't be loaded
+ */
+export async function fetchServerPresets(): Promise<FilterPreset[] | null> {
+  try {
+    const response = await fetch(filtersUrl());
+    if (!response.ok) {
+      return null;
+    }
+    const body: { presets: ServerFilterPreset[] } = await response.json();
+    return body.presets.map(fromServerPreset);
+  } catch {
+    return null;
+  }
+}
+
+/**
+ * Save changed presets on the server and delete the removed ones
+ */
+export async function syncServerPresets(
+  previous: FilterPreset[],
+  next: FilterPreset[]
+): Promise<void> {
+  const nextNames = new Set(next.map(p => p.name));
+  const removed = previous.filter(p => !nextNames.has(p.name));
+  const changed = next.filter(p => {
+    const old = previous.find(o => o.name === p.name);
+    return !old || JSON.stringify(toServerPreset(old)) !== JSON.stringify(toServerPreset(p));
+  });
+
+  await Promise.allSettled([
+    ...removed.map(p =>
+      fetch(filtersUrl(`/${encodeURIComponent(p.name)}`), { method: `DELETE` })
+    ),
+    ...changed.map(p =>
+      fetch(filtersUrl(), {
+        method: `POST`,
+        headers: { 'Content-Type': `application/json` },
+        body: JSON.stringify(toServerPreset(p)),
+      })
+    ),
+  ]);
+}
+
 /**
  * Load all presets from localStorage
  */
@@ -1,17 +1,34 @@
+// Numbers and booleans keep their type
+export type FieldValue = string | number | boolean | null | FieldValue[] | { [key: string]: FieldValue };
+
 export interface LogEvent {
+  id: string;
+  seq: number;
   timestamp: string;
   level: string;
   target: string;
   message: string;
-  fields: Record<string, string>;
+  fields: Record<string, FieldValue>;
   span?: {
     name: string;
     fields: Record<string, string>;
   };
+  module_path?: string;
   file?: string;
   line?: number;
+  source_url?: string;
+  thread_name?: string;
+  thread_id?: number;
+  repeat_count?: number;
+  // Set when an error was recorded, its causes are in the `error.chain` field
+  is_error?: boolean;
+  // Set when the message or a field value was cut to the configured length
+  truncated?: boolean;
 }
 
+// Field holding the messages of a recorded error and its sources, outermost first
+export const ERROR_CHAIN_FIELD = `error.chain`;
+
 export interface WSMessage {
   type: `log` | `historical`;
   data: LogEvent | LogEvent[];
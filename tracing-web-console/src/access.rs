@@ -0,0 +1,126 @@
+//! Tokens granting read or admin access to the console
+//!
+//! Without tokens the console is open. Once any is configured, every API
+//! request and the plain page need one: readers can query and stream events,
+//! admins can also change levels, rules, transforms and clear the buffer.
+//! Ingestion is authenticated separately by ingest tokens.
+
+use axum::http::header::{AUTHORIZATION, COOKIE};
+use axum::http::HeaderMap;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Cookie holding the token of a browser session, set by opening the console
+/// with `?token=<secret>`
+pub(crate) const TOKEN_COOKIE: &str = "twc_access";
+
+/// What a token allows, admins can do everything readers can
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Query, export and stream events
+    Reader,
+    /// Also change the console's state
+    Admin,
+}
+
+fn hash_secret(secret: &str) -> [u8; 32] {
+    Sha256::digest(secret.as_bytes()).into()
+}
+
+/// Configured access tokens by the hash of their secret
+#[derive(Clone, Default)]
+pub(crate) struct AccessTokens {
+    tokens: Arc<HashMap<[u8; 32], Role>>,
+}
+
+impl AccessTokens {
+    /// Tokens with their roles, a secret given for both roles is an admin
+    pub fn new<'a>(tokens: impl IntoIterator<Item = (&'a str, Role)>) -> Self {
+        let mut hashed = HashMap::new();
+        for (secret, role) in tokens {
+            let entry = hashed.entry(hash_secret(secret)).or_insert(role);
+            *entry = (*entry).max(role);
+        }
+        Self {
+            tokens: Arc::new(hashed),
+        }
+    }
+
+    /// Whether requests need a token
+    pub fn is_enforced(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// Role of a secret, `None` if it isn't a configured token
+    pub fn role(&self, secret: &str) -> Option<Role> {
+        self.tokens.get(&hash_secret(secret)).copied()
+    }
+
+    /// Role of the token presented with a request, as bearer token or cookie
+    pub fn role_of(&self, headers: &HeaderMap) -> Option<Role> {
+        presented_secret(headers).and_then(|secret| self.role(secret))
+    }
+}
+
+/// Secret sent as `Authorization: Bearer` or in the session cookie
+fn presented_secret(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    bearer.or_else(|| {
+        headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == TOKEN_COOKIE)
+            .map(|(_, secret)| secret)
+    })
+}
+
+/// Whether a secret can be stored in the session cookie as is
+pub(crate) fn is_cookie_safe(secret: &str) -> bool {
+    !secret.is_empty()
+        && secret
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && !matches!(b, b';' | b',' | b'"' | b'\\'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_roles_from_bearer_and_cookie() {
+        let tokens = AccessTokens::new([
+            ("dev-secret", Role::Reader),
+            ("ops-secret", Role::Admin),
+            ("both", Role::Admin),
+            ("both", Role::Reader),
+        ]);
+        assert!(tokens.is_enforced());
+        assert_eq!(tokens.role("both"), Some(Role::Admin));
+        assert_eq!(tokens.role("wrong"), None);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(tokens.role_of(&headers), None);
+        headers.insert(
+            COOKIE,
+            HeaderValue::from_static("theme=dark; twc_access=dev-secret"),
+        );
+        assert_eq!(tokens.role_of(&headers), Some(Role::Reader));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer ops-secret"));
+        assert_eq!(tokens.role_of(&headers), Some(Role::Admin));
+
+        assert!(!AccessTokens::default().is_enforced());
+        assert!(is_cookie_safe("twc_abc-123"));
+        assert!(!is_cookie_safe("a;b"));
+    }
+}
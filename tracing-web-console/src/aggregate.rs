@@ -0,0 +1,613 @@
+//! Event counts over time, across raw events and compacted summaries
+//!
+//! Recent events are counted exactly from the buffer, older ranges come from
+//! the per-minute summaries left by compaction. Every bucket says which of the
+//! two it was computed from, so charts can mark summarized portions.
+//!
+//! Buckets are aligned in a requested timezone offset, so daily charts of
+//! teams outside UTC start at their local midnight.
+
+use crate::memory::{MemoryEstimate, MemoryUsage};
+use crate::storage::{level_to_number, LogFilter, LogStorage};
+use crate::stream_clients::StreamClientStats;
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Most buckets a single timeline may have
+pub(crate) const MAX_BUCKETS: i64 = 10_000;
+
+/// What a bucket's counts were computed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketSource {
+    /// Counted from stored events
+    Exact,
+    /// Taken from compaction summaries
+    Summarized,
+    /// Partly from stored events, partly from summaries
+    Mixed,
+}
+
+/// Event counts of one time bucket
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineBucket {
+    pub start: DateTime<Utc>,
+    pub total: u64,
+    /// Counts by level, levels without events are left out
+    pub levels: BTreeMap<String, u64>,
+    pub source: BucketSource,
+}
+
+/// Event counts per time bucket
+#[derive(Debug, Clone, Serialize)]
+pub struct Timeline {
+    pub bucket_secs: i64,
+    /// Offset from UTC buckets are aligned in, e.g. `+02:00`
+    pub tz_offset: String,
+    /// Timestamp of the oldest stored event, earlier counts are summarized
+    pub exact_from: Option<DateTime<Utc>>,
+    /// Consecutive buckets from the first to the last with events, oldest first
+    pub buckets: Vec<TimelineBucket>,
+}
+
+/// Which events a timeline counts
+#[derive(Debug, Clone, Default)]
+pub struct TimelineFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Target or modules below it
+    pub target: Option<String>,
+    /// Minimum level
+    pub level: Option<String>,
+}
+
+impl TimelineFilter {
+    fn in_range(&self, timestamp: DateTime<Utc>) -> bool {
+        !matches!(self.from, Some(from) if timestamp < from)
+            && !matches!(self.to, Some(to) if timestamp > to)
+    }
+
+    fn matches(&self, target: &str, level: &str) -> bool {
+        if let Some(prefix) = &self.target {
+            if target != prefix && !target.starts_with(&format!("{}::", prefix)) {
+                return false;
+            }
+        }
+        match &self.level {
+            Some(min) => level_to_number(level) >= level_to_number(min),
+            None => true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    levels: BTreeMap<String, u64>,
+    exact: u64,
+    summarized: u64,
+}
+
+const HOUR_SECS: i64 = 3600;
+const DAY_SECS: i64 = 24 * HOUR_SECS;
+/// The Unix epoch was a Thursday, ISO weeks start on the Monday before it
+const WEEK_PHASE_SECS: i64 = -3 * DAY_SECS;
+
+/// How timestamps are grouped into buckets
+///
+/// Without daylight saving time, local hours, days and weeks all have a fixed
+/// width, only their boundaries shift with the offset.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Bucketing {
+    width_secs: i64,
+    offset: FixedOffset,
+    /// Position of a bucket boundary relative to local midnight of the epoch
+    phase_secs: i64,
+}
+
+impl Bucketing {
+    /// Buckets of `secs` seconds aligned in `offset`
+    pub fn fixed(secs: i64, offset: FixedOffset) -> Result<Self, String> {
+        if secs <= 0 {
+            return Err("bucket_secs must be positive".to_string());
+        }
+        Ok(Self {
+            width_secs: secs,
+            offset,
+            phase_secs: 0,
+        })
+    }
+
+    /// Local `hour`, `day` or ISO `week` (starting Monday) buckets in `offset`
+    pub fn calendar(unit: &str, offset: FixedOffset) -> Result<Self, String> {
+        let (width_secs, phase_secs) = match unit.to_ascii_lowercase().as_str() {
+            "hour" => (HOUR_SECS, 0),
+            "day" => (DAY_SECS, 0),
+            "week" => (7 * DAY_SECS, WEEK_PHASE_SECS),
+            other => {
+                return Err(format!(
+                    "unknown calendar unit: {} (expected hour, day or week)",
+                    other
+                ))
+            }
+        };
+        Ok(Self {
+            width_secs,
+            offset,
+            phase_secs,
+        })
+    }
+
+    /// Index of the bucket containing `timestamp`
+    fn index(&self, timestamp: DateTime<Utc>) -> i64 {
+        let local = timestamp.timestamp() + i64::from(self.offset.local_minus_utc());
+        (local - self.phase_secs).div_euclid(self.width_secs)
+    }
+
+    /// Start of the bucket with `index`
+    fn start(&self, index: i64) -> DateTime<Utc> {
+        let local = index * self.width_secs + self.phase_secs;
+        DateTime::from_timestamp(local - i64::from(self.offset.local_minus_utc()), 0)
+            .unwrap_or_default()
+    }
+}
+
+/// Parse a UTC offset such as `+02:00`, `-0530`, `+01` or `Z`
+///
+/// A leading space is read as `+`, which is what an unescaped `+` in a query
+/// string decodes to.
+pub(crate) fn parse_tz_offset(value: &str) -> Result<FixedOffset, String> {
+    let invalid = || format!("invalid timezone offset: {}", value);
+    let trimmed = value.trim_end();
+    if trimmed.eq_ignore_ascii_case("z") || trimmed.eq_ignore_ascii_case("utc") {
+        return Ok(FixedOffset::east_opt(0).expect("zero offset is valid"));
+    }
+    let (sign, digits) = match trimmed.chars().next() {
+        Some('+') | Some(' ') => (1, &trimmed[1..]),
+        Some('-') => (-1, &trimmed[1..]),
+        _ => return Err(invalid()),
+    };
+    let digits = digits.replace(':', "");
+    if !matches!(digits.len(), 2 | 4) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let hours: i32 = digits[..2].parse().map_err(|_| invalid())?;
+    let minutes: i32 = match digits.len() {
+        4 => digits[2..].parse().map_err(|_| invalid())?,
+        _ => 0,
+    };
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
+
+/// Count events per bucket
+///
+/// Summaries count towards the bucket containing the start of their minute.
+/// Fails if the range would need more than `MAX_BUCKETS` buckets.
+pub(crate) fn timeline(
+    storage: &LogStorage,
+    filter: &TimelineFilter,
+    bucketing: Bucketing,
+) -> Result<Timeline, String> {
+    let bucket_secs = bucketing.width_secs;
+    let tz_offset = bucketing.offset.to_string();
+
+    let mut buckets: BTreeMap<i64, Counts> = BTreeMap::new();
+    let mut exact_from = None;
+    storage.scan(|event| {
+        exact_from.get_or_insert(event.timestamp);
+        if filter.in_range(event.timestamp) && filter.matches(&event.target, &event.level) {
+            let counts = buckets.entry(bucketing.index(event.timestamp)).or_default();
+            *counts.levels.entry(event.level.to_string()).or_default() += 1;
+            counts.exact += 1;
+        }
+    });
+
+    // Summaries overlapping the range are counted in full
+    for summary in storage.summaries().between(filter.from, filter.to) {
+        if filter.matches(&summary.target, &summary.level) {
+            let counts = buckets.entry(bucketing.index(summary.minute)).or_default();
+            *counts.levels.entry(summary.level).or_default() += summary.count;
+            counts.summarized += summary.count;
+        }
+    }
+
+    let (Some(&first), Some(&last)) = (buckets.keys().next(), buckets.keys().next_back()) else {
+        return Ok(Timeline {
+            bucket_secs,
+            tz_offset,
+            exact_from,
+            buckets: Vec::new(),
+        });
+    };
+    if last - first >= MAX_BUCKETS {
+        return Err(format!(
+            "range needs more than {} buckets, use a larger bucket size",
+            MAX_BUCKETS
+        ));
+    }
+
+    let buckets = (first..=last)
+        .map(|index| {
+            let start = bucketing.start(index);
+            let counts = buckets.remove(&index).unwrap_or_default();
+            let end = start + chrono::Duration::seconds(bucket_secs);
+            let source = match (counts.exact > 0, counts.summarized > 0) {
+                (true, false) => BucketSource::Exact,
+                (false, true) => BucketSource::Summarized,
+                (true, true) => BucketSource::Mixed,
+                // Empty buckets are exact once stored events cover them
+                (false, false) if matches!(exact_from, Some(from) if end > from) => {
+                    BucketSource::Exact
+                }
+                (false, false) => BucketSource::Summarized,
+            };
+            TimelineBucket {
+                start,
+                total: counts.exact + counts.summarized,
+                levels: counts.levels,
+                source,
+            }
+        })
+        .collect();
+
+    Ok(Timeline {
+        bucket_secs,
+        tz_offset,
+        exact_from,
+        buckets,
+    })
+}
+
+/// Event counts of one histogram bucket
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBucket {
+    pub start: DateTime<Utc>,
+    pub total: u64,
+    /// Counts by level, levels without events are left out
+    pub levels: BTreeMap<String, u64>,
+}
+
+/// Stored events matching a filter, counted per time bucket
+#[derive(Debug, Clone, Serialize)]
+pub struct Histogram {
+    pub bucket_secs: i64,
+    /// Offset from UTC buckets are aligned in, e.g. `+02:00`
+    pub tz_offset: String,
+    /// Number of events counted
+    pub matched: u64,
+    /// Consecutive buckets, oldest first, from `from` (or the first matching
+    /// event) to `to` (or the last one)
+    pub buckets: Vec<HistogramBucket>,
+}
+
+/// Count the stored events matching `filter` per bucket and level
+///
+/// Fails if the range would need more than `MAX_BUCKETS` buckets.
+pub(crate) fn histogram(
+    storage: &LogStorage,
+    filter: &LogFilter,
+    bucketing: Bucketing,
+) -> Result<Histogram, String> {
+    let mut counts: BTreeMap<i64, BTreeMap<String, u64>> = BTreeMap::new();
+    let mut matched = 0;
+    storage.scan(|event| {
+        if filter.matches(event) {
+            let levels = counts.entry(bucketing.index(event.timestamp)).or_default();
+            *levels.entry(event.level.to_string()).or_default() += 1;
+            matched += 1;
+        }
+    });
+
+    let first = filter
+        .from
+        .map(|from| bucketing.index(from))
+        .or_else(|| counts.keys().next().copied());
+    let last = filter
+        .to
+        .map(|to| bucketing.index(to))
+        .or_else(|| counts.keys().next_back().copied());
+    let buckets = match (first, last) {
+        (Some(first), Some(last)) if first <= last => {
+            if last - first >= MAX_BUCKETS {
+                return Err(format!(
+                    "range needs more than {} buckets, use a larger bucket size",
+                    MAX_BUCKETS
+                ));
+            }
+            (first..=last)
+                .map(|index| {
+                    let levels = counts.remove(&index).unwrap_or_default();
+                    HistogramBucket {
+                        start: bucketing.start(index),
+                        total: levels.values().sum(),
+                        levels,
+                    }
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+
+    Ok(Histogram {
+        bucket_secs: bucketing.width_secs,
+        tz_offset: bucketing.offset.to_string(),
+        matched,
+        buckets,
+    })
+}
+
+/// Events per second within one minute
+#[derive(Debug, Clone, Serialize)]
+pub struct RatePoint {
+    pub minute: DateTime<Utc>,
+    pub events_per_sec: f64,
+}
+
+/// Aggregate statistics of the stored events and the buffer
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub stored_events: usize,
+    pub capacity: usize,
+    /// Capacity currently enforced, lower than `capacity` under memory pressure
+    pub effective_capacity: usize,
+    /// Stored events relative to the effective capacity, from 0 to 1
+    pub utilization: f64,
+    /// Events evicted from the full buffer since start
+    pub evicted_total: u64,
+    /// Events live stream clients missed by falling behind since start
+    pub dropped_total: u64,
+    /// Captured events discarded because the capture queue was full
+    pub capture_dropped_total: u64,
+    /// Events sinks discarded because their queue was full
+    pub sink_dropped_total: u64,
+    /// Connected WebSocket clients with their queued and missed events
+    pub stream_clients: Vec<StreamClientStats>,
+    /// Stored events by level
+    pub levels: BTreeMap<String, u64>,
+    /// Stored events by target
+    pub targets: BTreeMap<String, u64>,
+    pub window_minutes: i64,
+    /// Events per second over the window
+    pub events_per_sec: f64,
+    /// Events per second of each minute in the window, oldest first, the
+    /// last one is the current minute so far
+    pub rate: Vec<RatePoint>,
+    /// Estimated memory held by the stored events
+    pub memory: MemoryUsage,
+}
+
+/// Statistics of the stored events, with rates over the last `window_minutes`
+pub(crate) fn stats(storage: &LogStorage, window_minutes: i64, now: DateTime<Utc>) -> Stats {
+    let window_minutes = window_minutes.max(1);
+    let current = now.timestamp().div_euclid(60);
+    let first = current - window_minutes + 1;
+    let mut per_minute = vec![0u64; window_minutes as usize];
+    let mut levels: BTreeMap<String, u64> = BTreeMap::new();
+    let mut targets: BTreeMap<String, u64> = BTreeMap::new();
+    let mut stored_events = 0;
+    let mut memory = MemoryEstimate::default();
+
+    storage.scan(|event| {
+        stored_events += 1;
+        memory.add(event);
+        *levels.entry(event.level.to_string()).or_default() += 1;
+        *targets.entry(event.target.to_string()).or_default() += 1;
+        let minute = event.timestamp.timestamp().div_euclid(60);
+        if (first..=current).contains(&minute) {
+            per_minute[(minute - first) as usize] += 1;
+        }
+    });
+
+    // The current minute is only partly over
+    let current_secs = (now.timestamp().rem_euclid(60) + 1) as f64;
+    let rate = per_minute
+        .iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let minute = first + i as i64;
+            let secs = if minute == current {
+                current_secs
+            } else {
+                60.0
+            };
+            RatePoint {
+                minute: DateTime::from_timestamp(minute * 60, 0).unwrap_or_default(),
+                events_per_sec: *count as f64 / secs,
+            }
+        })
+        .collect();
+    let window_secs = (window_minutes - 1) as f64 * 60.0 + current_secs;
+    let effective_capacity = storage.effective_capacity();
+
+    Stats {
+        stored_events,
+        capacity: storage.capacity(),
+        effective_capacity,
+        utilization: stored_events as f64 / effective_capacity.max(1) as f64,
+        evicted_total: storage.evicted_total(),
+        dropped_total: storage.dropped_total(),
+        capture_dropped_total: storage.capture_dropped_total(),
+        sink_dropped_total: storage.sink_dropped_total(),
+        stream_clients: storage.stream_clients().stats(),
+        levels,
+        targets,
+        window_minutes,
+        events_per_sec: per_minute.iter().sum::<u64>() as f64 / window_secs,
+        rate,
+        memory: memory.finish(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LogEvent;
+    use std::collections::HashMap;
+
+    fn utc() -> FixedOffset {
+        FixedOffset::east_opt(0).unwrap()
+    }
+
+    fn hourly() -> Bucketing {
+        Bucketing::fixed(3600, utc()).unwrap()
+    }
+
+    fn event(level: &str, timestamp: DateTime<Utc>) -> LogEvent {
+        LogEvent {
+            target: "app".into(),
+            timestamp,
+            ..LogEvent::internal(level, "work", HashMap::new())
+        }
+    }
+
+    #[test]
+    fn test_combines_raw_and_summarized() {
+        let storage = LogStorage::new();
+        let now = DateTime::from_timestamp(Utc::now().timestamp().div_euclid(60) * 60, 0).unwrap();
+        let old = now - chrono::Duration::minutes(120);
+        storage.push(event("ERROR", old));
+        storage.push(event("INFO", old + chrono::Duration::seconds(5)));
+        crate::compaction::compact(&storage, std::time::Duration::from_secs(3600));
+        storage.push(event("INFO", now));
+        storage.push(event("WARN", now + chrono::Duration::seconds(1)));
+
+        let hourly = timeline(&storage, &TimelineFilter::default(), hourly()).unwrap();
+        let first = &hourly.buckets[0];
+        assert_eq!(first.total, 2);
+        assert_eq!(first.source, BucketSource::Summarized);
+        let last = hourly.buckets.last().unwrap();
+        assert_eq!(last.source, BucketSource::Exact);
+        assert_eq!(last.levels["WARN"], 1);
+        assert_eq!(hourly.exact_from, Some(now));
+
+        let errors = TimelineFilter {
+            level: Some("error".to_string()),
+            ..Default::default()
+        };
+        let minutely = timeline(&storage, &errors, Bucketing::fixed(60, utc()).unwrap()).unwrap();
+        assert_eq!(minutely.buckets.len(), 1);
+        assert_eq!(minutely.buckets[0].levels["ERROR"], 1);
+    }
+
+    #[test]
+    fn test_too_many_buckets() {
+        let storage = LogStorage::new();
+        let now = Utc::now();
+        storage.push(event("INFO", now - chrono::Duration::days(30)));
+        storage.push(event("INFO", now));
+        let secondly = Bucketing::fixed(1, utc()).unwrap();
+        assert!(timeline(&storage, &TimelineFilter::default(), secondly).is_err());
+        assert!(Bucketing::fixed(0, utc()).is_err());
+    }
+
+    #[test]
+    fn test_local_calendar_buckets() {
+        let storage = LogStorage::new();
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        // 23:30 and 00:30 in UTC+02:00, one day apart locally, same day in UTC
+        storage.push(event("INFO", at("2024-05-01T21:30:00Z")));
+        storage.push(event("INFO", at("2024-05-01T22:30:00Z")));
+
+        let plus_two = FixedOffset::east_opt(2 * 3600).unwrap();
+        let daily = Bucketing::calendar("day", plus_two).unwrap();
+        let local = timeline(&storage, &TimelineFilter::default(), daily).unwrap();
+        assert_eq!(local.tz_offset, "+02:00");
+        assert_eq!(local.buckets.len(), 2);
+        assert_eq!(local.buckets[1].start, at("2024-05-01T22:00:00Z"));
+
+        let daily_utc = Bucketing::calendar("day", utc()).unwrap();
+        let utc_days = timeline(&storage, &TimelineFilter::default(), daily_utc).unwrap();
+        assert_eq!(utc_days.buckets.len(), 1);
+        assert_eq!(utc_days.buckets[0].total, 2);
+
+        // 2024-05-01 was a Wednesday, its ISO week started Monday 2024-04-29
+        let weekly = Bucketing::calendar("week", plus_two).unwrap();
+        let weeks = timeline(&storage, &TimelineFilter::default(), weekly).unwrap();
+        assert_eq!(weeks.buckets.len(), 1);
+        assert_eq!(weeks.buckets[0].start, at("2024-04-28T22:00:00Z"));
+
+        assert!(Bucketing::calendar("fortnight", utc()).is_err());
+    }
+
+    #[test]
+    fn test_parse_tz_offset() {
+        let secs = |s: &str| parse_tz_offset(s).map(|o| o.local_minus_utc());
+        assert_eq!(secs("+02:00"), Ok(7200));
+        assert_eq!(secs("-0530"), Ok(-19800));
+        assert_eq!(secs(" 01"), Ok(3600));
+        assert_eq!(secs("Z"), Ok(0));
+        assert!(secs("+2").is_err());
+        assert!(secs("+25:00").is_err());
+        assert!(secs("Europe/Berlin").is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let storage = LogStorage::with_capacity(10);
+        let now = DateTime::parse_from_rfc3339("2024-05-01T12:00:29Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        for seconds in [1, 2, 3] {
+            storage.push(event("INFO", now - chrono::Duration::seconds(seconds)));
+        }
+        storage.push(event("ERROR", now - chrono::Duration::seconds(60)));
+        storage.push(LogEvent {
+            target: "other".into(),
+            ..event("WARN", now - chrono::Duration::hours(1))
+        });
+        storage.record_dropped(7);
+
+        let stats = stats(&storage, 2, now);
+        assert_eq!(stats.stored_events, 5);
+        assert_eq!(stats.utilization, 0.5);
+        assert_eq!(stats.dropped_total, 7);
+        assert_eq!(stats.levels["INFO"], 3);
+        assert_eq!(stats.targets["app"], 4);
+        assert_eq!(stats.rate.len(), 2);
+        assert_eq!(stats.rate[0].events_per_sec, 1.0 / 60.0);
+        // 30 seconds of the current minute have passed
+        assert_eq!(stats.rate[1].events_per_sec, 0.1);
+        assert_eq!(stats.events_per_sec, 4.0 / 90.0);
+        assert!(stats.memory.events_bytes >= 5 * stats.memory.bytes_per_event);
+        assert!(stats.memory.shared_strings_bytes > 0);
+    }
+
+    #[test]
+    fn test_histogram_uses_log_filter() {
+        let storage = LogStorage::new();
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        storage.push(event("INFO", at("2024-05-01T10:00:10Z")));
+        storage.push(event("ERROR", at("2024-05-01T10:00:20Z")));
+        storage.push(event("ERROR", at("2024-05-01T10:02:00Z")));
+        storage.push(LogEvent {
+            target: "other".into(),
+            ..event("ERROR", at("2024-05-01T10:01:00Z"))
+        });
+
+        let minutely = Bucketing::fixed(60, utc()).unwrap();
+        let filter = LogFilter {
+            target: Some("app".to_string()),
+            ..Default::default()
+        };
+        let counts = histogram(&storage, &filter, minutely).unwrap();
+        assert_eq!(counts.matched, 3);
+        let totals: Vec<u64> = counts.buckets.iter().map(|b| b.total).collect();
+        assert_eq!(totals, [2, 0, 1]);
+        assert_eq!(counts.buckets[0].levels["ERROR"], 1);
+        assert_eq!(counts.buckets[0].start, at("2024-05-01T10:00:00Z"));
+
+        // An explicit range is covered even where nothing matched
+        let ranged = LogFilter {
+            from: Some(at("2024-05-01T09:58:00Z")),
+            to: Some(at("2024-05-01T10:00:59Z")),
+            global_level: Some("ERROR".to_string()),
+            ..Default::default()
+        };
+        let counts = histogram(&storage, &ranged, minutely).unwrap();
+        let totals: Vec<u64> = counts.buckets.iter().map(|b| b.total).collect();
+        assert_eq!(totals, [0, 0, 1]);
+    }
+}
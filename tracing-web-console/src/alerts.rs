@@ -0,0 +1,885 @@
+//! Alert rules evaluated against stored events, with webhook notifications
+
+use crate::expr::FilterExpr;
+use crate::storage::{level_to_number, LogEvent, LogStorage};
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "webhooks")]
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Maximum number of sample events included in evaluations and notifications
+const MAX_SAMPLES: usize = 5;
+/// Maximum number of fired alerts kept for GET /api/alerts
+const MAX_FIRINGS: usize = 100;
+/// Timeout for webhook requests
+#[cfg(feature = "webhooks")]
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Longest rule window, matches within it are kept in memory
+const MAX_WINDOW_SECS: u64 = 7 * 24 * 60 * 60;
+/// Longest silence
+const MAX_SILENCE_SECS: u64 = 365 * 24 * 60 * 60;
+
+fn default_threshold() -> usize {
+    1
+}
+
+fn default_window_secs() -> u64 {
+    60
+}
+
+/// A rule like "more than 10 ERRORs from target X in 60s"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    /// Unique ID, generated when empty
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    /// Minimum level of matching events (e.g. "ERROR")
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Target prefix, "my_crate" matches "my_crate::module"
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Case-insensitive substring of the message
+    #[serde(default)]
+    pub message_contains: Option<String>,
+    /// Filter expression, e.g. `span:checkout AND level>=warn`
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Sandboxed Rhai condition evaluated against `event`
+    #[cfg(feature = "scripting")]
+    #[serde(default)]
+    pub script: Option<String>,
+    /// Number of matching events within the window required to fire
+    #[serde(default = "default_threshold")]
+    pub threshold: usize,
+    /// Sliding window in seconds
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    /// Generic HTTP or Slack incoming-webhook URL
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+}
+
+impl AlertRule {
+    /// Check that the rule is well-formed
+    pub fn validate(&self) -> Result<(), String> {
+        self.compile().map(drop)
+    }
+
+    /// Check the rule and parse its query and script
+    fn compile(&self) -> Result<CompiledRule, String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        if self.threshold == 0 {
+            return Err("threshold must be at least 1".to_string());
+        }
+        if self.window_secs == 0 {
+            return Err("window_secs must be at least 1".to_string());
+        }
+        if self.window_secs > MAX_WINDOW_SECS {
+            return Err(format!("window_secs must be at most {}", MAX_WINDOW_SECS));
+        }
+        #[cfg(feature = "webhooks")]
+        if let Some(url) = &self.webhook_url {
+            check_webhook_url(url)?;
+        }
+        #[cfg(not(feature = "webhooks"))]
+        if self.webhook_url.is_some() {
+            return Err("webhook_url needs the `webhooks` feature".to_string());
+        }
+        let query = match &self.query {
+            Some(query) => Some(FilterExpr::parse(query).map_err(|e| e.to_string())?),
+            None => None,
+        };
+        Ok(CompiledRule {
+            rule: self.clone(),
+            query,
+            #[cfg(feature = "scripting")]
+            script: self
+                .script
+                .as_deref()
+                .map(crate::script::ScriptCondition::new)
+                .transpose()?,
+        })
+    }
+
+    /// Length of the sliding window, `window_secs` is bounded by `validate`
+    fn window(&self) -> Duration {
+        Duration::try_seconds(self.window_secs.min(MAX_WINDOW_SECS) as i64).unwrap_or_default()
+    }
+}
+
+/// A rule with its query and script parsed, see `AlertManager::upsert`
+#[derive(Clone)]
+struct CompiledRule {
+    rule: AlertRule,
+    query: Option<FilterExpr>,
+    #[cfg(feature = "scripting")]
+    script: Option<crate::script::ScriptCondition>,
+}
+
+impl CompiledRule {
+    /// Check whether a single event matches the rule conditions
+    fn matches(&self, event: &LogEvent) -> bool {
+        let rule = &self.rule;
+        if let Some(level) = &rule.level {
+            if level_to_number(&event.level) < level_to_number(level) {
+                return false;
+            }
+        }
+        if let Some(target) = &rule.target {
+            if event.target != *target && !event.target.starts_with(&format!("{}::", target)) {
+                return false;
+            }
+        }
+        if let Some(needle) = &rule.message_contains {
+            if !event
+                .message
+                .to_lowercase()
+                .contains(&needle.to_lowercase())
+            {
+                return false;
+            }
+        }
+        if let Some(query) = &self.query {
+            if !query.matches(event) {
+                return false;
+            }
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &self.script {
+            if !script.matches(event) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Request to mute alerts for a while (e.g. during maintenance)
+#[derive(Debug, Clone, Deserialize)]
+pub struct SilenceRequest {
+    /// Silence only this rule
+    #[serde(default)]
+    pub rule_id: Option<String>,
+    /// Silence all rules whose target is this target or below it
+    #[serde(default)]
+    pub target: Option<String>,
+    /// How long the silence lasts
+    pub duration_secs: u64,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// An active silence, rules matching it don't send notifications
+///
+/// A silence with neither `rule_id` nor `target` mutes all rules.
+#[derive(Debug, Clone, Serialize)]
+pub struct Silence {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl Silence {
+    /// Check whether the silence applies to a rule
+    pub fn applies_to(&self, rule: &AlertRule) -> bool {
+        if let Some(rule_id) = &self.rule_id {
+            if *rule_id != rule.id {
+                return false;
+            }
+        }
+        if let Some(target) = &self.target {
+            let Some(rule_target) = &rule.target else {
+                return false;
+            };
+            if rule_target != target && !rule_target.starts_with(&format!("{}::", target)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.starts_at <= now && now < self.ends_at
+    }
+}
+
+/// Result of evaluating a rule against stored events
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertEvaluation {
+    pub rule_id: String,
+    /// Matching events within the window
+    pub matched: usize,
+    pub threshold: usize,
+    pub window_secs: u64,
+    /// Whether the rule would fire right now
+    pub would_fire: bool,
+    /// Whether notifications for the rule are currently silenced
+    pub silenced: bool,
+    /// Most recent matching events
+    pub samples: Vec<LogEvent>,
+}
+
+/// Parse a webhook URL, rejecting other schemes than HTTP(S) and hosts that
+/// are loopback or link-local addresses
+///
+/// Rules come from the API, so their webhooks must not reach services only
+/// listening locally or cloud metadata endpoints.
+#[cfg(feature = "webhooks")]
+fn check_webhook_url(url: &str) -> Result<reqwest::Url, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid webhook_url: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("webhook_url must use http or https".to_string());
+    }
+    let Some(host) = parsed.host_str() else {
+        return Err("webhook_url needs a host".to_string());
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let is_local_name =
+        host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost");
+    if is_local_name || host.parse().is_ok_and(is_blocked_address) {
+        return Err(format!("webhook_url must not point to {}", host));
+    }
+    Ok(parsed)
+}
+
+/// Whether webhooks may not be posted to an address
+#[cfg(feature = "webhooks")]
+fn is_blocked_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // fe80::/10
+                || (ip.segments()[0] & 0xffc0) == 0xfe80
+                || ip
+                    .to_ipv4_mapped()
+                    .is_some_and(|ip| is_blocked_address(IpAddr::V4(ip)))
+        }
+    }
+}
+
+/// Client posting to a checked webhook URL
+///
+/// The host is resolved once and pinned, so it can't resolve to a blocked
+/// address by the time the request is sent. Redirects aren't followed.
+#[cfg(feature = "webhooks")]
+async fn webhook_client(url: &reqwest::Url) -> Result<reqwest::Client, String> {
+    let builder = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none());
+    let Some(host) = url.domain() else {
+        // IP addresses were checked with the URL
+        return builder.build().map_err(|e| e.to_string());
+    };
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<_> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("failed to resolve {}: {}", host, e))?
+        .collect();
+    if let Some(blocked) = addrs.iter().find(|addr| is_blocked_address(addr.ip())) {
+        return Err(format!("{} resolves to {}", host, blocked.ip()));
+    }
+    builder
+        .resolve_to_addrs(host, &addrs)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Outcome of a webhook delivery
+#[derive(Debug, Clone, Serialize)]
+pub struct NotificationResult {
+    pub sent: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A rule that fired while evaluating incoming events
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertFiring {
+    pub id: String,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub fired_at: DateTime<Utc>,
+    /// Matching events within the window when the rule fired
+    pub matched: usize,
+    /// No notification was sent because of a silence
+    pub silenced: bool,
+    /// Most recent matching events
+    pub samples: Vec<LogEvent>,
+    /// Outcome of the webhook delivery, `None` while pending or without webhook
+    pub notification: Option<NotificationResult>,
+}
+
+/// Recent matches of a rule during live evaluation
+#[derive(Default)]
+struct RuleWindow {
+    /// Timestamps of matching events within the window, oldest first
+    matches: VecDeque<DateTime<Utc>>,
+    samples: VecDeque<LogEvent>,
+    last_fired: Option<DateTime<Utc>>,
+}
+
+/// Payload posted to webhooks, `text` makes it render in Slack as-is
+#[cfg(feature = "webhooks")]
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    text: String,
+    rule: &'a AlertRule,
+    matched: usize,
+    test: bool,
+    events: &'a [LogEvent],
+}
+
+/// Shared collection of alert rules
+#[derive(Clone, Default)]
+pub struct AlertManager {
+    /// Replaced as a whole on changes, so evaluation clones just the `Arc`
+    rules: Arc<RwLock<Arc<[CompiledRule]>>>,
+    silences: Arc<RwLock<Vec<Silence>>>,
+    /// Live evaluation state by rule ID
+    windows: Arc<Mutex<HashMap<String, RuleWindow>>>,
+    /// Fired alerts, newest last
+    firings: Arc<RwLock<VecDeque<AlertFiring>>>,
+}
+
+impl AlertManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get all rules
+    pub fn rules(&self) -> Vec<AlertRule> {
+        self.rules.read().iter().map(|c| c.rule.clone()).collect()
+    }
+
+    /// Get a single rule by ID
+    pub fn get(&self, id: &str) -> Option<AlertRule> {
+        self.rules
+            .read()
+            .iter()
+            .find(|c| c.rule.id == id)
+            .map(|c| c.rule.clone())
+    }
+
+    /// Insert or replace a rule, generating an ID when none is set
+    ///
+    /// The rule's query and script are parsed here once, not per event.
+    pub fn upsert(&self, mut rule: AlertRule) -> Result<AlertRule, String> {
+        if rule.id.is_empty() {
+            rule.id = crate::id::new_ulid();
+        }
+        let compiled = rule.compile()?;
+
+        let mut rules = self.rules.write();
+        let mut updated = rules.to_vec();
+        match updated.iter_mut().find(|c| c.rule.id == rule.id) {
+            Some(existing) => *existing = compiled,
+            None => updated.push(compiled),
+        }
+        *rules = updated.into();
+        // Changed conditions start counting from scratch
+        self.windows.lock().remove(&rule.id);
+        Ok(rule)
+    }
+
+    /// Remove a rule, returns whether it existed
+    pub fn remove(&self, id: &str) -> bool {
+        self.windows.lock().remove(id);
+        let mut rules = self.rules.write();
+        let before = rules.len();
+        let kept: Vec<_> = rules.iter().filter(|c| c.rule.id != id).cloned().collect();
+        *rules = kept.into();
+        rules.len() != before
+    }
+
+    /// Create a silence starting now
+    pub fn silence(&self, request: SilenceRequest) -> Result<Silence, String> {
+        if request.duration_secs == 0 {
+            return Err("duration_secs must be at least 1".to_string());
+        }
+        if request.duration_secs > MAX_SILENCE_SECS {
+            return Err(format!(
+                "duration_secs must be at most {}",
+                MAX_SILENCE_SECS
+            ));
+        }
+
+        let starts_at = Utc::now();
+        let ends_at = Duration::try_seconds(request.duration_secs as i64)
+            .and_then(|duration| starts_at.checked_add_signed(duration))
+            .ok_or_else(|| "duration_secs is out of range".to_string())?;
+        let silence = Silence {
+            id: crate::id::new_ulid(),
+            rule_id: request.rule_id,
+            target: request.target,
+            starts_at,
+            ends_at,
+            comment: request.comment,
+        };
+        self.silences.write().push(silence.clone());
+        Ok(silence)
+    }
+
+    /// Get all active silences, expired ones are dropped
+    pub fn active_silences(&self) -> Vec<Silence> {
+        let now = Utc::now();
+        let mut silences = self.silences.write();
+        silences.retain(|s| s.ends_at > now);
+        silences
+            .iter()
+            .filter(|s| s.is_active(now))
+            .cloned()
+            .collect()
+    }
+
+    /// Remove a silence before it expires, returns whether it existed
+    pub fn remove_silence(&self, id: &str) -> bool {
+        let mut silences = self.silences.write();
+        let before = silences.len();
+        silences.retain(|s| s.id != id);
+        silences.len() != before
+    }
+
+    /// Check whether notifications for a rule are currently muted
+    pub fn is_silenced(&self, rule: &AlertRule) -> bool {
+        let now = Utc::now();
+        self.silences
+            .read()
+            .iter()
+            .any(|s| s.is_active(now) && s.applies_to(rule))
+    }
+
+    /// Evaluate a rule against the events currently in storage
+    pub fn evaluate(&self, rule: &AlertRule, storage: &LogStorage) -> AlertEvaluation {
+        let since = Utc::now() - rule.window();
+        let matching = match rule.compile() {
+            Ok(compiled) => {
+                storage.matching(|event| event.timestamp >= since && compiled.matches(event))
+            }
+            Err(_) => Vec::new(),
+        };
+
+        AlertEvaluation {
+            rule_id: rule.id.clone(),
+            matched: matching.len(),
+            threshold: rule.threshold,
+            window_secs: rule.window_secs,
+            would_fire: matching.len() >= rule.threshold,
+            silenced: self.is_silenced(rule),
+            samples: matching.into_iter().rev().take(MAX_SAMPLES).collect(),
+        }
+    }
+
+    /// Count an incoming event towards every rule it matches
+    ///
+    /// Returns the rules that reached their threshold with the recorded
+    /// firing. A rule fires at most once per window and starts counting anew
+    /// after firing.
+    pub(crate) fn observe(&self, event: &LogEvent) -> Vec<(AlertRule, AlertFiring)> {
+        let rules = self.rules.read().clone();
+        let mut fired = Vec::new();
+        let mut windows = self.windows.lock();
+        for compiled in rules.iter() {
+            if !compiled.matches(event) {
+                continue;
+            }
+            let rule = &compiled.rule;
+            let window = windows.entry(rule.id.clone()).or_default();
+            let length = rule.window();
+            window.matches.push_back(event.timestamp);
+            while window
+                .matches
+                .front()
+                .is_some_and(|first| *first <= event.timestamp - length)
+            {
+                window.matches.pop_front();
+            }
+            window.samples.push_back(event.clone());
+            if window.samples.len() > MAX_SAMPLES {
+                window.samples.pop_front();
+            }
+
+            let cooling_down = window
+                .last_fired
+                .is_some_and(|fired| event.timestamp - fired < length);
+            if window.matches.len() < rule.threshold || cooling_down {
+                continue;
+            }
+            let firing = AlertFiring {
+                id: crate::id::new_ulid(),
+                rule_id: rule.id.clone(),
+                rule_name: rule.name.clone(),
+                fired_at: event.timestamp,
+                matched: window.matches.len(),
+                silenced: self.is_silenced(rule),
+                samples: window.samples.iter().rev().cloned().collect(),
+                notification: None,
+            };
+            window.matches.clear();
+            window.samples.clear();
+            window.last_fired = Some(event.timestamp);
+
+            let mut firings = self.firings.write();
+            if firings.len() >= MAX_FIRINGS {
+                firings.pop_front();
+            }
+            firings.push_back(firing.clone());
+            fired.push((rule.clone(), firing));
+        }
+        fired
+    }
+
+    /// Fired alerts, newest first
+    pub fn firings(&self) -> Vec<AlertFiring> {
+        self.firings.read().iter().rev().cloned().collect()
+    }
+
+    /// Attach the outcome of a webhook delivery to a fired alert
+    fn record_notification(&self, firing_id: &str, result: NotificationResult) {
+        if let Some(firing) = self.firings.write().iter_mut().find(|f| f.id == firing_id) {
+            firing.notification = Some(result);
+        }
+    }
+
+    /// Post a notification for a rule to its webhook
+    ///
+    /// The URL is checked again before posting, see `check_webhook_url`.
+    /// Without the `webhooks` feature nothing is sent.
+    pub async fn notify(
+        &self,
+        rule: &AlertRule,
+        matched: usize,
+        events: &[LogEvent],
+        test: bool,
+    ) -> NotificationResult {
+        let Some(url) = &rule.webhook_url else {
+            return NotificationResult {
+                sent: false,
+                status: None,
+                error: Some("rule has no webhook_url".to_string()),
+            };
+        };
+        post_notification(url, rule, matched, events, test).await
+    }
+}
+
+#[cfg(feature = "webhooks")]
+async fn post_notification(
+    url: &str,
+    rule: &AlertRule,
+    matched: usize,
+    events: &[LogEvent],
+    test: bool,
+) -> NotificationResult {
+    let client = match check_webhook_url(url) {
+        Ok(url) => webhook_client(&url).await,
+        Err(e) => Err(e),
+    };
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            return NotificationResult {
+                sent: false,
+                status: None,
+                error: Some(e),
+            }
+        }
+    };
+
+    let text = format!(
+        "{}Alert '{}': {} matching events in the last {}s (threshold {})",
+        if test { "[TEST] " } else { "" },
+        rule.name,
+        matched,
+        rule.window_secs,
+        rule.threshold
+    );
+    let payload = WebhookPayload {
+        text,
+        rule,
+        matched,
+        test,
+        events,
+    };
+
+    match client.post(url).json(&payload).send().await {
+        Ok(response) => NotificationResult {
+            sent: response.status().is_success(),
+            status: Some(response.status().as_u16()),
+            error: None,
+        },
+        Err(e) => NotificationResult {
+            sent: false,
+            status: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+#[cfg(not(feature = "webhooks"))]
+async fn post_notification(
+    _url: &str,
+    _rule: &AlertRule,
+    _matched: usize,
+    _events: &[LogEvent],
+    _test: bool,
+) -> NotificationResult {
+    NotificationResult {
+        sent: false,
+        status: None,
+        error: Some("webhooks need the `webhooks` feature".to_string()),
+    }
+}
+
+/// Evaluate rules against every stored event and notify their webhooks
+///
+/// The console's own events are skipped so fired alerts can't trigger rules.
+/// Events skipped because evaluation fell behind are counted as dropped and
+/// reported in the diagnostics.
+pub(crate) async fn run(storage: LogStorage, alerts: AlertManager) {
+    let mut rx = storage.subscribe();
+    loop {
+        let event = match rx.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                storage.record_dropped(skipped);
+                storage.diagnose(LogEvent::internal(
+                    "WARN",
+                    "Alert evaluation fell behind, events were not evaluated",
+                    HashMap::from([("skipped".to_string(), skipped.to_string())]),
+                ));
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if crate::forward::is_own(&event) {
+            continue;
+        }
+
+        for (rule, firing) in alerts.observe(&event) {
+            storage.push(LogEvent {
+                level: "WARN".into(),
+                ..LogEvent::lifecycle(
+                    "alert_fired",
+                    &format!(
+                        "Alert '{}' fired: {} matching events in the last {}s",
+                        rule.name, firing.matched, rule.window_secs
+                    ),
+                    HashMap::from([
+                        ("rule_id".to_string(), rule.id.clone()),
+                        ("rule".to_string(), rule.name.clone()),
+                        ("matched".to_string(), firing.matched.to_string()),
+                        ("silenced".to_string(), firing.silenced.to_string()),
+                    ]),
+                )
+            });
+            if firing.silenced || rule.webhook_url.is_none() {
+                continue;
+            }
+
+            // Slow webhooks must not hold back evaluation
+            let alerts = alerts.clone();
+            tokio::spawn(async move {
+                let result = alerts
+                    .notify(&rule, firing.matched, &firing.samples, false)
+                    .await;
+                if !result.sent {
+                    tracing::warn!(
+                        rule = %rule.name,
+                        status = ?result.status,
+                        error = ?result.error,
+                        "Alert notification failed"
+                    );
+                }
+                alerts.record_notification(&firing.id, result);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_event(level: &str, target: &str, message: &str) -> LogEvent {
+        LogEvent {
+            id: crate::id::new_ulid(),
+            seq: 0,
+            timestamp: Utc::now(),
+            level: level.into(),
+            target: target.into(),
+            message: message.to_string(),
+            fields: HashMap::new(),
+            span: None,
+            module_path: None,
+            file: None,
+            line: None,
+            source_url: None,
+            thread_name: None,
+            thread_id: None,
+            repeat_count: None,
+            is_error: false,
+            truncated: false,
+        }
+    }
+
+    fn create_rule(threshold: usize) -> AlertRule {
+        serde_json::from_value(serde_json::json!({
+            "name": "payment errors",
+            "level": "ERROR",
+            "target": "payments",
+            "threshold": threshold,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rule_matching() {
+        let rule = create_rule(1).compile().unwrap();
+        assert!(rule.matches(&create_test_event("ERROR", "payments::stripe", "declined")));
+        assert!(!rule.matches(&create_test_event("WARN", "payments", "slow")));
+        assert!(!rule.matches(&create_test_event("ERROR", "orders", "failed")));
+    }
+
+    #[test]
+    fn test_evaluate_against_storage() {
+        let manager = AlertManager::new();
+        let storage = LogStorage::new();
+        storage.push(create_test_event("ERROR", "payments", "declined"));
+        storage.push(create_test_event("ERROR", "payments", "declined again"));
+        storage.push(create_test_event("INFO", "payments", "ok"));
+
+        let rule = manager.upsert(create_rule(2)).unwrap();
+        assert!(!rule.id.is_empty());
+
+        let evaluation = manager.evaluate(&rule, &storage);
+        assert_eq!(evaluation.matched, 2);
+        assert!(evaluation.would_fire);
+        assert_eq!(evaluation.samples[0].message, "declined again");
+
+        let strict = manager.upsert(create_rule(3)).unwrap();
+        assert!(!manager.evaluate(&strict, &storage).would_fire);
+    }
+
+    #[test]
+    fn test_silences() {
+        let manager = AlertManager::new();
+        let rule = manager.upsert(create_rule(1)).unwrap();
+        let other = manager
+            .upsert(AlertRule {
+                id: String::new(),
+                target: Some("orders".to_string()),
+                ..create_rule(1)
+            })
+            .unwrap();
+
+        let silence = manager
+            .silence(SilenceRequest {
+                rule_id: None,
+                target: Some("payments".to_string()),
+                duration_secs: 60,
+                comment: Some("maintenance".to_string()),
+            })
+            .unwrap();
+
+        assert!(manager.is_silenced(&rule));
+        assert!(!manager.is_silenced(&other));
+        assert_eq!(manager.active_silences().len(), 1);
+
+        assert!(manager.remove_silence(&silence.id));
+        assert!(!manager.is_silenced(&rule));
+    }
+
+    #[test]
+    fn test_observe_fires_once_per_window() {
+        let manager = AlertManager::new();
+        let rule = manager.upsert(create_rule(2)).unwrap();
+        let at = |secs: i64, level: &str| LogEvent {
+            timestamp: Utc::now() + Duration::seconds(secs),
+            ..create_test_event(level, "payments", "declined")
+        };
+
+        assert!(manager.observe(&at(0, "ERROR")).is_empty());
+        assert!(manager.observe(&at(1, "INFO")).is_empty());
+        let fired = manager.observe(&at(2, "ERROR"));
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].0.id, rule.id);
+        assert_eq!(fired[0].1.matched, 2);
+        assert_eq!(fired[0].1.samples.len(), 2);
+
+        // Reaching the threshold again within the window doesn't re-fire
+        assert!(manager.observe(&at(3, "ERROR")).is_empty());
+        assert!(manager.observe(&at(4, "ERROR")).is_empty());
+        assert!(manager.observe(&at(70, "ERROR")).is_empty());
+        assert_eq!(manager.observe(&at(71, "ERROR")).len(), 1);
+        assert_eq!(manager.firings().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_rule_is_rejected() {
+        let manager = AlertManager::new();
+        assert!(manager.upsert(create_rule(0)).is_err());
+        let unparsable = AlertRule {
+            query: Some("level>=".to_string()),
+            ..create_rule(1)
+        };
+        assert!(manager.upsert(unparsable).is_err());
+        let endless = AlertRule {
+            window_secs: u64::MAX,
+            ..create_rule(1)
+        };
+        assert!(manager.upsert(endless).is_err());
+        assert!(manager
+            .silence(SilenceRequest {
+                rule_id: None,
+                target: None,
+                duration_secs: u64::MAX,
+                comment: None,
+            })
+            .is_err());
+    }
+
+    #[cfg(feature = "webhooks")]
+    #[test]
+    fn test_webhooks_must_not_reach_local_addresses() {
+        for url in [
+            "http://127.0.0.1:8080/admin",
+            "http://localhost/",
+            "http://[::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0/",
+            "file:///etc/passwd",
+        ] {
+            assert!(check_webhook_url(url).is_err(), "{}", url);
+        }
+        assert!(check_webhook_url("https://hooks.slack.com/services/x").is_ok());
+        assert!(check_webhook_url("http://10.0.0.5:9000/hook").is_ok());
+
+        let manager = AlertManager::new();
+        let rule = AlertRule {
+            webhook_url: Some("http://127.0.0.1:9/".to_string()),
+            ..create_rule(1)
+        };
+        assert!(manager.upsert(rule).is_err());
+    }
+}
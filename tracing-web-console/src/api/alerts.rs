@@ -0,0 +1,168 @@
+//! Alerts API for managing and testing alert rules
+
+use crate::alerts::{
+    AlertEvaluation, AlertFiring, AlertRule, NotificationResult, Silence, SilenceRequest,
+};
+use crate::api::error::ApiError;
+use crate::api::logs::LogsState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Response for GET /api/alerts/rules
+#[derive(Debug, Serialize)]
+pub struct RulesResponse {
+    pub rules: Vec<AlertRule>,
+}
+
+/// Response for GET /api/alerts
+#[derive(Debug, Serialize)]
+pub struct FiringsResponse {
+    /// Fired alerts, newest first
+    pub firings: Vec<AlertFiring>,
+}
+
+/// Response for GET /api/alerts/silences
+#[derive(Debug, Serialize)]
+pub struct SilencesResponse {
+    pub silences: Vec<Silence>,
+}
+
+/// Query parameters for POST /api/alerts/rules/{id}/test
+#[derive(Debug, Default, Deserialize)]
+pub struct TestRuleQuery {
+    /// Also send a test notification to the rule's webhook
+    #[serde(default)]
+    pub notify: bool,
+}
+
+/// Response for POST /api/alerts/rules/{id}/test
+#[derive(Debug, Serialize)]
+pub struct TestRuleResponse {
+    #[serde(flatten)]
+    pub evaluation: AlertEvaluation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification: Option<NotificationResult>,
+}
+
+fn rule_not_found(id: &str) -> Response {
+    ApiError::not_found("alert rule", id).into_response()
+}
+
+/// GET /api/alerts - Recently fired alerts with their notification outcome
+pub async fn list_firings(State(state): State<Arc<LogsState>>) -> Response {
+    let firings = state.alerts.firings();
+    Json(FiringsResponse { firings }).into_response()
+}
+
+/// GET /api/alerts/rules - List all alert rules
+pub async fn list_rules(State(state): State<Arc<LogsState>>) -> Response {
+    let rules = state.alerts.rules();
+    Json(RulesResponse { rules }).into_response()
+}
+
+/// POST /api/alerts/rules - Create or replace an alert rule
+///
+/// Also served at POST /api/alerts.
+pub async fn upsert_rule(
+    State(state): State<Arc<LogsState>>,
+    Json(rule): Json<AlertRule>,
+) -> Response {
+    match state.alerts.upsert(rule) {
+        Ok(rule) => (StatusCode::CREATED, Json(rule)).into_response(),
+        Err(e) => ApiError::BadRequest(format!("invalid alert rule: {}", e)).into_response(),
+    }
+}
+
+/// GET /api/alerts/rules/{id} - Get a single alert rule
+pub async fn get_rule(State(state): State<Arc<LogsState>>, Path(id): Path<String>) -> Response {
+    match state.alerts.get(&id) {
+        Some(rule) => Json(rule).into_response(),
+        None => rule_not_found(&id),
+    }
+}
+
+/// DELETE /api/alerts/rules/{id} - Delete an alert rule
+pub async fn delete_rule(State(state): State<Arc<LogsState>>, Path(id): Path<String>) -> Response {
+    if state.alerts.remove(&id) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        rule_not_found(&id)
+    }
+}
+
+/// POST /api/alerts/rules/{id}/test - Evaluate a rule against stored events
+///
+/// With `?notify=true` a test notification is sent to the rule's webhook
+/// regardless of whether the rule would fire.
+pub async fn test_rule(
+    State(state): State<Arc<LogsState>>,
+    Path(id): Path<String>,
+    Query(query): Query<TestRuleQuery>,
+) -> Response {
+    let Some(rule) = state.alerts.get(&id) else {
+        return rule_not_found(&id);
+    };
+
+    let evaluation = {
+        let (alerts, storage, rule) = (state.alerts.clone(), state.storage.clone(), rule.clone());
+        match super::blocking(move || alerts.evaluate(&rule, &storage)).await {
+            Ok(evaluation) => evaluation,
+            Err(response) => return response,
+        }
+    };
+    let notification = if query.notify {
+        Some(
+            state
+                .alerts
+                .notify(&rule, evaluation.matched, &evaluation.samples, true)
+                .await,
+        )
+    } else {
+        None
+    };
+
+    Json(TestRuleResponse {
+        evaluation,
+        notification,
+    })
+    .into_response()
+}
+
+/// GET /api/alerts/silences - List active silences
+pub async fn list_silences(State(state): State<Arc<LogsState>>) -> Response {
+    let silences = state.alerts.active_silences();
+    Json(SilencesResponse { silences }).into_response()
+}
+
+/// POST /api/alerts/silences - Mute a rule, all rules below a target, or everything
+pub async fn create_silence(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<SilenceRequest>,
+) -> Response {
+    if let Some(rule_id) = &request.rule_id {
+        if state.alerts.get(rule_id).is_none() {
+            return rule_not_found(rule_id);
+        }
+    }
+
+    match state.alerts.silence(request) {
+        Ok(silence) => (StatusCode::CREATED, Json(silence)).into_response(),
+        Err(e) => ApiError::BadRequest(format!("invalid silence: {}", e)).into_response(),
+    }
+}
+
+/// DELETE /api/alerts/silences/{id} - End a silence early
+pub async fn delete_silence(
+    State(state): State<Arc<LogsState>>,
+    Path(id): Path<String>,
+) -> Response {
+    if state.alerts.remove_silence(&id) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        ApiError::not_found("silence", id).into_response()
+    }
+}
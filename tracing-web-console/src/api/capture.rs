@@ -0,0 +1,26 @@
+//! Capture API for freezing the buffer while investigating an incident
+
+use super::logs::LogsState;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use std::sync::Arc;
+
+/// GET /api/capture - Whether capture is paused
+pub async fn get_capture(State(state): State<Arc<LogsState>>) -> Response {
+    Json(state.storage.capture_status()).into_response()
+}
+
+/// POST /api/capture/pause - Stop storing new events until resumed
+///
+/// Pausing a paused console changes nothing.
+pub async fn pause_capture(State(state): State<Arc<LogsState>>) -> Response {
+    state.storage.pause();
+    Json(state.storage.capture_status()).into_response()
+}
+
+/// POST /api/capture/resume - Store new events again
+pub async fn resume_capture(State(state): State<Arc<LogsState>>) -> Response {
+    state.storage.resume();
+    Json(state.storage.capture_status()).into_response()
+}
@@ -0,0 +1,198 @@
+//! Runtime configuration API
+
+use crate::access::Role;
+use crate::api::error::ApiError;
+use crate::api::logs::LogsState;
+use crate::api::meta::{auth_info, AuthInfo};
+use crate::storage::LogEvent;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Optional features of this console, for the frontend to detect
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    /// A storage backend keeps events across restarts, `history` queries work
+    pub persistence: bool,
+    /// Evicted events are kept in spill files and still queried
+    pub spill: bool,
+    /// `GET /api/metrics` is served
+    pub metrics: bool,
+}
+
+/// Response for GET /api/config, also injected into index.html as
+/// `window.__TRACING_CONSOLE__`
+#[derive(Debug, Serialize)]
+pub struct ConsoleConfig {
+    pub version: &'static str,
+    /// Path browsers reach the console at, without a trailing slash
+    pub base_path: String,
+    pub ws_path: String,
+    pub sse_path: String,
+    /// Absolute stream URLs as seen by the client, `wss` and `https` when the
+    /// request arrived over TLS according to `X-Forwarded-Proto`, missing
+    /// without a `Host` header
+    pub ws_url: Option<String>,
+    pub sse_url: Option<String>,
+    pub auth: AuthInfo,
+    /// Role of the token the client presented, `None` without a valid token
+    pub role: Option<Role>,
+    pub capabilities: Capabilities,
+}
+
+/// First value of a header that may be repeated or comma-separated
+fn first_header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .split(',')
+        .map(str::trim)
+        .find(|value| !value.is_empty())
+}
+
+/// Configuration of a console as seen by the client sending `headers`
+///
+/// `X-Forwarded-*` headers are only followed if the console trusts them.
+pub(crate) fn console_config(state: &LogsState, headers: &HeaderMap) -> ConsoleConfig {
+    let ws_path = format!("{}/api/ws", state.base_path);
+    let sse_path = format!("{}/api/sse", state.base_path);
+    let forwarded = |name| {
+        state
+            .trust_forwarded_headers
+            .then(|| first_header_value(headers, name))
+            .flatten()
+    };
+    let tls =
+        forwarded("x-forwarded-proto").is_some_and(|proto| proto.eq_ignore_ascii_case("https"));
+    let host = forwarded("x-forwarded-host").or_else(|| first_header_value(headers, "host"));
+    let (ws_scheme, http_scheme) = if tls {
+        ("wss", "https")
+    } else {
+        ("ws", "http")
+    };
+
+    ConsoleConfig {
+        version: env!("CARGO_PKG_VERSION"),
+        base_path: state.base_path.clone(),
+        ws_url: host.map(|host| format!("{}://{}{}", ws_scheme, host, ws_path)),
+        sse_url: host.map(|host| format!("{}://{}{}", http_scheme, host, sse_path)),
+        ws_path,
+        sse_path,
+        auth: auth_info(state),
+        role: state.access.role_of(headers),
+        capabilities: Capabilities {
+            persistence: state.backend.is_some(),
+            spill: state.storage.is_spilling(),
+            metrics: state.metrics,
+        },
+    }
+}
+
+/// GET /api/config - Base path, stream URLs, capabilities and version, for
+/// the frontend to build URLs and detect features
+pub async fn get_config(State(state): State<Arc<LogsState>>, headers: HeaderMap) -> Response {
+    Json(console_config(&state, &headers)).into_response()
+}
+
+/// Request body for PUT /api/config/capacity
+#[derive(Debug, Deserialize)]
+pub struct CapacityRequest {
+    /// New maximum number of stored events
+    pub capacity: usize,
+}
+
+/// Response for PUT /api/config/capacity
+#[derive(Debug, Serialize)]
+pub struct CapacityResponse {
+    pub capacity: usize,
+    pub previous_capacity: usize,
+    /// Lower than `capacity` while a memory limit is enforced
+    pub effective_capacity: usize,
+    /// Events dropped because the buffer shrank
+    pub evicted: usize,
+}
+
+/// PUT /api/config/capacity - Grow or shrink the buffer without a restart
+pub async fn set_capacity(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<CapacityRequest>,
+) -> Response {
+    if request.capacity == 0 {
+        return ApiError::BadRequest("capacity must be at least 1".to_string()).into_response();
+    }
+
+    let storage = state.storage.clone();
+    let previous_capacity = storage.capacity();
+    let capacity = request.capacity;
+    // Shrinking drains the buffer under the write lock
+    let evicted = match super::blocking(move || storage.set_capacity(capacity)).await {
+        Ok(evicted) => evicted,
+        Err(response) => return response,
+    };
+
+    state.storage.push(LogEvent::lifecycle(
+        "capacity_reconfigured",
+        "Buffer capacity changed through the API",
+        HashMap::from([
+            ("reason".to_string(), "api".to_string()),
+            ("capacity".to_string(), capacity.to_string()),
+            (
+                "previous_capacity".to_string(),
+                previous_capacity.to_string(),
+            ),
+            ("evicted".to_string(), evicted.to_string()),
+        ]),
+    ));
+
+    Json(CapacityResponse {
+        capacity,
+        previous_capacity,
+        effective_capacity: state.storage.effective_capacity(),
+        evicted,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LogStorage;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_config_follows_forwarded_headers() {
+        let mut state = LogsState::new(LogStorage::new());
+        state.base_path = "/myapp/tracing".to_string();
+
+        let config = console_config(&state, &HeaderMap::new());
+        assert_eq!(config.ws_path, "/myapp/tracing/api/ws");
+        assert!(config.ws_url.is_none());
+        assert!(!config.capabilities.persistence);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("10.0.0.5:3000"));
+        headers.insert("x-forwarded-host", HeaderValue::from_static("example.com"));
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("https, http"));
+        let config = console_config(&state, &headers);
+        assert_eq!(
+            config.ws_url.as_deref(),
+            Some("ws://10.0.0.5:3000/myapp/tracing/api/ws")
+        );
+
+        state.trust_forwarded_headers = true;
+        let config = console_config(&state, &headers);
+        assert_eq!(
+            config.ws_url.as_deref(),
+            Some("wss://example.com/myapp/tracing/api/ws")
+        );
+        assert_eq!(
+            config.sse_url.as_deref(),
+            Some("https://example.com/myapp/tracing/api/sse")
+        );
+    }
+}
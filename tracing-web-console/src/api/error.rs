@@ -0,0 +1,199 @@
+//! Error type and JSON error bodies shared by all API endpoints
+
+use crate::expr::ExprError;
+use axum::extract::Request;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Largest plain-text error body converted to JSON by `json_errors`
+const MAX_TEXT_ERROR_LEN: usize = 64 * 1024;
+
+/// Error returned by API endpoints
+///
+/// Rendered as `{"code": "...", "message": "...", "details": {...}}` with a
+/// matching status code. `code` is stable and meant for programmatic handling,
+/// `message` is human readable.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// The request is malformed or fails validation
+    #[error("{0}")]
+    BadRequest(String),
+    /// A filter expression failed to parse
+    #[error("invalid query: {0}")]
+    InvalidQuery(#[from] ExprError),
+    /// The addressed resource doesn't exist
+    #[error("{resource} not found: {id}")]
+    NotFound { resource: &'static str, id: String },
+    /// Missing or invalid credentials
+    #[error("{0}")]
+    Unauthorized(String),
+    /// The presented token's role doesn't allow the request
+    #[error("{0}")]
+    Forbidden(String),
+    /// A state-changing request was sent to a read-only console
+    #[error("this console is read-only")]
+    ReadOnly,
+    /// A background task failed
+    #[error("internal error: {0}")]
+    Internal(String),
+    /// A request rejected before reaching a handler, e.g. invalid JSON
+    #[error("{message}")]
+    Rejected { status: StatusCode, message: String },
+}
+
+/// JSON body of every API error response
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl ApiError {
+    /// HTTP status of the error
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) | ApiError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound { .. } => StatusCode::NOT_FOUND,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) | ApiError::ReadOnly => StatusCode::FORBIDDEN,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Rejected { status, .. } => *status,
+        }
+    }
+
+    /// Stable machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::InvalidQuery(_) => "invalid_query",
+            ApiError::NotFound { .. } => "not_found",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::ReadOnly => "read_only",
+            ApiError::Internal(_) => "internal",
+            ApiError::Rejected { status, .. } => match *status {
+                StatusCode::BAD_REQUEST => "bad_request",
+                StatusCode::UNAUTHORIZED => "unauthorized",
+                StatusCode::FORBIDDEN => "forbidden",
+                StatusCode::NOT_FOUND => "not_found",
+                StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+                StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+                StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+                StatusCode::UNPROCESSABLE_ENTITY => "unprocessable_entity",
+                status if status.is_server_error() => "internal",
+                _ => "rejected",
+            },
+        }
+    }
+
+    fn details(&self) -> Option<Value> {
+        match self {
+            ApiError::InvalidQuery(e) => Some(serde_json::json!({ "clause": e.clause() })),
+            ApiError::NotFound { resource, id } => {
+                Some(serde_json::json!({ "resource": resource, "id": id }))
+            }
+            _ => None,
+        }
+    }
+
+    /// JSON body of the error, for channels other than HTTP responses
+    pub fn body(&self) -> ErrorBody {
+        ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            details: self.details(),
+        }
+    }
+
+    /// Shorthand for a `NotFound` error
+    pub fn not_found(resource: &'static str, id: impl Into<String>) -> Self {
+        ApiError::NotFound {
+            resource,
+            id: id.into(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self.body())).into_response()
+    }
+}
+
+/// Turn plain-text error responses, such as extractor rejections, into JSON
+/// error bodies so clients only have to handle one format
+pub(crate) async fn json_errors(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if is_json || !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    let body = axum::body::to_bytes(response.into_body(), MAX_TEXT_ERROR_LEN)
+        .await
+        .unwrap_or_default();
+    let message = match String::from_utf8_lossy(&body).trim() {
+        "" => status
+            .canonical_reason()
+            .unwrap_or("request failed")
+            .to_string(),
+        text => text.to_string(),
+    };
+    ApiError::Rejected { status, message }.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_json(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_error_body() {
+        let response = ApiError::not_found("alert rule", "abc").into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = body_json(response).await;
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["message"], "alert rule not found: abc");
+        assert_eq!(body["details"]["id"], "abc");
+    }
+
+    #[tokio::test]
+    async fn test_plain_text_errors_become_json() {
+        use axum::routing::post;
+        use tower::ServiceExt;
+
+        let app = axum::Router::new()
+            .route("/", post(|Json(_): Json<Value>| async {}))
+            .layer(axum::middleware::from_fn(json_errors));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from("{not json"))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = body_json(response).await;
+        assert_eq!(body["code"], "bad_request");
+        assert!(!body["message"].as_str().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,48 @@
+//! Filter preset API shared by everyone using the UI
+
+use crate::api::error::ApiError;
+use crate::api::logs::LogsState;
+use crate::presets::FilterPreset;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Response for GET /api/filters
+#[derive(Debug, Serialize)]
+pub struct PresetsResponse {
+    pub presets: Vec<FilterPreset>,
+}
+
+/// GET /api/filters - List the saved filter presets
+pub async fn list_presets(State(state): State<Arc<LogsState>>) -> Response {
+    Json(PresetsResponse {
+        presets: state.presets.list(),
+    })
+    .into_response()
+}
+
+/// POST /api/filters - Save a preset, replacing the one with the same name
+pub async fn upsert_preset(
+    State(state): State<Arc<LogsState>>,
+    Json(preset): Json<FilterPreset>,
+) -> Response {
+    match state.presets.upsert(preset) {
+        Ok(preset) => (StatusCode::CREATED, Json(preset)).into_response(),
+        Err(e) => ApiError::BadRequest(format!("invalid preset: {}", e)).into_response(),
+    }
+}
+
+/// DELETE /api/filters/{name} - Delete a preset
+pub async fn delete_preset(
+    State(state): State<Arc<LogsState>>,
+    Path(name): Path<String>,
+) -> Response {
+    if state.presets.remove(&name) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        ApiError::not_found("preset", name).into_response()
+    }
+}
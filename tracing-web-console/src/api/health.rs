@@ -0,0 +1,135 @@
+//! Health API reporting whether the console receives events
+
+use crate::api::logs::LogsState;
+use crate::storage::LogEvent;
+use crate::subscriber::{capture_active, installed_by_console};
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Default number of diagnostic events returned
+const DEFAULT_DIAGNOSTICS_LIMIT: usize = 100;
+
+/// How to feed the consoles when the application sets its own subscriber
+pub(crate) const CAPTURE_REMEDY: &str = "tracing_subscriber::registry()\
+    .with(tracing_web_console::capture_layer())\
+    .with(/* your layers */)\
+    .init()";
+
+/// State of event capture
+#[derive(Debug, Serialize)]
+pub struct CaptureHealth {
+    /// A capture layer feeds the consoles
+    pub active: bool,
+    /// The global subscriber is the one installed by the console, false if
+    /// the application set its own
+    pub installed_by_console: bool,
+}
+
+/// Response for GET /api/health
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    /// "ok", or "degraded" with `warnings` explaining why
+    pub status: &'static str,
+    pub capture: CaptureHealth,
+    pub stored_events: usize,
+    pub capacity: usize,
+    pub warnings: Vec<String>,
+    /// How to fix a missing capture layer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remedy: Option<&'static str>,
+}
+
+fn health(state: &LogsState, capture: CaptureHealth) -> HealthResponse {
+    let mut warnings = Vec::new();
+    if !capture.active {
+        warnings.push(
+            "no capture layer is active, another global subscriber was set before the console's"
+                .to_string(),
+        );
+    }
+    HealthResponse {
+        status: if warnings.is_empty() {
+            "ok"
+        } else {
+            "degraded"
+        },
+        remedy: (!capture.active).then_some(CAPTURE_REMEDY),
+        capture,
+        stored_events: state.storage.len(),
+        capacity: state.storage.capacity(),
+        warnings,
+    }
+}
+
+/// GET /api/health - Whether events are captured, with a remedy if not
+pub async fn get_health(State(state): State<Arc<LogsState>>) -> Response {
+    let capture = CaptureHealth {
+        active: capture_active(),
+        installed_by_console: installed_by_console(),
+    };
+    Json(health(&state, capture)).into_response()
+}
+
+/// Query parameters for GET /api/diagnostics
+#[derive(Debug, Default, Deserialize)]
+pub struct DiagnosticsQuery {
+    pub limit: Option<usize>,
+}
+
+/// Response for GET /api/diagnostics
+#[derive(Debug, Serialize)]
+pub struct DiagnosticsResponse {
+    /// Newest first
+    pub events: Vec<LogEvent>,
+    pub stored: usize,
+    pub capacity: usize,
+}
+
+/// GET /api/diagnostics - The console's own warnings and errors, kept apart
+/// from captured events
+pub async fn get_diagnostics(
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<DiagnosticsQuery>,
+) -> Response {
+    let diagnostics = state.storage.diagnostics();
+    Json(DiagnosticsResponse {
+        events: diagnostics.recent(query.limit.unwrap_or(DEFAULT_DIAGNOSTICS_LIMIT)),
+        stored: diagnostics.len(),
+        capacity: diagnostics.capacity(),
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LogStorage;
+
+    #[test]
+    fn test_inactive_capture_is_degraded() {
+        let state = LogsState::new(LogStorage::new());
+        let response = health(
+            &state,
+            CaptureHealth {
+                active: false,
+                installed_by_console: false,
+            },
+        );
+        assert_eq!(response.status, "degraded");
+        assert_eq!(response.warnings.len(), 1);
+        assert!(response.remedy.unwrap().contains("capture_layer()"));
+
+        let response = health(
+            &state,
+            CaptureHealth {
+                active: true,
+                installed_by_console: false,
+            },
+        );
+        assert_eq!(response.status, "ok");
+        assert!(response.remedy.is_none());
+    }
+}
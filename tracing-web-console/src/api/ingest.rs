@@ -0,0 +1,438 @@
+//! Ingest API for pushing plain-text log lines and configuring line patterns
+
+use crate::api::error::ApiError;
+use crate::api::logs::LogsState;
+use crate::forward::{ForwardedEvents, INSTANCE_FIELD};
+use crate::ingest::grok::normalize_level;
+use crate::ingest::tokens::{IngestToken, SOURCE_FIELD, TOKEN_ID_FIELD};
+use crate::ingest::LinePattern;
+use crate::limits::truncate_string;
+use crate::storage::{field_text, LogEvent};
+use axum::extract::{Path, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+/// Longest accepted instance name in bytes
+const MAX_INSTANCE_LEN: usize = 128;
+/// Fields kept per forwarded event, the rest are dropped
+const MAX_FORWARDED_FIELDS: usize = 64;
+/// Longest kept field name in bytes, fields with longer names are dropped
+const MAX_FIELD_NAME_LEN: usize = 128;
+/// Longest forwarded message and field value in bytes, longer ones are cut
+const MAX_FORWARDED_MESSAGE_LEN: usize = 64 * 1024;
+const MAX_FORWARDED_VALUE_LEN: usize = 8 * 1024;
+
+/// Request body for POST /api/ingest/lines
+#[derive(Debug, Deserialize)]
+pub struct IngestLinesRequest {
+    /// Source label used as the target when the pattern doesn't capture one
+    pub source: String,
+    /// Raw log lines
+    pub lines: Vec<String>,
+}
+
+/// Response for POST /api/ingest/lines
+#[derive(Debug, Serialize)]
+pub struct IngestLinesResponse {
+    pub ingested: usize,
+}
+
+/// Response for POST /api/ingest/events
+#[derive(Debug, Serialize)]
+pub struct IngestEventsResponse {
+    pub ingested: usize,
+}
+
+/// A service events were forwarded from
+#[derive(Debug, Serialize)]
+pub struct InstanceInfo {
+    pub instance: String,
+    /// Stored events of the instance
+    pub events: u64,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Response for GET /api/instances
+#[derive(Debug, Serialize)]
+pub struct InstancesResponse {
+    /// Sorted by name
+    pub instances: Vec<InstanceInfo>,
+}
+
+/// Request and response body for /api/ingest/patterns
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PatternsBody {
+    /// Grok (`%{LOGLEVEL:level}`) or plain regex patterns, first match wins
+    pub patterns: Vec<String>,
+}
+
+/// Request body for POST /api/ingest/tokens
+#[derive(Debug, Deserialize)]
+pub struct IssueTokenRequest {
+    /// Identity stamped on events ingested with the token, e.g. "edge-eu-1"
+    pub source: String,
+}
+
+/// Response for GET /api/ingest/tokens
+#[derive(Debug, Serialize)]
+pub struct TokensResponse {
+    pub tokens: Vec<IngestToken>,
+}
+
+/// Check the bearer token of an ingest request
+///
+/// Returns `None` while ingestion is open, before any token was issued.
+fn authenticate(state: &LogsState, headers: &HeaderMap) -> Result<Option<IngestToken>, ApiError> {
+    if !state.ingest_tokens.is_enforced() {
+        return Ok(None);
+    }
+    let secret = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::Unauthorized("missing bearer token".to_string()))?;
+    state
+        .ingest_tokens
+        .authenticate(secret.trim())
+        .map(Some)
+        .ok_or_else(|| ApiError::Unauthorized("invalid or revoked token".to_string()))
+}
+
+/// POST /api/ingest/lines - Parse plain-text lines and store them as events
+///
+/// Once ingest tokens are issued, requests need `Authorization: Bearer <secret>`
+/// and events are stamped with the token's source.
+pub async fn ingest_lines(
+    State(state): State<Arc<LogsState>>,
+    headers: HeaderMap,
+    Json(request): Json<IngestLinesRequest>,
+) -> Response {
+    let token = match authenticate(&state, &headers) {
+        Ok(token) => token,
+        Err(e) => return e.into_response(),
+    };
+
+    let ingested = request.lines.len();
+    for line in &request.lines {
+        let mut event = state.line_parser.parse(line, &request.source);
+        if let Some(token) = &token {
+            event
+                .fields
+                .insert(SOURCE_FIELD.into(), token.source.clone().into());
+            event
+                .fields
+                .insert(TOKEN_ID_FIELD.into(), token.id.clone().into());
+        }
+        state.storage.push(event);
+    }
+
+    Json(IngestLinesResponse { ingested }).into_response()
+}
+
+/// Drop excess fields and cut long values of a forwarded event
+fn limit_forwarded(event: &mut LogEvent) {
+    event.truncated |= truncate_string(&mut event.message, MAX_FORWARDED_MESSAGE_LEN);
+
+    let before = event.fields.len();
+    event
+        .fields
+        .retain(|name, _| name.len() <= MAX_FIELD_NAME_LEN);
+    if event.fields.len() > MAX_FORWARDED_FIELDS {
+        // Keep the same fields whatever the map's order
+        let mut names: Vec<_> = event.fields.keys().cloned().collect();
+        names.sort_unstable();
+        for name in &names[MAX_FORWARDED_FIELDS..] {
+            event.fields.remove(name);
+        }
+    }
+    event.truncated |= event.fields.len() < before;
+
+    for value in event.fields.values_mut() {
+        match value {
+            Value::String(text) => {
+                event.truncated |= truncate_string(text, MAX_FORWARDED_VALUE_LEN);
+            }
+            Value::Array(_) | Value::Object(_) => {
+                let mut text = value.to_string();
+                if truncate_string(&mut text, MAX_FORWARDED_VALUE_LEN) {
+                    *value = Value::String(text);
+                    event.truncated = true;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// POST /api/ingest/events - Store events forwarded by another console
+///
+/// Events keep their timestamp and are tagged with the sending `instance`.
+/// They keep their ID unless it isn't a ULID or is already stored. Excess
+/// fields are dropped and long values cut. Authenticated like
+/// `/api/ingest/lines`, a token only accepts events of the instance named
+/// by its source.
+pub async fn ingest_events(
+    State(state): State<Arc<LogsState>>,
+    headers: HeaderMap,
+    Json(request): Json<ForwardedEvents>,
+) -> Response {
+    let token = match authenticate(&state, &headers) {
+        Ok(token) => token,
+        Err(e) => return e.into_response(),
+    };
+    let instance = request.instance.trim();
+    if instance.is_empty() || instance.len() > MAX_INSTANCE_LEN {
+        return ApiError::BadRequest(format!(
+            "instance must be 1 to {} bytes long",
+            MAX_INSTANCE_LEN
+        ))
+        .into_response();
+    }
+    if let Some(token) = &token {
+        // Services can't pose as each other once tokens are enforced
+        if token.source != instance {
+            return ApiError::Forbidden(format!(
+                "token of '{}' can't forward events of '{}'",
+                token.source, instance
+            ))
+            .into_response();
+        }
+    }
+
+    // IDs already stored, or sent twice, are replaced so lookups by ID stay unambiguous
+    let mut taken: HashSet<String> = HashSet::new();
+    let sent: HashSet<String> = request
+        .events
+        .iter()
+        .map(|event| event.id.to_ascii_uppercase())
+        .collect();
+    state.storage.scan(|event| {
+        let id = event.id.to_ascii_uppercase();
+        if sent.contains(&id) {
+            taken.insert(id);
+        }
+    });
+
+    let ingested = request.events.len();
+    for mut event in request.events {
+        if !crate::id::is_valid_ulid(&event.id) || !taken.insert(event.id.to_ascii_uppercase()) {
+            event.id = crate::id::new_ulid();
+        }
+        // Levels are trusted by the pages showing them, only known ones are kept
+        event.level = normalize_level(&event.level).into();
+        limit_forwarded(&mut event);
+        event.fields.insert(INSTANCE_FIELD.into(), instance.into());
+        if let Some(token) = &token {
+            event
+                .fields
+                .insert(SOURCE_FIELD.into(), token.source.clone().into());
+            event
+                .fields
+                .insert(TOKEN_ID_FIELD.into(), token.id.clone().into());
+        }
+        state.storage.push(event);
+    }
+
+    Json(IngestEventsResponse { ingested }).into_response()
+}
+
+/// GET /api/instances - Services with forwarded events in the buffer
+pub async fn list_instances(State(state): State<Arc<LogsState>>) -> Response {
+    let storage = state.storage.clone();
+    let instances = super::blocking(move || {
+        let mut instances: BTreeMap<String, (u64, DateTime<Utc>)> = BTreeMap::new();
+        storage.scan(|event| {
+            if let Some(instance) = event.fields.get(INSTANCE_FIELD) {
+                let entry = instances
+                    .entry(field_text(instance).into_owned())
+                    .or_insert((0, event.timestamp));
+                entry.0 += 1;
+                entry.1 = entry.1.max(event.timestamp);
+            }
+        });
+        instances
+    })
+    .await;
+    match instances {
+        Ok(instances) => Json(InstancesResponse {
+            instances: instances
+                .into_iter()
+                .map(|(instance, (events, last_seen))| InstanceInfo {
+                    instance,
+                    events,
+                    last_seen,
+                })
+                .collect(),
+        })
+        .into_response(),
+        Err(response) => response,
+    }
+}
+
+/// GET /api/ingest/patterns - Get the configured line patterns
+pub async fn get_patterns(State(state): State<Arc<LogsState>>) -> Response {
+    let patterns = state
+        .line_parser
+        .patterns()
+        .into_iter()
+        .map(|p| p.pattern)
+        .collect();
+    Json(PatternsBody { patterns }).into_response()
+}
+
+/// PUT /api/ingest/patterns - Replace the line patterns
+pub async fn update_patterns(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<PatternsBody>,
+) -> Response {
+    let mut compiled = Vec::with_capacity(request.patterns.len());
+    for pattern in &request.patterns {
+        match LinePattern::new(pattern) {
+            Ok(p) => compiled.push(p),
+            Err(e) => {
+                return ApiError::BadRequest(format!("invalid pattern '{}': {}", pattern, e))
+                    .into_response();
+            }
+        }
+    }
+
+    state.line_parser.set_patterns(compiled);
+    Json(request).into_response()
+}
+
+/// GET /api/ingest/tokens - List issued ingest tokens, without secrets
+pub async fn list_tokens(State(state): State<Arc<LogsState>>) -> Response {
+    let tokens = state.ingest_tokens.list();
+    Json(TokensResponse { tokens }).into_response()
+}
+
+/// POST /api/ingest/tokens - Issue a token for a source, the secret is only returned once
+pub async fn issue_token(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<IssueTokenRequest>,
+) -> Response {
+    let source = request.source.trim();
+    if source.is_empty() {
+        return ApiError::BadRequest("source must not be empty".to_string()).into_response();
+    }
+    match state.ingest_tokens.issue(source) {
+        Ok(issued) => (StatusCode::CREATED, Json(issued)).into_response(),
+        Err(e) => ApiError::Internal(format!("failed to generate secret: {}", e)).into_response(),
+    }
+}
+
+/// DELETE /api/ingest/tokens/{id} - Revoke a token
+pub async fn revoke_token(State(state): State<Arc<LogsState>>, Path(id): Path<String>) -> Response {
+    if state.ingest_tokens.revoke(&id) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        ApiError::not_found("ingest token", id).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{LogEvent, LogStorage};
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_forwarded_events_are_tagged_with_instance() {
+        let state = Arc::new(LogsState::new(LogStorage::new()));
+        let event = LogEvent {
+            target: "orders::db".into(),
+            ..LogEvent::internal("ERROR", "connection lost", HashMap::new())
+        };
+        let id = event.id.clone();
+        let request = ForwardedEvents {
+            instance: "orders-api".to_string(),
+            events: vec![event],
+        };
+        let response = ingest_events(State(state.clone()), HeaderMap::new(), Json(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let stored = state.storage.get_by_id(&id).unwrap();
+        assert_eq!(stored.fields[INSTANCE_FIELD], "orders-api");
+        assert_eq!(stored.target, "orders::db");
+
+        let response = list_instances(State(state)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let listed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(listed["instances"][0]["instance"], "orders-api");
+        assert_eq!(listed["instances"][0]["events"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_levels_are_normalized() {
+        let state = Arc::new(LogsState::new(LogStorage::new()));
+        let known = LogEvent::internal("warning", "disk almost full", HashMap::new());
+        let unknown = LogEvent::internal("<img src=x onerror=alert(1)>", "hi", HashMap::new());
+        let ids = [known.id.clone(), unknown.id.clone()];
+        let request = ForwardedEvents {
+            instance: "orders-api".to_string(),
+            events: vec![known, unknown],
+        };
+        let response = ingest_events(State(state.clone()), HeaderMap::new(), Json(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(state.storage.get_by_id(&ids[0]).unwrap().level, "WARN");
+        assert_eq!(state.storage.get_by_id(&ids[1]).unwrap().level, "INFO");
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_events_get_fresh_ids_and_limits() {
+        let state = Arc::new(LogsState::new(LogStorage::new()));
+        let stored = LogEvent::internal("INFO", "already here", HashMap::new());
+        state.storage.push(stored.clone());
+
+        let fields = (0..100)
+            .map(|i| (format!("field_{:03}", i), "x".repeat(10_000)))
+            .collect();
+        let events = vec![
+            LogEvent {
+                id: stored.id.clone(),
+                ..LogEvent::internal("INFO", "same id", fields)
+            },
+            LogEvent {
+                id: "<script>".to_string(),
+                ..LogEvent::internal("INFO", "bad id", HashMap::new())
+            },
+        ];
+        let request = ForwardedEvents {
+            instance: "orders-api".to_string(),
+            events,
+        };
+        let response = ingest_events(State(state.clone()), HeaderMap::new(), Json(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(
+            state.storage.get_by_id(&stored.id).unwrap().message,
+            "already here"
+        );
+        let (events, _) =
+            state
+                .storage
+                .get_filtered(&crate::storage::LogFilter::default(), None, None);
+        assert_eq!(events.len(), 3);
+        for event in &events {
+            assert!(crate::id::is_valid_ulid(&event.id));
+        }
+        let limited = events.iter().find(|e| e.message == "same id").unwrap();
+        assert!(limited.truncated);
+        // The instance is added after the limit
+        assert_eq!(limited.fields.len(), MAX_FORWARDED_FIELDS + 1);
+        assert!(limited
+            .fields
+            .values()
+            .all(|value| field_text(value).len() <= MAX_FORWARDED_VALUE_LEN));
+    }
+}
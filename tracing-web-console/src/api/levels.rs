@@ -0,0 +1,174 @@
+//! Levels API changing what is captured at runtime
+
+use crate::api::error::ApiError;
+use crate::api::logs::LogsState;
+use crate::filter::TargetFilter;
+use crate::storage::LogEvent;
+use crate::subscriber::{current_env_filter, set_env_filter, OWN_TARGET};
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Response for GET and POST /api/levels
+#[derive(Debug, Serialize)]
+pub struct CaptureLevels {
+    /// Directives of this console, e.g. "info,my_app=debug"
+    pub filter: Option<String>,
+    /// Environment filter shared by all consoles of the process, initially
+    /// from `RUST_LOG`
+    pub env_filter: Option<String>,
+}
+
+/// Request body for POST /api/levels, fields left out stay unchanged
+#[derive(Debug, Deserialize)]
+pub struct LevelsUpdate {
+    pub filter: Option<String>,
+    pub env_filter: Option<String>,
+}
+
+fn capture_levels(state: &LogsState) -> CaptureLevels {
+    CaptureLevels {
+        filter: state
+            .capture
+            .filter_of(&state.storage)
+            .map(|f| f.to_string()),
+        env_filter: current_env_filter(),
+    }
+}
+
+/// GET /api/levels - Current capture filters
+pub async fn get_levels(State(state): State<Arc<LogsState>>) -> Response {
+    Json(capture_levels(&state)).into_response()
+}
+
+/// POST /api/levels - Change the capture filters without a restart
+pub async fn update_levels(
+    State(state): State<Arc<LogsState>>,
+    Json(update): Json<LevelsUpdate>,
+) -> Response {
+    // Validate both before applying either
+    let filter = match update.filter.as_deref().map(TargetFilter::parse) {
+        Some(Ok(filter)) => Some(filter),
+        Some(Err(e)) => {
+            return ApiError::BadRequest(format!("invalid filter: {}", e)).into_response()
+        }
+        None => None,
+    };
+    if filter.is_some() && state.capture.filter_of(&state.storage).is_none() {
+        return ApiError::BadRequest("this console isn't fed by a capture layer".into())
+            .into_response();
+    }
+    if let Some(env_filter) = &update.env_filter {
+        if let Err(e) = set_env_filter(env_filter) {
+            return ApiError::BadRequest(format!("invalid env_filter: {}", e)).into_response();
+        }
+    }
+    if let Some(filter) = filter {
+        state.capture.set_filter(&state.storage, filter);
+    }
+
+    let levels = capture_levels(&state);
+    let mut fields = HashMap::new();
+    if let Some(filter) = &levels.filter {
+        fields.insert("filter".to_string(), filter.clone());
+    }
+    if let Some(env_filter) = &levels.env_filter {
+        fields.insert("env_filter".to_string(), env_filter.clone());
+    }
+    state.storage.push(LogEvent::lifecycle(
+        "filter_changed",
+        "Capture filter changed",
+        fields,
+    ));
+
+    Json(levels).into_response()
+}
+
+/// Response for the /api/ignored-targets endpoints
+#[derive(Debug, Serialize)]
+pub struct IgnoredTargets {
+    /// Targets this console never captures, with their submodules
+    pub targets: Vec<String>,
+    /// Targets ignored regardless of the list, to avoid recursive logging
+    pub always_ignored: &'static [&'static str],
+}
+
+/// Request body for POST /api/ignored-targets
+#[derive(Debug, Deserialize)]
+pub struct IgnoreTarget {
+    pub target: String,
+}
+
+fn ignored_targets(targets: Vec<String>) -> IgnoredTargets {
+    IgnoredTargets {
+        targets,
+        always_ignored: &[OWN_TARGET],
+    }
+}
+
+/// Apply a change to the ignored targets and record it
+fn update_ignored(
+    state: &LogsState,
+    update: impl FnOnce(&mut Vec<String>) -> Result<(), ApiError>,
+) -> Response {
+    let Some(mut targets) = state.capture.ignored_targets_of(&state.storage) else {
+        return ApiError::BadRequest("this console isn't fed by a capture layer".into())
+            .into_response();
+    };
+    if let Err(e) = update(&mut targets) {
+        return e.into_response();
+    }
+    state
+        .capture
+        .set_ignored_targets(&state.storage, targets.clone());
+    state.storage.push(LogEvent::lifecycle(
+        "filter_changed",
+        "Ignored targets changed",
+        HashMap::from([("ignored_targets".to_string(), targets.join(","))]),
+    ));
+    Json(ignored_targets(targets)).into_response()
+}
+
+/// GET /api/ignored-targets - Targets this console never captures
+pub async fn get_ignored_targets(State(state): State<Arc<LogsState>>) -> Response {
+    let targets = state
+        .capture
+        .ignored_targets_of(&state.storage)
+        .unwrap_or_default();
+    Json(ignored_targets(targets)).into_response()
+}
+
+/// POST /api/ignored-targets - Stop capturing a target and its submodules
+pub async fn add_ignored_target(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<IgnoreTarget>,
+) -> Response {
+    let target = request.target.trim().to_string();
+    update_ignored(&state, |targets| {
+        if target.is_empty() {
+            return Err(ApiError::BadRequest("target must not be empty".into()));
+        }
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+        Ok(())
+    })
+}
+
+/// DELETE /api/ignored-targets/{target} - Capture a target again
+pub async fn remove_ignored_target(
+    State(state): State<Arc<LogsState>>,
+    Path(target): Path<String>,
+) -> Response {
+    update_ignored(&state, |targets| {
+        let before = targets.len();
+        targets.retain(|t| *t != target);
+        if targets.len() == before {
+            return Err(ApiError::not_found("ignored target", target));
+        }
+        Ok(())
+    })
+}
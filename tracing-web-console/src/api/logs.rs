@@ -1,46 +1,416 @@
 //! Logs API for querying logs and streaming real-time events
 
-use crate::storage::{LogEvent, LogFilter, LogStorage, SortOrder};
+use crate::access::AccessTokens;
+use crate::alerts::AlertManager;
+use crate::api::error::{ApiError, ErrorBody};
+use crate::backend::StorageBackend;
+use crate::cancel::CancelToken;
+use crate::expr::FilterExpr;
+use crate::format::{Format, FormatRegistry, MSGPACK_CONTENT_TYPE};
+use crate::ingest::{IngestTokens, LineParser};
+use crate::partition::PartitionCount;
+use crate::presets::FilterPresets;
+use crate::replay::Replays;
+use crate::storage::{LogEvent, LogFilter, LogStorage, Marker, SortOrder};
+use crate::stream_clients::ClientCounters;
+use crate::subscriber::{CaptureRegistration, CaptureRegistry};
+use crate::target_stats::TargetOverview;
+use crate::watchdog::Watchdog;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
-use axum::extract::State;
+use axum::extract::{Path, Query, State};
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::HeaderMap;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::Arc;
+use tokio::sync::mpsc;
 
 /// Shared state for logs API
 #[derive(Clone)]
 pub struct LogsState {
     pub storage: LogStorage,
+    pub line_parser: LineParser,
+    pub alerts: AlertManager,
+    pub ingest_tokens: IngestTokens,
+    pub watchdog: Watchdog,
+    /// Filter presets shared by all users of the UI
+    pub presets: FilterPresets,
+    pub formats: FormatRegistry,
+    /// Persistent store queried by `history` requests
+    pub backend: Option<Arc<dyn StorageBackend>>,
+    /// Prepared replays waiting for their WebSocket
+    pub(crate) replays: Replays,
+    /// Capture layer registry this console's filter is registered in
+    pub(crate) capture: CaptureRegistry,
+    /// Keeps the console registered while the router is in use
+    pub(crate) _registration: Option<Arc<CaptureRegistration>>,
+    /// Reject requests that change state
+    pub read_only: bool,
+    /// Tokens required for the API, open if none are configured
+    pub(crate) access: AccessTokens,
+    /// Serve `GET /api/metrics`
+    pub metrics: bool,
+    /// Build stream URLs from `X-Forwarded-Host` and `X-Forwarded-Proto`
+    pub trust_forwarded_headers: bool,
+    /// Path browsers reach the console at, the external base URL if set
+    pub base_path: String,
 }
 
 impl LogsState {
     pub fn new(storage: LogStorage) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            line_parser: LineParser::new(),
+            alerts: AlertManager::new(),
+            ingest_tokens: IngestTokens::new(),
+            watchdog: Watchdog::new(),
+            presets: FilterPresets::new(),
+            formats: FormatRegistry::new(),
+            backend: None,
+            replays: Replays::default(),
+            capture: crate::subscriber::global_registry().clone(),
+            _registration: None,
+            read_only: false,
+            access: AccessTokens::default(),
+            metrics: false,
+            trust_forwarded_headers: false,
+            base_path: String::new(),
+        }
     }
 }
 
-/// Request body for POST /api/logs
-#[derive(Debug, Deserialize)]
-pub struct LogsRequest {
-    /// Maximum number of logs to return (None = return all)
-    pub limit: Option<usize>,
-    /// Offset for pagination
-    #[serde(default)]
-    pub offset: usize,
+/// Filters shared by POST /api/logs, GET /api/export and DELETE /api/logs
+///
+/// Lists are JSON arrays in request bodies and comma-separated in query
+/// strings, `target_levels` is an object or comma-separated `target=LEVEL`
+/// pairs such as `my_crate=DEBUG,hyper=WARN`.
+#[derive(Debug, Default, Deserialize)]
+pub struct FilterParams {
     /// Global log level filter (e.g., "INFO", "DEBUG")
     pub global_level: Option<String>,
     /// Target-specific log level filters (e.g., {"my_crate": "DEBUG", "other_crate": "INFO"})
-    #[serde(default)]
+    #[serde(default, deserialize_with = "target_levels")]
     pub target_levels: HashMap<String, String>,
     /// Search filter for message content (case-insensitive)
     pub search: Option<String>,
     /// Target filter (case-insensitive contains match)
     pub target: Option<String>,
+    /// Filter expression, e.g. `span:create_order AND level>=debug`
+    pub query: Option<String>,
+    /// Only events logged at or after this RFC3339 timestamp
+    pub from: Option<DateTime<Utc>>,
+    /// Only events logged at or before this RFC3339 timestamp
+    pub to: Option<DateTime<Utc>>,
+    /// Hide events of these targets and their submodules, e.g. heartbeats
+    #[serde(default, deserialize_with = "list")]
+    pub exclude_targets: Vec<String>,
+    /// Hide events whose message contains this (case-insensitive)
+    pub exclude_search: Option<String>,
+    /// Hide events of these levels
+    #[serde(default, deserialize_with = "list")]
+    pub exclude_levels: Vec<String>,
+    /// Only events logged on the thread with this name or ID
+    pub thread: Option<String>,
+    /// Only events of this value of the `partition_by` field, "" for events
+    /// without one
+    pub partition: Option<String>,
+}
+
+/// Request body for POST /api/logs
+#[derive(Debug, Deserialize)]
+pub struct LogsRequest {
+    /// Maximum number of logs to return (None = return all)
+    pub limit: Option<usize>,
+    /// Offset for pagination
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(flatten)]
+    pub filter: FilterParams,
     /// Sort order: "newest_first" (default) or "oldest_first"
     #[serde(default)]
     pub sort_order: Option<String>,
+    /// Only return events stored after this sequence number (a previous `last_seq`),
+    /// also accepted as `since_seq`
+    #[serde(default, alias = "since_seq")]
+    pub after_seq: Option<u64>,
+    /// Continue after the page that returned this `next_cursor`
+    ///
+    /// Unlike `offset`, cursors are not shifted by events logged in between.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Query the persisted events of the storage backend instead of the
+    /// buffer, newest first and including earlier runs
+    #[serde(default)]
+    pub history: bool,
+    /// Bucket the returned events by "span" (their root span) or by the
+    /// value of a correlation field such as "request_id"
+    #[serde(default)]
+    pub group_by: Option<String>,
+}
+
+/// Query parameters for GET /api/ws
+#[derive(Debug, Default, Deserialize)]
+pub struct WsQuery {
+    /// Only stream events matching this filter expression
+    pub query: Option<String>,
+    /// Encoding of streamed events, "json" by default
+    pub format: Option<String>,
+    /// Comma-separated targets hidden from the stream, with their submodules
+    pub exclude_targets: Option<String>,
+    /// Hide events whose message contains this (case-insensitive)
+    pub exclude_search: Option<String>,
+    /// Comma-separated levels hidden from the stream
+    pub exclude_levels: Option<String>,
+    /// Only stream events of this partition, kept when the filter changes
+    pub partition: Option<String>,
+}
+
+/// Split a comma-separated query parameter, skipping empty entries
+fn split_list(list: Option<&str>) -> Vec<String> {
+    list.into_iter()
+        .flat_map(|list| list.split(','))
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// A list given as a JSON array or as a comma-separated query parameter
+fn list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    struct ListVisitor;
+
+    impl<'de> Visitor<'de> for ListVisitor {
+        type Value = Vec<String>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("a list or a comma-separated string")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            Ok(split_list(Some(value)))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut items = Vec::new();
+            while let Some(item) = seq.next_element::<String>()? {
+                items.extend(split_list(Some(&item)));
+            }
+            Ok(items)
+        }
+    }
+
+    deserializer.deserialize_any(ListVisitor)
+}
+
+/// Target levels given as a JSON object or as comma-separated `target=LEVEL`
+/// pairs such as `my_crate=DEBUG,hyper=WARN`
+fn target_levels<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, String>, D::Error> {
+    struct TargetLevelsVisitor;
+
+    impl<'de> Visitor<'de> for TargetLevelsVisitor {
+        type Value = HashMap<String, String>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("an object or comma-separated target=LEVEL pairs")
+        }
+
+        fn visit_str<E: de::Error>(self, pairs: &str) -> Result<Self::Value, E> {
+            let mut target_levels = HashMap::new();
+            for pair in split_list(Some(pairs)) {
+                let Some((target, level)) = pair.split_once('=') else {
+                    return Err(E::custom(format!("invalid target level: {}", pair)));
+                };
+                target_levels.insert(target.trim().to_string(), level.trim().to_uppercase());
+            }
+            Ok(target_levels)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut target_levels = HashMap::new();
+            while let Some((target, level)) = map.next_entry::<String, String>()? {
+                target_levels.insert(target, level.to_uppercase());
+            }
+            Ok(target_levels)
+        }
+    }
+
+    deserializer.deserialize_any(TargetLevelsVisitor)
+}
+
+/// Filter of a live stream: an expression and exclusions
+fn stream_filter(
+    expr: Option<FilterExpr>,
+    exclude_targets: Vec<String>,
+    exclude_search: Option<String>,
+    exclude_levels: Vec<String>,
+) -> LogFilter {
+    LogFilter {
+        expr,
+        exclude_targets: exclude_targets
+            .into_iter()
+            .filter(|t| !t.is_empty())
+            .collect(),
+        exclude_search: exclude_search.filter(|s| !s.is_empty()),
+        exclude_levels: exclude_levels
+            .into_iter()
+            .filter(|l| !l.is_empty())
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Filter on a partition, `partition` being a value of the storage's
+/// partition field
+fn partition_filter(
+    storage: &LogStorage,
+    partition: Option<&str>,
+) -> Result<Option<(String, String)>, Response> {
+    let Some(partition) = partition else {
+        return Ok(None);
+    };
+    match storage.partition_field() {
+        Some(field) => Ok(Some((field, partition.to_string()))),
+        None => Err(
+            ApiError::BadRequest("no partition_by field is configured".to_string()).into_response(),
+        ),
+    }
+}
+
+/// Parse the filter parameters of GET /api/ws and GET /api/sse
+pub(super) fn parse_stream_filter(
+    storage: &LogStorage,
+    query: &WsQuery,
+) -> Result<LogFilter, Response> {
+    Ok(LogFilter {
+        partition: partition_filter(storage, query.partition.as_deref())?,
+        ..stream_filter(
+            parse_expr(query.query.as_deref())?,
+            split_list(query.exclude_targets.as_deref()),
+            query.exclude_search.clone(),
+            split_list(query.exclude_levels.as_deref()),
+        )
+    })
+}
+
+/// Page size of history queries without a limit
+const DEFAULT_HISTORY_LIMIT: usize = 1_000;
+/// Delivered events remembered per WebSocket client for reconciliation
+const MAX_TRACKED_DELIVERIES: usize = 10_000;
+/// Largest backlog sent after a WebSocket filter change
+const MAX_WS_BACKLOG: usize = 1_000;
+/// Messages queued per WebSocket client before events are dropped
+const WS_QUEUE_CAPACITY: usize = 1_000;
+
+/// Message sent by WebSocket clients
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsClientMessage {
+    /// Replace the stream's filter expression, empty clears it
+    SetFilter {
+        #[serde(default)]
+        query: Option<String>,
+        /// Also send up to this many of the newest stored events matching the
+        /// new filter, to replace the client's view
+        #[serde(default)]
+        backlog: Option<usize>,
+        /// Exclusions replacing the previous ones, omitted fields clear them
+        #[serde(default)]
+        exclude_targets: Vec<String>,
+        #[serde(default)]
+        exclude_search: Option<String>,
+        #[serde(default)]
+        exclude_levels: Vec<String>,
+    },
+}
+
+/// Control message sent to WebSocket clients, next to the plain log events
+///
+/// Log events never have a `type` field, control messages always do.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsServerMessage {
+    /// The filter was changed, reconciling what was delivered under the old one
+    FilterUpdated {
+        query: Option<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        exclude_targets: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exclude_search: Option<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        exclude_levels: Vec<String>,
+        /// Sequence numbers of delivered events that don't match the new filter
+        removed: Vec<u64>,
+        /// Delivered events that were already evicted and couldn't be checked
+        unchecked: usize,
+        /// Newest matching events, oldest first, if a backlog was requested
+        #[serde(skip_serializing_if = "Option::is_none")]
+        backlog: Option<Vec<LogEvent>>,
+    },
+    /// A client message was rejected, the previous filter stays active
+    Error(ErrorBody),
+    /// All events of a replay were sent
+    ReplayFinished { events: usize },
+    /// Events were dropped because the client fell behind, sent before the
+    /// next event that is delivered
+    Dropped { count: u64 },
+}
+
+/// Re-check delivered events against a new filter
+///
+/// `delivered` is updated to the events the client should still show.
+fn reconcile_filter(
+    storage: &LogStorage,
+    delivered: &mut VecDeque<u64>,
+    filter: &LogFilter,
+    backlog: Option<usize>,
+) -> WsServerMessage {
+    let seqs: Vec<u64> = delivered.iter().copied().collect();
+    let stored = storage.get_by_seqs(&seqs);
+    let unchecked = seqs.len() - stored.len();
+
+    let (kept, removed): (Vec<_>, Vec<_>) = stored.iter().partition(|event| filter.matches(event));
+    let removed = removed.into_iter().map(|event| event.seq).collect();
+    *delivered = kept.into_iter().map(|event| event.seq).collect();
+
+    let backlog = backlog.map(|limit| {
+        let mut events = storage
+            .query(filter, Some(limit.min(MAX_WS_BACKLOG)), None)
+            .events;
+        events.reverse();
+        *delivered = events.iter().map(|event| event.seq).collect();
+        events
+    });
+
+    WsServerMessage::FilterUpdated {
+        query: filter.expr.as_ref().map(|e| e.source().to_string()),
+        exclude_targets: filter.exclude_targets.clone(),
+        exclude_search: filter.exclude_search.clone(),
+        exclude_levels: filter.exclude_levels.clone(),
+        removed,
+        unchecked,
+        backlog,
+    }
+}
+
+/// Parse an optional filter expression, empty means no filter
+fn parse_query(query: Option<&str>) -> Result<Option<FilterExpr>, ApiError> {
+    match query.map(str::trim).filter(|q| !q.is_empty()) {
+        Some(query) => Ok(Some(FilterExpr::parse(query)?)),
+        None => Ok(None),
+    }
+}
+
+/// Like `parse_query`, rendering errors as a response
+pub(super) fn parse_expr(query: Option<&str>) -> Result<Option<FilterExpr>, Response> {
+    parse_query(query).map_err(IntoResponse::into_response)
 }
 
 /// Response for GET /api/logs
@@ -48,6 +418,133 @@ pub struct LogsRequest {
 pub struct LogsResponse {
     pub logs: Vec<LogEvent>,
     pub total: usize,
+    /// Time spent executing the query in milliseconds
+    pub query_ms: f64,
+    /// Number of buffered events examined
+    pub scanned: usize,
+    /// Number of events matching the filter (same as `total`)
+    pub matched: usize,
+    /// Whether `limit` cut off matching events after this page
+    pub truncated: bool,
+    /// Highest sequence number in storage, pass as `after_seq` to poll for newer events
+    pub last_seq: u64,
+    /// Whether the result came from the query cache (nothing changed since the last identical query)
+    pub cached: bool,
+    /// Markers within the time range covered by `logs`
+    pub markers: Vec<Marker>,
+    /// Whether more matching events follow this page
+    pub has_more: bool,
+    /// Offset of the next page, if any
+    pub next_offset: Option<usize>,
+    /// Cursor of the next page, if any, stable while new events arrive
+    pub next_cursor: Option<String>,
+    /// Number of pages of `limit` events, if a limit was given
+    pub total_pages: Option<usize>,
+    /// The filters and paging parameters this response was computed with
+    pub filters: AppliedFilters,
+    /// The events of this page bucketed by `group_by`, `logs` is empty then
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<LogGroup>>,
+}
+
+/// Events sharing a root span or correlation field value
+#[derive(Debug, Clone, Serialize)]
+pub struct LogGroup {
+    /// Root span ID or field value, `None` for events without one
+    pub key: Option<String>,
+    pub count: usize,
+    /// Timestamp of the earliest event
+    pub first: DateTime<Utc>,
+    /// Timestamp of the latest event
+    pub last: DateTime<Utc>,
+    /// Number of events per level
+    pub levels: BTreeMap<String, usize>,
+    pub logs: Vec<LogEvent>,
+}
+
+/// Bucket events by `group_by`, groups in the order of their first event
+fn group_events(storage: &LogStorage, logs: Vec<LogEvent>, group_by: &str) -> Vec<LogGroup> {
+    let mut groups: Vec<LogGroup> = Vec::new();
+    let mut index: HashMap<Option<String>, usize> = HashMap::new();
+    for event in logs {
+        let key = if group_by == "span" {
+            event
+                .span
+                .as_ref()
+                .and_then(|span| span.id.as_deref())
+                .map(|id| {
+                    storage
+                        .spans()
+                        .root_id(id)
+                        .unwrap_or_else(|| id.to_string())
+                })
+        } else {
+            event
+                .fields
+                .get(group_by)
+                .map(|value| crate::storage::field_text(value).into_owned())
+                .or_else(|| {
+                    event
+                        .span
+                        .as_ref()
+                        .and_then(|span| span.field(group_by))
+                        .map(str::to_string)
+                })
+        };
+        let position = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push(LogGroup {
+                key,
+                count: 0,
+                first: event.timestamp,
+                last: event.timestamp,
+                levels: BTreeMap::new(),
+                logs: Vec::new(),
+            });
+            groups.len() - 1
+        });
+        let group = &mut groups[position];
+        group.count += 1;
+        group.first = group.first.min(event.timestamp);
+        group.last = group.last.max(event.timestamp);
+        *group.levels.entry(event.level.to_string()).or_default() += 1;
+        group.logs.push(event);
+    }
+    groups
+}
+
+/// Split `logs` into groups if requested, returns the remaining logs and the groups
+fn apply_grouping(
+    storage: &LogStorage,
+    logs: Vec<LogEvent>,
+    group_by: Option<&str>,
+) -> (Vec<LogEvent>, Option<Vec<LogGroup>>) {
+    match group_by {
+        Some(group_by) => (Vec::new(), Some(group_events(storage, logs, group_by))),
+        None => (logs, None),
+    }
+}
+
+/// Normalized filters echoed back in `LogsResponse`
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedFilters {
+    pub limit: Option<usize>,
+    pub offset: usize,
+    pub cursor: Option<String>,
+    pub global_level: Option<String>,
+    pub target_levels: HashMap<String, String>,
+    pub search: Option<String>,
+    pub target: Option<String>,
+    pub sort_order: &'static str,
+    pub after_seq: Option<u64>,
+    pub query: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub history: bool,
+    pub exclude_targets: Vec<String>,
+    pub exclude_search: Option<String>,
+    pub exclude_levels: Vec<String>,
+    pub thread: Option<String>,
+    pub partition: Option<String>,
 }
 
 /// Response for GET /api/targets
@@ -56,9 +553,46 @@ pub struct TargetsResponse {
     pub targets: Vec<String>,
 }
 
+/// Build the filter of shared filter parameters, without sorting and paging
+pub(super) fn request_filter(
+    storage: &LogStorage,
+    request: &FilterParams,
+) -> Result<LogFilter, Response> {
+    let expr = parse_expr(request.query.as_deref())?;
+    let partition = partition_filter(storage, request.partition.as_deref())?;
+    if let (Some(from), Some(to)) = (request.from, request.to) {
+        if from > to {
+            return Err(
+                ApiError::BadRequest(format!("`from` ({}) is after `to` ({})", from, to))
+                    .into_response(),
+            );
+        }
+    }
+    Ok(LogFilter {
+        global_level: request.global_level.as_ref().map(|l| l.to_uppercase()),
+        target_levels: request
+            .target_levels
+            .iter()
+            .map(|(k, v)| (k.clone(), v.to_uppercase()))
+            .collect(),
+        search: request.search.clone().filter(|s| !s.is_empty()),
+        target: request.target.clone().filter(|t| !t.is_empty()),
+        from: request.from,
+        to: request.to,
+        expr,
+        exclude_targets: request.exclude_targets.clone(),
+        exclude_search: request.exclude_search.clone().filter(|s| !s.is_empty()),
+        exclude_levels: request.exclude_levels.clone(),
+        thread: request.thread.clone().filter(|t| !t.is_empty()),
+        partition,
+        ..Default::default()
+    })
+}
+
 /// POST /api/logs - Get historical logs with optional filters
 pub async fn get_logs(
     State(state): State<Arc<LogsState>>,
+    headers: HeaderMap,
     Json(request): Json<LogsRequest>,
 ) -> Response {
     // Parse sort order from request
@@ -67,42 +601,534 @@ pub async fn get_logs(
         _ => SortOrder::NewestFirst, // Default
     };
 
-    // Build filter from request
+    let base = match request_filter(&state.storage, &request.filter) {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+    let group_by = request.group_by.clone();
+    if group_by.as_deref().is_some_and(|g| g.trim().is_empty()) {
+        return ApiError::BadRequest("group_by must not be empty".to_string()).into_response();
+    }
+
+    // A cursor is the sequence number of the last event of the previous page
+    let cursor = match request.cursor.as_deref().filter(|c| !c.is_empty()) {
+        Some(cursor) => match cursor.parse::<u64>() {
+            Ok(seq) => Some(seq),
+            Err(_) => {
+                return ApiError::BadRequest(format!("invalid cursor: {}", cursor)).into_response()
+            }
+        },
+        None => None,
+    };
+    let (after_seq, before_seq) = match (sort_order, cursor) {
+        (SortOrder::NewestFirst, Some(seq)) => (request.after_seq, Some(seq)),
+        (SortOrder::OldestFirst, Some(seq)) => {
+            (Some(request.after_seq.unwrap_or(0).max(seq)), None)
+        }
+        (_, None) => (request.after_seq, None),
+    };
+
     let filter = LogFilter {
-        global_level: request.global_level.map(|l| l.to_uppercase()),
-        target_levels: request
-            .target_levels
-            .iter()
-            .map(|(k, v)| (k.clone(), v.to_uppercase()))
-            .collect(),
-        search: request.search.filter(|s| !s.is_empty()),
-        target: request.target.filter(|t| !t.is_empty()),
         sort_order,
+        after_seq,
+        before_seq,
+        ..base
     };
+    let applied = AppliedFilters {
+        limit: request.limit,
+        offset: request.offset,
+        cursor: cursor.map(|seq| seq.to_string()),
+        global_level: filter.global_level.clone(),
+        target_levels: filter.target_levels.clone(),
+        search: filter.search.clone(),
+        target: filter.target.clone(),
+        sort_order: match sort_order {
+            SortOrder::NewestFirst => "newest_first",
+            SortOrder::OldestFirst => "oldest_first",
+        },
+        after_seq: request.after_seq,
+        query: filter.expr.as_ref().map(|e| e.source().to_string()),
+        from: request.filter.from,
+        to: request.filter.to,
+        history: request.history,
+        exclude_targets: filter.exclude_targets.clone(),
+        exclude_search: filter.exclude_search.clone(),
+        exclude_levels: filter.exclude_levels.clone(),
+        thread: filter.thread.clone(),
+        partition: request.filter.partition.clone(),
+    };
+
+    if request.history {
+        let response = history_logs(&state, filter, applied, cursor, request.limit, group_by);
+        return match response.await {
+            Ok(response) => negotiated(&headers, &response),
+            Err(response) => response,
+        };
+    }
 
     // Get filtered logs (None limit means return all)
-    let (logs, total_filtered) =
-        state
-            .storage
-            .get_filtered(&filter, request.limit, Some(request.offset));
+    let started = std::time::Instant::now();
+    let storage = state.storage.clone();
+    let (limit, offset) = (request.limit, request.offset);
+    let query =
+        move |cancel: &CancelToken| storage.query_cancellable(&filter, limit, Some(offset), cancel);
+    let result = match super::cancellable(query).await {
+        Ok(result) => result,
+        Err(response) => return response,
+    };
+    let query_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let logs = result.events;
+
+    let from = logs.iter().map(|e| e.timestamp).min();
+    let to = logs.iter().map(|e| e.timestamp).max();
+    let markers = if logs.is_empty() {
+        Vec::new()
+    } else {
+        state.storage.markers_between(from, to)
+    };
+
+    let has_more = result.truncated;
+    let next_offset = has_more.then(|| offset + logs.len());
+    let next_cursor = logs.last().filter(|_| has_more).map(|e| e.seq.to_string());
+    let total_pages = limit.filter(|l| *l > 0).map(|l| result.matched.div_ceil(l));
+    let (logs, groups) = apply_grouping(&state.storage, logs, group_by.as_deref());
 
     let response = LogsResponse {
         logs,
-        total: total_filtered,
+        total: result.matched,
+        query_ms,
+        scanned: result.scanned,
+        matched: result.matched,
+        truncated: result.truncated,
+        cached: result.cached,
+        last_seq: result.last_seq,
+        markers,
+        has_more,
+        next_offset,
+        next_cursor,
+        total_pages,
+        filters: applied,
+        groups,
     };
 
-    Json(response).into_response()
+    negotiated(&headers, &response)
+}
+
+/// Whether the client asked for MessagePack with `Accept: application/msgpack`
+fn accepts_msgpack(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|media| media.split(';').next().unwrap_or_default().trim())
+        .any(|media| {
+            media.eq_ignore_ascii_case(MSGPACK_CONTENT_TYPE)
+                || media.eq_ignore_ascii_case("application/x-msgpack")
+        })
+}
+
+/// Encode a response as MessagePack if the client accepts it, JSON otherwise
+fn negotiated<T: Serialize>(headers: &HeaderMap, body: &T) -> Response {
+    if !accepts_msgpack(headers) {
+        return Json(body).into_response();
+    }
+    match rmp_serde::to_vec_named(body) {
+        Ok(bytes) => ([(CONTENT_TYPE, MSGPACK_CONTENT_TYPE)], bytes).into_response(),
+        Err(e) => ApiError::Internal(format!("failed to encode response: {}", e)).into_response(),
+    }
+}
+
+/// Answer a `history` request from the storage backend
+///
+/// The cursor is a backend position instead of a sequence number, offsets
+/// aren't supported.
+async fn history_logs(
+    state: &LogsState,
+    filter: LogFilter,
+    mut applied: AppliedFilters,
+    cursor: Option<u64>,
+    limit: Option<usize>,
+    group_by: Option<String>,
+) -> Result<LogsResponse, Response> {
+    let Some(backend) = state.backend.clone() else {
+        return Err(
+            ApiError::BadRequest("no storage backend configured".to_string()).into_response(),
+        );
+    };
+    if applied.offset > 0 {
+        return Err(ApiError::BadRequest(
+            "history queries page with cursors, not offsets".to_string(),
+        )
+        .into_response());
+    }
+    applied.sort_order = "newest_first";
+
+    let started = std::time::Instant::now();
+    let limit = limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    let query = move |cancel: &CancelToken| {
+        crate::backend::query_history(&*backend, &filter, cursor, limit, cancel).transpose()
+    };
+    let page = match super::cancellable(query).await {
+        Ok(Ok(page)) => page,
+        Ok(Err(e)) => {
+            return Err(ApiError::Internal(format!("history query failed: {}", e)).into_response())
+        }
+        Err(response) => return Err(response),
+    };
+    let query_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let has_more = page.next_cursor.is_some();
+    let markers = if page.events.is_empty() {
+        Vec::new()
+    } else {
+        let from = page.events.iter().map(|e| e.timestamp).min();
+        let to = page.events.iter().map(|e| e.timestamp).max();
+        state.storage.markers_between(from, to)
+    };
+    let matched = page.events.len();
+    let (logs, groups) = apply_grouping(&state.storage, page.events, group_by.as_deref());
+    Ok(LogsResponse {
+        logs,
+        total: matched,
+        query_ms,
+        scanned: page.scanned,
+        matched,
+        truncated: has_more,
+        last_seq: state.storage.last_seq(),
+        cached: false,
+        markers,
+        has_more,
+        next_offset: None,
+        next_cursor: page.next_cursor.map(|position| position.to_string()),
+        total_pages: None,
+        filters: applied,
+        groups,
+    })
+}
+
+/// Response for DELETE /api/logs
+#[derive(Debug, Serialize)]
+pub struct ClearLogsResponse {
+    /// Number of removed events
+    pub cleared: usize,
+}
+
+/// DELETE /api/logs - Remove the stored events matching the filters
+///
+/// Without any parameter every event is removed.
+pub async fn clear_logs(
+    State(state): State<Arc<LogsState>>,
+    Query(params): Query<FilterParams>,
+) -> Response {
+    let filter = match request_filter(&state.storage, &params) {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+    let storage = state.storage.clone();
+    match super::blocking(move || storage.clear_filtered(&filter)).await {
+        Ok(cleared) => Json(ClearLogsResponse { cleared }).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// GET /api/logs/{id} - Get a single log event by its ID (permalink)
+pub async fn get_log_by_id(
+    State(state): State<Arc<LogsState>>,
+    Path(id): Path<String>,
+) -> Response {
+    if !crate::id::is_valid_ulid(&id) {
+        return ApiError::BadRequest(format!("invalid log ID: {}", id)).into_response();
+    }
+
+    match state.storage.get_by_id(&id) {
+        Some(event) => Json(event).into_response(),
+        None => ApiError::not_found("log event", id).into_response(),
+    }
+}
+
+/// Query parameters for GET /api/export, filtering like `LogsRequest`
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    /// Name of a registered format, "ndjson" by default
+    pub format: Option<String>,
+    /// Maximum number of events exported (None = all matching)
+    pub limit: Option<usize>,
+    /// "newest_first" (default) or "oldest_first"
+    pub sort_order: Option<String>,
+    pub after_seq: Option<u64>,
+    #[serde(flatten)]
+    pub filter: FilterParams,
+}
+
+/// Size of the body chunks an export is streamed in
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Writer handing full chunks to the response body
+struct ChunkWriter {
+    buffer: Vec<u8>,
+    tx: tokio::sync::mpsc::Sender<std::io::Result<Vec<u8>>>,
+}
+
+impl ChunkWriter {
+    fn send(&mut self) -> std::io::Result<()> {
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(EXPORT_CHUNK_SIZE));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "client disconnected"))
+    }
+}
+
+impl std::io::Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= EXPORT_CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+/// GET /api/export - Download the filtered events as a file
+pub async fn export_logs(
+    State(state): State<Arc<LogsState>>,
+    Query(request): Query<ExportQuery>,
+) -> Response {
+    use axum::http::header;
+
+    let format_name = request.format.as_deref().unwrap_or("ndjson");
+    let Some(format) = state.formats.get(format_name) else {
+        return ApiError::BadRequest(format!("unknown format: {}", format_name)).into_response();
+    };
+    let base = match request_filter(&state.storage, &request.filter) {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+    let filter = LogFilter {
+        sort_order: match request.sort_order.as_deref() {
+            Some("oldest_first") => SortOrder::OldestFirst,
+            _ => SortOrder::NewestFirst,
+        },
+        after_seq: request.after_seq,
+        ..base
+    };
+    let storage = state.storage.clone();
+    let limit = request.limit;
+    let query = move |cancel: &CancelToken| storage.query_cancellable(&filter, limit, None, cancel);
+    let events = match super::cancellable(query).await {
+        Ok(result) => result.events,
+        Err(response) => return response,
+    };
+
+    // Encoding runs on the blocking pool and stops once the client is gone
+    let (tx, rx) = tokio::sync::mpsc::channel(4);
+    let encoder = format.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ChunkWriter {
+            buffer: Vec::with_capacity(EXPORT_CHUNK_SIZE),
+            tx: tx.clone(),
+        };
+        let written = encoder
+            .write_events(&mut writer, &mut events.iter())
+            .and_then(|()| std::io::Write::flush(&mut writer));
+        if let Err(e) = written {
+            if e.kind() != std::io::ErrorKind::BrokenPipe {
+                let _ = tx.blocking_send(Err(e));
+            }
+        }
+    });
+
+    let file_name = format!(
+        "logs-{}.{}",
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ"),
+        format.extension()
+    );
+    let body = axum::body::Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(rx));
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Encode a streamed event, binary formats are sent as binary messages
+pub(super) fn encode_ws_event(format: &dyn Format, event: &LogEvent) -> std::io::Result<Message> {
+    let encoded = format.encode_event(event)?;
+    if format.is_binary() {
+        return Ok(Message::Binary(encoded.into()));
+    }
+    String::from_utf8(encoded)
+        .map(|text| Message::Text(text.into()))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Apply a message from a WebSocket client and build the reply
+async fn handle_client_message(
+    text: &str,
+    state: &LogsState,
+    filter: &mut LogFilter,
+    delivered: &mut VecDeque<u64>,
+) -> WsServerMessage {
+    let message = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => {
+            let error = ApiError::BadRequest(format!("invalid message: {}", e));
+            return WsServerMessage::Error(error.body());
+        }
+    };
+    let WsClientMessage::SetFilter {
+        query,
+        backlog,
+        exclude_targets,
+        exclude_search,
+        exclude_levels,
+    } = message;
+    let new_expr = match parse_query(query.as_deref()) {
+        Ok(new_expr) => new_expr,
+        Err(e) => return WsServerMessage::Error(e.body()),
+    };
+
+    let new_filter = LogFilter {
+        partition: filter.partition.clone(),
+        ..stream_filter(new_expr, exclude_targets, exclude_search, exclude_levels)
+    };
+    *filter = new_filter.clone();
+    let storage = state.storage.clone();
+    let mut tracked = std::mem::take(delivered);
+    let reconciled = super::blocking(move || {
+        let reply = reconcile_filter(&storage, &mut tracked, &new_filter, backlog);
+        (reply, tracked)
+    })
+    .await;
+    match reconciled {
+        Ok((reply, tracked)) => {
+            *delivered = tracked;
+            reply
+        }
+        Err(_) => {
+            let error = ApiError::Internal("failed to reconcile delivered events".to_string());
+            WsServerMessage::Error(error.body())
+        }
+    }
 }
 
 /// GET /api/ws - WebSocket endpoint for real-time log streaming
-pub async fn ws_logs(ws: WebSocketUpgrade, State(state): State<Arc<LogsState>>) -> Response {
-    ws.on_upgrade(|socket| handle_ws_connection(socket, state))
+pub async fn ws_logs(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<WsQuery>,
+) -> Response {
+    let filter = match parse_stream_filter(&state.storage, &query) {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+    let format_name = query.format.as_deref().unwrap_or("json");
+    let Some(format) = state.formats.get(format_name) else {
+        return ApiError::BadRequest(format!("unknown format: {}", format_name)).into_response();
+    };
+
+    ws.on_upgrade(|socket| async move {
+        let client = crate::id::new_ulid();
+        state.storage.push(LogEvent::lifecycle(
+            "client_connected",
+            "WebSocket client connected",
+            HashMap::from([("client".to_string(), client.clone())]),
+        ));
+
+        handle_ws_connection(socket, state.clone(), filter, format, &client).await;
+
+        state.storage.push(LogEvent::lifecycle(
+            "client_disconnected",
+            "WebSocket client disconnected",
+            HashMap::from([("client".to_string(), client)]),
+        ));
+    })
+}
+
+/// Send queued messages to the client until it disconnects
+async fn write_ws_messages(
+    mut sender: SplitSink<WebSocket, Message>,
+    mut queue: mpsc::Receiver<Message>,
+    counters: Arc<ClientCounters>,
+) {
+    while let Some(message) = queue.recv().await {
+        counters.dequeued();
+        if sender.send(message).await.is_err() {
+            // Client disconnected
+            tracing::debug!("WebSocket client disconnected");
+            return;
+        }
+    }
+}
+
+/// Queue the notice of events missed since the last one, if any
+///
+/// Returns whether the queue is still open.
+fn queue_dropped_notice(
+    queue: &mpsc::Sender<Message>,
+    counters: &ClientCounters,
+    pending: &mut u64,
+) -> bool {
+    if *pending == 0 {
+        return true;
+    }
+    let notice = WsServerMessage::Dropped { count: *pending };
+    let json = match serde_json::to_string(&notice) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::error!("Failed to serialize WebSocket notice: {}", e);
+            return true;
+        }
+    };
+    match queue.try_send(Message::Text(json.into())) {
+        Ok(()) => {
+            counters.enqueued();
+            *pending = 0;
+            true
+        }
+        Err(mpsc::error::TrySendError::Full(_)) => true,
+        Err(mpsc::error::TrySendError::Closed(_)) => false,
+    }
 }
 
 /// Handle WebSocket connection for real-time log streaming
-async fn handle_ws_connection(mut socket: WebSocket, state: Arc<LogsState>) {
+///
+/// Events are queued for a separate writer task. When the client can't keep
+/// up and the queue is full, events are dropped and the client is told how
+/// many it missed once there is room again.
+async fn handle_ws_connection(
+    socket: WebSocket,
+    state: Arc<LogsState>,
+    mut filter: LogFilter,
+    format: Arc<dyn Format>,
+    client: &str,
+) {
     tracing::debug!("WebSocket connection established");
 
+    let registration = state.storage.stream_clients().register(client);
+    let counters = registration.counters().clone();
+    let (sender, mut receiver) = socket.split();
+    let (queue, queued) = mpsc::channel(WS_QUEUE_CAPACITY);
+    let mut writer = tokio::spawn(write_ws_messages(sender, queued, counters.clone()));
+
+    // Sequence numbers of recently sent events, re-checked when the filter changes
+    let mut delivered = VecDeque::new();
+    // Events missed since the last notice to the client
+    let mut pending_dropped = 0;
+
     // Subscribe to the broadcast channel to receive new log events
     let mut rx = state.storage.subscribe();
 
@@ -117,25 +1143,49 @@ async fn handle_ws_connection(mut socket: WebSocket, state: Arc<LogsState>) {
             result = rx.recv() => {
                 match result {
                     Ok(log_event) => {
-                        // Serialize the log event to JSON
-                        let json = match serde_json::to_string(&log_event) {
-                            Ok(json) => json,
+                        if !filter.matches(&log_event) {
+                            continue;
+                        }
+
+                        let message = match encode_ws_event(format.as_ref(), &log_event) {
+                            Ok(message) => message,
                             Err(e) => {
                                 tracing::error!("Failed to serialize log event: {}", e);
                                 continue;
                             }
                         };
 
-                        // Send the JSON message to the client
-                        if socket.send(Message::Text(json.into())).await.is_err() {
-                            // Client disconnected
-                            tracing::debug!("WebSocket client disconnected");
+                        // The notice goes first so the client sees where the gap is
+                        if !queue_dropped_notice(&queue, &counters, &mut pending_dropped) {
                             break;
                         }
+                        if pending_dropped > 0 {
+                            pending_dropped += 1;
+                            counters.dropped(1);
+                            state.storage.record_dropped(1);
+                            continue;
+                        }
+                        match queue.try_send(message) {
+                            Ok(()) => counters.enqueued(),
+                            Err(mpsc::error::TrySendError::Full(_)) => {
+                                pending_dropped += 1;
+                                counters.dropped(1);
+                                state.storage.record_dropped(1);
+                                continue;
+                            }
+                            Err(mpsc::error::TrySendError::Closed(_)) => break,
+                        }
+                        if delivered.len() >= MAX_TRACKED_DELIVERIES {
+                            delivered.pop_front();
+                        }
+                        delivered.push_back(log_event.seq);
                     }
                     Err(tokio::sync::broadcast::error::RecvError::Lagged(count)) => {
                         // Receiver fell behind, some messages were dropped - continue receiving
                         tracing::debug!("WebSocket receiver lagged, missed {} messages", count);
+                        pending_dropped += count;
+                        counters.dropped(count);
+                        state.storage.record_dropped(count);
                         continue;
                     }
                     Err(tokio::sync::broadcast::error::RecvError::Closed) => {
@@ -147,17 +1197,33 @@ async fn handle_ws_connection(mut socket: WebSocket, state: Arc<LogsState>) {
             }
 
             // Handle incoming messages from client (ping/pong, close)
-            msg = socket.recv() => {
+            msg = receiver.next() => {
                 match msg {
                     Some(Ok(Message::Ping(data))) => {
                         // Respond to ping with pong
-                        if socket.send(Message::Pong(data)).await.is_err() {
+                        if queue.send(Message::Pong(data)).await.is_err() {
                             break;
                         }
+                        counters.enqueued();
                     }
                     Some(Ok(Message::Pong(_))) => {
                         // Client responded to our ping - connection is alive
                     }
+                    Some(Ok(Message::Text(text))) => {
+                        let reply = handle_client_message(text.as_str(), &state, &mut filter, &mut delivered).await;
+                        let json = match serde_json::to_string(&reply) {
+                            Ok(json) => json,
+                            Err(e) => {
+                                tracing::error!("Failed to serialize WebSocket reply: {}", e);
+                                continue;
+                            }
+                        };
+                        // Replies are never dropped, wait for room in the queue
+                        if queue.send(Message::Text(json.into())).await.is_err() {
+                            break;
+                        }
+                        counters.enqueued();
+                    }
                     Some(Ok(Message::Close(_))) => {
                         // Client requested close
                         tracing::debug!("WebSocket client sent close frame");
@@ -178,26 +1244,113 @@ async fn handle_ws_connection(mut socket: WebSocket, state: Arc<LogsState>) {
                 }
             }
 
-            // Send periodic ping to keep connection alive
+            // Send periodic ping to keep connection alive, and a pending
+            // notice if no events arrived since the queue had room again
             _ = ping_interval.tick() => {
-                if socket.send(Message::Ping(vec![].into())).await.is_err() {
-                    tracing::debug!("Failed to send ping, client disconnected");
+                if !queue_dropped_notice(&queue, &counters, &mut pending_dropped) {
                     break;
                 }
+                match queue.try_send(Message::Ping(vec![].into())) {
+                    Ok(()) => counters.enqueued(),
+                    // A busy queue shows the connection is in use
+                    Err(mpsc::error::TrySendError::Full(_)) => {}
+                    Err(mpsc::error::TrySendError::Closed(_)) => {
+                        tracing::debug!("Failed to send ping, client disconnected");
+                        break;
+                    }
+                }
             }
+
+            // The writer stops when sending fails
+            _ = &mut writer => break,
         }
     }
 
+    writer.abort();
     tracing::debug!("WebSocket connection closed");
 }
 
 /// GET /api/targets - Get list of all unique targets
 pub async fn get_targets(State(state): State<Arc<LogsState>>) -> Response {
-    let targets = state.storage.get_targets();
+    let storage = state.storage.clone();
+    let targets = match super::blocking(move || storage.get_targets()).await {
+        Ok(targets) => targets,
+        Err(response) => return response,
+    };
     let response = TargetsResponse { targets };
     Json(response).into_response()
 }
 
+/// Response for GET /api/partitions
+#[derive(Debug, Serialize)]
+pub struct PartitionsResponse {
+    /// Field events are partitioned by, `None` if partitioning is off
+    pub field: Option<String>,
+    /// Stored events per partition, largest first
+    pub partitions: Vec<PartitionCount>,
+}
+
+/// GET /api/partitions - Stored events per value of the `partition_by` field
+pub async fn get_partitions(State(state): State<Arc<LogsState>>) -> Response {
+    Json(PartitionsResponse {
+        field: state.storage.partition_field(),
+        partitions: state.storage.partition_counts(),
+    })
+    .into_response()
+}
+
+/// Query parameters for GET /api/targets/stats
+#[derive(Debug, Default, Deserialize)]
+pub struct TargetsStatsQuery {
+    /// "target" (default), "total", "rate", "last_seen" or "last_error", all
+    /// but "target" sort descending
+    pub sort: Option<String>,
+}
+
+/// Response for GET /api/targets/stats
+#[derive(Debug, Serialize)]
+pub struct TargetsStatsResponse {
+    pub targets: Vec<TargetOverview>,
+}
+
+/// GET /api/targets/stats - Counts, first and last timestamp, rate and last
+/// error of every target
+pub async fn get_targets_stats(
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<TargetsStatsQuery>,
+) -> Response {
+    let mut targets = state.storage.target_overview();
+    match query.sort.as_deref().unwrap_or("target") {
+        "target" => {}
+        "total" => targets.sort_by(|a, b| b.total.cmp(&a.total)),
+        "rate" => targets.sort_by(|a, b| b.events_per_minute.total_cmp(&a.events_per_minute)),
+        "last_seen" => targets.sort_by(|a, b| b.last_seen.cmp(&a.last_seen)),
+        "last_error" => targets.sort_by(|a, b| {
+            let at = |t: &TargetOverview| t.last_error.as_ref().map(|e| e.timestamp);
+            at(b).cmp(&at(a))
+        }),
+        other => {
+            return ApiError::BadRequest(format!(
+                "unknown sort: {} (expected target, total, rate, last_seen or last_error)",
+                other
+            ))
+            .into_response()
+        }
+    }
+    Json(TargetsStatsResponse { targets }).into_response()
+}
+
+/// GET /api/targets/{target}/stats - Rate, level counts and last error of a target
+pub async fn get_target_stats(
+    State(state): State<Arc<LogsState>>,
+    Path(target): Path<String>,
+) -> Response {
+    match state.storage.target_stats(&target) {
+        Some(stats) => Json(stats).into_response(),
+        None => ApiError::not_found("target", target).into_response(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,14 +1360,251 @@ mod tests {
         let request = LogsRequest {
             limit: Some(100),
             offset: 0,
-            global_level: None,
-            target_levels: HashMap::new(),
-            search: None,
-            target: None,
+            filter: FilterParams::default(),
             sort_order: None,
+            after_seq: None,
+            cursor: None,
+            history: false,
+            group_by: None,
         };
 
         assert_eq!(request.limit, Some(100));
         assert_eq!(request.offset, 0);
+
+        let polled: LogsRequest = serde_json::from_str(r#"{"since_seq": 42}"#).unwrap();
+        assert_eq!(polled.after_seq, Some(42));
+
+        let filtered: LogsRequest = serde_json::from_str(
+            r#"{"limit": 5, "target_levels": {"app": "debug"}, "exclude_targets": ["heartbeat"]}"#,
+        )
+        .unwrap();
+        assert_eq!(filtered.limit, Some(5));
+        assert_eq!(filtered.filter.target_levels["app"], "DEBUG");
+        assert_eq!(filtered.filter.exclude_targets, ["heartbeat"]);
+    }
+
+    #[test]
+    fn test_group_events() {
+        let storage = LogStorage::new();
+        let record = |id: &str, parent_id: Option<&str>| crate::spans::SpanRecord {
+            id: id.to_string(),
+            parent_id: parent_id.map(str::to_string),
+            name: "request".to_string(),
+            target: "http".to_string(),
+            level: "INFO".to_string(),
+            fields: HashMap::new(),
+            started_at: Utc::now(),
+            closed_at: None,
+            duration_us: None,
+            busy_us: 0,
+            events: 0,
+        };
+        storage.spans().open(record("root", None));
+        storage.spans().open(record("child", Some("root")));
+
+        let event = |level: &str, request_id: Option<&str>, span_id: Option<&str>| LogEvent {
+            level: level.into(),
+            fields: request_id
+                .map(|id| HashMap::from([("request_id".into(), id.into())]))
+                .unwrap_or_default(),
+            span: span_id.map(|id| crate::storage::SpanInfo {
+                id: Some(id.to_string()),
+                name: "request".to_string(),
+                fields: HashMap::new(),
+                ancestors: Vec::new(),
+                parent_fields: HashMap::new(),
+            }),
+            ..LogEvent::internal("INFO", "event", HashMap::new())
+        };
+        let logs = vec![
+            event("INFO", Some("a"), Some("root")),
+            event("ERROR", Some("b"), Some("child")),
+            event("WARN", Some("a"), Some("child")),
+            event("INFO", None, None),
+        ];
+
+        let groups = group_events(&storage, logs.clone(), "request_id");
+        let keys: Vec<_> = groups.iter().map(|g| g.key.as_deref()).collect();
+        assert_eq!(keys, [Some("a"), Some("b"), None]);
+        assert_eq!(groups[0].count, 2);
+        assert_eq!(groups[0].levels["WARN"], 1);
+
+        let groups = group_events(&storage, logs, "span");
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key.as_deref(), Some("root"));
+        assert_eq!(groups[0].count, 3);
+        assert_eq!(groups[1].key, None);
+    }
+
+    #[test]
+    fn test_reconcile_filter() {
+        let storage = LogStorage::new();
+        for level in ["INFO", "ERROR", "INFO", "ERROR"] {
+            storage.push(LogEvent::internal(level, "event", HashMap::new()));
+        }
+        let mut delivered: VecDeque<u64> = VecDeque::from([0, 1, 2, 3, 4]);
+
+        let filter = LogFilter {
+            expr: Some(FilterExpr::parse("level>=error").unwrap()),
+            ..Default::default()
+        };
+        let reply = reconcile_filter(&storage, &mut delivered, &filter, None);
+        match reply {
+            WsServerMessage::FilterUpdated {
+                removed,
+                unchecked,
+                backlog,
+                ..
+            } => {
+                assert_eq!(removed, [1, 3]);
+                // Sequence number 0 is never assigned
+                assert_eq!(unchecked, 1);
+                assert!(backlog.is_none());
+            }
+            other => panic!("unexpected reply: {:?}", other),
+        }
+        assert_eq!(delivered, [2, 4]);
+
+        let reply = reconcile_filter(&storage, &mut delivered, &LogFilter::default(), Some(3));
+        match reply {
+            WsServerMessage::FilterUpdated {
+                removed, backlog, ..
+            } => {
+                assert!(removed.is_empty());
+                let seqs: Vec<u64> = backlog.unwrap().iter().map(|e| e.seq).collect();
+                assert_eq!(seqs, [2, 3, 4]);
+            }
+            other => panic!("unexpected reply: {:?}", other),
+        }
+        assert_eq!(delivered, [2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_export_as_csv() {
+        let storage = LogStorage::new();
+        storage.push(LogEvent::internal("INFO", "started", HashMap::new()));
+        storage.push(LogEvent::internal(
+            "ERROR",
+            "payment failed",
+            HashMap::new(),
+        ));
+        let state = Arc::new(LogsState::new(storage));
+
+        let query = ExportQuery {
+            format: Some("csv".to_string()),
+            filter: FilterParams {
+                global_level: Some("error".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let response = export_logs(State(state.clone()), Query(query)).await;
+        let headers = response.headers();
+        assert_eq!(headers["content-type"], "text/csv");
+        let disposition = headers["content-disposition"].to_str().unwrap();
+        assert!(disposition.starts_with("attachment; filename=\"logs-"));
+        assert!(disposition.ends_with(".csv\""));
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[1].contains(",payment failed,"));
+
+        let query = ExportQuery {
+            format: Some("xlsx".to_string()),
+            ..Default::default()
+        };
+        let response = export_logs(State(state), Query(query)).await;
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_export_and_clear_apply_exclusions() {
+        let storage = LogStorage::new();
+        for (target, message) in [("app", "order created"), ("heartbeat", "ping")] {
+            storage.push(LogEvent {
+                target: target.into(),
+                ..LogEvent::internal("INFO", message, HashMap::new())
+            });
+        }
+        let state = Arc::new(LogsState::new(storage.clone()));
+
+        let uri = "/api/export?format=ndjson&limit=10&exclude_targets=heartbeat,noise&target_levels=app%3Dinfo"
+            .parse()
+            .unwrap();
+        let query: Query<ExportQuery> = Query::try_from_uri(&uri).unwrap();
+        assert_eq!(query.limit, Some(10));
+        assert_eq!(query.filter.exclude_targets, ["heartbeat", "noise"]);
+        assert_eq!(query.filter.target_levels["app"], "INFO");
+        let response = export_logs(State(state.clone()), query).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let exported = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(exported.lines().count(), 1);
+        assert!(exported.contains("order created"));
+
+        let uri = "/api/logs?exclude_targets=app".parse().unwrap();
+        let params: Query<FilterParams> = Query::try_from_uri(&uri).unwrap();
+        let response = clear_logs(State(state), params).await;
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let remaining = storage.query(&LogFilter::default(), None, None);
+        assert_eq!(remaining.events.len(), 1);
+        assert_eq!(remaining.events[0].target, "app");
+    }
+
+    #[tokio::test]
+    async fn test_logs_as_msgpack_when_accepted() {
+        let storage = LogStorage::new();
+        storage.push(LogEvent::internal("INFO", "started", HashMap::new()));
+        let state = Arc::new(LogsState::new(storage));
+        let request = || serde_json::from_str::<LogsRequest>("{}").unwrap();
+
+        let response = get_logs(State(state.clone()), HeaderMap::new(), Json(request())).await;
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT,
+            "application/msgpack, application/json;q=0.5"
+                .parse()
+                .unwrap(),
+        );
+        let response = get_logs(State(state), headers, Json(request())).await;
+        assert_eq!(response.headers()[CONTENT_TYPE], MSGPACK_CONTENT_TYPE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let decoded: serde_json::Value = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(decoded["total"], 1);
+        assert_eq!(decoded["logs"][0]["message"], "started");
+    }
+
+    #[test]
+    fn test_dropped_notice_waits_for_room() {
+        let storage = LogStorage::new();
+        let registration = storage.stream_clients().register("client");
+        let counters = registration.counters();
+        let (queue, mut queued) = mpsc::channel(1);
+        queue.try_send(Message::Ping(vec![].into())).unwrap();
+
+        let mut pending = 3;
+        assert!(queue_dropped_notice(&queue, counters, &mut pending));
+        assert_eq!(pending, 3);
+
+        queued.try_recv().unwrap();
+        assert!(queue_dropped_notice(&queue, counters, &mut pending));
+        assert_eq!(pending, 0);
+        let Ok(Message::Text(text)) = queued.try_recv() else {
+            panic!("expected a text message");
+        };
+        assert_eq!(text.as_str(), r#"{"type":"dropped","count":3}"#);
+
+        drop(queued);
+        pending = 1;
+        assert!(!queue_dropped_notice(&queue, counters, &mut pending));
     }
 }
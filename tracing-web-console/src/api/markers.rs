@@ -0,0 +1,67 @@
+//! Markers API for annotating deployments and config changes
+
+use super::error::ApiError;
+use super::logs::LogsState;
+use crate::storage::Marker;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Request body for POST /api/markers
+#[derive(Debug, Deserialize)]
+pub struct MarkerRequest {
+    pub label: String,
+    /// Free-form category such as "deploy" or "config"
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// When the change happened, defaults to now
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// Query parameters for GET /api/markers
+#[derive(Debug, Default, Deserialize)]
+pub struct MarkersQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Response for GET /api/markers
+#[derive(Debug, Serialize)]
+pub struct MarkersResponse {
+    pub markers: Vec<Marker>,
+}
+
+/// GET /api/markers - List markers, optionally within a time range
+pub async fn list_markers(
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<MarkersQuery>,
+) -> Response {
+    let markers = state.storage.markers_between(query.from, query.to);
+    Json(MarkersResponse { markers }).into_response()
+}
+
+/// POST /api/markers - Record a deployment or config-change marker
+pub async fn create_marker(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<MarkerRequest>,
+) -> Response {
+    let label = request.label.trim();
+    if label.is_empty() {
+        return ApiError::BadRequest("label must not be empty".to_string()).into_response();
+    }
+
+    let marker = Marker {
+        id: crate::id::new_ulid(),
+        timestamp: request.timestamp.unwrap_or_else(Utc::now),
+        label: label.to_string(),
+        kind: request.kind.filter(|k| !k.is_empty()),
+    };
+    state.storage.add_marker(marker.clone());
+
+    (StatusCode::CREATED, Json(marker)).into_response()
+}
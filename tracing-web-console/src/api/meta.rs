@@ -0,0 +1,237 @@
+//! Metadata API describing levels, formats and capabilities, so external UIs
+//! render like the embedded one and feature-detect instead of assuming a build
+
+use crate::api::logs::LogsState;
+use crate::ingest::grok::LEVEL_ALIASES;
+use crate::ingest::syslog::{severity_to_level, SEVERITY_NAMES};
+use crate::storage::level_to_number;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Display colors of the embedded UI, by level
+const LEVEL_COLORS: &[(&str, &str)] = &[
+    ("TRACE", "#9ca3af"),
+    ("DEBUG", "#3b82f6"),
+    ("INFO", "#22c55e"),
+    ("WARN", "#eab308"),
+    ("ERROR", "#ef4444"),
+];
+
+/// A level events can have
+#[derive(Debug, Serialize)]
+pub struct LevelInfo {
+    /// Name as stored on events, e.g. "WARN"
+    pub name: &'static str,
+    /// Display label, e.g. "Warn"
+    pub label: String,
+    /// Ordering, higher is more severe
+    pub severity: u8,
+    /// Display color of the embedded UI as a hex RGB value
+    pub color: &'static str,
+    /// Level names of ingested formats mapped to this level
+    pub aliases: &'static [&'static str],
+}
+
+/// How a syslog severity is mapped
+#[derive(Debug, Serialize)]
+pub struct SyslogSeverity {
+    pub severity: u8,
+    pub name: &'static str,
+    pub level: &'static str,
+}
+
+/// Response for GET /api/meta/levels
+#[derive(Debug, Serialize)]
+pub struct LevelsResponse {
+    /// Known levels, least severe first
+    pub levels: Vec<LevelInfo>,
+    pub syslog_severities: Vec<SyslogSeverity>,
+}
+
+fn levels() -> LevelsResponse {
+    let levels = LEVEL_COLORS
+        .iter()
+        .map(|&(name, color)| LevelInfo {
+            name,
+            label: format!("{}{}", &name[..1], name[1..].to_lowercase()),
+            severity: level_to_number(name),
+            color,
+            aliases: LEVEL_ALIASES
+                .iter()
+                .find(|(level, _)| *level == name)
+                .map(|(_, aliases)| *aliases)
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    let syslog_severities = SEVERITY_NAMES
+        .iter()
+        .zip(0u8..)
+        .map(|(name, severity)| SyslogSeverity {
+            severity,
+            name,
+            level: severity_to_level(severity),
+        })
+        .collect();
+
+    LevelsResponse {
+        levels,
+        syslog_severities,
+    }
+}
+
+/// GET /api/meta/levels - Known levels with ordering, colors and ingest mappings
+pub async fn get_levels() -> Response {
+    Json(levels()).into_response()
+}
+
+/// A registered output format
+#[derive(Debug, Serialize)]
+pub struct FormatInfo {
+    /// Name to select the format with, e.g. `?format=csv`
+    pub name: &'static str,
+    pub content_type: &'static str,
+    pub extension: &'static str,
+    pub binary: bool,
+}
+
+/// Response for GET /api/meta/formats
+#[derive(Debug, Serialize)]
+pub struct FormatsResponse {
+    pub formats: Vec<FormatInfo>,
+}
+
+/// GET /api/meta/formats - Output formats events can be streamed and written in
+pub async fn get_formats(State(state): State<Arc<LogsState>>) -> Response {
+    let formats = state
+        .formats
+        .list()
+        .iter()
+        .map(|format| FormatInfo {
+            name: format.name(),
+            content_type: format.content_type(),
+            extension: format.extension(),
+            binary: format.is_binary(),
+        })
+        .collect();
+    Json(FormatsResponse { formats }).into_response()
+}
+
+/// Cargo features the console can be built with
+const FEATURES: &[(&str, bool)] = &[
+    ("scripting", cfg!(feature = "scripting")),
+    ("docker", cfg!(feature = "docker")),
+    ("mmap", cfg!(feature = "mmap")),
+    ("sqlite", cfg!(feature = "sqlite")),
+    ("generator", cfg!(feature = "generator")),
+];
+
+/// An API endpoint and its methods
+#[derive(Debug, Serialize)]
+pub struct EndpointInfo {
+    /// Path including the `/api` prefix, relative to the console's base path
+    pub path: String,
+    pub methods: &'static [&'static str],
+}
+
+/// How requests are authorized
+#[derive(Debug, Serialize)]
+pub struct AuthInfo {
+    /// Authentication required for the console API, "token" for access
+    /// tokens, "none" if it's open
+    pub mode: &'static str,
+    /// State-changing requests are rejected
+    pub read_only: bool,
+    /// `/api/ingest/lines` requires an ingest token
+    pub ingest_tokens_required: bool,
+}
+
+/// Response for GET /api/ui-manifest
+#[derive(Debug, Serialize)]
+pub struct UiManifest {
+    pub version: &'static str,
+    pub endpoints: Vec<EndpointInfo>,
+    /// Names of the registered output formats
+    pub formats: Vec<&'static str>,
+    pub auth: AuthInfo,
+    /// Cargo features the console was built with
+    pub features: BTreeMap<&'static str, bool>,
+}
+
+/// How requests to this console are authorized
+pub(super) fn auth_info(state: &LogsState) -> AuthInfo {
+    AuthInfo {
+        mode: if state.access.is_enforced() {
+            "token"
+        } else {
+            "none"
+        },
+        read_only: state.read_only,
+        ingest_tokens_required: state.ingest_tokens.is_enforced(),
+    }
+}
+
+fn ui_manifest(state: &LogsState) -> UiManifest {
+    UiManifest {
+        version: env!("CARGO_PKG_VERSION"),
+        endpoints: super::ENDPOINTS
+            .iter()
+            .map(|&(path, methods)| EndpointInfo {
+                path: format!("/api{}", path),
+                methods,
+            })
+            .collect(),
+        formats: state.formats.list().iter().map(|f| f.name()).collect(),
+        auth: auth_info(state),
+        features: FEATURES.iter().copied().collect(),
+    }
+}
+
+/// GET /api/ui-manifest - Endpoints, formats, auth mode and features of this build
+pub async fn get_ui_manifest(State(state): State<Arc<LogsState>>) -> Response {
+    Json(ui_manifest(&state)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_are_ordered_and_complete() {
+        let response = levels();
+        let names: Vec<_> = response.levels.iter().map(|l| l.name).collect();
+        assert_eq!(names, ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"]);
+        assert!(response
+            .levels
+            .windows(2)
+            .all(|w| w[0].severity < w[1].severity));
+
+        let warn = &response.levels[3];
+        assert_eq!(warn.label, "Warn");
+        assert!(warn.aliases.contains(&"warning"));
+
+        assert_eq!(response.syslog_severities.len(), 8);
+        assert_eq!(response.syslog_severities[4].name, "warning");
+        assert_eq!(response.syslog_severities[4].level, "WARN");
+    }
+
+    #[test]
+    fn test_ui_manifest() {
+        let mut state = LogsState::new(crate::storage::LogStorage::new());
+        state.read_only = true;
+        let manifest = ui_manifest(&state);
+
+        assert!(manifest.auth.read_only);
+        assert!(!manifest.auth.ingest_tokens_required);
+        assert!(manifest.formats.contains(&"csv"));
+        assert!(manifest
+            .endpoints
+            .iter()
+            .any(|e| e.path == "/api/ui-manifest" && e.methods == ["GET"]));
+        assert_eq!(manifest.features["scripting"], cfg!(feature = "scripting"));
+    }
+}
@@ -1,25 +1,318 @@
 //! API module for log endpoints
 
+pub mod alerts;
+pub mod capture;
+pub mod config;
+pub mod error;
+pub mod filters;
+pub mod health;
+pub mod ingest;
+pub mod levels;
 pub mod logs;
+pub mod markers;
+pub mod meta;
+pub mod replay;
+pub mod snapshot;
+pub mod spans;
+pub mod sse;
+pub mod stats;
+pub mod summaries;
+pub mod transforms;
+pub mod watchdog;
 
-use axum::routing::{get, post};
+use axum::extract::{DefaultBodyLimit, Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
 use axum::Router;
 use std::sync::Arc;
 
+use crate::access::Role;
+use crate::cancel::CancelToken;
+use error::ApiError;
 use logs::LogsState;
 
+/// Paths below `/api` with their methods, listed by `GET /api/ui-manifest`
+///
+/// Keep in sync with `create_api_router`.
+pub(crate) const ENDPOINTS: &[(&str, &[&str])] = &[
+    ("/logs", &["POST", "DELETE"]),
+    ("/logs/{id}", &["GET"]),
+    ("/export", &["GET"]),
+    ("/ws", &["GET"]),
+    ("/sse", &["GET"]),
+    ("/targets", &["GET"]),
+    ("/targets/stats", &["GET"]),
+    ("/partitions", &["GET"]),
+    ("/targets/{target}/stats", &["GET"]),
+    ("/health", &["GET"]),
+    ("/diagnostics", &["GET"]),
+    ("/stats", &["GET"]),
+    ("/metrics", &["GET"]),
+    ("/capture", &["GET"]),
+    ("/capture/pause", &["POST"]),
+    ("/capture/resume", &["POST"]),
+    ("/levels", &["GET", "POST"]),
+    ("/ignored-targets", &["GET", "POST"]),
+    ("/ignored-targets/{target}", &["DELETE"]),
+    ("/summaries", &["GET"]),
+    ("/timeline", &["GET"]),
+    ("/histogram", &["POST"]),
+    ("/spans", &["GET"]),
+    ("/spans/slow", &["GET"]),
+    ("/spans/{id}", &["GET"]),
+    ("/meta/levels", &["GET"]),
+    ("/meta/formats", &["GET"]),
+    ("/ui-manifest", &["GET"]),
+    ("/markers", &["GET", "POST"]),
+    ("/frontend/status", &["GET"]),
+    ("/ingest/lines", &["POST"]),
+    ("/ingest/events", &["POST"]),
+    ("/instances", &["GET"]),
+    ("/ingest/tokens", &["GET", "POST"]),
+    ("/ingest/tokens/{id}", &["DELETE"]),
+    ("/ingest/patterns", &["GET", "PUT"]),
+    ("/alerts", &["GET", "POST"]),
+    ("/alerts/rules", &["GET", "POST"]),
+    ("/alerts/rules/{id}", &["GET", "DELETE"]),
+    ("/alerts/rules/{id}/test", &["POST"]),
+    ("/alerts/silences", &["GET", "POST"]),
+    ("/alerts/silences/{id}", &["DELETE"]),
+    ("/transforms", &["GET", "PUT"]),
+    ("/watchdog", &["GET", "POST"]),
+    ("/watchdog/{target}", &["DELETE"]),
+    ("/filters", &["GET", "POST"]),
+    ("/filters/{name}", &["DELETE"]),
+    ("/config", &["GET"]),
+    ("/config/capacity", &["PUT"]),
+    ("/replay", &["POST"]),
+    ("/replay/{id}/ws", &["GET"]),
+    ("/snapshot", &["POST"]),
+    ("/snapshot/import", &["POST"]),
+];
+
 /// Create the API router with all endpoints
 pub fn create_api_router(state: Arc<LogsState>) -> Router {
     Router::new().nest(
         "/api",
         Router::new()
-            .route("/logs", post(logs::get_logs))
+            .route("/logs", post(logs::get_logs).delete(logs::clear_logs))
+            .route("/logs/{id}", get(logs::get_log_by_id))
+            .route("/export", get(logs::export_logs))
             .route("/ws", get(logs::ws_logs))
+            .route("/sse", get(sse::sse_logs))
             .route("/targets", get(logs::get_targets))
+            .route("/targets/stats", get(logs::get_targets_stats))
+            .route("/partitions", get(logs::get_partitions))
+            .route("/targets/{target}/stats", get(logs::get_target_stats))
+            .route("/health", get(health::get_health))
+            .route("/diagnostics", get(health::get_diagnostics))
+            .route("/stats", get(stats::get_stats))
+            .route("/metrics", get(stats::get_metrics))
+            .route("/capture", get(capture::get_capture))
+            .route("/capture/pause", post(capture::pause_capture))
+            .route("/capture/resume", post(capture::resume_capture))
+            .route(
+                "/levels",
+                get(levels::get_levels).post(levels::update_levels),
+            )
+            .route(
+                "/ignored-targets",
+                get(levels::get_ignored_targets).post(levels::add_ignored_target),
+            )
+            .route(
+                "/ignored-targets/{target}",
+                axum::routing::delete(levels::remove_ignored_target),
+            )
+            .route("/summaries", get(summaries::list_summaries))
+            .route("/timeline", get(summaries::get_timeline))
+            .route("/histogram", post(summaries::get_histogram))
+            .route("/spans", get(spans::list_spans))
+            .route("/spans/slow", get(spans::get_slow_spans))
+            .route("/spans/{id}", get(spans::get_span_tree))
+            .route("/meta/levels", get(meta::get_levels))
+            .route("/meta/formats", get(meta::get_formats))
+            .route("/ui-manifest", get(meta::get_ui_manifest))
+            .route(
+                "/markers",
+                get(markers::list_markers).post(markers::create_marker),
+            )
+            .route("/frontend/status", get(crate::frontend::frontend_status))
+            .route("/ingest/lines", post(ingest::ingest_lines))
+            .route("/ingest/events", post(ingest::ingest_events))
+            .route("/instances", get(ingest::list_instances))
+            .route(
+                "/ingest/tokens",
+                get(ingest::list_tokens).post(ingest::issue_token),
+            )
+            .route(
+                "/ingest/tokens/{id}",
+                axum::routing::delete(ingest::revoke_token),
+            )
+            .route(
+                "/ingest/patterns",
+                get(ingest::get_patterns).put(ingest::update_patterns),
+            )
+            .route(
+                "/alerts",
+                get(alerts::list_firings).post(alerts::upsert_rule),
+            )
+            .route(
+                "/alerts/rules",
+                get(alerts::list_rules).post(alerts::upsert_rule),
+            )
+            .route(
+                "/alerts/rules/{id}",
+                get(alerts::get_rule).delete(alerts::delete_rule),
+            )
+            .route("/alerts/rules/{id}/test", post(alerts::test_rule))
+            .route(
+                "/alerts/silences",
+                get(alerts::list_silences).post(alerts::create_silence),
+            )
+            .route(
+                "/alerts/silences/{id}",
+                axum::routing::delete(alerts::delete_silence),
+            )
+            .route(
+                "/transforms",
+                get(transforms::get_transforms).put(transforms::update_transforms),
+            )
+            .route(
+                "/watchdog",
+                get(watchdog::list_watches).post(watchdog::upsert_watch),
+            )
+            .route(
+                "/watchdog/{target}",
+                axum::routing::delete(watchdog::delete_watch),
+            )
+            .route(
+                "/filters",
+                get(filters::list_presets).post(filters::upsert_preset),
+            )
+            .route(
+                "/filters/{name}",
+                axum::routing::delete(filters::delete_preset),
+            )
+            .route("/config", get(config::get_config))
+            .route("/config/capacity", put(config::set_capacity))
+            .route("/replay", post(replay::create_replay))
+            .route("/replay/{id}/ws", get(replay::replay_ws))
+            .route("/snapshot", post(snapshot::create_snapshot))
+            .route(
+                "/snapshot/import",
+                post(snapshot::import_snapshot)
+                    .layer(DefaultBodyLimit::max(snapshot::MAX_IMPORT_BYTES)),
+            )
+            .route_layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                read_only_guard,
+            ))
+            .route_layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                access_guard,
+            ))
+            .layer(axum::middleware::from_fn(error::json_errors))
             .with_state(state),
     )
 }
 
+/// Run CPU-heavy work such as full buffer scans on the blocking thread pool
+///
+/// Keeps large queries from adding latency to the application's tokio workers.
+pub(crate) async fn blocking<F, R>(f: F) -> Result<R, Response>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ApiError::Internal(format!("query task failed: {}", e)).into_response())
+}
+
+/// Run a scan on the blocking pool that is cancelled if the request is dropped
+///
+/// When the client disconnects, axum drops the handler future, which sets the
+/// token `f` receives. `f` should check it regularly and return `None` to stop.
+pub(crate) async fn cancellable<F, R>(f: F) -> Result<R, Response>
+where
+    F: FnOnce(&CancelToken) -> Option<R> + Send + 'static,
+    R: Send + 'static,
+{
+    let token = CancelToken::default();
+    let _guard = token.cancel_on_drop();
+    blocking(move || f(&token))
+        .await?
+        .ok_or_else(|| ApiError::Internal("query cancelled".to_string()).into_response())
+}
+
+/// POST endpoints that only read data and stay available in read-only mode
+const READ_ONLY_POST_PATHS: &[&str] = &["/logs", "/histogram", "/replay", "/snapshot"];
+
+/// Endpoints authenticated by ingest tokens instead of access tokens, once
+/// ingest tokens are enforced
+const INGEST_PATHS: &[&str] = &["/ingest/lines", "/ingest/events"];
+
+/// Endpoints listing, issuing and revoking ingest tokens
+const INGEST_TOKEN_PATH: &str = "/ingest/tokens";
+
+/// Whether a request only reads data
+fn is_read_request(request: &Request) -> bool {
+    let method = request.method();
+    method == Method::GET
+        || method == Method::HEAD
+        || method == Method::OPTIONS
+        || (method == Method::POST && READ_ONLY_POST_PATHS.contains(&request.uri().path()))
+}
+
+/// Reject state-changing requests when the console is read-only
+async fn read_only_guard(
+    State(state): State<Arc<LogsState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.read_only && !is_read_request(&request) {
+        return ApiError::ReadOnly.into_response();
+    }
+    next.run(request).await
+}
+
+/// Require an access token once any is configured, and an admin token for
+/// state-changing requests
+///
+/// Ingestion is left to ingest tokens while they are enforced. Before that
+/// it needs an admin token like other writes, so it isn't open to anyone.
+/// Ingest tokens grant write access, so they can only be managed with an
+/// admin token, also on consoles that are otherwise open.
+async fn access_guard(
+    State(state): State<Arc<LogsState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ingest_authenticated =
+        state.ingest_tokens.is_enforced() && INGEST_PATHS.contains(&request.uri().path());
+    if state.access.is_enforced() && !ingest_authenticated {
+        let Some(role) = state.access.role_of(request.headers()) else {
+            return ApiError::Unauthorized("missing or invalid access token".to_string())
+                .into_response();
+        };
+        if role < Role::Admin && !is_read_request(&request) {
+            return ApiError::Forbidden("this request needs an admin token".to_string())
+                .into_response();
+        }
+    }
+    let path = request.uri().path();
+    let manages_tokens = path
+        .strip_prefix(INGEST_TOKEN_PATH)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+    if manages_tokens && state.access.role_of(request.headers()) != Some(Role::Admin) {
+        return ApiError::Forbidden("managing ingest tokens needs an admin token".to_string())
+            .into_response();
+    }
+    next.run(request).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -32,4 +325,163 @@ mod tests {
         let state = Arc::new(LogsState::new(storage));
         let _router = create_api_router(state);
     }
+
+    #[tokio::test]
+    async fn test_listed_endpoints_are_routed() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let router = create_api_router(Arc::new(LogsState::new(LogStorage::new())));
+        for (path, methods) in ENDPOINTS {
+            let uri = format!("/api{}", path.replace(['{', '}'], ""));
+            for method in *methods {
+                let request = Request::builder()
+                    .method(*method)
+                    .uri(&uri)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = router.clone().oneshot(request).await.unwrap();
+                assert_ne!(
+                    response.status(),
+                    axum::http::StatusCode::METHOD_NOT_ALLOWED,
+                    "{} {}",
+                    method,
+                    path
+                );
+                if response.status() == axum::http::StatusCode::NOT_FOUND {
+                    // Handlers report missing resources with details, unknown routes don't have any
+                    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                        .await
+                        .unwrap();
+                    let details = serde_json::from_slice::<serde_json::Value>(&body)
+                        .ok()
+                        .and_then(|body| body.get("details").cloned());
+                    assert!(details.is_some(), "{} {} is not routed", method, path);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_access_tokens_separate_roles() {
+        use crate::access::AccessTokens;
+        use axum::body::Body;
+        use axum::http::StatusCode;
+        use tower::ServiceExt;
+
+        let mut state = LogsState::new(LogStorage::new());
+        state.access = AccessTokens::new([("dev", Role::Reader), ("ops", Role::Admin)]);
+        let router = create_api_router(Arc::new(state));
+        let status = |method: &str, uri: &str, token: Option<&str>| {
+            let mut request = Request::builder().method(method).uri(uri);
+            if let Some(token) = token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            let request = request.body(Body::empty()).unwrap();
+            let router = router.clone();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+
+        assert_eq!(
+            status("GET", "/api/targets", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status("GET", "/api/targets", Some("x")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status("GET", "/api/targets", Some("dev")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status("DELETE", "/api/logs", Some("dev")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status("DELETE", "/api/logs", Some("ops")).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_needs_admin_until_ingest_tokens_exist() {
+        use crate::access::AccessTokens;
+        use axum::body::Body;
+        use axum::http::StatusCode;
+        use tower::ServiceExt;
+
+        let mut state = LogsState::new(LogStorage::new());
+        state.access = AccessTokens::new([("dev", Role::Reader), ("ops", Role::Admin)]);
+        let ingest_tokens = state.ingest_tokens.clone();
+        let router = create_api_router(Arc::new(state));
+        let ingest = |token: Option<&str>| {
+            let mut request = Request::builder()
+                .method("POST")
+                .uri("/api/ingest/lines")
+                .header("content-type", "application/json");
+            if let Some(token) = token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            let body = Body::from(r#"{"source": "edge", "lines": ["started"]}"#);
+            let request = request.body(body).unwrap();
+            let router = router.clone();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+
+        assert_eq!(ingest(None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(ingest(Some("dev")).await, StatusCode::FORBIDDEN);
+        assert_eq!(ingest(Some("ops")).await, StatusCode::OK);
+
+        // Ingest tokens take over once enforced
+        ingest_tokens.insert("edge", "twc_edge");
+        assert_eq!(ingest(Some("twc_edge")).await, StatusCode::OK);
+        assert_eq!(ingest(None).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_tokens_are_managed_by_admins_only() {
+        use crate::access::AccessTokens;
+        use axum::body::Body;
+        use axum::http::StatusCode;
+        use tower::ServiceExt;
+
+        let status = |router: Router, method: &str, uri: &str, token: Option<&str>| {
+            let mut request = Request::builder().method(method).uri(uri);
+            if let Some(token) = token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            let request = request.body(Body::empty()).unwrap();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+
+        // Without access tokens everything else is open, token management isn't
+        let open = create_api_router(Arc::new(LogsState::new(LogStorage::new())));
+        assert_eq!(
+            status(open.clone(), "GET", "/api/targets", None).await,
+            StatusCode::OK
+        );
+        for (method, uri) in [
+            ("GET", "/api/ingest/tokens"),
+            ("POST", "/api/ingest/tokens"),
+            ("DELETE", "/api/ingest/tokens/abc"),
+        ] {
+            assert_eq!(
+                status(open.clone(), method, uri, None).await,
+                StatusCode::FORBIDDEN
+            );
+        }
+
+        let mut state = LogsState::new(LogStorage::new());
+        state.access = AccessTokens::new([("dev", Role::Reader), ("ops", Role::Admin)]);
+        let router = create_api_router(Arc::new(state));
+        assert_eq!(
+            status(router.clone(), "GET", "/api/ingest/tokens", Some("dev")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(router, "GET", "/api/ingest/tokens", Some("ops")).await,
+            StatusCode::OK
+        );
+    }
 }
@@ -0,0 +1,155 @@
+//! Replay API re-streaming a stored time range at its original pace
+
+use super::error::ApiError;
+use super::logs::{encode_ws_event, parse_expr, LogsState, WsQuery, WsServerMessage};
+use crate::replay::Replay;
+use crate::storage::LogEvent;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+/// Most events a single replay holds
+const MAX_REPLAY_EVENTS: usize = 100_000;
+/// Fastest accepted playback speed
+const MAX_SPEED: f64 = 1_000.0;
+
+/// Request body for POST /api/replay
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Playback speed, 1.0 (the default) is real time
+    pub speed: Option<f64>,
+    /// Only replay events matching this filter expression
+    pub query: Option<String>,
+}
+
+/// Response for POST /api/replay
+#[derive(Debug, Serialize)]
+pub struct ReplayResponse {
+    pub id: String,
+    /// Number of events that will be replayed
+    pub events: usize,
+    /// Whether events past `MAX_REPLAY_EVENTS` were left out
+    pub truncated: bool,
+    /// WebSocket path streaming the replay, relative to the base path
+    pub stream: String,
+}
+
+/// POST /api/replay - Prepare a replay of stored events in a time range
+pub async fn create_replay(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<ReplayRequest>,
+) -> Response {
+    if request.from >= request.to {
+        return ApiError::BadRequest("from must be before to".to_string()).into_response();
+    }
+    let speed = request.speed.unwrap_or(1.0);
+    if !(speed > 0.0 && speed <= MAX_SPEED) {
+        return ApiError::BadRequest(format!("speed must be within (0, {}]", MAX_SPEED))
+            .into_response();
+    }
+    let expr = match parse_expr(request.query.as_deref()) {
+        Ok(expr) => expr,
+        Err(response) => return response,
+    };
+
+    let storage = state.storage.clone();
+    let (from, to) = (request.from, request.to);
+    let collect = move || {
+        // Keep the earliest matches by timestamp without cloning the others,
+        // buffer order follows capture while replay follows the events' own clocks
+        let mut earliest = BinaryHeap::new();
+        let mut matched = 0;
+        storage.scan(|event| {
+            if event.timestamp >= from
+                && event.timestamp <= to
+                && expr.as_ref().is_none_or(|expr| expr.matches(event))
+            {
+                matched += 1;
+                earliest.push((event.timestamp, event.seq));
+                if earliest.len() > MAX_REPLAY_EVENTS {
+                    earliest.pop();
+                }
+            }
+        });
+        let seqs: Vec<u64> = earliest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(_, seq)| seq)
+            .collect();
+        // Events evicted since the scan are left out
+        (storage.get_by_seqs(&seqs), matched > MAX_REPLAY_EVENTS)
+    };
+    let (events, truncated): (Vec<LogEvent>, bool) = match super::blocking(collect).await {
+        Ok(collected) => collected,
+        Err(response) => return response,
+    };
+
+    let count = events.len();
+    let id = state.replays.insert(Replay::new(events, speed));
+    let response = ReplayResponse {
+        stream: format!("api/replay/{}/ws", id),
+        id,
+        events: count,
+        truncated,
+    };
+    (StatusCode::CREATED, Json(response)).into_response()
+}
+
+/// GET /api/replay/{id}/ws - Stream a prepared replay, once
+pub async fn replay_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<LogsState>>,
+    Path(id): Path<String>,
+    Query(query): Query<WsQuery>,
+) -> Response {
+    let format_name = query.format.as_deref().unwrap_or("json");
+    let Some(format) = state.formats.get(format_name) else {
+        return ApiError::BadRequest(format!("unknown format: {}", format_name)).into_response();
+    };
+    let Some(replay) = state.replays.take(&id) else {
+        return ApiError::not_found("replay", id).into_response();
+    };
+
+    ws.on_upgrade(move |mut socket| async move {
+        stream_replay(&mut socket, replay, format.as_ref()).await;
+    })
+}
+
+/// Send the events of a replay with their original spacing
+async fn stream_replay(socket: &mut WebSocket, replay: Replay, format: &dyn crate::format::Format) {
+    let mut previous: Option<&LogEvent> = None;
+    for event in &replay.events {
+        if let Some(previous) = previous {
+            tokio::time::sleep(crate::replay::delay(previous, event, replay.speed)).await;
+        }
+        previous = Some(event);
+
+        let message = match encode_ws_event(format, event) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::warn!("Failed to encode replayed event: {}", e);
+                continue;
+            }
+        };
+        if socket.send(message).await.is_err() {
+            tracing::debug!("Replay client disconnected");
+            return;
+        }
+    }
+
+    let finished = WsServerMessage::ReplayFinished {
+        events: replay.events.len(),
+    };
+    if let Ok(json) = serde_json::to_string(&finished) {
+        let _ = socket.send(Message::Text(json.into())).await;
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
@@ -0,0 +1,130 @@
+//! Snapshot API for sharing the buffer as a downloadable bundle
+
+use super::error::ApiError;
+use super::logs::LogsState;
+use crate::ingest::grok::normalize_level;
+use crate::snapshot::{BundleMetadata, SnapshotBundle};
+use crate::storage::LogEvent;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Largest compressed bundle accepted by POST /api/snapshot/import
+pub(crate) const MAX_IMPORT_BYTES: usize = 64 * 1024 * 1024;
+/// Field holding the ID of the bundle an imported event came from
+pub const SNAPSHOT_FIELD: &str = "snapshot";
+
+/// Request body for POST /api/snapshot
+#[derive(Debug, Default, Deserialize)]
+pub struct SnapshotRequest {
+    /// Filters applied in the UI, stored in the metadata as sent
+    #[serde(default)]
+    pub view: Option<Value>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Response for POST /api/snapshot/import
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    pub imported: usize,
+    pub metadata: BundleMetadata,
+}
+
+/// POST /api/snapshot - Download the buffer with its markers and metadata as
+/// a gzip-compressed bundle
+pub async fn create_snapshot(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<SnapshotRequest>,
+) -> Response {
+    let capture_filter = state
+        .capture
+        .filter_of(&state.storage)
+        .map(|f| f.to_string());
+    let storage = state.storage.clone();
+    let encoded = super::blocking(move || {
+        let bundle = SnapshotBundle::capture(&storage, capture_filter, request.view, request.note);
+        bundle.to_gzip().map(|bytes| (bundle.metadata, bytes))
+    })
+    .await;
+    let (metadata, bytes) = match encoded {
+        Ok(Ok(encoded)) => encoded,
+        Ok(Err(e)) => {
+            return ApiError::Internal(format!("failed to encode snapshot: {}", e)).into_response()
+        }
+        Err(response) => return response,
+    };
+
+    let file_name = format!(
+        "snapshot-{}-{}.json.gz",
+        metadata.host.as_deref().unwrap_or("console"),
+        metadata.created_at.format("%Y%m%dT%H%M%SZ")
+    );
+    (
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        bytes,
+    )
+        .into_response()
+}
+
+/// POST /api/snapshot/import - Load a bundle taken by another console
+///
+/// The events are added to the buffer with their original timestamps and
+/// tagged with the bundle ID in the `snapshot` field, markers are added too.
+pub async fn import_snapshot(State(state): State<Arc<LogsState>>, body: Bytes) -> Response {
+    let bundle = match super::blocking(move || SnapshotBundle::from_gzip(&body)).await {
+        Ok(Ok(bundle)) => bundle,
+        Ok(Err(e)) => return ApiError::BadRequest(e).into_response(),
+        Err(response) => return response,
+    };
+
+    let SnapshotBundle {
+        metadata,
+        mut events,
+        markers,
+    } = bundle;
+    for event in &mut events {
+        // Bundles can be edited by hand, only known levels are kept
+        event.level = normalize_level(&event.level).into();
+        event
+            .fields
+            .insert(SNAPSHOT_FIELD.into(), metadata.id.clone().into());
+    }
+    let imported = events.len();
+    state.storage.restore(events);
+    for marker in markers {
+        state.storage.add_marker(marker);
+    }
+
+    let mut fields = HashMap::from([
+        ("snapshot".to_string(), metadata.id.clone()),
+        ("imported".to_string(), imported.to_string()),
+    ]);
+    if let Some(host) = &metadata.host {
+        fields.insert("host".to_string(), host.clone());
+    }
+    state.storage.push(LogEvent::lifecycle(
+        "snapshot_imported",
+        &format!(
+            "Imported {} events from a snapshot taken on {} at {}",
+            imported,
+            metadata.host.as_deref().unwrap_or("an unknown host"),
+            metadata.created_at
+        ),
+        fields,
+    ));
+
+    Json(ImportResponse { imported, metadata }).into_response()
+}
@@ -0,0 +1,196 @@
+//! Spans API exposing captured span lifecycles and their nesting
+
+use super::error::ApiError;
+use super::logs::LogsState;
+use crate::spans::{SpanQuery, SpanRecord};
+use axum::extract::{Path, Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Default and maximum number of spans listed
+const DEFAULT_SPAN_LIMIT: usize = 100;
+const MAX_SPAN_LIMIT: usize = 1000;
+
+/// Query parameters for GET /api/spans
+#[derive(Debug, Default, Deserialize)]
+pub struct SpansQuery {
+    /// Exact span name
+    pub name: Option<String>,
+    /// Only spans of this target or modules below it
+    pub target: Option<String>,
+    /// Only children of this span
+    pub parent: Option<String>,
+    /// Only spans without a parent
+    #[serde(default)]
+    pub roots: bool,
+    /// Only open (`true`) or closed (`false`) spans
+    pub open: Option<bool>,
+    pub limit: Option<usize>,
+}
+
+/// Response for GET /api/spans
+#[derive(Debug, Serialize)]
+pub struct SpansResponse {
+    /// Newest first
+    pub spans: Vec<SpanRecord>,
+    /// Number of spans stored
+    pub stored: usize,
+}
+
+/// GET /api/spans - Recorded spans with their timing, newest first
+pub async fn list_spans(
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<SpansQuery>,
+) -> Response {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SPAN_LIMIT)
+        .min(MAX_SPAN_LIMIT);
+    let filter = SpanQuery {
+        name: query.name,
+        target: query.target,
+        parent_id: query.parent,
+        roots: query.roots,
+        open: query.open,
+    };
+    let spans = state.storage.spans();
+    Json(SpansResponse {
+        spans: spans.list(&filter, limit),
+        stored: spans.len(),
+    })
+    .into_response()
+}
+
+/// Query parameters for GET /api/spans/slow
+#[derive(Debug, Default, Deserialize)]
+pub struct SlowSpansQuery {
+    /// Minimum duration, all closed spans by default
+    #[serde(default)]
+    pub threshold_ms: u64,
+    /// Exact span name
+    pub name: Option<String>,
+    /// Only spans of this target or modules below it
+    pub target: Option<String>,
+    /// Only spans without a parent, e.g. request handlers
+    #[serde(default)]
+    pub roots: bool,
+    pub limit: Option<usize>,
+}
+
+/// Response for GET /api/spans/slow
+#[derive(Debug, Serialize)]
+pub struct SlowSpansResponse {
+    pub threshold_ms: u64,
+    /// Longest first
+    pub spans: Vec<SpanRecord>,
+}
+
+/// GET /api/spans/slow - The slowest stored closed spans
+pub async fn get_slow_spans(
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<SlowSpansQuery>,
+) -> Response {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SPAN_LIMIT)
+        .min(MAX_SPAN_LIMIT);
+    let filter = SpanQuery {
+        name: query.name,
+        target: query.target,
+        roots: query.roots,
+        open: Some(false),
+        ..Default::default()
+    };
+    let threshold_us = query.threshold_ms.saturating_mul(1000);
+    Json(SlowSpansResponse {
+        threshold_ms: query.threshold_ms,
+        spans: state.storage.spans().slowest(&filter, threshold_us, limit),
+    })
+    .into_response()
+}
+
+/// GET /api/spans/{id} - A span with its nested child spans
+pub async fn get_span_tree(
+    State(state): State<Arc<LogsState>>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.storage.spans().tree(&id) {
+        Some(tree) => Json(tree).into_response(),
+        None => ApiError::not_found("span", id).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LogStorage;
+    use crate::subscriber::LogCaptureLayer;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[tokio::test]
+    async fn test_span_tree_from_captured_spans() {
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!(target: "app", "request", route = "/orders");
+            let _request = request.enter();
+            tracing::debug_span!(target: "app::db", "db_query").in_scope(|| {
+                tracing::info!(target: "app::db", "querying");
+            });
+        });
+        let state = Arc::new(LogsState::new(storage.clone()));
+
+        let roots = SpansQuery {
+            roots: true,
+            ..Default::default()
+        };
+        let response = list_spans(State(state.clone()), Query(roots)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let listed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let root = &listed["spans"][0];
+        assert_eq!(root["name"], "request");
+        assert_eq!(root["fields"]["route"], "/orders");
+        assert!(root["closed_at"].is_string());
+
+        let id = root["id"].as_str().unwrap().to_string();
+        let response = get_span_tree(State(state.clone()), Path(id.clone())).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let tree: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let child = &tree["children"][0];
+        assert_eq!(child["name"], "db_query");
+        assert_eq!(child["parent_id"], id.as_str());
+
+        // Events link to the span they were logged in
+        let (events, _) = storage.get_filtered(&Default::default(), None, None);
+        let span = events[0].span.as_ref().unwrap();
+        assert_eq!(span.id.as_deref(), child["id"].as_str());
+        assert_eq!(child["events"], 1);
+        assert_eq!(root["events"], 0);
+
+        let response = get_slow_spans(State(state.clone()), Query(Default::default())).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let slow: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(slow["spans"].as_array().unwrap().len(), 2);
+        let slow = SlowSpansQuery {
+            threshold_ms: 60_000,
+            ..Default::default()
+        };
+        let response = get_slow_spans(State(state.clone()), Query(slow)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let slow: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(slow["spans"].as_array().unwrap().is_empty());
+
+        let response = get_span_tree(State(state), Path("missing".to_string())).await;
+        assert_eq!(response.status(), axum::http::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,149 @@
+//! Server-Sent Events stream of new events, for networks that break WebSockets
+
+use super::error::ApiError;
+use super::logs::{parse_stream_filter, LogsState, WsQuery};
+use crate::format::Format;
+use crate::storage::{LogEvent, LogFilter, LogStorage};
+use axum::extract::{Query, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often a comment is sent while no events arrive, so proxies keep the
+/// connection open
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Records the client's disconnect when its stream is dropped
+struct SseClient {
+    storage: LogStorage,
+    client: String,
+}
+
+impl SseClient {
+    fn connect(storage: LogStorage) -> Self {
+        let client = crate::id::new_ulid();
+        storage.push(LogEvent::lifecycle(
+            "client_connected",
+            "SSE client connected",
+            HashMap::from([("client".to_string(), client.clone())]),
+        ));
+        Self { storage, client }
+    }
+}
+
+impl Drop for SseClient {
+    fn drop(&mut self) {
+        self.storage.push(LogEvent::lifecycle(
+            "client_disconnected",
+            "SSE client disconnected",
+            HashMap::from([("client".to_string(), std::mem::take(&mut self.client))]),
+        ));
+    }
+}
+
+/// SSE event of a log event, its sequence number as the event ID
+fn encode_sse_event(format: &dyn Format, event: &LogEvent) -> std::io::Result<Event> {
+    let encoded = String::from_utf8(format.encode_event(event)?)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok(Event::default().id(event.seq.to_string()).data(encoded))
+}
+
+/// New events matching `filter`, encoded in `format`
+fn sse_events(
+    storage: &LogStorage,
+    filter: LogFilter,
+    format: Arc<dyn Format>,
+) -> impl Stream<Item = Result<Event, Infallible>> + Send + 'static {
+    let client = SseClient::connect(storage.clone());
+    storage.event_stream().filter_map(move |event| {
+        // Dropped together with the stream when the client goes away
+        let _client = &client;
+        let encoded = if !filter.matches(&event) {
+            None
+        } else {
+            match encode_sse_event(format.as_ref(), &event) {
+                Ok(encoded) => Some(Ok(encoded)),
+                Err(e) => {
+                    tracing::error!("Failed to serialize log event: {}", e);
+                    None
+                }
+            }
+        };
+        futures::future::ready(encoded)
+    })
+}
+
+/// GET /api/sse - Server-Sent Events stream of new events
+///
+/// Takes the same `query`, `exclude_*` and `format` parameters as GET
+/// /api/ws. Binary formats can't be sent as SSE data.
+pub async fn sse_logs(
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<WsQuery>,
+) -> Response {
+    let filter = match parse_stream_filter(&state.storage, &query) {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+    let format_name = query.format.as_deref().unwrap_or("json");
+    let Some(format) = state.formats.get(format_name) else {
+        return ApiError::BadRequest(format!("unknown format: {}", format_name)).into_response();
+    };
+    if format.is_binary() {
+        return ApiError::BadRequest(format!(
+            "format {} is binary and can't be streamed as SSE",
+            format_name
+        ))
+        .into_response();
+    }
+
+    Sse::new(sse_events(&state.storage, filter, format))
+        .keep_alive(
+            KeepAlive::new()
+                .interval(KEEP_ALIVE_INTERVAL)
+                .text("keep-alive"),
+        )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::FormatRegistry;
+
+    #[tokio::test]
+    async fn test_sse_events_are_filtered() {
+        let storage = LogStorage::new();
+        let query = WsQuery {
+            query: Some("level>=info".to_string()),
+            exclude_levels: Some("info".to_string()),
+            ..Default::default()
+        };
+        let filter = parse_stream_filter(&storage, &query).unwrap();
+        let format = FormatRegistry::default().get("json").unwrap();
+        let mut events = Box::pin(sse_events(&storage, filter, format));
+
+        let info = LogEvent {
+            target: "app".into(),
+            ..LogEvent::internal("INFO", "skipped", HashMap::new())
+        };
+        let error = LogEvent {
+            level: "ERROR".into(),
+            message: "failed".to_string(),
+            ..info.clone()
+        };
+        storage.push(info);
+        storage.push(error);
+        assert!(events.next().await.is_some());
+
+        drop(events);
+        let last = storage.get_filtered(&Default::default(), None, None).0;
+        assert!(last
+            .iter()
+            .any(|event| event.message == "SSE client disconnected"));
+    }
+}
@@ -0,0 +1,53 @@
+//! Stats API summarizing stored events for dashboard charts
+
+use super::error::ApiError;
+use super::logs::LogsState;
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Default and maximum window of the event rates
+const DEFAULT_WINDOW_MINUTES: i64 = 5;
+const MAX_WINDOW_MINUTES: i64 = 24 * 60;
+
+/// Query parameters for GET /api/stats
+#[derive(Debug, Default, Deserialize)]
+pub struct StatsQuery {
+    /// Minutes of event rates to report
+    pub minutes: Option<i64>,
+}
+
+/// GET /api/stats - Counts by level and target, event rates, buffer
+/// utilization and lost events
+pub async fn get_stats(
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<StatsQuery>,
+) -> Response {
+    let minutes = query
+        .minutes
+        .unwrap_or(DEFAULT_WINDOW_MINUTES)
+        .clamp(1, MAX_WINDOW_MINUTES);
+    let storage = state.storage.clone();
+    match super::blocking(move || crate::aggregate::stats(&storage, minutes, chrono::Utc::now()))
+        .await
+    {
+        Ok(stats) => Json(stats).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// GET /api/metrics - Event counts and buffer state in the Prometheus text
+/// format, only if enabled with `with_metrics`
+pub async fn get_metrics(State(state): State<Arc<LogsState>>) -> Response {
+    if !state.metrics {
+        return ApiError::not_found("endpoint", "/api/metrics").into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, crate::metrics::CONTENT_TYPE)],
+        crate::metrics::render(&state.storage),
+    )
+        .into_response()
+}
@@ -0,0 +1,151 @@
+//! Summaries API for events that were compacted, and timelines spanning them
+//! or counting the events matching a log filter
+
+use super::error::ApiError;
+use super::logs::{request_filter, FilterParams, LogsState};
+use crate::aggregate::{parse_tz_offset, Bucketing, TimelineFilter};
+use crate::compaction::Summary;
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Query parameters for GET /api/summaries
+#[derive(Debug, Default, Deserialize)]
+pub struct SummariesQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Only summaries of this target or modules below it
+    pub target: Option<String>,
+    pub level: Option<String>,
+}
+
+/// Response for GET /api/summaries
+#[derive(Debug, Serialize)]
+pub struct SummariesResponse {
+    /// Oldest minute first
+    pub summaries: Vec<Summary>,
+}
+
+/// GET /api/summaries - Per-minute counts and examples of compacted events
+pub async fn list_summaries(
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<SummariesQuery>,
+) -> Response {
+    let summaries = state
+        .storage
+        .summaries()
+        .between(query.from, query.to)
+        .into_iter()
+        .filter(|s| match &query.target {
+            Some(target) => s.target == *target || s.target.starts_with(&format!("{}::", target)),
+            None => true,
+        })
+        .filter(|s| match &query.level {
+            Some(level) => s.level.eq_ignore_ascii_case(level),
+            None => true,
+        })
+        .collect();
+    Json(SummariesResponse { summaries }).into_response()
+}
+
+/// Default bucket size of GET /api/timeline
+const DEFAULT_BUCKET_SECS: i64 = 60;
+
+/// Query parameters for GET /api/timeline
+#[derive(Debug, Default, Deserialize)]
+pub struct TimelineQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Only events of this target or modules below it
+    pub target: Option<String>,
+    /// Minimum level
+    pub level: Option<String>,
+    pub bucket_secs: Option<i64>,
+    /// Local `hour`, `day` or ISO `week` buckets instead of `bucket_secs`
+    pub calendar: Option<String>,
+    /// UTC offset buckets are aligned in, e.g. `+02:00`, UTC by default
+    pub tz_offset: Option<String>,
+}
+
+/// GET /api/timeline - Event counts over time, exact for stored events and
+/// summarized for compacted ones
+pub async fn get_timeline(
+    State(state): State<Arc<LogsState>>,
+    Query(query): Query<TimelineQuery>,
+) -> Response {
+    let offset = match query.tz_offset.as_deref().filter(|o| !o.is_empty()) {
+        Some(offset) => match parse_tz_offset(offset) {
+            Ok(offset) => offset,
+            Err(message) => return ApiError::BadRequest(message).into_response(),
+        },
+        None => chrono::FixedOffset::east_opt(0).expect("zero offset is valid"),
+    };
+    let bucketing = match (&query.calendar, query.bucket_secs) {
+        (Some(_), Some(_)) => {
+            return ApiError::BadRequest("pass either calendar or bucket_secs".to_string())
+                .into_response()
+        }
+        (Some(unit), None) => Bucketing::calendar(unit, offset),
+        (None, secs) => Bucketing::fixed(secs.unwrap_or(DEFAULT_BUCKET_SECS), offset),
+    };
+    let bucketing = match bucketing {
+        Ok(bucketing) => bucketing,
+        Err(message) => return ApiError::BadRequest(message).into_response(),
+    };
+    let filter = TimelineFilter {
+        from: query.from,
+        to: query.to,
+        target: query.target,
+        level: query.level,
+    };
+    let storage = state.storage.clone();
+    match super::blocking(move || crate::aggregate::timeline(&storage, &filter, bucketing)).await {
+        Ok(Ok(timeline)) => Json(timeline).into_response(),
+        Ok(Err(message)) => ApiError::BadRequest(message).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Request body for POST /api/histogram
+#[derive(Debug, Deserialize)]
+pub struct HistogramRequest {
+    /// The same filters as POST /api/logs, paging and sorting are ignored
+    #[serde(flatten)]
+    pub filter: FilterParams,
+    pub bucket_secs: Option<i64>,
+    /// UTC offset buckets are aligned in, e.g. `+02:00`, UTC by default
+    pub tz_offset: Option<String>,
+}
+
+/// POST /api/histogram - Stored events matching a log filter, counted per
+/// time bucket and level
+pub async fn get_histogram(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<HistogramRequest>,
+) -> Response {
+    let filter = match request_filter(&state.storage, &request.filter) {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+    let offset = match request.tz_offset.as_deref().filter(|o| !o.is_empty()) {
+        Some(offset) => match parse_tz_offset(offset) {
+            Ok(offset) => offset,
+            Err(message) => return ApiError::BadRequest(message).into_response(),
+        },
+        None => chrono::FixedOffset::east_opt(0).expect("zero offset is valid"),
+    };
+    let bucketing =
+        match Bucketing::fixed(request.bucket_secs.unwrap_or(DEFAULT_BUCKET_SECS), offset) {
+            Ok(bucketing) => bucketing,
+            Err(message) => return ApiError::BadRequest(message).into_response(),
+        };
+    let storage = state.storage.clone();
+    match super::blocking(move || crate::aggregate::histogram(&storage, &filter, bucketing)).await {
+        Ok(Ok(histogram)) => Json(histogram).into_response(),
+        Ok(Err(message)) => ApiError::BadRequest(message).into_response(),
+        Err(response) => response,
+    }
+}
@@ -0,0 +1,57 @@
+//! Transforms API for inspecting and replacing the ingest pipeline
+
+use crate::api::error::ApiError;
+use crate::api::logs::LogsState;
+use crate::transform::TransformStage;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Response for GET /api/transforms
+#[derive(Debug, Serialize)]
+pub struct TransformsResponse {
+    /// Built-in stages, editable at runtime
+    pub stages: Vec<TransformStage>,
+    /// Names of custom transforms registered in code (always run first)
+    pub custom: Vec<String>,
+}
+
+/// Request body for PUT /api/transforms
+#[derive(Debug, Deserialize)]
+pub struct UpdateTransformsRequest {
+    pub stages: Vec<TransformStage>,
+}
+
+/// GET /api/transforms - Get the configured ingest pipeline
+pub async fn get_transforms(State(state): State<Arc<LogsState>>) -> Response {
+    let pipeline = state.storage.transforms();
+    let response = TransformsResponse {
+        stages: pipeline.stages(),
+        custom: pipeline.custom_names(),
+    };
+    Json(response).into_response()
+}
+
+/// PUT /api/transforms - Replace the built-in pipeline stages
+pub async fn update_transforms(
+    State(state): State<Arc<LogsState>>,
+    Json(request): Json<UpdateTransformsRequest>,
+) -> Response {
+    // Reject the whole update if any stage is invalid (e.g. a script that doesn't compile)
+    for stage in &request.stages {
+        if let Err(e) = stage.validate() {
+            return ApiError::BadRequest(format!("invalid transform stage: {}", e)).into_response();
+        }
+    }
+
+    let pipeline = state.storage.transforms();
+    pipeline.set_stages(request.stages);
+
+    let response = TransformsResponse {
+        stages: pipeline.stages(),
+        custom: pipeline.custom_names(),
+    };
+    Json(response).into_response()
+}
@@ -0,0 +1,46 @@
+//! Watchdog API for managing silent-target watches
+
+use crate::api::error::ApiError;
+use crate::api::logs::LogsState;
+use crate::watchdog::{TargetWatch, WatchStatus};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Response for GET /api/watchdog
+#[derive(Debug, Serialize)]
+pub struct WatchesResponse {
+    pub watches: Vec<WatchStatus>,
+}
+
+/// GET /api/watchdog - List watched targets and whether they are silent
+pub async fn list_watches(State(state): State<Arc<LogsState>>) -> Response {
+    let watches = state.watchdog.statuses(&state.storage);
+    Json(WatchesResponse { watches }).into_response()
+}
+
+/// POST /api/watchdog - Watch a target or change its allowed silence
+pub async fn upsert_watch(
+    State(state): State<Arc<LogsState>>,
+    Json(watch): Json<TargetWatch>,
+) -> Response {
+    match state.watchdog.upsert(watch) {
+        Ok(watch) => (StatusCode::CREATED, Json(watch)).into_response(),
+        Err(e) => ApiError::BadRequest(format!("invalid watch: {}", e)).into_response(),
+    }
+}
+
+/// DELETE /api/watchdog/{target} - Stop watching a target
+pub async fn delete_watch(
+    State(state): State<Arc<LogsState>>,
+    Path(target): Path<String>,
+) -> Response {
+    if state.watchdog.remove(&target) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        ApiError::not_found("watch", target).into_response()
+    }
+}
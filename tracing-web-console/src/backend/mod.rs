@@ -0,0 +1,179 @@
+//! Persistent storage backends keeping events across restarts
+//!
+//! The in-memory buffer stays the primary store. A backend receives a copy of
+//! every stored event, refills the buffer on startup and answers `/api/logs`
+//! queries with `history: true`, which reach back further than the buffer.
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use crate::cancel::CancelToken;
+use crate::sink::BatchSink;
+use crate::storage::{LogEvent, LogFilter};
+use std::io;
+use std::sync::Arc;
+
+/// Rows read from the backend at once while searching history
+const HISTORY_PAGE: usize = 1_000;
+/// Most persisted events examined by a single history query
+const MAX_HISTORY_SCAN: usize = 1_000_000;
+
+/// Durable store of log events
+///
+/// Events are addressed by a position that increases with every appended
+/// event and stays stable across restarts.
+pub trait StorageBackend: Send + Sync {
+    /// Persist a non-empty batch of events, oldest first
+    fn append(&self, events: &[LogEvent]) -> io::Result<()>;
+
+    /// Up to `limit` events with a position below `before`, newest first
+    fn load_before(&self, before: Option<u64>, limit: usize) -> io::Result<Vec<(u64, LogEvent)>>;
+}
+
+/// Hands batches from the buffered sink to a backend
+pub(crate) struct BackendWriter(pub Arc<dyn StorageBackend>);
+
+impl BatchSink for BackendWriter {
+    fn write_batch(&self, events: &[LogEvent]) {
+        if let Err(e) = self.0.append(events) {
+            tracing::warn!("Failed to persist {} events: {}", events.len(), e);
+        }
+    }
+}
+
+/// The newest `limit` persisted events, oldest first, to refill the buffer
+pub(crate) fn load_recent(backend: &dyn StorageBackend, limit: usize) -> io::Result<Vec<LogEvent>> {
+    let mut events: Vec<_> = backend
+        .load_before(None, limit)?
+        .into_iter()
+        .map(|(_, event)| event)
+        .collect();
+    events.reverse();
+    Ok(events)
+}
+
+/// A page of persisted events matching a filter
+pub(crate) struct HistoryPage {
+    /// Newest first
+    pub events: Vec<LogEvent>,
+    pub scanned: usize,
+    /// Position to continue before, if more events may match
+    pub next_cursor: Option<u64>,
+}
+
+/// Search persisted events below position `before`, newest first
+///
+/// Sequence number bounds of `filter` are ignored, sequence numbers restart
+/// with every run. Returns `None` if cancelled.
+pub(crate) fn query_history(
+    backend: &dyn StorageBackend,
+    filter: &LogFilter,
+    mut before: Option<u64>,
+    limit: usize,
+    cancel: &CancelToken,
+) -> io::Result<Option<HistoryPage>> {
+    let filter = LogFilter {
+        after_seq: None,
+        before_seq: None,
+        ..filter.clone()
+    };
+    let limit = limit.max(1);
+    let mut events = Vec::new();
+    let mut scanned = 0;
+
+    loop {
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+        let rows = backend.load_before(before, HISTORY_PAGE)?;
+        let exhausted = rows.len() < HISTORY_PAGE;
+        for (position, event) in rows {
+            scanned += 1;
+            before = Some(position);
+            if filter.matches(&event) {
+                events.push(event);
+                if events.len() >= limit {
+                    return Ok(Some(HistoryPage {
+                        events,
+                        scanned,
+                        next_cursor: before,
+                    }));
+                }
+            }
+        }
+        if exhausted || scanned >= MAX_HISTORY_SCAN {
+            return Ok(Some(HistoryPage {
+                events,
+                scanned,
+                next_cursor: before.filter(|_| !exhausted),
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct MemoryBackend(Mutex<Vec<LogEvent>>);
+
+    impl StorageBackend for MemoryBackend {
+        fn append(&self, events: &[LogEvent]) -> io::Result<()> {
+            self.0.lock().extend_from_slice(events);
+            Ok(())
+        }
+
+        fn load_before(
+            &self,
+            before: Option<u64>,
+            limit: usize,
+        ) -> io::Result<Vec<(u64, LogEvent)>> {
+            let events = self.0.lock();
+            let end = before.map_or(events.len(), |b| (b as usize).min(events.len()));
+            Ok((0..end)
+                .rev()
+                .take(limit)
+                .map(|i| (i as u64, events[i].clone()))
+                .collect())
+        }
+    }
+
+    fn event(level: &str, message: &str) -> LogEvent {
+        LogEvent::internal(level, message, HashMap::new())
+    }
+
+    #[test]
+    fn test_query_history_pages_newest_first() {
+        let backend = MemoryBackend::default();
+        let events: Vec<_> = (0..2_500)
+            .map(|i| event(if i % 2 == 0 { "ERROR" } else { "INFO" }, &i.to_string()))
+            .collect();
+        backend.append(&events).unwrap();
+
+        let filter = LogFilter {
+            global_level: Some("ERROR".to_string()),
+            // Ignored, sequence numbers of earlier runs mean nothing
+            after_seq: Some(u64::MAX),
+            ..Default::default()
+        };
+        let cancel = CancelToken::default();
+        let page = query_history(&backend, &filter, None, 10, &cancel)
+            .unwrap()
+            .unwrap();
+        assert_eq!(page.events.len(), 10);
+        assert_eq!(page.events[0].message, "2498");
+        assert_eq!(page.next_cursor, Some(2480));
+
+        let rest = query_history(&backend, &filter, page.next_cursor, 5_000, &cancel)
+            .unwrap()
+            .unwrap();
+        assert_eq!(rest.events.len(), 1_240);
+        assert_eq!(rest.events.last().unwrap().message, "0");
+        assert_eq!(rest.next_cursor, None);
+
+        assert_eq!(load_recent(&backend, 2).unwrap()[1].message, "2499");
+    }
+}
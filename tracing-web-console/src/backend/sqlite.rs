@@ -0,0 +1,163 @@
+//! SQLite storage backend
+//!
+//! Events are stored as JSON next to a few columns for external tools:
+//!
+//! ```sql
+//! CREATE TABLE events (
+//!     position  INTEGER PRIMARY KEY AUTOINCREMENT,
+//!     id        TEXT NOT NULL,
+//!     timestamp TEXT NOT NULL, -- RFC 3339
+//!     level     TEXT NOT NULL,
+//!     target    TEXT NOT NULL,
+//!     message   TEXT NOT NULL,
+//!     event     TEXT NOT NULL  -- the full event as JSON
+//! );
+//! ```
+
+use super::StorageBackend;
+use crate::storage::LogEvent;
+use parking_lot::Mutex;
+use rusqlite::{params, Connection};
+use std::io;
+use std::path::Path;
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    PRAGMA synchronous = NORMAL;
+    CREATE TABLE IF NOT EXISTS events (
+        position  INTEGER PRIMARY KEY AUTOINCREMENT,
+        id        TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        level     TEXT NOT NULL,
+        target    TEXT NOT NULL,
+        message   TEXT NOT NULL,
+        event     TEXT NOT NULL
+    );
+";
+
+fn to_io(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+/// Events persisted in an SQLite database file
+pub struct SqliteBackend {
+    conn: Mutex<Connection>,
+    /// Oldest rows beyond this are deleted after each append
+    max_rows: Option<u64>,
+}
+
+impl SqliteBackend {
+    /// Open or create the database at `path`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let conn = Connection::open(path).map_err(to_io)?;
+        conn.execute_batch(SCHEMA).map_err(to_io)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            max_rows: None,
+        })
+    }
+
+    /// Keep at most `max_rows` events, deleting the oldest
+    pub fn with_max_rows(mut self, max_rows: u64) -> Self {
+        self.max_rows = Some(max_rows.max(1));
+        self
+    }
+}
+
+impl StorageBackend for SqliteBackend {
+    fn append(&self, events: &[LogEvent]) -> io::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(to_io)?;
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT INTO events (id, timestamp, level, target, message, event)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .map_err(to_io)?;
+            for event in events {
+                insert
+                    .execute(params![
+                        event.id,
+                        event.timestamp.to_rfc3339(),
+                        event.level.as_str(),
+                        event.target.as_str(),
+                        event.message,
+                        serde_json::to_string(event)?,
+                    ])
+                    .map_err(to_io)?;
+            }
+        }
+        if let Some(max_rows) = self.max_rows {
+            tx.execute(
+                "DELETE FROM events WHERE position <= (SELECT MAX(position) FROM events) - ?1",
+                params![max_rows as i64],
+            )
+            .map_err(to_io)?;
+        }
+        tx.commit().map_err(to_io)
+    }
+
+    fn load_before(&self, before: Option<u64>, limit: usize) -> io::Result<Vec<(u64, LogEvent)>> {
+        let conn = self.conn.lock();
+        let mut select = conn
+            .prepare_cached(
+                "SELECT position, event FROM events WHERE position < ?1
+                 ORDER BY position DESC LIMIT ?2",
+            )
+            .map_err(to_io)?;
+        let before = before.map_or(i64::MAX, |b| b.min(i64::MAX as u64) as i64);
+        let rows = select
+            .query_map(params![before, limit as i64], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(to_io)?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            let (position, json) = row.map_err(to_io)?;
+            match serde_json::from_str(&json) {
+                Ok(event) => events.push((position as u64, event)),
+                // Skip rows written by incompatible versions instead of failing the query
+                Err(e) => tracing::debug!("Skipping unreadable event at {}: {}", position, e),
+            }
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn temp_db() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("twc-sqlite-{}.db", crate::id::new_ulid()))
+    }
+
+    #[test]
+    fn test_events_survive_reopen() {
+        let path = temp_db();
+        let events: Vec<_> = (0..5)
+            .map(|i| LogEvent::internal("INFO", &format!("msg{}", i), HashMap::new()))
+            .collect();
+        SqliteBackend::open(&path)
+            .unwrap()
+            .with_max_rows(3)
+            .append(&events)
+            .unwrap();
+
+        let backend = SqliteBackend::open(&path).unwrap();
+        let loaded = backend.load_before(None, 10).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded[0].1.message, "msg4");
+        assert_eq!(loaded[0].1.id, events[4].id);
+
+        let older = backend.load_before(Some(loaded[0].0), 10).unwrap();
+        assert_eq!(older.len(), 2);
+        assert_eq!(older[0].1.message, "msg3");
+
+        drop(backend);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,49 @@
+//! Cooperative cancellation of long-running buffer scans
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Flag checked by long scans, set when nobody waits for the result anymore
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Guard cancelling the token when dropped
+    ///
+    /// Held by the request future, so a dropped connection (which drops the
+    /// future) cancels the scan running on the blocking pool.
+    pub fn cancel_on_drop(&self) -> CancelOnDrop {
+        CancelOnDrop(self.clone())
+    }
+}
+
+/// Cancels its token when dropped, see [`CancelToken::cancel_on_drop`]
+pub(crate) struct CancelOnDrop(CancelToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_cancels_on_drop() {
+        let token = CancelToken::default();
+        let guard = token.cancel_on_drop();
+        assert!(!token.is_cancelled());
+        drop(guard);
+        assert!(token.is_cancelled());
+    }
+}
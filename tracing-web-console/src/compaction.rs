@@ -0,0 +1,234 @@
+//! Compaction of aged events into per-minute summaries
+//!
+//! Raw events are dropped once they are older than the configured age, but
+//! their counts per minute, target and level are kept with a few examples.
+//! Summaries are much smaller than events, so they cover a far longer time
+//! range within a fixed budget.
+
+use crate::storage::{LogEvent, LogStorage};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Default number of summaries kept, the oldest minutes are dropped first
+const DEFAULT_MAX_SUMMARIES: usize = 100_000;
+/// Examples kept per summary
+const MAX_EXAMPLES: usize = 3;
+/// Example messages are cut to this many characters
+const MAX_EXAMPLE_LEN: usize = 512;
+/// Bounds of the compaction interval, derived from the compaction age
+const MIN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// An event kept as an example of a summary
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryExample {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub message: String,
+}
+
+/// Number of events of a target and level within one minute
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    /// Start of the minute
+    pub minute: DateTime<Utc>,
+    pub target: String,
+    pub level: String,
+    pub count: u64,
+    /// The first few compacted events of the minute
+    pub examples: Vec<SummaryExample>,
+}
+
+/// Summaries ordered by minute, target and level
+type SummaryKey = (DateTime<Utc>, String, String);
+
+/// Start of the minute containing `timestamp`
+fn minute_of(timestamp: DateTime<Utc>) -> DateTime<Utc> {
+    let seconds = timestamp.timestamp();
+    DateTime::from_timestamp(seconds - seconds.rem_euclid(60), 0).unwrap_or(timestamp)
+}
+
+/// Bounded store of compacted summaries
+pub(crate) struct SummaryStore {
+    summaries: RwLock<BTreeMap<SummaryKey, Summary>>,
+    max_summaries: AtomicUsize,
+}
+
+impl Default for SummaryStore {
+    fn default() -> Self {
+        Self {
+            summaries: RwLock::new(BTreeMap::new()),
+            max_summaries: AtomicUsize::new(DEFAULT_MAX_SUMMARIES),
+        }
+    }
+}
+
+impl SummaryStore {
+    /// Change the number of summaries kept, dropping the oldest if needed
+    pub fn set_max_summaries(&self, max: usize) {
+        self.max_summaries.store(max.max(1), Ordering::Relaxed);
+        let mut summaries = self.summaries.write();
+        self.enforce_limit(&mut summaries);
+    }
+
+    fn enforce_limit(&self, summaries: &mut BTreeMap<SummaryKey, Summary>) {
+        let max = self.max_summaries.load(Ordering::Relaxed);
+        while summaries.len() > max {
+            summaries.pop_first();
+        }
+    }
+
+    /// Fold events into their summaries
+    pub fn add(&self, events: &[LogEvent]) {
+        let mut summaries = self.summaries.write();
+        for event in events {
+            let minute = minute_of(event.timestamp);
+            let key = (minute, event.target.to_string(), event.level.to_string());
+            let summary = summaries.entry(key).or_insert_with(|| Summary {
+                minute,
+                target: event.target.to_string(),
+                level: event.level.to_string(),
+                count: 0,
+                examples: Vec::new(),
+            });
+            summary.count += 1;
+            if summary.examples.len() < MAX_EXAMPLES {
+                summary.examples.push(SummaryExample {
+                    id: event.id.clone(),
+                    timestamp: event.timestamp,
+                    message: event.message.chars().take(MAX_EXAMPLE_LEN).collect(),
+                });
+            }
+        }
+        self.enforce_limit(&mut summaries);
+    }
+
+    /// Summaries of minutes starting within `from..=to`, oldest first
+    pub fn between(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Vec<Summary> {
+        self.summaries
+            .read()
+            .values()
+            .filter(|s| !matches!(from, Some(from) if s.minute < minute_of(from)))
+            .filter(|s| !matches!(to, Some(to) if s.minute > to))
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.summaries.write().clear();
+    }
+}
+
+/// When events are compacted and how many summaries are kept
+#[derive(Debug, Clone)]
+pub(crate) struct CompactionConfig {
+    /// Events older than this are compacted
+    pub after: Duration,
+    pub max_summaries: usize,
+}
+
+impl CompactionConfig {
+    /// Check often enough that events aren't kept longer than a tenth past the age
+    fn check_interval(&self) -> Duration {
+        (self.after / 10).clamp(MIN_CHECK_INTERVAL, MAX_CHECK_INTERVAL)
+    }
+}
+
+/// Replace events older than `after` with summaries, returns how many were compacted
+pub(crate) fn compact(storage: &LogStorage, after: Duration) -> usize {
+    let Ok(after) = chrono::Duration::from_std(after) else {
+        // An age too large for chrono never compacts anything
+        return 0;
+    };
+    let cutoff = Utc::now() - after;
+
+    let mut compacted = Vec::new();
+    storage.retain(|event| {
+        if event.timestamp < cutoff {
+            compacted.push(event.clone());
+            false
+        } else {
+            true
+        }
+    });
+    // Summaries are locked after the buffer is released
+    storage.summaries().add(&compacted);
+    compacted.len()
+}
+
+/// Compact aged events forever
+pub(crate) async fn run(storage: LogStorage, config: CompactionConfig) {
+    storage.summaries().set_max_summaries(config.max_summaries);
+    let mut interval = tokio::time::interval(config.check_interval());
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        if storage.is_paused() {
+            continue;
+        }
+        let storage = storage.clone();
+        let after = config.after;
+        // Compaction rewrites the buffer under the write lock, keep it off the async workers
+        let _ = tokio::task::spawn_blocking(move || compact(&storage, after)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LogFilter;
+    use std::collections::HashMap;
+
+    fn event(level: &str, target: &str, timestamp: DateTime<Utc>) -> LogEvent {
+        LogEvent {
+            target: target.into(),
+            timestamp,
+            ..LogEvent::internal(level, "payment failed", HashMap::new())
+        }
+    }
+
+    #[test]
+    fn test_compact_into_minutes() {
+        let storage = LogStorage::new();
+        let minute = minute_of(Utc::now() - chrono::Duration::hours(2));
+        for i in 0..5 {
+            let timestamp = minute + chrono::Duration::seconds(i * 10);
+            storage.push(event("ERROR", "app::payments", timestamp));
+        }
+        storage.push(event("INFO", "app::payments", minute));
+        storage.push(event("INFO", "app::payments", Utc::now()));
+
+        assert_eq!(compact(&storage, Duration::from_secs(3600)), 6);
+        let (_, count) = storage.get_filtered(&LogFilter::default(), None, None);
+        assert_eq!(count, 1);
+
+        let summaries = storage.summaries().between(None, None);
+        assert_eq!(summaries.len(), 2);
+        let errors = summaries.iter().find(|s| s.level == "ERROR").unwrap();
+        assert_eq!(errors.minute, minute);
+        assert_eq!(errors.count, 5);
+        assert_eq!(errors.examples.len(), MAX_EXAMPLES);
+    }
+
+    #[test]
+    fn test_oldest_summaries_are_dropped() {
+        let store = SummaryStore::default();
+        store.set_max_summaries(2);
+        let now = minute_of(Utc::now());
+        let events: Vec<_> = (0..3)
+            .map(|i| event("INFO", "app", now - chrono::Duration::minutes(i)))
+            .collect();
+        store.add(&events);
+
+        let summaries = store.between(None, None);
+        assert_eq!(summaries.len(), 2);
+        let oldest = summaries[0].minute;
+        assert_eq!(oldest, now - chrono::Duration::minutes(1));
+        assert_eq!(store.between(Some(now), None).len(), 1);
+    }
+}
@@ -0,0 +1,85 @@
+//! Small buffer of the console's own diagnostic events
+//!
+//! Failures inside the console, such as WebSocket errors, sink or export
+//! failures and shedding notices, are kept apart from the application's
+//! events. They stay available for debugging the console, while events of
+//! this crate are never captured into the main buffer to avoid recursion.
+
+use crate::storage::LogEvent;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+
+/// Default number of diagnostic events kept, the oldest are dropped first
+const DEFAULT_MAX_DIAGNOSTICS: usize = 500;
+
+/// Bounded buffer of diagnostic events
+///
+/// Unlike the main buffer, events are not streamed, transformed or written to
+/// sinks, so recording one can't trigger another.
+pub(crate) struct Diagnostics {
+    events: Mutex<VecDeque<LogEvent>>,
+    capacity: usize,
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_MAX_DIAGNOSTICS)
+    }
+}
+
+impl Diagnostics {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&self, event: LogEvent) {
+        let mut events = self.events.lock();
+        if events.len() >= self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// The newest events, newest first
+    pub fn recent(&self, limit: usize) -> Vec<LogEvent> {
+        self.events
+            .lock()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.lock().len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_bounded_newest_first() {
+        let diagnostics = Diagnostics::with_capacity(2);
+        for message in ["a", "b", "c"] {
+            diagnostics.push(LogEvent::internal("WARN", message, HashMap::new()));
+        }
+        let messages: Vec<_> = diagnostics
+            .recent(10)
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages, ["c", "b"]);
+        assert_eq!(diagnostics.recent(1).len(), 1);
+    }
+}
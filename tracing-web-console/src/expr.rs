@@ -0,0 +1,411 @@
+//! Filter expressions like `span:create_order AND level>=debug`
+//!
+//! Shared by log queries, WebSocket streams and alert rules, so an expression
+//! matches the same events everywhere.
+
+use crate::storage::{field_text, level_to_number, LogEvent};
+use crate::subscriber::TASK_ID_FIELD;
+use serde_json::Value;
+
+/// Error parsing a filter expression, each variant carries the offending clause
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ExprError {
+    #[error("'{0}' is not supported, clauses are combined with AND")]
+    Unsupported(String),
+    #[error("expected a comparison after 'level' in '{0}'")]
+    MissingComparison(String),
+    #[error("unknown level in '{0}'")]
+    UnknownLevel(String),
+    #[error("missing value in '{0}'")]
+    MissingValue(String),
+    #[error("expected a number in '{0}'")]
+    NotANumber(String),
+}
+
+impl ExprError {
+    /// The clause that failed to parse
+    pub fn clause(&self) -> &str {
+        match self {
+            ExprError::Unsupported(clause)
+            | ExprError::MissingComparison(clause)
+            | ExprError::UnknownLevel(clause)
+            | ExprError::MissingValue(clause)
+            | ExprError::NotANumber(clause) => clause,
+        }
+    }
+}
+
+/// Comparison operator of a `level` or numeric field clause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+}
+
+impl Comparison {
+    fn compare<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ge => left >= right,
+            Comparison::Gt => left > right,
+            Comparison::Le => left <= right,
+            Comparison::Lt => left < right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Clause {
+    /// `span:name`, matches if the event's span or any ancestor has this name
+    Span(String),
+    /// `span.user_id=42`, a field of the event's span or any ancestor
+    SpanField(String, String),
+    /// `field:order_id=abc` or `field.order_id=abc`, a field of the event itself
+    Field(String, String),
+    /// `field:latency_ms>250`, a numeric field of the event compared to a number
+    FieldRange(String, Comparison, f64),
+    /// `level>=debug`
+    Level(Comparison, u8),
+    /// `target:my_app`, target or module below it
+    Target(String),
+    /// `task:42`, logged from the tokio task or inside a span created in it
+    Task(String),
+    /// Bare word or `message:word`, case-insensitive message substring
+    Text(String),
+}
+
+impl Clause {
+    fn matches(&self, event: &LogEvent) -> bool {
+        match self {
+            Clause::Span(name) => event
+                .span
+                .as_ref()
+                .is_some_and(|span| span.name == *name || span.ancestors.iter().any(|a| a == name)),
+            Clause::SpanField(name, value) => event
+                .span
+                .as_ref()
+                .is_some_and(|span| span.field(name) == Some(value.as_str())),
+            Clause::Field(name, value) => event
+                .fields
+                .get(name.as_str())
+                .is_some_and(|field| field_text(field) == *value),
+            Clause::FieldRange(name, comparison, number) => event
+                .fields
+                .get(name.as_str())
+                .and_then(field_number)
+                .is_some_and(|field| comparison.compare(field, *number)),
+            Clause::Level(comparison, level) => {
+                comparison.compare(level_to_number(&event.level), *level)
+            }
+            Clause::Target(target) => {
+                event.target == *target || event.target.starts_with(&format!("{}::", target))
+            }
+            Clause::Task(id) => {
+                event
+                    .fields
+                    .get(TASK_ID_FIELD)
+                    .is_some_and(|task| field_text(task) == *id)
+                    || event
+                        .span
+                        .as_ref()
+                        .and_then(|span| span.fields.get(TASK_ID_FIELD))
+                        .is_some_and(|task| task == id)
+            }
+            Clause::Text(text) => event.message.to_lowercase().contains(text),
+        }
+    }
+}
+
+/// A parsed filter expression, all clauses must match
+#[derive(Debug, Clone)]
+pub struct FilterExpr {
+    source: String,
+    clauses: Vec<Clause>,
+}
+
+/// Parse `level>=debug` style clauses, `rest` is everything after "level"
+fn parse_level_clause(token: &str, rest: &str) -> Result<Clause, ExprError> {
+    let (comparison, level) = [
+        (">=", Comparison::Ge),
+        ("<=", Comparison::Le),
+        (">", Comparison::Gt),
+        ("<", Comparison::Lt),
+        (":", Comparison::Eq),
+        ("=", Comparison::Eq),
+    ]
+    .iter()
+    .find_map(|(op, comparison)| rest.strip_prefix(op).map(|level| (*comparison, level)))
+    .ok_or_else(|| ExprError::MissingComparison(token.to_string()))?;
+
+    match level_to_number(level) {
+        0 => Err(ExprError::UnknownLevel(token.to_string())),
+        number => Ok(Clause::Level(comparison, number)),
+    }
+}
+
+/// Numeric value of a field, numbers recorded as text included
+fn field_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parse `name>=number` style comparisons of a field clause, `None` if the
+/// clause isn't a comparison
+fn parse_field_comparison(token: &str, field: &str) -> Option<Result<Clause, ExprError>> {
+    let position = field.find(['<', '>'])?;
+    let (name, rest) = field.split_at(position);
+    let (comparison, number) = [
+        (">=", Comparison::Ge),
+        ("<=", Comparison::Le),
+        (">", Comparison::Gt),
+        ("<", Comparison::Lt),
+    ]
+    .iter()
+    .find_map(|(op, comparison)| rest.strip_prefix(op).map(|number| (*comparison, number)))?;
+    if name.is_empty() || number.is_empty() {
+        return Some(Err(ExprError::MissingValue(token.to_string())));
+    }
+    Some(match number.parse() {
+        Ok(number) => Ok(Clause::FieldRange(name.to_string(), comparison, number)),
+        Err(_) => Err(ExprError::NotANumber(token.to_string())),
+    })
+}
+
+/// Split `name=value` (or `name:value`) of a field clause
+fn parse_field(token: &str, field: &str) -> Result<(String, String), ExprError> {
+    match field.split_once(['=', ':']) {
+        Some((name, value)) if !name.is_empty() && !value.is_empty() => {
+            Ok((name.to_string(), value.to_string()))
+        }
+        _ => Err(ExprError::MissingValue(token.to_string())),
+    }
+}
+
+impl FilterExpr {
+    /// Parse clauses separated by whitespace or `AND`
+    pub fn parse(source: &str) -> Result<Self, ExprError> {
+        let mut clauses = Vec::new();
+
+        for token in source.split_whitespace() {
+            if token.eq_ignore_ascii_case("and") {
+                continue;
+            }
+            if token.eq_ignore_ascii_case("or") || token.eq_ignore_ascii_case("not") {
+                return Err(ExprError::Unsupported(token.to_string()));
+            }
+
+            let clause = if let Some(name) = token.strip_prefix("span:") {
+                Clause::Span(name.to_string())
+            } else if let Some(field) = token.strip_prefix("span.") {
+                let (name, value) = parse_field(token, field)?;
+                Clause::SpanField(name, value)
+            } else if let Some(field) = token
+                .strip_prefix("field:")
+                .or_else(|| token.strip_prefix("field."))
+            {
+                match parse_field_comparison(token, field) {
+                    Some(clause) => clause?,
+                    None => {
+                        let (name, value) = parse_field(token, field)?;
+                        Clause::Field(name, value)
+                    }
+                }
+            } else if let Some(target) = token.strip_prefix("target:") {
+                Clause::Target(target.to_string())
+            } else if let Some(id) = token.strip_prefix("task:") {
+                Clause::Task(id.to_string())
+            } else if let Some(text) = token.strip_prefix("message:") {
+                Clause::Text(text.to_lowercase())
+            } else if let Some(rest) = token.strip_prefix("level") {
+                parse_level_clause(token, rest)?
+            } else {
+                Clause::Text(token.to_lowercase())
+            };
+
+            if matches!(&clause, Clause::Span(v) | Clause::Target(v) | Clause::Task(v) | Clause::Text(v) if v.is_empty())
+            {
+                return Err(ExprError::MissingValue(token.to_string()));
+            }
+            clauses.push(clause);
+        }
+
+        Ok(Self {
+            source: source.trim().to_string(),
+            clauses,
+        })
+    }
+
+    /// The expression as written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// `(field, value)` pairs of the span field clauses, used to look up
+    /// candidate events in the span field index
+    pub(crate) fn span_field_terms(&self) -> Vec<(&str, &str)> {
+        self.clauses
+            .iter()
+            .filter_map(|clause| match clause {
+                Clause::SpanField(name, value) => Some((name.as_str(), value.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Check whether an event matches every clause
+    pub fn matches(&self, event: &LogEvent) -> bool {
+        self.clauses.iter().all(|clause| clause.matches(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SpanInfo;
+    use std::collections::HashMap;
+
+    fn event(level: &str, message: &str, span: Option<(&str, &[&str])>) -> LogEvent {
+        LogEvent {
+            span: span.map(|(name, ancestors)| SpanInfo {
+                id: None,
+                name: name.to_string(),
+                fields: HashMap::new(),
+                ancestors: ancestors.iter().map(|a| a.to_string()).collect(),
+                parent_fields: HashMap::from([("user_id".to_string(), "42".to_string())]),
+            }),
+            target: "app::orders".into(),
+            ..LogEvent::internal(level, message, HashMap::new())
+        }
+    }
+
+    #[test]
+    fn test_span_matches_ancestors() {
+        let expr = FilterExpr::parse("span:create_order AND level>=debug").unwrap();
+
+        let nested = event(
+            "DEBUG",
+            "insert row",
+            Some(("db_query", &["http", "create_order"])),
+        );
+        assert!(expr.matches(&nested));
+
+        let direct = event("INFO", "validated", Some(("create_order", &[])));
+        assert!(expr.matches(&direct));
+
+        let too_verbose = event("TRACE", "bytes", Some(("create_order", &[])));
+        assert!(!expr.matches(&too_verbose));
+
+        let other_span = event("ERROR", "boom", Some(("cancel_order", &["http"])));
+        assert!(!expr.matches(&other_span));
+        assert!(!expr.matches(&event("ERROR", "boom", None)));
+    }
+
+    #[test]
+    fn test_other_clauses() {
+        let expr = FilterExpr::parse("target:app level=warn timeout").unwrap();
+        assert!(expr.matches(&event("WARN", "Request TIMEOUT", None)));
+        assert!(!expr.matches(&event("ERROR", "Request timeout", None)));
+        assert!(!expr.matches(&event("WARN", "ok", None)));
+    }
+
+    #[test]
+    fn test_span_field_clause() {
+        let expr = FilterExpr::parse("span.user_id=42").unwrap();
+        assert_eq!(expr.span_field_terms(), [("user_id", "42")]);
+        assert!(expr.matches(&event("INFO", "query", Some(("db_query", &["request"])))));
+        assert!(!expr.matches(&event("INFO", "query", None)));
+
+        let other = FilterExpr::parse("span.user_id:7").unwrap();
+        assert!(!other.matches(&event("INFO", "query", Some(("db_query", &["request"])))));
+        assert!(matches!(
+            FilterExpr::parse("span.user_id"),
+            Err(ExprError::MissingValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_event_field_clause() {
+        let expr = FilterExpr::parse("field:order_id=abc AND level>=WARN").unwrap();
+        let mut failed = event("ERROR", "payment failed", None);
+        failed.fields.insert("order_id".into(), "abc".into());
+        assert!(expr.matches(&failed));
+
+        let mut info = failed.clone();
+        info.level = "INFO".into();
+        assert!(!expr.matches(&info));
+        // Span fields are only matched by `span.` clauses
+        assert!(!expr.matches(&event("ERROR", "payment failed", Some(("pay", &[])))));
+
+        let dotted = FilterExpr::parse("field.order_id:abc").unwrap();
+        assert!(dotted.matches(&failed));
+        assert!(matches!(
+            FilterExpr::parse("field:order_id="),
+            Err(ExprError::MissingValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_numeric_field_clause() {
+        let expr = FilterExpr::parse("field:latency_ms>=250").unwrap();
+        let mut slow = event("INFO", "handled", None);
+        slow.fields.insert("latency_ms".into(), 1200.into());
+        assert!(expr.matches(&slow));
+
+        let mut fast = slow.clone();
+        fast.fields.insert("latency_ms".into(), 12.5.into());
+        assert!(!expr.matches(&fast));
+        // Numbers ingested as text compare numerically too
+        fast.fields.insert("latency_ms".into(), "300".into());
+        assert!(expr.matches(&fast));
+        assert!(!expr.matches(&event("INFO", "handled", None)));
+
+        // An exact match compares the text of a number
+        assert!(FilterExpr::parse("field:latency_ms=1200")
+            .unwrap()
+            .matches(&slow));
+        assert_eq!(
+            FilterExpr::parse("field:latency_ms<fast").unwrap_err(),
+            ExprError::NotANumber("field:latency_ms<fast".to_string())
+        );
+    }
+
+    #[test]
+    fn test_task_clause() {
+        let expr = FilterExpr::parse("task:7").unwrap();
+        let mut logged = event("INFO", "sent", None);
+        logged.fields.insert(TASK_ID_FIELD.into(), "7".into());
+        assert!(expr.matches(&logged));
+
+        let mut in_span = event("INFO", "reserved", Some(("reserve", &[])));
+        let span = in_span.span.as_mut().unwrap();
+        span.fields
+            .insert(TASK_ID_FIELD.to_string(), "7".to_string());
+        assert!(expr.matches(&in_span));
+
+        logged.fields.insert(TASK_ID_FIELD.into(), "8".into());
+        assert!(!expr.matches(&logged));
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        assert_eq!(
+            FilterExpr::parse("level>=loud").unwrap_err(),
+            ExprError::UnknownLevel("level>=loud".to_string())
+        );
+        assert!(matches!(
+            FilterExpr::parse("level~debug"),
+            Err(ExprError::MissingComparison(_))
+        ));
+        assert!(matches!(
+            FilterExpr::parse("span:"),
+            Err(ExprError::MissingValue(_))
+        ));
+        assert_eq!(FilterExpr::parse("a OR b").unwrap_err().clause(), "OR");
+    }
+}
@@ -0,0 +1,159 @@
+//! Target/level directives deciding which events a console captures
+
+use crate::target_trie::TargetTrie;
+use std::fmt;
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+
+/// Parsed filter like `info,my_app=debug,hyper=off`
+///
+/// A bare level sets the default, `target=level` overrides it for a target and
+/// everything below it. The most specific (longest) matching target wins, a
+/// repeated target replaces the earlier directive.
+#[derive(Debug, Clone)]
+pub(crate) struct TargetFilter {
+    default: LevelFilter,
+    /// Sorted by target length, longest first
+    directives: Vec<(String, LevelFilter)>,
+    /// The directives for lookups by target
+    trie: TargetTrie<LevelFilter>,
+}
+
+impl Default for TargetFilter {
+    /// Capture everything
+    fn default() -> Self {
+        Self {
+            default: LevelFilter::TRACE,
+            directives: Vec::new(),
+            trie: TargetTrie::default(),
+        }
+    }
+}
+
+/// Error parsing a filter directive list
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FilterError {
+    #[error("unknown level '{0}'")]
+    UnknownLevel(String),
+    #[error("missing target in '{0}'")]
+    MissingTarget(String),
+}
+
+/// Parse a level name, case-insensitive
+fn parse_level(level: &str) -> Result<LevelFilter, FilterError> {
+    match level.trim().to_lowercase().as_str() {
+        "off" => Ok(LevelFilter::OFF),
+        "error" => Ok(LevelFilter::ERROR),
+        "warn" => Ok(LevelFilter::WARN),
+        "info" => Ok(LevelFilter::INFO),
+        "debug" => Ok(LevelFilter::DEBUG),
+        "trace" => Ok(LevelFilter::TRACE),
+        other => Err(FilterError::UnknownLevel(other.to_string())),
+    }
+}
+
+impl TargetFilter {
+    /// Parse a comma separated list of directives
+    pub fn parse(spec: &str) -> Result<Self, FilterError> {
+        let mut filter = Self::default();
+
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        return Err(FilterError::MissingTarget(directive.to_string()));
+                    }
+                    let level = parse_level(level)?;
+                    filter.directives.retain(|(t, _)| t != target);
+                    filter.directives.push((target.to_string(), level));
+                    filter.trie.insert(target, level);
+                }
+                None => filter.default = parse_level(directive)?,
+            }
+        }
+
+        filter.directives.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        Ok(filter)
+    }
+
+    /// Get the level filter applying to a target
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.trie
+            .longest_match(target)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Check whether an event with the given target and level passes the filter
+    pub fn enabled(&self, target: &str, level: &Level) -> bool {
+        self.level_for(target) >= *level
+    }
+
+    /// The most verbose level any directive lets through
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, |max, level| max.max(level))
+    }
+}
+
+/// Renders the directives in the syntax `parse` accepts
+impl fmt::Display for TargetFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default)?;
+        // Shortest first, the order they are usually written in
+        for (target, level) in self.directives.iter().rev() {
+            write!(f, ",{}={}", target, level)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_match() {
+        let filter = TargetFilter::parse("info, my_app=debug, my_app::noisy=off").unwrap();
+
+        assert!(filter.enabled("other", &Level::INFO));
+        assert!(!filter.enabled("other", &Level::DEBUG));
+        assert!(filter.enabled("my_app::orders", &Level::DEBUG));
+        assert!(!filter.enabled("my_app::noisy", &Level::ERROR));
+        assert!(!filter.enabled("my_app::noisy::deep", &Level::ERROR));
+        // Prefixes only match at module boundaries
+        assert!(!filter.enabled("my_application", &Level::DEBUG));
+        assert_eq!(filter.max_level(), LevelFilter::DEBUG);
+    }
+
+    #[test]
+    fn test_default_captures_everything() {
+        let filter = TargetFilter::parse("").unwrap();
+        assert!(filter.enabled("anything", &Level::TRACE));
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let filter = TargetFilter::parse("my_app::noisy=off, warn, my_app=debug").unwrap();
+        assert_eq!(filter.to_string(), "warn,my_app=debug,my_app::noisy=off");
+        let parsed = TargetFilter::parse(&filter.to_string()).unwrap();
+        assert_eq!(parsed.to_string(), filter.to_string());
+    }
+
+    #[test]
+    fn test_repeated_target_replaces() {
+        let filter = TargetFilter::parse("my_app=debug,my_app=warn").unwrap();
+        assert!(!filter.enabled("my_app", &Level::INFO));
+        assert_eq!(filter.to_string(), "trace,my_app=warn");
+    }
+
+    #[test]
+    fn test_invalid_directives() {
+        assert!(TargetFilter::parse("loud").is_err());
+        assert!(TargetFilter::parse("=debug").is_err());
+        assert!(TargetFilter::parse("my_app=verbose").is_err());
+    }
+}
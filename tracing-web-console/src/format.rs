@@ -0,0 +1,579 @@
+//! Serialization formats shared by the live stream, sinks and snapshots
+//!
+//! Every place that writes events out looks formats up by name in a
+//! [`FormatRegistry`], so a new format is a single [`Format`] implementation.
+
+use crate::intern::Interned;
+use crate::storage::{field_text, LogEvent};
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+/// Encoding of log events
+///
+/// A format encodes single events as records, used for streaming and line
+/// based sinks, and sequences of events as documents, used for files.
+pub trait Format: Send + Sync {
+    /// Unique name selecting the format, e.g. `?format=csv`
+    fn name(&self) -> &'static str;
+
+    /// MIME type of documents in this format
+    fn content_type(&self) -> &'static str;
+
+    /// File extension without the dot
+    fn extension(&self) -> &'static str;
+
+    /// Whether the encoding isn't valid UTF-8 text
+    fn is_binary(&self) -> bool {
+        false
+    }
+
+    /// Encode a single event, without a trailing newline
+    fn encode_event(&self, event: &LogEvent) -> io::Result<Vec<u8>>;
+
+    /// Write a document of events, by default one record per line
+    fn write_events(
+        &self,
+        writer: &mut dyn Write,
+        events: &mut dyn Iterator<Item = &LogEvent>,
+    ) -> io::Result<()> {
+        for event in events {
+            writer.write_all(&self.encode_event(event)?)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats by name, shared by everything that writes events out
+#[derive(Clone)]
+pub struct FormatRegistry {
+    formats: Arc<RwLock<BTreeMap<&'static str, Arc<dyn Format>>>>,
+}
+
+impl FormatRegistry {
+    /// Registry with the built-in formats
+    pub fn new() -> Self {
+        let registry = Self {
+            formats: Arc::new(RwLock::new(BTreeMap::new())),
+        };
+        registry.register(Arc::new(Json));
+        registry.register(Arc::new(Ndjson));
+        registry.register(Arc::new(Csv));
+        registry.register(Arc::new(Logfmt));
+        registry.register(Arc::new(Compact));
+        registry.register(Arc::new(MessagePack));
+        #[cfg(feature = "parquet")]
+        registry.register(Arc::new(Parquet));
+        registry
+    }
+
+    /// Add a format, replacing a format with the same name
+    pub fn register(&self, format: Arc<dyn Format>) {
+        self.formats.write().insert(format.name(), format);
+    }
+
+    /// Look up a format by name, case-insensitive
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Format>> {
+        let name = name.to_ascii_lowercase();
+        self.formats.read().get(name.as_str()).cloned()
+    }
+
+    /// All registered formats, by name
+    pub fn list(&self) -> Vec<Arc<dyn Format>> {
+        self.formats.read().values().cloned().collect()
+    }
+}
+
+impl Default for FormatRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fields sorted by name for stable output
+fn sorted_fields(event: &LogEvent) -> Vec<(&Interned, &serde_json::Value)> {
+    let mut fields: Vec<_> = event.fields.iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    fields
+}
+
+/// Fields as a JSON object sorted by name, a cell of the tabular formats
+fn fields_json(event: &LogEvent) -> String {
+    let fields: serde_json::Map<_, _> = sorted_fields(event)
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect();
+    serde_json::Value::Object(fields).to_string()
+}
+
+/// JSON objects, a document is an array
+pub struct Json;
+
+impl Format for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode_event(&self, event: &LogEvent) -> io::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(event)?)
+    }
+
+    fn write_events(
+        &self,
+        writer: &mut dyn Write,
+        events: &mut dyn Iterator<Item = &LogEvent>,
+    ) -> io::Result<()> {
+        writer.write_all(b"[")?;
+        for (i, event) in events.enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut *writer, event)?;
+        }
+        writer.write_all(b"]")
+    }
+}
+
+/// One JSON object per line
+pub struct Ndjson;
+
+impl Format for Ndjson {
+    fn name(&self) -> &'static str {
+        "ndjson"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/x-ndjson"
+    }
+
+    fn extension(&self) -> &'static str {
+        "ndjson"
+    }
+
+    fn encode_event(&self, event: &LogEvent) -> io::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(event)?)
+    }
+}
+
+/// Comma-separated values with a header row, fields as a JSON object
+pub struct Csv;
+
+/// Columns written by the CSV format
+const CSV_COLUMNS: &[&str] = &[
+    "id",
+    "seq",
+    "timestamp",
+    "level",
+    "target",
+    "message",
+    "span",
+    "fields",
+];
+
+/// Quote a CSV cell if needed (RFC 4180)
+fn csv_cell(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl Format for Csv {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/csv"
+    }
+
+    fn extension(&self) -> &'static str {
+        "csv"
+    }
+
+    fn encode_event(&self, event: &LogEvent) -> io::Result<Vec<u8>> {
+        let cells = [
+            event.id.clone(),
+            event.seq.to_string(),
+            event.timestamp.to_rfc3339(),
+            event.level.to_string(),
+            event.target.to_string(),
+            event.message.clone(),
+            event
+                .span
+                .as_ref()
+                .map(|s| s.name.clone())
+                .unwrap_or_default(),
+            fields_json(event),
+        ];
+        let row: Vec<String> = cells.iter().map(|cell| csv_cell(cell)).collect();
+        Ok(row.join(",").into_bytes())
+    }
+
+    fn write_events(
+        &self,
+        writer: &mut dyn Write,
+        events: &mut dyn Iterator<Item = &LogEvent>,
+    ) -> io::Result<()> {
+        writer.write_all(CSV_COLUMNS.join(",").as_bytes())?;
+        writer.write_all(b"\r\n")?;
+        for event in events {
+            writer.write_all(&self.encode_event(event)?)?;
+            writer.write_all(b"\r\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// `key=value` pairs, one event per line
+pub struct Logfmt;
+
+/// Quote a logfmt value if it contains spaces, quotes or `=`
+fn logfmt_value(value: &str) -> String {
+    let needs_quotes =
+        value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=');
+    if needs_quotes {
+        format!("{:?}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+impl Format for Logfmt {
+    fn name(&self) -> &'static str {
+        "logfmt"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/plain; charset=utf-8"
+    }
+
+    fn extension(&self) -> &'static str {
+        "log"
+    }
+
+    fn encode_event(&self, event: &LogEvent) -> io::Result<Vec<u8>> {
+        let mut line = format!(
+            "ts={} level={} target={} msg={}",
+            event.timestamp.to_rfc3339(),
+            event.level.to_lowercase(),
+            logfmt_value(&event.target),
+            logfmt_value(&event.message)
+        );
+        if let Some(span) = &event.span {
+            line.push_str(&format!(" span={}", logfmt_value(&span.name)));
+        }
+        for (key, value) in sorted_fields(event) {
+            line.push_str(&format!(" {}={}", key, logfmt_value(&field_text(value))));
+        }
+        Ok(line.into_bytes())
+    }
+}
+
+/// Single human readable line, similar to `tracing_subscriber::fmt().compact()`
+pub struct Compact;
+
+impl Format for Compact {
+    fn name(&self) -> &'static str {
+        "compact"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/plain; charset=utf-8"
+    }
+
+    fn extension(&self) -> &'static str {
+        "log"
+    }
+
+    fn encode_event(&self, event: &LogEvent) -> io::Result<Vec<u8>> {
+        let mut line = format!(
+            "{} {:>5} {}: {}",
+            event.timestamp.format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+            event.level,
+            event.target,
+            event.message
+        );
+        for (key, value) in sorted_fields(event) {
+            line.push_str(&format!(" {}={}", key, field_text(value)));
+        }
+        if let Some(span) = &event.span {
+            line.push_str(&format!(" span={}", span.name));
+        }
+        Ok(line.into_bytes())
+    }
+}
+
+/// MIME type of MessagePack documents
+pub(crate) const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// MessagePack maps with the same keys as JSON, a document is an array
+///
+/// Smaller and faster to decode than JSON for high-volume streams, sent as
+/// binary WebSocket messages.
+pub struct MessagePack;
+
+impl Format for MessagePack {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn content_type(&self) -> &'static str {
+        MSGPACK_CONTENT_TYPE
+    }
+
+    fn extension(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn is_binary(&self) -> bool {
+        true
+    }
+
+    fn encode_event(&self, event: &LogEvent) -> io::Result<Vec<u8>> {
+        rmp_serde::to_vec_named(event).map_err(io::Error::other)
+    }
+
+    fn write_events(
+        &self,
+        writer: &mut dyn Write,
+        events: &mut dyn Iterator<Item = &LogEvent>,
+    ) -> io::Result<()> {
+        let events: Vec<&LogEvent> = events.collect();
+        rmp_serde::encode::write_named(writer, &events).map_err(io::Error::other)
+    }
+}
+
+/// Parquet schema with the same columns as the CSV format, `fields` holds
+/// a JSON object
+#[cfg(feature = "parquet")]
+const PARQUET_SCHEMA: &str = "
+message log_event {
+    REQUIRED BYTE_ARRAY id (UTF8);
+    REQUIRED INT64 seq;
+    REQUIRED INT64 timestamp (TIMESTAMP(MICROS,true));
+    REQUIRED BYTE_ARRAY level (UTF8);
+    REQUIRED BYTE_ARRAY target (UTF8);
+    REQUIRED BYTE_ARRAY message (UTF8);
+    OPTIONAL BYTE_ARRAY span (UTF8);
+    REQUIRED BYTE_ARRAY fields (UTF8);
+}
+";
+
+/// Columnar Parquet files for analysis tools like DuckDB or pandas, with
+/// the `parquet` feature
+///
+/// A document is a file with one row group. Parquet has no encoding of
+/// single records, so an event on its own is encoded as a one-row file.
+#[cfg(feature = "parquet")]
+pub struct Parquet;
+
+#[cfg(feature = "parquet")]
+impl Parquet {
+    fn write_file(events: &[&LogEvent]) -> parquet::errors::Result<Vec<u8>> {
+        use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+        use parquet::file::writer::SerializedFileWriter;
+
+        let text = |value: fn(&LogEvent) -> String| -> Vec<ByteArray> {
+            events
+                .iter()
+                .map(|event| ByteArray::from(value(event).into_bytes()))
+                .collect()
+        };
+
+        let schema = Arc::new(parquet::schema::parser::parse_message_type(PARQUET_SCHEMA)?);
+        let mut buffer = Vec::new();
+        let mut file = SerializedFileWriter::new(&mut buffer, schema, Default::default())?;
+        let mut row_group = file.next_row_group()?;
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            match index {
+                1 | 2 => {
+                    let values: Vec<i64> = events
+                        .iter()
+                        .map(|event| match index {
+                            1 => event.seq as i64,
+                            _ => event.timestamp.timestamp_micros(),
+                        })
+                        .collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                6 => {
+                    // Events outside of spans have no value, only a definition level of 0
+                    let levels: Vec<i16> = events
+                        .iter()
+                        .map(|event| i16::from(event.span.is_some()))
+                        .collect();
+                    let values: Vec<ByteArray> = events
+                        .iter()
+                        .filter_map(|event| event.span.as_ref())
+                        .map(|span| ByteArray::from(span.name.as_str()))
+                        .collect();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, Some(&levels), None)?;
+                }
+                _ => {
+                    let values = match index {
+                        0 => text(|event| event.id.clone()),
+                        3 => text(|event| event.level.to_string()),
+                        4 => text(|event| event.target.to_string()),
+                        5 => text(|event| event.message.clone()),
+                        _ => text(fields_json),
+                    };
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, None, None)?;
+                }
+            }
+            column.close()?;
+            index += 1;
+        }
+        row_group.close()?;
+        file.close()?;
+        Ok(buffer)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl Format for Parquet {
+    fn name(&self) -> &'static str {
+        "parquet"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/vnd.apache.parquet"
+    }
+
+    fn extension(&self) -> &'static str {
+        "parquet"
+    }
+
+    fn is_binary(&self) -> bool {
+        true
+    }
+
+    fn encode_event(&self, event: &LogEvent) -> io::Result<Vec<u8>> {
+        Self::write_file(&[event]).map_err(io::Error::other)
+    }
+
+    fn write_events(
+        &self,
+        writer: &mut dyn Write,
+        events: &mut dyn Iterator<Item = &LogEvent>,
+    ) -> io::Result<()> {
+        // The footer describes the whole file, so it's built in memory first
+        let events: Vec<&LogEvent> = events.collect();
+        writer.write_all(&Self::write_file(&events).map_err(io::Error::other)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn event() -> LogEvent {
+        LogEvent {
+            target: "app::orders".into(),
+            ..LogEvent::internal(
+                "INFO",
+                "order \"42\" created",
+                HashMap::from([("amount".to_string(), "9,99".to_string())]),
+            )
+        }
+    }
+
+    fn document(format: &dyn Format, events: &[LogEvent]) -> String {
+        let mut out = Vec::new();
+        format.write_events(&mut out, &mut events.iter()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_registry_lookup() {
+        let registry = FormatRegistry::new();
+        assert_eq!(registry.get("CSV").unwrap().content_type(), "text/csv");
+        assert_eq!(registry.get("parquet").is_some(), cfg!(feature = "parquet"));
+        assert!(registry.list().iter().any(|f| f.name() == "logfmt"));
+    }
+
+    #[test]
+    fn test_json_document_is_an_array() {
+        let events = [event(), event()];
+        let parsed: Vec<LogEvent> = serde_json::from_str(&document(&Json, &events)).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(document(&Json, &[]), "[]");
+    }
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let encoded = MessagePack.encode_event(&event()).unwrap();
+        let decoded: LogEvent = rmp_serde::from_slice(&encoded).unwrap();
+        assert_eq!(decoded.message, "order \"42\" created");
+        assert_eq!(decoded.fields["amount"], "9,99");
+        assert!(encoded.len() < Json.encode_event(&event()).unwrap().len());
+
+        let mut document = Vec::new();
+        MessagePack
+            .write_events(&mut document, &mut [event(), event()].iter())
+            .unwrap();
+        let decoded: Vec<LogEvent> = rmp_serde::from_slice(&document).unwrap();
+        assert_eq!(decoded.len(), 2);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_file() {
+        let mut in_span = event();
+        in_span.span = Some(crate::storage::SpanInfo {
+            id: None,
+            name: "checkout".to_string(),
+            fields: HashMap::new(),
+            ancestors: Vec::new(),
+            parent_fields: HashMap::new(),
+        });
+        let mut file = Vec::new();
+        Parquet
+            .write_events(&mut file, &mut [event(), in_span].iter())
+            .unwrap();
+        assert!(file.starts_with(b"PAR1") && file.ends_with(b"PAR1"));
+
+        let reader =
+            parquet::file::reader::SerializedFileReader::new(bytes::Bytes::from(file)).unwrap();
+        let metadata = parquet::file::reader::FileReader::metadata(&reader);
+        assert_eq!(metadata.file_metadata().num_rows(), 2);
+        assert_eq!(metadata.file_metadata().schema_descr().num_columns(), 8);
+    }
+
+    #[test]
+    fn test_csv_quotes_cells() {
+        let csv = document(&Csv, &[event()]);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next().unwrap(), CSV_COLUMNS.join(","));
+        let row = lines.next().unwrap();
+        assert!(row.contains(r#","order ""42"" created","#));
+        assert!(row.ends_with(r#""{""amount"":""9,99""}""#));
+    }
+
+    #[test]
+    fn test_logfmt() {
+        let line = String::from_utf8(Logfmt.encode_event(&event()).unwrap()).unwrap();
+        assert!(line.contains(" level=info target=app::orders "));
+        assert!(line.contains(r#"msg="order \"42\" created""#));
+        assert!(line.ends_with(" amount=9,99"));
+    }
+}
@@ -0,0 +1,211 @@
+//! Forwarding of captured events to a central console
+//!
+//! A service running its own console can also push every event to a
+//! collector, another `tracing-web-console` instance, which stores them with
+//! an `instance` field naming the service. One console then shows the events
+//! of all services and filters them by source. Sending needs the `forwarder`
+//! feature, every console can be a collector.
+
+#[cfg(feature = "forwarder")]
+use crate::storage::LogStorage;
+use crate::storage::{LogEvent, INTERNAL_TARGET};
+#[cfg(feature = "forwarder")]
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "forwarder")]
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "forwarder")]
+use std::time::Duration;
+
+/// Field naming the service an event was forwarded from
+pub const INSTANCE_FIELD: &str = "instance";
+/// Path of the collector's ingest endpoint, below the console's base path
+#[cfg(feature = "forwarder")]
+const INGEST_PATH: &str = "/api/ingest/events";
+/// Timeout for requests to the collector
+#[cfg(feature = "forwarder")]
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long events are collected before a batch is sent
+#[cfg(feature = "forwarder")]
+const BATCH_DELAY: Duration = Duration::from_secs(1);
+/// Most events sent in one request
+#[cfg(feature = "forwarder")]
+const MAX_BATCH_SIZE: usize = 500;
+/// Events kept for retry while the collector is unreachable, the oldest are
+/// dropped first
+#[cfg(feature = "forwarder")]
+const MAX_PENDING: usize = 10_000;
+
+/// Where and as whom events are forwarded
+#[cfg(feature = "forwarder")]
+#[derive(Debug, Clone)]
+pub struct ForwarderConfig {
+    /// Base URL of the collector's console, e.g. `http://logs.internal:3000/tracing`
+    pub collector_url: String,
+    /// Name of this service, stored with every forwarded event
+    pub instance: String,
+    /// Ingest token secret, needed once the collector has issued tokens,
+    /// its source must be `instance`
+    pub token: Option<String>,
+}
+
+/// Request body for POST /api/ingest/events
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForwardedEvents {
+    /// Name of the sending service
+    pub instance: String,
+    pub events: Vec<LogEvent>,
+}
+
+/// Whether an event describes the forwarding console itself
+///
+/// The collector records its own state, e.g. lifecycle events.
+pub(crate) fn is_own(event: &LogEvent) -> bool {
+    event.target == INTERNAL_TARGET
+        || event
+            .target
+            .strip_prefix(INTERNAL_TARGET)
+            .is_some_and(|rest| rest.starts_with("::"))
+}
+
+/// Events waiting to be delivered, bounded by `MAX_PENDING`
+#[cfg(feature = "forwarder")]
+#[derive(Default)]
+struct Pending {
+    events: VecDeque<LogEvent>,
+    /// Dropped since the last successful delivery
+    dropped: u64,
+}
+
+#[cfg(feature = "forwarder")]
+impl Pending {
+    fn push(&mut self, event: LogEvent) {
+        if self.events.len() >= MAX_PENDING {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(event);
+    }
+
+    /// The oldest events, at most one batch
+    fn batch(&self) -> Vec<LogEvent> {
+        self.events.iter().take(MAX_BATCH_SIZE).cloned().collect()
+    }
+
+    fn delivered(&mut self, count: usize) {
+        self.events.drain(..count.min(self.events.len()));
+    }
+}
+
+/// Forward stored events forever
+#[cfg(feature = "forwarder")]
+pub(crate) async fn run(storage: LogStorage, config: ForwarderConfig) {
+    let client = reqwest::Client::new();
+    let url = format!(
+        "{}{}",
+        config.collector_url.trim_end_matches('/'),
+        INGEST_PATH
+    );
+    let mut events = Box::pin(storage.event_stream());
+    let mut pending = Pending::default();
+    let mut failing = false;
+
+    loop {
+        // Wait for an event unless there are some left over from a failed attempt
+        if pending.events.is_empty() {
+            match events.next().await {
+                Some(event) => pending.push((*event).clone()),
+                None => return,
+            }
+        }
+        let deadline = tokio::time::Instant::now() + BATCH_DELAY;
+        while pending.events.len() < MAX_BATCH_SIZE {
+            match tokio::time::timeout_at(deadline, events.next()).await {
+                Ok(Some(event)) => pending.push((*event).clone()),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        pending.events.retain(|event| !is_own(event));
+        if pending.events.is_empty() {
+            continue;
+        }
+
+        let batch = ForwardedEvents {
+            instance: config.instance.clone(),
+            events: pending.batch(),
+        };
+        let mut request = client.post(&url).timeout(FORWARD_TIMEOUT).json(&batch);
+        if let Some(token) = &config.token {
+            request = request.bearer_auth(token);
+        }
+        let sent = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match sent {
+            Ok(_) => {
+                pending.delivered(batch.events.len());
+                if failing || pending.dropped > 0 {
+                    storage.diagnose(LogEvent::internal(
+                        "INFO",
+                        "Forwarding to collector resumed",
+                        HashMap::from([("dropped".to_string(), pending.dropped.to_string())]),
+                    ));
+                }
+                failing = false;
+                pending.dropped = 0;
+            }
+            Err(e) => {
+                // Report once per outage, then retry after the next batch delay
+                if !failing {
+                    storage.diagnose(LogEvent::internal(
+                        "WARN",
+                        "Failed to forward events to collector",
+                        HashMap::from([
+                            ("collector".to_string(), url.clone()),
+                            ("error".to_string(), e.to_string()),
+                        ]),
+                    ));
+                }
+                failing = true;
+                tokio::time::sleep(BATCH_DELAY).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[cfg(feature = "forwarder")]
+    #[test]
+    fn test_pending_is_bounded() {
+        let mut pending = Pending::default();
+        for i in 0..MAX_PENDING + 3 {
+            pending.push(LogEvent::internal("INFO", &i.to_string(), HashMap::new()));
+        }
+        assert_eq!(pending.events.len(), MAX_PENDING);
+        assert_eq!(pending.dropped, 3);
+
+        let batch = pending.batch();
+        assert_eq!(batch.len(), MAX_BATCH_SIZE);
+        assert_eq!(batch[0].message, "3");
+        pending.delivered(batch.len());
+        assert_eq!(pending.events[0].message, (MAX_BATCH_SIZE + 3).to_string());
+    }
+
+    #[test]
+    fn test_own_events_are_not_forwarded() {
+        let internal = LogEvent::internal("WARN", "sink failed", HashMap::new());
+        let lifecycle = LogEvent::lifecycle("started", "started", HashMap::new());
+        let app = LogEvent {
+            target: "tracing_web_console_app".into(),
+            ..internal.clone()
+        };
+        assert!(is_own(&internal));
+        assert!(is_own(&lifecycle));
+        assert!(!is_own(&app));
+    }
+}
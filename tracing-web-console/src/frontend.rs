@@ -1,23 +1,233 @@
 //! Frontend asset serving using embedded files
 
 use axum::body::Body;
-use axum::extract::{Path, State};
-use axum::http::{header, StatusCode};
-use axum::response::Response;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
 use include_dir::{include_dir, Dir};
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::access::{is_cookie_safe, TOKEN_COOKIE};
+use crate::api::config::{console_config, ConsoleConfig};
+use crate::api::error::ApiError;
+use crate::api::logs::LogsState;
+use crate::storage::{LogEvent, LogStorage};
 
 // Embed the frontend dist directory at compile time
 static FRONTEND_DIST: Dir = include_dir!("$CARGO_MANIFEST_DIR/frontend/dist");
 
+// SRI hashes computed by build.rs (ASSET_INTEGRITY)
+include!(concat!(env!("OUT_DIR"), "/asset_integrity.rs"));
+
+/// Set once the placeholder page has been served, so the warning is only emitted once
+static PLACEHOLDER_SERVED: AtomicBool = AtomicBool::new(false);
+
+/// Information about a single embedded file
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddedFile {
+    pub path: String,
+    pub bytes: usize,
+    /// Subresource-integrity hash for JS/CSS files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+}
+
+/// Response for GET /api/frontend/status
+#[derive(Debug, Clone, Serialize)]
+pub struct FrontendStatus {
+    /// Whether a built index.html was embedded at compile time
+    pub embedded: bool,
+    /// Hash over all embedded file paths and contents (FNV-1a, hex)
+    pub build_hash: String,
+    /// Total size of all embedded files
+    pub total_bytes: usize,
+    pub files: Vec<EmbeddedFile>,
+    /// Whether the placeholder page has been served since startup
+    pub placeholder_served: bool,
+    /// Files whose embedded contents don't match their build-time hash
+    pub integrity_mismatches: Vec<String>,
+}
+
+/// Recursively collect all embedded files
+fn collect_files<'a>(dir: &'a Dir<'a>, out: &mut Vec<&'a include_dir::File<'a>>) {
+    out.extend(dir.files());
+    for sub in dir.dirs() {
+        collect_files(sub, out);
+    }
+}
+
+/// Compute the status of the embedded assets once
+fn embedded_status() -> &'static FrontendStatus {
+    static STATUS: OnceLock<FrontendStatus> = OnceLock::new();
+    STATUS.get_or_init(|| {
+        let mut files = Vec::new();
+        collect_files(&FRONTEND_DIST, &mut files);
+        files.sort_by_key(|f| f.path());
+
+        // FNV-1a is stable across builds and platforms, unlike DefaultHasher
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for file in &files {
+            for byte in file
+                .path()
+                .to_string_lossy()
+                .as_bytes()
+                .iter()
+                .chain(file.contents())
+            {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+
+        FrontendStatus {
+            embedded: FRONTEND_DIST.get_file("index.html").is_some(),
+            build_hash: format!("{:016x}", hash),
+            total_bytes: files.iter().map(|f| f.contents().len()).sum(),
+            files: files
+                .iter()
+                .map(|f| {
+                    let path = f.path().to_string_lossy().replace('\\', "/");
+                    EmbeddedFile {
+                        integrity: integrity_for(&path).map(|h| h.to_string()),
+                        path,
+                        bytes: f.contents().len(),
+                    }
+                })
+                .collect(),
+            placeholder_served: false,
+            integrity_mismatches: verify_integrity(),
+        }
+    })
+}
+
+/// Look up the build-time SRI hash of an embedded file
+fn integrity_for(path: &str) -> Option<&'static str> {
+    ASSET_INTEGRITY
+        .iter()
+        .find(|(p, _)| *p == path)
+        .map(|(_, hash)| *hash)
+}
+
+/// Recompute the SRI hashes of embedded files and return those that don't match
+///
+/// A mismatch means the binary was tampered with or the embedded assets are
+/// out of sync with the build script output.
+pub fn verify_integrity() -> Vec<String> {
+    use base64::Engine;
+    use sha2::{Digest, Sha384};
+
+    ASSET_INTEGRITY
+        .iter()
+        .filter(|(path, expected)| {
+            let Some(file) = FRONTEND_DIST.get_file(path) else {
+                return true;
+            };
+            let actual = format!(
+                "sha384-{}",
+                base64::engine::general_purpose::STANDARD.encode(Sha384::digest(file.contents()))
+            );
+            actual != *expected
+        })
+        .map(|(path, _)| path.to_string())
+        .collect()
+}
+
+/// Add `integrity` attributes to script and stylesheet references in index.html
+fn inject_integrity(html: &mut String) {
+    for (path, hash) in ASSET_INTEGRITY {
+        for prefix in ["./", "/", ""] {
+            for attr in ["src", "href"] {
+                let needle = format!("{}=\"{}{}\"", attr, prefix, path);
+                if let Some(pos) = html.find(&needle) {
+                    let insert_pos = pos + needle.len();
+                    if !html[insert_pos..].trim_start().starts_with("integrity=") {
+                        html.insert_str(insert_pos, &format!(" integrity=\"{}\"", hash));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// GET /api/frontend/status - Report which frontend assets were embedded
+pub async fn frontend_status() -> Response {
+    let mut status = embedded_status().clone();
+    status.placeholder_served = PLACEHOLDER_SERVED.load(Ordering::Relaxed);
+    Json(status).into_response()
+}
+
 /// State for frontend serving (stores base path)
 #[derive(Clone)]
 pub struct FrontendState {
+    /// Path browsers reach the console at, the external base URL if set
     pub base_path: Arc<String>,
+    /// Storage used by the server-rendered plain page
+    pub storage: LogStorage,
+    /// State of the API, for the configuration injected into index.html
+    pub logs: Arc<LogsState>,
+}
+
+/// Add a script defining `window.__TRACING_CONSOLE__` to the head of index.html
+fn inject_config(html: &mut String, config: &ConsoleConfig) {
+    let Some(head_end) = html.find("</head>") else {
+        return;
+    };
+    // `</script>` in a value must not end the script element
+    let json = serde_json::to_string(config)
+        .unwrap_or_else(|_| "{}".to_string())
+        .replace("</", "<\\/");
+    html.insert_str(
+        head_end,
+        &format!(
+            "    <script>window.__TRACING_CONSOLE__ = {};</script>\n",
+            json
+        ),
+    );
+}
+
+/// Query parameters for GET /
+#[derive(Debug, Default, Deserialize)]
+pub struct IndexQuery {
+    /// Access token to remember in a session cookie
+    pub token: Option<String>,
+}
+
+/// Store a valid access token in the session cookie and redirect to the
+/// console without it in the URL
+fn start_session(state: &FrontendState, token: &str) -> Response {
+    if state.logs.access.role(token).is_none() || !is_cookie_safe(token) {
+        return ApiError::Unauthorized("invalid access token".to_string()).into_response();
+    }
+    let path = format!("{}/", state.base_path);
+    Response::builder()
+        .status(StatusCode::SEE_OTHER)
+        .header(header::LOCATION, &path)
+        .header(
+            header::SET_COOKIE,
+            format!(
+                "{}={}; Path={}; HttpOnly; SameSite=Strict",
+                TOKEN_COOKIE, token, path
+            ),
+        )
+        .body(Body::empty())
+        .unwrap()
 }
 
 /// Serve the index.html file at the root path
-pub async fn serve_index(State(state): State<FrontendState>) -> Response {
+///
+/// With `?token=`, the token is stored in a cookie the API accepts instead.
+pub async fn serve_index(
+    State(state): State<FrontendState>,
+    Query(query): Query<IndexQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(token) = query.token.as_deref() {
+        return start_session(&state, token);
+    }
     // Try to serve embedded index.html, fallback to placeholder
     if let Some(file) = FRONTEND_DIST.get_file("index.html") {
         let mut contents = String::from_utf8_lossy(file.contents()).to_string();
@@ -30,13 +240,42 @@ pub async fn serve_index(State(state): State<FrontendState>) -> Response {
             contents.insert_str(insert_pos, &base_tag);
         }
 
+        // Pin scripts and stylesheets to their build-time hashes
+        inject_integrity(&mut contents);
+        // Tell the app where the API is and what this console supports
+        inject_config(&mut contents, &console_config(&state.logs, &headers));
+
         Response::builder()
             .status(StatusCode::OK)
             .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-            .header(header::CACHE_CONTROL, "public, max-age=3600")
+            // Carries the config of this request, e.g. the client's role
+            .header(header::CACHE_CONTROL, "no-store")
             .body(Body::from(contents))
             .unwrap()
     } else {
+        // Explain why the placeholder is shown, once per process
+        if !PLACEHOLDER_SERVED.swap(true, Ordering::Relaxed) {
+            let message = "Frontend assets are not embedded, serving placeholder page. \
+                           Build the frontend (pnpm build in tracing-web-console/frontend) and recompile.";
+            tracing::warn!(
+                dist_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/frontend/dist"),
+                "{}",
+                message
+            );
+            let fields = HashMap::from([
+                (
+                    "dist_dir".to_string(),
+                    concat!(env!("CARGO_MANIFEST_DIR"), "/frontend/dist").to_string(),
+                ),
+                (
+                    "embedded_files".to_string(),
+                    embedded_status().files.len().to_string(),
+                ),
+            ]);
+            state
+                .storage
+                .push(LogEvent::internal("WARN", message, fields));
+        }
         serve_placeholder().await
     }
 }
@@ -143,6 +382,8 @@ pub async fn serve_placeholder() -> Response {
                 <li>GET /api/logs - Get historical logs</li>
                 <li>POST /api/levels - Update log levels</li>
                 <li>GET /api/targets - Get log targets</li>
+                <li>GET /plain - Server-rendered log view (no JavaScript)</li>
+                <li>GET /api/frontend/status - Embedded asset diagnostics</li>
             </ul>
         </div>
     </div>
@@ -178,6 +419,35 @@ mod tests {
         assert_eq!(png_mime.as_ref(), "image/png");
     }
 
+    #[test]
+    fn test_embedded_assets_match_build_hashes() {
+        assert!(verify_integrity().is_empty());
+    }
+
+    #[test]
+    fn test_inject_integrity() {
+        let Some((path, hash)) = ASSET_INTEGRITY.first() else {
+            return;
+        };
+        let mut html = format!(
+            "<script type=\"module\" crossorigin src=\"./{}\"></script>",
+            path
+        );
+        inject_integrity(&mut html);
+        assert!(html.contains(&format!("integrity=\"{}\"", hash)));
+    }
+
+    #[test]
+    fn test_inject_config() {
+        let mut state = LogsState::new(LogStorage::new());
+        state.base_path = "/myapp/tracing</script>".to_string();
+        let mut html = "<html><head><title>Console</title></head></html>".to_string();
+        inject_config(&mut html, &console_config(&state, &HeaderMap::new()));
+        assert!(html.contains("\"ws_path\":\"/myapp/tracing<\\/script>/api/ws\""));
+        assert!(html.find("__TRACING_CONSOLE__").unwrap() < html.find("</head>").unwrap());
+        assert_eq!(html.matches("</script>").count(), 1);
+    }
+
     #[tokio::test]
     async fn test_placeholder() {
         let response = serve_placeholder().await;
@@ -0,0 +1,280 @@
+//! Synthetic traffic for demos and for testing dashboards
+//!
+//! Simulates an HTTP service: every request logs a start, a few database
+//! queries and a completion, some of them slow or failing. Events are pushed
+//! straight into storage under their own targets, so they're easy to filter
+//! out again.
+
+use crate::storage::{LogEvent, LogStorage, SpanInfo};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often generated events are pushed
+const TICK: Duration = Duration::from_millis(100);
+
+const ROUTES: &[(&str, &str)] = &[
+    ("GET", "/api/users"),
+    ("GET", "/api/users/{id}"),
+    ("POST", "/api/orders"),
+    ("GET", "/api/products"),
+    ("PUT", "/api/products/{id}"),
+];
+const TABLES: &[&str] = &["users", "orders", "products", "inventory"];
+const FAILURES: &[&str] = &[
+    "connection reset by peer",
+    "deadline exceeded",
+    "unique constraint violated",
+    "upstream returned 503",
+];
+
+/// Shape of the generated traffic
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    /// Average number of events per second
+    pub rate: f64,
+    /// Share of requests failing with an `ERROR`
+    pub error_rate: f64,
+    /// Share of requests logging a slow request `WARN`
+    pub warn_rate: f64,
+    /// Prefix of the generated targets, "demo" gives "demo::http" and "demo::db"
+    pub target_prefix: String,
+    /// Seed for reproducible traffic, random if `None`
+    pub seed: Option<u64>,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            rate: 10.0,
+            error_rate: 0.02,
+            warn_rate: 0.1,
+            target_prefix: "demo".to_string(),
+            seed: None,
+        }
+    }
+}
+
+/// xorshift64*, plenty for fake traffic and free of dependencies
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero
+        Self(seed | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        unit < probability
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+struct Generator {
+    config: GeneratorConfig,
+    rng: Rng,
+    requests: u64,
+}
+
+impl Generator {
+    fn new(config: GeneratorConfig) -> Self {
+        let seed = config
+            .seed
+            .unwrap_or_else(|| Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64);
+        Self {
+            config,
+            rng: Rng::new(seed),
+            requests: 0,
+        }
+    }
+
+    fn event(
+        &self,
+        level: &str,
+        module: &str,
+        message: String,
+        fields: HashMap<String, String>,
+    ) -> LogEvent {
+        LogEvent {
+            target: format!("{}::{}", self.config.target_prefix, module).into(),
+            ..LogEvent::internal(level, &message, fields)
+        }
+    }
+
+    /// Events of one simulated request, oldest first
+    fn request(&mut self, now: DateTime<Utc>) -> Vec<LogEvent> {
+        self.requests += 1;
+        let (method, route) = *self.rng.pick(ROUTES);
+        let request_span = SpanInfo {
+            id: None,
+            name: "http_request".to_string(),
+            fields: HashMap::from([
+                ("method".to_string(), method.to_string()),
+                ("route".to_string(), route.to_string()),
+                (
+                    "request_id".to_string(),
+                    format!("req-{:06}", self.requests),
+                ),
+            ]),
+            ancestors: Vec::new(),
+            parent_fields: HashMap::new(),
+        };
+
+        let mut events = vec![self.event(
+            "INFO",
+            "http",
+            format!("{} {} started", method, route),
+            HashMap::new(),
+        )];
+        let mut latency_ms = 0;
+        for _ in 0..1 + self.rng.below(3) {
+            let table = *self.rng.pick(TABLES);
+            let rows = self.rng.below(50);
+            let query_ms = 1 + self.rng.below(20);
+            latency_ms += query_ms;
+            let mut event = self.event(
+                "DEBUG",
+                "db",
+                format!("query on {} returned {} rows", table, rows),
+                HashMap::from([
+                    ("table".to_string(), table.to_string()),
+                    ("rows".to_string(), rows.to_string()),
+                    ("duration_ms".to_string(), query_ms.to_string()),
+                ]),
+            );
+            event.span = Some(SpanInfo {
+                id: None,
+                name: "db_query".to_string(),
+                fields: HashMap::from([("table".to_string(), table.to_string())]),
+                ancestors: vec![request_span.name.clone()],
+                parent_fields: request_span.fields.clone(),
+            });
+            events.push(event);
+        }
+
+        let status = if self.rng.chance(self.config.error_rate) {
+            let failure = *self.rng.pick(FAILURES);
+            events.push(self.event(
+                "ERROR",
+                "http",
+                format!("request failed: {}", failure),
+                HashMap::from([("error".to_string(), failure.to_string())]),
+            ));
+            500
+        } else if self.rng.chance(self.config.warn_rate) {
+            latency_ms += 500 + self.rng.below(2_000);
+            events.push(self.event(
+                "WARN",
+                "http",
+                format!("slow request took {}ms", latency_ms),
+                HashMap::from([("threshold_ms".to_string(), "500".to_string())]),
+            ));
+            200
+        } else {
+            200
+        };
+        events.push(self.event(
+            "INFO",
+            "http",
+            format!("{} {} completed", method, route),
+            HashMap::from([
+                ("status".to_string(), status.to_string()),
+                ("latency_ms".to_string(), latency_ms.to_string()),
+            ]),
+        ));
+
+        for event in &mut events {
+            event.timestamp = now;
+            if event.span.is_none() {
+                event.span = Some(request_span.clone());
+            }
+        }
+        events
+    }
+}
+
+/// Push synthetic events into storage forever
+pub(crate) async fn run(storage: LogStorage, config: GeneratorConfig) {
+    let rate = config.rate.max(0.0);
+    let mut generator = Generator::new(config);
+    let mut pending: Vec<LogEvent> = Vec::new();
+    let mut budget = 0.0;
+    let mut last = Instant::now();
+    let mut interval = tokio::time::interval(TICK);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        // A long stall shouldn't turn into a burst
+        budget = (budget + rate * last.elapsed().as_secs_f64()).min(rate.max(1.0));
+        last = Instant::now();
+        while budget >= 1.0 {
+            if pending.is_empty() {
+                pending = generator.request(Utc::now());
+                pending.reverse();
+            }
+            if let Some(mut event) = pending.pop() {
+                event.timestamp = Utc::now();
+                storage.push(event);
+            }
+            budget -= 1.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(error_rate: f64) -> Generator {
+        Generator::new(GeneratorConfig {
+            error_rate,
+            seed: Some(42),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_request_shape() {
+        let events = generator(1.0).request(Utc::now());
+        assert_eq!(events.first().unwrap().target, "demo::http");
+        assert!(events.iter().any(|e| e.target == "demo::db"));
+        assert_eq!(events.iter().filter(|e| e.level == "ERROR").count(), 1);
+        let completed = events.last().unwrap();
+        assert_eq!(completed.fields["status"], "500");
+
+        let db = events.iter().find(|e| e.target == "demo::db").unwrap();
+        let span = db.span.as_ref().unwrap();
+        assert_eq!(span.ancestors, ["http_request"]);
+        assert_eq!(span.field("request_id"), Some("req-000001"));
+    }
+
+    #[test]
+    fn test_seed_is_reproducible() {
+        let messages = |mut g: Generator| -> Vec<String> {
+            (0..20)
+                .flat_map(|_| g.request(Utc::now()))
+                .map(|e| e.message)
+                .collect()
+        };
+        assert_eq!(messages(generator(0.1)), messages(generator(0.1)));
+        assert!(!messages(generator(0.0))
+            .iter()
+            .any(|m| m.contains("failed")));
+    }
+}
@@ -0,0 +1,104 @@
+//! Programmatic handle to a running console
+
+use crate::ingest::process::{self, PipeOptions};
+use crate::ingest::LineParser;
+use crate::storage::{LogEvent, LogStorage};
+use crate::subscriber::CaptureRegistration;
+use futures::Stream;
+use std::sync::Arc;
+use tokio::process::{Child, Command};
+
+/// Cloneable handle for interacting with the console from application code
+///
+/// Obtained via [`TracingLayer::handle`](crate::TracingLayer::handle) before
+/// the layer is turned into a router.
+#[derive(Clone)]
+pub struct ConsoleHandle {
+    pub(crate) storage: LogStorage,
+    pub(crate) line_parser: LineParser,
+    /// Keeps the console registered while handles exist
+    pub(crate) _registration: Arc<CaptureRegistration>,
+}
+
+impl ConsoleHandle {
+    /// Spawn a child process and capture its stdout/stderr lines as events
+    ///
+    /// stdout lines are logged at INFO and stderr lines at WARN under the
+    /// target `process::<program name>`, unless a line pattern captures a level.
+    /// Must be called from within a tokio runtime.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # async fn run(console: tracing_web_console::ConsoleHandle) -> std::io::Result<()> {
+    /// let mut command = tokio::process::Command::new("git");
+    /// command.args(["fetch", "--all"]);
+    /// let status = console.pipe_child(&mut command)?.wait().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pipe_child(&self, command: &mut Command) -> std::io::Result<Child> {
+        self.pipe_child_with(command, PipeOptions::default())
+    }
+
+    /// Like [`pipe_child`](Self::pipe_child) with a custom target and level mapping
+    pub fn pipe_child_with(
+        &self,
+        command: &mut Command,
+        options: PipeOptions,
+    ) -> std::io::Result<Child> {
+        process::spawn_piped(
+            command,
+            options,
+            self.storage.clone(),
+            self.line_parser.clone(),
+        )
+    }
+
+    /// Stream of events as they are stored, without a WebSocket round trip
+    ///
+    /// Useful to drive metrics from events or to assert on logs in tests.
+    /// Events are shared, not copied, between the stream's consumers. A
+    /// consumer falling behind skips the events it missed.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use futures::StreamExt;
+    ///
+    /// # async fn run(console: tracing_web_console::ConsoleHandle) {
+    /// let mut events = Box::pin(console.event_stream());
+    /// while let Some(event) = events.next().await {
+    ///     if event.level == "ERROR" {
+    ///         println!("error in {}: {}", event.target, event.message);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn event_stream(&self) -> impl Stream<Item = Arc<LogEvent>> + Send + 'static {
+        self.storage.event_stream()
+    }
+
+    /// Stop storing new events, e.g. when an incident is detected, so the
+    /// events leading up to it aren't evicted
+    ///
+    /// Events captured while paused are discarded. Returns false if capture
+    /// was already paused.
+    pub fn pause_capture(&self) -> bool {
+        self.storage.pause()
+    }
+
+    /// Store new events again, returns false if capture wasn't paused
+    pub fn resume_capture(&self) -> bool {
+        self.storage.resume()
+    }
+
+    /// Wait until all captured events have been delivered to every sink
+    ///
+    /// Useful in tests and on shutdown, before the process exits.
+    pub async fn flush(&self) {
+        let storage = self.storage.clone();
+        // Flushing blocks on the sink flusher threads
+        let _ = tokio::task::spawn_blocking(move || storage.flush()).await;
+    }
+}
@@ -0,0 +1,97 @@
+//! ULID generation for log event identifiers
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Crockford base32 alphabet used by the ULID spec
+const ENCODING: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Length of an encoded ULID
+pub const ULID_LEN: usize = 26;
+
+/// Per-process counter mixed into the random part so IDs never collide
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Generate a new ULID (48-bit millisecond timestamp + 80 bits of randomness)
+///
+/// IDs sort lexicographically by creation time, which keeps them usable as
+/// stable permalinks for individual log events.
+pub fn new_ulid() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+
+    // RandomState is seeded randomly per process, hashing a counter gives
+    // cheap unpredictable bits without pulling in an RNG dependency
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(count);
+    let random = hasher.finish();
+
+    let value: u128 = ((millis as u128 & 0xFFFF_FFFF_FFFF) << 80)
+        | ((random as u128) << 16)
+        | (count as u128 & 0xFFFF);
+
+    encode(value)
+}
+
+/// Encode a 128-bit value as a 26-character Crockford base32 string
+fn encode(mut value: u128) -> String {
+    let mut buf = [0u8; ULID_LEN];
+    for slot in buf.iter_mut().rev() {
+        *slot = ENCODING[(value & 0x1F) as usize];
+        value >>= 5;
+    }
+    // All bytes come from the ASCII alphabet above
+    String::from_utf8(buf.to_vec()).unwrap_or_default()
+}
+
+/// Decode a ULID into its 128-bit value, case-insensitively
+pub fn decode(id: &str) -> Option<u128> {
+    if id.len() != ULID_LEN {
+        return None;
+    }
+    id.bytes().try_fold(0u128, |value, b| {
+        let digit = ENCODING.iter().position(|c| *c == b.to_ascii_uppercase())?;
+        Some((value << 5) | digit as u128)
+    })
+}
+
+/// Check whether a string looks like a valid ULID
+pub fn is_valid_ulid(id: &str) -> bool {
+    id.len() == ULID_LEN
+        && id
+            .bytes()
+            .all(|b| ENCODING.contains(&b.to_ascii_uppercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ulid_format() {
+        let id = new_ulid();
+        assert_eq!(id.len(), ULID_LEN);
+        assert!(is_valid_ulid(&id));
+    }
+
+    #[test]
+    fn test_ulids_are_unique_and_sorted_by_time() {
+        let first = new_ulid();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = new_ulid();
+        assert_ne!(first, second);
+        assert!(first[..10] <= second[..10]);
+    }
+
+    #[test]
+    fn test_decode_roundtrip() {
+        let value = 0x0123_4567_89AB_CDEF_FEDC_BA98_7654_3210;
+        assert_eq!(decode(&encode(value)), Some(value));
+        assert_eq!(decode(&encode(value).to_lowercase()), Some(value));
+        assert_eq!(decode("not-a-ulid"), None);
+    }
+}
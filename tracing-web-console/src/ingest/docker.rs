@@ -0,0 +1,89 @@
+//! Docker container log ingestion (requires the `docker` feature)
+
+use crate::ingest::LineParser;
+use crate::storage::LogStorage;
+use bollard::container::{InspectContainerOptions, LogOutput, LogsOptions};
+use bollard::Docker;
+use futures::StreamExt;
+use std::time::Duration;
+
+/// Delay before re-attaching after the log stream ended (e.g. container restart)
+const REATTACH_DELAY: Duration = Duration::from_secs(5);
+
+/// Stream logs of a single container into storage until the process exits
+///
+/// Lines are parsed with the shared line patterns and labelled with the
+/// container name and image. stderr lines default to WARN.
+pub async fn follow_container(container: String, storage: LogStorage, parser: LineParser) {
+    let docker = match Docker::connect_with_local_defaults() {
+        Ok(docker) => docker,
+        Err(e) => {
+            tracing::error!("Failed to connect to Docker daemon: {}", e);
+            return;
+        }
+    };
+
+    let target = format!("docker::{}", container);
+    let mut since = 0;
+
+    loop {
+        let image = docker
+            .inspect_container(&container, None::<InspectContainerOptions>)
+            .await
+            .ok()
+            .and_then(|info| info.config)
+            .and_then(|config| config.image)
+            .unwrap_or_default();
+
+        let options = LogsOptions::<String> {
+            follow: true,
+            stdout: true,
+            stderr: true,
+            since,
+            // Only new output on first attach, the buffer is not meant to hold history
+            tail: if since == 0 { "0" } else { "all" }.to_string(),
+            ..Default::default()
+        };
+
+        let mut stream = Box::pin(docker.logs(&container, Some(options)));
+        while let Some(result) = stream.next().await {
+            let (stream_name, message) = match result {
+                Ok(LogOutput::StdOut { message }) | Ok(LogOutput::Console { message }) => {
+                    ("stdout", message)
+                }
+                Ok(LogOutput::StdErr { message }) => ("stderr", message),
+                Ok(LogOutput::StdIn { .. }) => continue,
+                Err(e) => {
+                    tracing::debug!("Docker log stream for {} failed: {}", container, e);
+                    break;
+                }
+            };
+
+            let default_level = if stream_name == "stderr" {
+                "WARN"
+            } else {
+                "INFO"
+            };
+            for line in String::from_utf8_lossy(&message).lines() {
+                if line.is_empty() {
+                    continue;
+                }
+                let mut event = parser.parse_with_level(line, &target, default_level);
+                event
+                    .fields
+                    .insert("container.name".into(), container.clone().into());
+                if !image.is_empty() {
+                    event
+                        .fields
+                        .insert("container.image".into(), image.clone().into());
+                }
+                event.fields.insert("stream".into(), stream_name.into());
+                storage.push(event);
+            }
+        }
+
+        // Resume from where we stopped once the container is back
+        since = chrono::Utc::now().timestamp();
+        tokio::time::sleep(REATTACH_DELAY).await;
+    }
+}
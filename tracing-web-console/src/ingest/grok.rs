@@ -0,0 +1,261 @@
+//! Grok-style pattern expansion and structured line parsing
+
+use crate::storage::LogEvent;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use parking_lot::RwLock;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+/// Built-in grok patterns usable as `%{NAME}` or `%{NAME:field}`
+const GROK_PATTERNS: &[(&str, &str)] = &[
+    ("WORD", r"\b\w+\b"),
+    ("NOTSPACE", r"\S+"),
+    ("SPACE", r"\s*"),
+    ("DATA", r".*?"),
+    ("GREEDYDATA", r".*"),
+    ("INT", r"[+-]?\d+"),
+    ("NUMBER", r"[+-]?\d+(?:\.\d+)?"),
+    ("IP", r"(?:\d{1,3}\.){3}\d{1,3}"),
+    ("HOSTNAME", r"[a-zA-Z0-9._-]+"),
+    ("PATH", r"(?:/[^\s]*)+"),
+    (
+        "UUID",
+        r"[0-9a-fA-F]{8}-(?:[0-9a-fA-F]{4}-){3}[0-9a-fA-F]{12}",
+    ),
+    ("QUOTEDSTRING", r#""(?:[^"\\]|\\.)*""#),
+    (
+        "LOGLEVEL",
+        r"(?i:trace|debug|info|notice|warn(?:ing)?|err(?:or)?|crit(?:ical)?|fatal|alert|emerg(?:ency)?)",
+    ),
+    (
+        "TIMESTAMP_ISO8601",
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+    ),
+];
+
+/// Expand `%{NAME}` / `%{NAME:field}` references into a plain regex
+///
+/// Field names containing dots are stored with underscores since regex group
+/// names can't contain them.
+pub fn expand_grok(pattern: &str) -> Result<String, String> {
+    static REFERENCE: OnceLock<Regex> = OnceLock::new();
+    let reference = REFERENCE
+        .get_or_init(|| Regex::new(r"%\{(\w+)(?::([\w.]+))?\}").expect("valid grok regex"));
+
+    let mut error = None;
+    let expanded = reference.replace_all(pattern, |caps: &regex::Captures| {
+        let name = &caps[1];
+        let Some((_, regex)) = GROK_PATTERNS.iter().find(|(n, _)| *n == name) else {
+            error = Some(format!("Unknown grok pattern: {}", name));
+            return String::new();
+        };
+        match caps.get(2) {
+            Some(field) => format!("(?P<{}>{})", field.as_str().replace('.', "_"), regex),
+            None => format!("(?:{})", regex),
+        }
+    });
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(expanded.into_owned()),
+    }
+}
+
+/// Level names of foreign log formats mapped to each tracing level
+pub const LEVEL_ALIASES: &[(&str, &[&str])] = &[
+    ("TRACE", &["trace"]),
+    ("DEBUG", &["debug"]),
+    ("INFO", &["info", "notice", "informational"]),
+    ("WARN", &["warn", "warning"]),
+    (
+        "ERROR",
+        &[
+            "err",
+            "error",
+            "crit",
+            "critical",
+            "fatal",
+            "alert",
+            "emerg",
+            "emergency",
+        ],
+    ),
+];
+
+/// Normalize level names from foreign log formats to the tracing levels
+///
+/// Unknown names become INFO.
+pub fn normalize_level(level: &str) -> String {
+    let level = level.to_lowercase();
+    LEVEL_ALIASES
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&level.as_str()))
+        .map_or("INFO", |(name, _)| name)
+        .to_string()
+}
+
+/// Parse timestamps in RFC 3339 or `YYYY-MM-DD HH:MM:SS[.f]` (assumed UTC)
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Some(ts.with_timezone(&Utc));
+    }
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(value, fmt).ok())
+        .map(|naive| naive.and_utc())
+}
+
+/// A compiled line pattern
+#[derive(Debug, Clone, Serialize)]
+pub struct LinePattern {
+    /// The pattern as configured (grok or plain regex)
+    pub pattern: String,
+    #[serde(skip)]
+    regex: Regex,
+}
+
+impl LinePattern {
+    /// Compile a grok or regex pattern
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let expanded = expand_grok(pattern)?;
+        let regex = Regex::new(&expanded).map_err(|e| e.to_string())?;
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+}
+
+/// Turns plain-text lines into structured log events using the configured patterns
+///
+/// The first matching pattern wins. Named groups `timestamp`, `level`, `target`
+/// and `message` populate the event itself, all other groups become fields.
+/// Lines matching no pattern are stored verbatim as the message.
+#[derive(Clone, Default)]
+pub struct LineParser {
+    patterns: Arc<RwLock<Vec<LinePattern>>>,
+}
+
+impl LineParser {
+    /// Create a parser with no patterns
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the configured patterns
+    pub fn patterns(&self) -> Vec<LinePattern> {
+        self.patterns.read().clone()
+    }
+
+    /// Replace all patterns
+    pub fn set_patterns(&self, patterns: Vec<LinePattern>) {
+        *self.patterns.write() = patterns;
+    }
+
+    /// Parse a single line, `source` is used as the target when none is captured
+    pub fn parse(&self, line: &str, source: &str) -> LogEvent {
+        self.parse_with_level(line, source, "INFO")
+    }
+
+    /// Parse a single line, using `default_level` when the pattern captures no level
+    pub fn parse_with_level(&self, line: &str, source: &str, default_level: &str) -> LogEvent {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let mut event = LogEvent {
+            id: crate::id::new_ulid(),
+            seq: 0,
+            timestamp: Utc::now(),
+            level: default_level.into(),
+            target: source.into(),
+            message: line.to_string(),
+            fields: HashMap::new(),
+            span: None,
+            module_path: None,
+            file: None,
+            line: None,
+            source_url: None,
+            thread_name: None,
+            thread_id: None,
+            repeat_count: None,
+            is_error: false,
+            truncated: false,
+        };
+
+        let patterns = self.patterns.read();
+        let Some((pattern, caps)) = patterns
+            .iter()
+            .find_map(|p| p.regex.captures(line).map(|caps| (p, caps)))
+        else {
+            return event;
+        };
+
+        for name in pattern.regex.capture_names().flatten() {
+            let Some(value) = caps.name(name).map(|m| m.as_str()) else {
+                continue;
+            };
+            match name {
+                "timestamp" => {
+                    if let Some(ts) = parse_timestamp(value) {
+                        event.timestamp = ts;
+                    }
+                }
+                "level" => event.level = normalize_level(value).into(),
+                "target" => event.target = value.into(),
+                "message" => event.message = value.to_string(),
+                _ => {
+                    event.fields.insert(name.into(), value.into());
+                }
+            }
+        }
+
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_level() {
+        assert_eq!(normalize_level("Warning"), "WARN");
+        assert_eq!(normalize_level("crit"), "ERROR");
+        assert_eq!(normalize_level("verbose"), "INFO");
+    }
+
+    #[test]
+    fn test_expand_grok() {
+        let expanded = expand_grok("%{LOGLEVEL:level} %{GREEDYDATA:message}").unwrap();
+        assert!(expanded.starts_with("(?P<level>"));
+        assert!(expand_grok("%{NOPE:x}").is_err());
+    }
+
+    #[test]
+    fn test_parse_line_with_pattern() {
+        let parser = LineParser::new();
+        parser.set_patterns(vec![LinePattern::new(
+            "%{TIMESTAMP_ISO8601:timestamp} %{LOGLEVEL:level} \\[%{NOTSPACE:target}\\] %{GREEDYDATA:message} user=%{WORD:user.name}",
+        )
+        .unwrap()]);
+
+        let event = parser.parse(
+            "2024-05-01T12:00:00Z warning [legacy::auth] login failed user=bob",
+            "legacy",
+        );
+        assert_eq!(event.level, "WARN");
+        assert_eq!(event.target, "legacy::auth");
+        assert_eq!(event.message, "login failed");
+        assert_eq!(event.fields["user_name"], "bob");
+        assert_eq!(event.timestamp.to_rfc3339(), "2024-05-01T12:00:00+00:00");
+    }
+
+    #[test]
+    fn test_unmatched_line_is_kept_verbatim() {
+        let parser = LineParser::new();
+        let event = parser.parse("just some text\n", "stdin");
+        assert_eq!(event.message, "just some text");
+        assert_eq!(event.target, "stdin");
+        assert_eq!(event.level, "INFO");
+    }
+}
@@ -0,0 +1,11 @@
+//! Ingestion of logs from sources other than the tracing subscriber
+
+#[cfg(feature = "docker")]
+pub mod docker;
+pub mod grok;
+pub mod process;
+pub mod syslog;
+pub mod tokens;
+
+pub use grok::{LineParser, LinePattern};
+pub use tokens::IngestTokens;
@@ -0,0 +1,140 @@
+//! Capture of child process stdout/stderr as log events
+
+use crate::ingest::LineParser;
+use crate::storage::LogStorage;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+
+/// Options for capturing a child process
+#[derive(Debug, Clone)]
+pub struct PipeOptions {
+    /// Target for the captured events, defaults to `process::<program name>`
+    pub target: Option<String>,
+    /// Level for stdout lines when no line pattern captures one
+    pub stdout_level: String,
+    /// Level for stderr lines when no line pattern captures one
+    pub stderr_level: String,
+}
+
+impl Default for PipeOptions {
+    fn default() -> Self {
+        Self {
+            target: None,
+            stdout_level: "INFO".to_string(),
+            stderr_level: "WARN".to_string(),
+        }
+    }
+}
+
+/// Spawn `command` with piped stdout/stderr and forward every line into storage
+pub fn spawn_piped(
+    command: &mut Command,
+    options: PipeOptions,
+    storage: LogStorage,
+    parser: LineParser,
+) -> std::io::Result<Child> {
+    let program = command.as_std().get_program();
+    let program = Path::new(program)
+        .file_name()
+        .unwrap_or(program)
+        .to_string_lossy()
+        .to_string();
+    let target = options
+        .target
+        .unwrap_or_else(|| format!("process::{}", program));
+
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = command.spawn()?;
+    let pid = child.id().map(|id| id.to_string());
+
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(forward_lines(
+            stdout,
+            "stdout",
+            options.stdout_level,
+            target.clone(),
+            pid.clone(),
+            storage.clone(),
+            parser.clone(),
+        ));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(forward_lines(
+            stderr,
+            "stderr",
+            options.stderr_level,
+            target,
+            pid,
+            storage,
+            parser,
+        ));
+    }
+
+    Ok(child)
+}
+
+/// Read lines until EOF and push them as events
+async fn forward_lines<R: AsyncRead + Unpin>(
+    reader: R,
+    stream: &'static str,
+    level: String,
+    target: String,
+    pid: Option<String>,
+    storage: LogStorage,
+    parser: LineParser,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.is_empty() {
+            continue;
+        }
+        let mut event = parser.parse_with_level(&line, &target, &level);
+        event.fields.insert("stream".into(), stream.into());
+        if let Some(pid) = &pid {
+            event
+                .fields
+                .insert("process.pid".into(), pid.clone().into());
+        }
+        storage.push(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LogFilter;
+
+    #[tokio::test]
+    async fn test_pipe_child_captures_stdout_and_stderr() {
+        let storage = LogStorage::new();
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo hello; echo oops 1>&2"]);
+
+        let mut child = spawn_piped(
+            &mut command,
+            PipeOptions::default(),
+            storage.clone(),
+            LineParser::new(),
+        )
+        .unwrap();
+        child.wait().await.unwrap();
+
+        // Give the forwarding tasks a moment to drain the pipes
+        for _ in 0..50 {
+            if storage.get_filtered(&LogFilter::default(), None, None).1 == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let (events, count) = storage.get_filtered(&LogFilter::default(), None, None);
+        assert_eq!(count, 2);
+        let stderr = events.iter().find(|e| e.message == "oops").unwrap();
+        assert_eq!(stderr.level, "WARN");
+        assert_eq!(stderr.target, "process::sh");
+        let stdout = events.iter().find(|e| e.message == "hello").unwrap();
+        assert_eq!(stdout.level, "INFO");
+    }
+}
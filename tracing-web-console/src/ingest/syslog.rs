@@ -0,0 +1,431 @@
+//! Syslog listener (RFC 3164 and RFC 5424 over UDP or TCP)
+
+use crate::storage::{LogEvent, LogStorage};
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// Maximum size of a single UDP datagram we accept
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+/// Longest message kept from a TCP stream, the rest of a longer one is skipped
+const MAX_FRAME_LEN: usize = MAX_DATAGRAM_SIZE;
+/// Most digits of an RFC 6587 octet count
+const MAX_COUNT_DIGITS: usize = 10;
+/// Pause after a failed accept, e.g. while out of file descriptors
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Facility names indexed by facility code
+const FACILITIES: &[&str] = &[
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv",
+    "ftp", "ntp", "audit", "alert", "clock", "local0", "local1", "local2", "local3", "local4",
+    "local5", "local6", "local7",
+];
+
+/// Syslog severity names, indexed by severity
+pub const SEVERITY_NAMES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// Map a syslog severity (0-7) to a tracing level
+pub fn severity_to_level(severity: u8) -> &'static str {
+    match severity {
+        0..=3 => "ERROR", // emerg, alert, crit, err
+        4 => "WARN",      // warning
+        5 | 6 => "INFO",  // notice, info
+        _ => "DEBUG",     // debug
+    }
+}
+
+/// Parse a single syslog message received from `peer`
+///
+/// Messages without a valid `<PRI>` header are kept as INFO with the raw text.
+pub fn parse_message(raw: &str, peer: SocketAddr) -> LogEvent {
+    let raw = raw.trim_end_matches(['\r', '\n', '\0']);
+    let mut fields = HashMap::new();
+    fields.insert("syslog.peer".into(), peer.to_string().into());
+
+    let mut event = LogEvent {
+        id: crate::id::new_ulid(),
+        seq: 0,
+        timestamp: Utc::now(),
+        level: "INFO".into(),
+        target: "syslog".into(),
+        message: raw.to_string(),
+        fields,
+        span: None,
+        module_path: None,
+        file: None,
+        line: None,
+        source_url: None,
+        thread_name: None,
+        thread_id: None,
+        repeat_count: None,
+        is_error: false,
+        truncated: false,
+    };
+
+    let Some((pri, rest)) = parse_pri(raw) else {
+        return event;
+    };
+    let facility = pri / 8;
+    let severity = (pri % 8) as u8;
+    event.level = severity_to_level(severity).into();
+    event.fields.insert(
+        "syslog.facility".into(),
+        FACILITIES
+            .get(facility as usize)
+            .map(|f| f.to_string())
+            .unwrap_or_else(|| facility.to_string())
+            .into(),
+    );
+    event
+        .fields
+        .insert("syslog.severity".into(), severity.to_string().into());
+
+    if let Some(rest) = rest.strip_prefix("1 ") {
+        parse_rfc5424(rest, &mut event);
+    } else {
+        parse_rfc3164(rest, &mut event);
+    }
+
+    event
+}
+
+/// Split off the `<PRI>` header
+fn parse_pri(raw: &str) -> Option<(u32, &str)> {
+    let rest = raw.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    let pri: u32 = rest[..end].parse().ok()?;
+    if pri > 191 {
+        return None;
+    }
+    Some((pri, &rest[end + 1..]))
+}
+
+/// Store an optional ("-" means absent) header value as a field
+fn insert_field(event: &mut LogEvent, key: &str, value: &str) {
+    if value != "-" && !value.is_empty() {
+        event.fields.insert(key.into(), value.into());
+    }
+}
+
+/// RFC 5424: `TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG`
+fn parse_rfc5424(rest: &str, event: &mut LogEvent) {
+    let mut parts = rest.splitn(6, ' ');
+    let timestamp = parts.next().unwrap_or("-");
+    let hostname = parts.next().unwrap_or("-");
+    let app_name = parts.next().unwrap_or("-");
+    let proc_id = parts.next().unwrap_or("-");
+    let msg_id = parts.next().unwrap_or("-");
+    let remainder = parts.next().unwrap_or("");
+
+    if let Ok(ts) = DateTime::parse_from_rfc3339(timestamp) {
+        event.timestamp = ts.with_timezone(&Utc);
+    }
+    insert_field(event, "syslog.host", hostname);
+    insert_field(event, "syslog.proc_id", proc_id);
+    insert_field(event, "syslog.msg_id", msg_id);
+    if app_name != "-" {
+        event.target = format!("syslog::{}", app_name).into();
+    }
+
+    let (structured_data, message) = split_structured_data(remainder);
+    insert_field(event, "syslog.structured_data", structured_data);
+    // Strip the optional UTF-8 BOM
+    event.message = message.trim_start_matches('\u{feff}').to_string();
+}
+
+/// Split `[sd-element]...` (or `-`) from the message, honoring escaped `]`
+fn split_structured_data(input: &str) -> (&str, &str) {
+    if let Some(message) = input.strip_prefix("- ") {
+        return ("-", message);
+    }
+    if input == "-" || !input.starts_with('[') {
+        return ("-", input.strip_prefix('-').unwrap_or(input));
+    }
+
+    let bytes = input.as_bytes();
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'[' => depth += 1,
+            b']' => {
+                depth -= 1;
+                // End of the last element when not directly followed by another
+                if depth == 0 && bytes.get(i + 1) != Some(&b'[') {
+                    let message = input[i + 1..].strip_prefix(' ').unwrap_or(&input[i + 1..]);
+                    return (&input[..=i], message);
+                }
+            }
+            _ => {}
+        }
+    }
+    (input, "")
+}
+
+/// RFC 3164: `Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG`
+fn parse_rfc3164(rest: &str, event: &mut LogEvent) {
+    let mut rest = rest;
+
+    // The timestamp has no year, assume the current one
+    if rest.len() >= 16 && rest.is_char_boundary(15) {
+        let with_year = format!("{} {}", Utc::now().year(), &rest[..15]);
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&with_year, "%Y %b %e %H:%M:%S") {
+            event.timestamp = naive.and_utc();
+            rest = &rest[16..];
+
+            if let Some((host, remainder)) = rest.split_once(' ') {
+                insert_field(event, "syslog.host", host);
+                rest = remainder;
+            }
+        }
+    }
+
+    // TAG is terminated by ':' or '[' and limited to alphanumerics
+    let tag_end = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '/'))
+        .unwrap_or(0);
+    if tag_end > 0 {
+        let tag = &rest[..tag_end];
+        let mut remainder = &rest[tag_end..];
+        if let Some(after) = remainder.strip_prefix('[') {
+            if let Some((pid, after)) = after.split_once(']') {
+                insert_field(event, "syslog.proc_id", pid);
+                remainder = after;
+            }
+        }
+        if let Some(message) = remainder.strip_prefix(':') {
+            event.target = format!("syslog::{}", tag).into();
+            rest = message.trim_start();
+        }
+    }
+
+    event.message = rest.to_string();
+}
+
+/// Listen for syslog datagrams and push them into storage
+pub async fn run_udp(addr: SocketAddr, storage: LogStorage) -> std::io::Result<()> {
+    let socket = UdpSocket::bind(addr).await?;
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+
+    loop {
+        let (len, peer) = socket.recv_from(&mut buf).await?;
+        let raw = String::from_utf8_lossy(&buf[..len]);
+        storage.push(parse_message(&raw, peer));
+    }
+}
+
+/// Read the next message of a TCP stream into `buf`
+///
+/// Messages are either octet-counted (RFC 6587, `123 <34>1 ...`) or end with
+/// a newline. A count is told apart from a line starting with digits by the
+/// `<` of the PRI that follows it. Only the first `MAX_FRAME_LEN` bytes are kept and the rest of a
+/// longer message is skipped. Returns whether the message was truncated, or
+/// `None` at the end of the stream.
+async fn read_frame<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+) -> io::Result<Option<bool>> {
+    buf.clear();
+
+    // A count is digits followed by a space and a PRI, other digits start a line
+    loop {
+        let Some(&byte) = reader.fill_buf().await?.first() else {
+            return Ok((!buf.is_empty()).then_some(false));
+        };
+        if byte.is_ascii_digit() && buf.len() < MAX_COUNT_DIGITS {
+            buf.push(byte);
+            reader.consume(1);
+            continue;
+        }
+        if byte == b' ' && !buf.is_empty() {
+            reader.consume(1);
+            if reader.fill_buf().await?.first() != Some(&b'<') {
+                buf.push(b' ');
+                break;
+            }
+            let len: u64 = std::str::from_utf8(buf)
+                .ok()
+                .and_then(|count| count.parse().ok())
+                .unwrap_or(0);
+            let kept = len.min(MAX_FRAME_LEN as u64);
+            buf.clear();
+            (&mut *reader).take(kept).read_to_end(buf).await?;
+            tokio::io::copy(&mut (&mut *reader).take(len - kept), &mut tokio::io::sink()).await?;
+            return Ok(Some(len > kept));
+        }
+        break;
+    }
+
+    let limit = (MAX_FRAME_LEN - buf.len()) as u64 + 1;
+    (&mut *reader).take(limit).read_until(b'\n', buf).await?;
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+        return Ok(Some(false));
+    }
+    if buf.len() <= MAX_FRAME_LEN {
+        // The stream ended without a newline
+        return Ok((!buf.is_empty()).then_some(false));
+    }
+    buf.truncate(MAX_FRAME_LEN);
+    skip_line(reader).await?;
+    Ok(Some(true))
+}
+
+/// Skip the rest of the current line
+async fn skip_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(());
+        }
+        if let Some(end) = available.iter().position(|&b| b == b'\n') {
+            reader.consume(end + 1);
+            return Ok(());
+        }
+        let len = available.len();
+        reader.consume(len);
+    }
+}
+
+/// Accept syslog over TCP and push it into storage
+///
+/// Messages are octet-counted or newline-terminated, see `read_frame`.
+/// Failing to accept a connection is logged and doesn't stop the listener.
+pub async fn run_tcp(addr: SocketAddr, storage: LogStorage) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::warn!("Failed to accept syslog connection: {}", e);
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let storage = storage.clone();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(stream);
+            let mut buf = Vec::new();
+            while let Ok(Some(truncated)) = read_frame(&mut reader, &mut buf).await {
+                if buf.is_empty() {
+                    continue;
+                }
+                let mut event = parse_message(&String::from_utf8_lossy(&buf), peer);
+                event.truncated |= truncated;
+                storage.push(event);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> SocketAddr {
+        "127.0.0.1:514".parse().unwrap()
+    }
+
+    async fn frames(mut input: &[u8]) -> Vec<(String, bool)> {
+        let mut frames = Vec::new();
+        let mut buf = Vec::new();
+        while let Some(truncated) = read_frame(&mut input, &mut buf).await.unwrap() {
+            frames.push((String::from_utf8_lossy(&buf).to_string(), truncated));
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn test_tcp_framing() {
+        let input = b"<34>1 first\n11 <34>1 a\nb c\n2024 starts with digits\r\n<34>1 last";
+        let frames = frames(input).await;
+        let messages: Vec<_> = frames.iter().map(|(m, _)| m.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "<34>1 first",
+                "<34>1 a\nb c",
+                "",
+                "2024 starts with digits",
+                "<34>1 last"
+            ]
+        );
+        assert!(frames.iter().all(|(_, truncated)| !truncated));
+    }
+
+    #[tokio::test]
+    async fn test_long_tcp_messages_are_truncated() {
+        let mut input = vec![b'a'; MAX_FRAME_LEN + 10];
+        input.extend_from_slice(b"\nnext\n");
+        input.extend_from_slice(format!("{} ", MAX_FRAME_LEN + 5).as_bytes());
+        input.push(b'<');
+        input.extend(vec![b'b'; MAX_FRAME_LEN + 4]);
+        input.extend_from_slice(b"after");
+        let frames = frames(&input).await;
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].0.len(), MAX_FRAME_LEN);
+        assert!(frames[0].1);
+        assert_eq!(frames[1], ("next".to_string(), false));
+        assert_eq!(frames[2].0.len(), MAX_FRAME_LEN);
+        assert!(frames[2].1);
+        assert_eq!(frames[3], ("after".to_string(), false));
+    }
+
+    #[test]
+    fn test_severity_mapping() {
+        assert_eq!(severity_to_level(0), "ERROR");
+        assert_eq!(severity_to_level(3), "ERROR");
+        assert_eq!(severity_to_level(4), "WARN");
+        assert_eq!(severity_to_level(6), "INFO");
+        assert_eq!(severity_to_level(7), "DEBUG");
+    }
+
+    #[test]
+    fn test_rfc5424() {
+        let event = parse_message(
+            "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut=\"3\"] An application event",
+            peer(),
+        );
+        assert_eq!(event.level, "INFO");
+        assert_eq!(event.target, "syslog::evntslog");
+        assert_eq!(event.message, "An application event");
+        assert_eq!(event.fields["syslog.host"], "mymachine.example.com");
+        assert_eq!(event.fields["syslog.facility"], "local4");
+        assert_eq!(event.fields["syslog.msg_id"], "ID47");
+        assert!(!event.fields.contains_key("syslog.proc_id"));
+    }
+
+    #[test]
+    fn test_rfc3164() {
+        let event = parse_message(
+            "<34>Oct 11 22:14:15 mymachine su[123]: 'su root' failed for lonvick",
+            peer(),
+        );
+        assert_eq!(event.level, "ERROR");
+        assert_eq!(event.target, "syslog::su");
+        assert_eq!(event.message, "'su root' failed for lonvick");
+        assert_eq!(event.fields["syslog.host"], "mymachine");
+        assert_eq!(event.fields["syslog.proc_id"], "123");
+        assert_eq!(event.fields["syslog.facility"], "auth");
+    }
+
+    #[test]
+    fn test_message_without_pri() {
+        let event = parse_message("hello", peer());
+        assert_eq!(event.level, "INFO");
+        assert_eq!(event.target, "syslog");
+        assert_eq!(event.message, "hello");
+    }
+}
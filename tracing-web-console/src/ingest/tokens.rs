@@ -0,0 +1,161 @@
+//! Per-source tokens authenticating remote ingestion
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Field stamped on ingested events with the authenticated source
+pub const SOURCE_FIELD: &str = "ingest.source";
+/// Field stamped on ingested events with the ID of the token used
+pub const TOKEN_ID_FIELD: &str = "ingest.token_id";
+/// Prefix of generated secrets, makes leaked tokens easy to grep for
+const SECRET_PREFIX: &str = "twc_";
+
+/// An issued token, without its secret
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestToken {
+    pub id: String,
+    /// Identity stamped on every event ingested with this token
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A newly issued token, the only time its secret is returned
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedToken {
+    #[serde(flatten)]
+    pub token: IngestToken,
+    pub secret: String,
+}
+
+/// Random secret from the OS's generator
+fn generate_secret() -> Result<String, getrandom::Error> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes)?;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("{}{}", SECRET_PREFIX, hex))
+}
+
+/// Only hashes of secrets are kept in memory
+fn hash_secret(secret: &str) -> [u8; 32] {
+    Sha256::digest(secret.as_bytes()).into()
+}
+
+/// Issued ingest tokens, shared by the API and the ingest endpoints
+///
+/// Ingestion is open until the first token is issued or `require` is
+/// called, from then on every request must present a valid token, even
+/// after all tokens were revoked.
+#[derive(Clone, Default)]
+pub struct IngestTokens {
+    tokens: Arc<RwLock<HashMap<[u8; 32], IngestToken>>>,
+    enforced: Arc<AtomicBool>,
+}
+
+impl IngestTokens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require a token for ingestion, even before any is issued
+    pub fn require(&self) {
+        self.enforced.store(true, Ordering::Release);
+    }
+
+    /// Issue a token with a generated secret for `source`
+    ///
+    /// Fails only if the OS's random number generator is unavailable.
+    pub fn issue(&self, source: &str) -> Result<IssuedToken, getrandom::Error> {
+        let secret = generate_secret()?;
+        let token = self.insert(source, &secret);
+        Ok(IssuedToken { token, secret })
+    }
+
+    /// Register a token with a known secret, e.g. from configuration
+    pub fn insert(&self, source: &str, secret: &str) -> IngestToken {
+        let token = IngestToken {
+            id: crate::id::new_ulid(),
+            source: source.to_string(),
+            created_at: Utc::now(),
+        };
+        self.tokens
+            .write()
+            .insert(hash_secret(secret), token.clone());
+        self.require();
+        token
+    }
+
+    /// Revoke a token by ID, returns false if it doesn't exist
+    pub fn revoke(&self, id: &str) -> bool {
+        let mut tokens = self.tokens.write();
+        let before = tokens.len();
+        tokens.retain(|_, token| token.id != id);
+        tokens.len() != before
+    }
+
+    /// All issued tokens, oldest first
+    pub fn list(&self) -> Vec<IngestToken> {
+        let mut tokens: Vec<_> = self.tokens.read().values().cloned().collect();
+        tokens.sort_by(|a, b| a.id.cmp(&b.id));
+        tokens
+    }
+
+    /// Whether ingestion requires a token
+    ///
+    /// Stays on once a token existed, revoking the last one, e.g. because it
+    /// leaked, must not open ingestion to everyone.
+    pub fn is_enforced(&self) -> bool {
+        self.enforced.load(Ordering::Acquire)
+    }
+
+    /// Look up the token a secret belongs to
+    pub fn authenticate(&self, secret: &str) -> Option<IngestToken> {
+        self.tokens.read().get(&hash_secret(secret)).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_authenticate_revoke() {
+        let tokens = IngestTokens::new();
+        assert!(!tokens.is_enforced());
+
+        let issued = tokens.issue("edge-eu-1").unwrap();
+        assert!(issued.secret.starts_with(SECRET_PREFIX));
+        assert!(tokens.is_enforced());
+
+        let token = tokens.authenticate(&issued.secret).unwrap();
+        assert_eq!(token.source, "edge-eu-1");
+        assert!(tokens.authenticate("twc_wrong").is_none());
+
+        assert!(tokens.revoke(&token.id));
+        assert!(tokens.authenticate(&issued.secret).is_none());
+        assert!(!tokens.revoke(&token.id));
+    }
+
+    #[test]
+    fn test_revoking_the_last_token_keeps_ingestion_closed() {
+        let tokens = IngestTokens::new();
+        let token = tokens.insert("edge-eu-1", "twc_configured");
+        assert!(tokens.revoke(&token.id));
+        assert!(tokens.list().is_empty());
+        assert!(tokens.is_enforced());
+        assert!(tokens.authenticate("twc_configured").is_none());
+
+        let required = IngestTokens::new();
+        required.require();
+        assert!(required.is_enforced());
+    }
+
+    #[test]
+    fn test_secrets_are_unique() {
+        assert_ne!(generate_secret().unwrap(), generate_secret().unwrap());
+    }
+}
@@ -0,0 +1,290 @@
+//! Shared strings for the targets, levels and field names of events
+//!
+//! Most events repeat the same few of them. Interned strings point to one
+//! allocation per distinct value instead of a copy per event.
+//!
+//! Only strings from tracing metadata and the level names are added to the
+//! table. Strings from ingested or imported events reuse its entries but are
+//! allocated per use otherwise, so senders can't fill the table.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, OnceLock};
+
+/// Distinct strings kept in the table, later ones are allocated per use
+///
+/// Bounds the table when field names are generated, e.g. one per callsite
+/// of a macro expanding to many.
+const MAX_ENTRIES: usize = 4096;
+/// Strings in the table from the start, ingested events use them too
+const LEVELS: [&str; 5] = ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
+
+/// Table of all interned strings
+fn table() -> &'static RwLock<HashSet<Arc<str>>> {
+    static TABLE: OnceLock<RwLock<HashSet<Arc<str>>>> = OnceLock::new();
+    TABLE.get_or_init(|| RwLock::new(LEVELS.iter().map(|level| Arc::from(*level)).collect()))
+}
+
+/// An immutable string shared by all events using the same value
+///
+/// Derefs to `str` and compares, hashes and serializes like one, so maps
+/// keyed by it are looked up with a `&str`.
+#[derive(Clone)]
+pub struct Interned(Arc<str>);
+
+impl Interned {
+    /// Shared copy of `value` if it is in the table, a new allocation otherwise
+    ///
+    /// Never adds to the table, so untrusted strings can't fill it.
+    pub fn new(value: &str) -> Self {
+        match table().read().get(value) {
+            Some(shared) => Self(shared.clone()),
+            None => Self(Arc::from(value)),
+        }
+    }
+
+    /// Shared copy of `value`, added to the table while it isn't full
+    fn insert(value: &str) -> Self {
+        {
+            let table = table().read();
+            if let Some(shared) = table.get(value) {
+                return Self(shared.clone());
+            }
+            if table.len() >= MAX_ENTRIES {
+                return Self(Arc::from(value));
+            }
+        }
+        let mut table = table().write();
+        if let Some(shared) = table.get(value) {
+            return Self(shared.clone());
+        }
+        let shared: Arc<str> = Arc::from(value);
+        if table.len() < MAX_ENTRIES {
+            table.insert(shared.clone());
+        }
+        Self(shared)
+    }
+
+    /// Interned copy of a string that lives forever, e.g. a target or field
+    /// name from tracing metadata
+    ///
+    /// Looked up by address in a per-thread cache first, so capturing takes
+    /// the table's lock once per thread and name.
+    pub fn from_static(value: &'static str) -> Self {
+        thread_local! {
+            static CACHE: RefCell<HashMap<(usize, usize), Interned>> = RefCell::default();
+        }
+        let key = (value.as_ptr() as usize, value.len());
+        CACHE.with(|cache| {
+            if let Some(interned) = cache.borrow().get(&key) {
+                return interned.clone();
+            }
+            let interned = Self::insert(value);
+            let mut cache = cache.borrow_mut();
+            if cache.len() < MAX_ENTRIES {
+                cache.insert(key, interned.clone());
+            }
+            interned
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether both point to the same allocation
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Deref for Interned {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Interned {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Interned {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for Interned {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl From<&str> for Interned {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&String> for Interned {
+    fn from(value: &String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for Interned {
+    fn from(value: String) -> Self {
+        Self::new(&value)
+    }
+}
+
+impl From<Interned> for String {
+    fn from(value: Interned) -> Self {
+        value.0.to_string()
+    }
+}
+
+impl PartialEq for Interned {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || *self.0 == *other.0
+    }
+}
+
+impl Eq for Interned {}
+
+impl PartialEq<str> for Interned {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Interned {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Interned {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<Interned> for str {
+    fn eq(&self, other: &Interned) -> bool {
+        self == &*other.0
+    }
+}
+
+impl PartialEq<Interned> for &str {
+    fn eq(&self, other: &Interned) -> bool {
+        *self == &*other.0
+    }
+}
+
+impl PartialEq<Interned> for String {
+    fn eq(&self, other: &Interned) -> bool {
+        **self == *other.0
+    }
+}
+
+impl PartialOrd for Interned {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Interned {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+// Must match `str` for lookups through `Borrow<str>`
+impl Hash for Interned {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl fmt::Debug for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl Serialize for Interned {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Self::new(&value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equal_strings_share_an_allocation() {
+        let first = Interned::from_static("orders::checkout");
+        let second = Interned::from("orders::checkout".to_string());
+        assert!(first.ptr_eq(&second));
+        assert_eq!(first, "orders::checkout");
+        assert!(!first.ptr_eq(&Interned::from_static("orders::payment")));
+    }
+
+    #[test]
+    fn test_untrusted_strings_are_not_added() {
+        let first = Interned::new("tenant-7f3a9c::checkout");
+        let second = Interned::from("tenant-7f3a9c::checkout".to_string());
+        assert_eq!(first, second);
+        assert!(!first.ptr_eq(&second));
+        assert!(table().read().get("tenant-7f3a9c::checkout").is_none());
+        assert!(Interned::new("WARN").ptr_eq(&Interned::new("WARN")));
+    }
+
+    #[test]
+    fn test_static_strings_share_the_table() {
+        let name: &'static str = "request_id";
+        assert!(Interned::from_static(name).ptr_eq(&Interned::from_static(name)));
+        assert!(Interned::from_static(name).ptr_eq(&Interned::new("request_id")));
+        assert!(Interned::from_static(name).ptr_eq(&Interned::from_static("request_id")));
+    }
+
+    #[test]
+    fn test_maps_are_looked_up_by_str() {
+        let map = HashMap::from([(Interned::new("order_id"), 42)]);
+        assert_eq!(map.get("order_id"), Some(&42));
+        assert_eq!(map["order_id"], 42);
+    }
+
+    #[test]
+    fn test_serializes_as_a_string() {
+        let value = Interned::new("INFO");
+        assert_eq!(serde_json::to_string(&value).unwrap(), "\"INFO\"");
+        let parsed: Interned = serde_json::from_str("\"INFO\"").unwrap();
+        assert!(parsed.ptr_eq(&value));
+    }
+}
@@ -1,20 +1,70 @@
 //! Main TracingLayer that integrates with Axum
 
+use crate::access::{AccessTokens, Role};
+use crate::alerts::AlertRule;
 use crate::api::logs::LogsState;
-use crate::storage::LogStorage;
-use crate::subscriber::LogCaptureLayer;
+use crate::backend::{BackendWriter, StorageBackend};
+use crate::compaction::CompactionConfig;
+use crate::filter::TargetFilter;
+use crate::format::{Format, FormatRegistry};
+#[cfg(feature = "forwarder")]
+use crate::forward::ForwarderConfig;
+use crate::handle::ConsoleHandle;
+use crate::ingest::{syslog, LinePattern};
+use crate::limits::ValueLimits;
+#[cfg(feature = "otlp")]
+use crate::otlp::OtlpTracesConfig;
+use crate::presets::FilterPreset;
+use crate::request_id::HttpTraceLayer;
+use crate::retention::{LevelTtls, RetentionPolicy};
+use crate::saturation::SaturationConfig;
+use crate::sink::{BatchSink, BufferedSink, EventSink, MirrorFormat, MirrorSink, MirrorTarget};
+use crate::snapshot::SnapshotConfig;
+use crate::spool::{DiskSpool, SpoolingSink, TryBatchSink};
+use crate::storage::{LogEvent, LogStorage};
+use crate::subscriber::CaptureRegistry;
+use crate::transform::{EventTransform, TransformStage};
+use crate::watchdog::TargetWatch;
 use axum::routing::get;
 use axum::Router;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::cors::{Any, CorsLayer};
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::EnvFilter;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Longest time events wait before they're written to the storage backend
+const BACKEND_FLUSH_LATENCY: Duration = Duration::from_secs(1);
+
+/// Time given to the application to compose `capture_layer()` into its own
+/// subscriber before warning that nothing is captured
+const CAPTURE_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Warning shown in the console when no capture layer feeds it
+fn capture_inactive_event() -> LogEvent {
+    LogEvent {
+        level: "WARN".into(),
+        ..LogEvent::lifecycle(
+            "capture_inactive",
+            "No events are captured: another global tracing subscriber was set before the \
+             console's. Add tracing_web_console::capture_layer() to that subscriber.",
+            HashMap::from([(
+                "remedy".to_string(),
+                crate::api::health::CAPTURE_REMEDY.to_string(),
+            )]),
+        )
+    }
+}
 
 /// Main tracing layer that can be added to an Axum application
 #[derive(Clone)]
 pub struct TracingLayer {
     router: Router,
+    handle: ConsoleHandle,
 }
 
 impl TracingLayer {
@@ -46,58 +96,39 @@ impl TracingLayer {
     /// * `base_path` - The base path for all tracing UI routes
     /// * `capacity` - Maximum number of log events to store in memory
     pub fn with_capacity(base_path: &str, capacity: usize) -> Self {
-        // Create storage for log events
-        let storage = LogStorage::with_capacity(capacity);
-
-        // Set up tracing subscriber with env filter
-        // Default to "trace" for all targets except:
-        // - this crate (to avoid recursive logging)
-        // - "log" target (noisy compatibility layer from log crate)
-        let env_filter = EnvFilter::try_from_default_env()
-            .unwrap_or_else(|_| EnvFilter::new("trace,tracing_web_console=off,log=off"));
-
-        // Create our custom log capture layer
-        let log_capture_layer = LogCaptureLayer::new(storage.clone());
-
-        // Initialize the tracing subscriber
-        // Note: This will set the global default subscriber
-        tracing_subscriber::registry()
-            .with(env_filter)
-            .with(log_capture_layer)
-            .try_init()
-            .ok(); // Ignore error if already initialized
-
-        // Create shared state
-        let logs_state = Arc::new(LogsState::new(storage.clone()));
-
-        // Create frontend state with base path
-        let frontend_state = crate::frontend::FrontendState {
-            base_path: Arc::new(base_path.to_string()),
-        };
-
-        // Create frontend router with its state
-        let frontend_router = Router::new()
-            .route("/", get(crate::frontend::serve_index))
-            .route("/assets/{*path}", get(crate::frontend::serve_static))
-            .with_state(frontend_state);
-
-        // Create the API router
-        let api_router = crate::api::create_api_router(logs_state);
-
-        // Merge frontend and API routers
-        let inner_router = frontend_router.merge(api_router);
+        TracingLayerBuilder::new(base_path)
+            .with_capacity(capacity)
+            .build()
+    }
 
-        // Add CORS middleware for development
-        // In production this allows all origins, which is fine for a debugging/monitoring tool
-        let cors = CorsLayer::new()
-            .allow_origin(Any)
-            .allow_methods(Any)
-            .allow_headers(Any);
+    /// Create a builder for configuring a TracingLayer
+    pub fn builder(base_path: &str) -> TracingLayerBuilder {
+        TracingLayerBuilder::new(base_path)
+    }
 
-        // Nest everything under the base path and add CORS
-        let router = Router::new().nest(base_path, inner_router).layer(cors);
+    /// Middleware tracing every request of the application's own routes
+    ///
+    /// Runs each request in a span with its `request_id`, `method` and
+    /// `route`, so all events of a request can be grouped in the console, and
+    /// records an event with the `path`, `status` and `latency_ms` when the
+    /// response is ready. Add it with `Router::layer`, like `request_id`.
+    ///
+    /// ```rust,no_run
+    /// use axum::Router;
+    /// use axum::routing::get;
+    /// use tracing_web_console::TracingLayer;
+    ///
+    /// let app: Router = Router::new()
+    ///     .route("/", get(|| async { "Hello World" }))
+    ///     .layer(TracingLayer::http_trace_layer());
+    /// ```
+    pub fn http_trace_layer() -> HttpTraceLayer {
+        HttpTraceLayer
+    }
 
-        Self { router }
+    /// Get a handle for interacting with the console from application code
+    pub fn handle(&self) -> ConsoleHandle {
+        self.handle.clone()
     }
 
     /// Merge this tracing layer with an existing Axum router
@@ -121,42 +152,1085 @@ impl TracingLayer {
 }
 
 /// Builder for configuring TracingLayer
-#[allow(dead_code)]
 pub struct TracingLayerBuilder {
     base_path: String,
+    external_base_url: Option<String>,
     capacity: usize,
     initial_filter: String,
+    ignored_targets: Vec<String>,
+    source_url_template: Option<String>,
+    dedup_window: Option<Duration>,
+    combined_writes: bool,
+    capture_queue: Option<usize>,
+    max_message_len: Option<usize>,
+    max_field_len: Option<usize>,
+    partition_by: Option<String>,
+    read_only: bool,
+    access_tokens: Vec<(String, Role)>,
+    metrics: bool,
+    trust_forwarded_headers: bool,
+    transforms: Vec<TransformStage>,
+    custom_transforms: Vec<Arc<dyn EventTransform>>,
+    line_patterns: Vec<String>,
+    ingest_tokens: Vec<(String, String)>,
+    require_ingest_tokens: bool,
+    sinks: Vec<Arc<dyn EventSink>>,
+    alert_rules: Vec<AlertRule>,
+    syslog_udp: Option<SocketAddr>,
+    syslog_tcp: Option<SocketAddr>,
+    snapshots: Option<SnapshotConfig>,
+    spill: Option<(PathBuf, u64)>,
+    snapshot_format: String,
+    formats: FormatRegistry,
+    adaptive_capacity: bool,
+    memory_limit: Option<u64>,
+    level_ttls: LevelTtls,
+    level_caps: HashMap<tracing::Level, usize>,
+    compaction: Option<CompactionConfig>,
+    target_watches: Vec<TargetWatch>,
+    filter_presets: Vec<FilterPreset>,
+    saturation: Option<SaturationConfig>,
+    #[cfg(feature = "otlp")]
+    otlp_traces: Option<OtlpTracesConfig>,
+    #[cfg(feature = "forwarder")]
+    forwarder: Option<ForwarderConfig>,
+    storage_backend: Option<Arc<dyn StorageBackend>>,
+    #[cfg(feature = "mmap")]
+    ring_file: Option<(PathBuf, usize)>,
+    #[cfg(feature = "docker")]
+    docker_containers: Vec<String>,
+    #[cfg(feature = "generator")]
+    generator: Option<crate::generator::GeneratorConfig>,
+    #[cfg(feature = "otlp")]
+    otlp_endpoint: Option<String>,
 }
 
 impl TracingLayerBuilder {
     /// Create a new builder with the specified base path
-    #[allow(dead_code)]
     pub fn new(base_path: &str) -> Self {
         Self {
             base_path: base_path.to_string(),
+            external_base_url: None,
             capacity: 10_000,
             initial_filter: "trace".to_string(),
+            ignored_targets: crate::subscriber::DEFAULT_IGNORED_TARGETS
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+            source_url_template: None,
+            dedup_window: None,
+            combined_writes: true,
+            capture_queue: None,
+            max_message_len: None,
+            max_field_len: None,
+            partition_by: None,
+            read_only: false,
+            access_tokens: Vec::new(),
+            metrics: false,
+            trust_forwarded_headers: false,
+            transforms: Vec::new(),
+            custom_transforms: Vec::new(),
+            line_patterns: Vec::new(),
+            ingest_tokens: Vec::new(),
+            require_ingest_tokens: false,
+            sinks: Vec::new(),
+            alert_rules: Vec::new(),
+            syslog_udp: None,
+            syslog_tcp: None,
+            snapshots: None,
+            spill: None,
+            snapshot_format: "ndjson".to_string(),
+            formats: FormatRegistry::new(),
+            adaptive_capacity: false,
+            memory_limit: None,
+            level_ttls: LevelTtls::default(),
+            level_caps: HashMap::new(),
+            compaction: None,
+            target_watches: Vec::new(),
+            filter_presets: Vec::new(),
+            saturation: None,
+            #[cfg(feature = "otlp")]
+            otlp_traces: None,
+            #[cfg(feature = "forwarder")]
+            forwarder: None,
+            storage_backend: None,
+            #[cfg(feature = "mmap")]
+            ring_file: None,
+            #[cfg(feature = "docker")]
+            docker_containers: Vec::new(),
+            #[cfg(feature = "generator")]
+            generator: None,
+            #[cfg(feature = "otlp")]
+            otlp_endpoint: None,
         }
     }
 
+    /// Path browsers reach the console at when a reverse proxy mounts it
+    /// under a prefix, e.g. `"/myapp/tracing"` for nginx forwarding
+    /// `/myapp/` to the application
+    ///
+    /// Routes stay under the base path, only the links handed to the browser
+    /// change: the `<base>` tag, the asset URLs it resolves and the WebSocket
+    /// endpoint of the injected configuration.
+    pub fn with_external_base_url(mut self, url: &str) -> Self {
+        self.external_base_url = Some(url.trim_end_matches('/').to_string());
+        self
+    }
+
     /// Set the storage capacity
-    #[allow(dead_code)]
     pub fn with_capacity(mut self, capacity: usize) -> Self {
         self.capacity = capacity;
         self
     }
 
-    /// Set the initial log filter
-    #[allow(dead_code)]
+    /// Set which events this console captures, e.g. `"info,my_app=debug,hyper=off"`
+    ///
+    /// Each console has its own filter, so one process can mount a verbose
+    /// internal console next to a quieter public one.
     pub fn with_filter(mut self, filter: &str) -> Self {
         self.initial_filter = filter.to_string();
         self
     }
 
-    /// Build the TracingLayer
-    #[allow(dead_code)]
+    /// Never capture events and spans of these targets or their submodules
+    ///
+    /// Added to the defaults `log`, `tungstenite` and `tokio_tungstenite`.
+    /// This crate's own targets are always ignored. The list can be changed
+    /// at runtime via `/api/ignored-targets`.
+    pub fn with_ignored_targets(mut self, targets: &[&str]) -> Self {
+        for target in targets {
+            if !self.ignored_targets.iter().any(|t| t == target) {
+                self.ignored_targets.push(target.to_string());
+            }
+        }
+        self
+    }
+
+    /// Link events to their source location, e.g.
+    /// `"https://github.com/org/repo/blob/main/{file}#L{line}"`
+    ///
+    /// `{file}`, `{line}` and `{module}` are replaced with the event's
+    /// location and the result is returned as `source_url` of each event.
+    /// Only files with relative paths, those of the workspace, get a link.
+    pub fn with_source_url_template(mut self, template: &str) -> Self {
+        self.source_url_template = Some(template.to_string());
+        self
+    }
+
+    /// Collapse consecutive identical events into one with a `repeat_count`
+    ///
+    /// An event repeats the previous one if level, target, message and fields
+    /// are equal and it was logged within `window` of the first occurrence.
+    pub fn with_dedup(mut self, window: Duration) -> Self {
+        self.dedup_window = Some(window);
+        self
+    }
+
+    /// Whether a thread holding the write lock also stores the events of
+    /// threads waiting for it, on by default
+    ///
+    /// Turning it off stores each event under its own write lock, which is
+    /// only useful to compare throughput.
+    pub fn with_combined_writes(mut self, combine: bool) -> Self {
+        self.combined_writes = combine;
+        self
+    }
+
+    /// Store captured events from a background thread instead of the thread
+    /// that logged them
+    ///
+    /// Logging then only queues the event in a channel of `capacity` events,
+    /// so tracing never waits for the buffer. Events logged while the queue
+    /// is full are discarded and counted in `capture_dropped_total` of
+    /// `/api/stats`. `LogStorage::flush` waits for the queued events.
+    pub fn with_capture_queue(mut self, capacity: usize) -> Self {
+        self.capture_queue = Some(capacity);
+        self
+    }
+
+    /// Cut event messages longer than `len` bytes
+    ///
+    /// The kept text ends with a marker like `… [1024 bytes truncated]`,
+    /// counted towards `len`, and the event is flagged `truncated`. Limits
+    /// below 64 bytes are raised to 64.
+    pub fn with_max_message_len(mut self, len: usize) -> Self {
+        self.max_message_len = Some(len);
+        self
+    }
+
+    /// Cut field values longer than `len` bytes, like `with_max_message_len`
+    ///
+    /// Applies to text and `Debug` formatted values, numbers and booleans
+    /// are kept whole.
+    pub fn with_max_field_len(mut self, len: usize) -> Self {
+        self.max_field_len = Some(len);
+        self
+    }
+
+    /// Share the buffer fairly between the values of `field`, e.g. `"tenant_id"`
+    ///
+    /// The value is taken from the event's fields or its span's. When the
+    /// buffer is full, the oldest event of the value with the most stored
+    /// events is evicted, so one noisy tenant can't push out the others.
+    /// Queries and streams select a value with their `partition` parameter.
+    pub fn with_partition_by(mut self, field: &str) -> Self {
+        self.partition_by = Some(field.to_string());
+        self
+    }
+
+    /// Reject API requests that change state (rules, transforms, ingestion, ...)
+    ///
+    /// Querying and streaming logs keeps working.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Allow querying and streaming events with this token
+    ///
+    /// Once any access token is configured, the API and the plain page
+    /// require one as `Authorization: Bearer <token>`, or in a cookie set by
+    /// opening the console with `?token=<token>`. Tokens should be printable
+    /// ASCII without `;`, `,`, `"` or `\` to work as cookies.
+    pub fn with_reader_token(mut self, token: &str) -> Self {
+        self.access_tokens.push((token.to_string(), Role::Reader));
+        self
+    }
+
+    /// Allow everything readers can, plus requests that change state such as
+    /// levels, rules, transforms and clearing the buffer
+    ///
+    /// See `with_reader_token` for how tokens are presented.
+    pub fn with_admin_token(mut self, token: &str) -> Self {
+        self.access_tokens.push((token.to_string(), Role::Admin));
+        self
+    }
+
+    /// Serve event counts and buffer state for Prometheus at `/api/metrics`
+    ///
+    /// Counts cover every stored event since start, also those already evicted.
+    pub fn with_metrics(mut self) -> Self {
+        self.metrics = true;
+        self
+    }
+
+    /// Build the stream URLs served to the UI from `X-Forwarded-Host` and
+    /// `X-Forwarded-Proto`
+    ///
+    /// Only enable this behind a reverse proxy that sets both headers, they
+    /// are ignored by default since any client can send them.
+    pub fn with_trusted_forwarded_headers(mut self) -> Self {
+        self.trust_forwarded_headers = true;
+        self
+    }
+
+    /// Append a built-in transform stage
+    ///
+    /// These stages can later be inspected and replaced via `/api/transforms`.
+    pub fn with_transform(mut self, stage: TransformStage) -> Self {
+        self.transforms.push(stage);
+        self
+    }
+
+    /// Append a custom transform implemented in code
+    ///
+    /// Custom transforms run before the built-in stages and cannot be changed at runtime.
+    pub fn with_custom_transform<T: EventTransform + 'static>(mut self, transform: T) -> Self {
+        self.custom_transforms.push(Arc::new(transform));
+        self
+    }
+
+    /// Add a grok or regex pattern used to parse ingested plain-text lines
+    ///
+    /// Named groups `timestamp`, `level`, `target` and `message` populate the
+    /// event, any other named group becomes a field. Example:
+    /// `%{TIMESTAMP_ISO8601:timestamp} %{LOGLEVEL:level} %{GREEDYDATA:message}`
+    pub fn with_line_pattern(mut self, pattern: &str) -> Self {
+        self.line_patterns.push(pattern.to_string());
+        self
+    }
+
+    /// Accept a pre-shared token for `/api/ingest/lines`, stamping `source` on its events
+    ///
+    /// Once any token existed, ingestion without a valid token is rejected,
+    /// also after all tokens were revoked. More tokens can be issued and revoked via `/api/ingest/tokens`.
+    pub fn with_ingest_token(mut self, source: &str, secret: &str) -> Self {
+        self.ingest_tokens
+            .push((source.to_string(), secret.to_string()));
+        self
+    }
+
+    /// Reject ingestion without a valid token even before any token is issued
+    ///
+    /// Without this, `/api/ingest/lines` and `/api/ingest/events` are open
+    /// until the first token is configured or issued via `/api/ingest/tokens`.
+    pub fn with_required_ingest_tokens(mut self) -> Self {
+        self.require_ingest_tokens = true;
+        self
+    }
+
+    /// Also print every captured event to stderr or stdout
+    ///
+    /// Useful on container platforms that scrape process output, without
+    /// installing a second `fmt` subscriber next to the console.
+    pub fn with_mirror(mut self, format: MirrorFormat, target: MirrorTarget) -> Self {
+        self.sinks.push(Arc::new(MirrorSink::new(format, target)));
+        self
+    }
+
+    /// Add a custom sink receiving a copy of every captured event
+    pub fn with_sink<S: EventSink + 'static>(mut self, sink: S) -> Self {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Add a sink receiving events in batches from a background flusher
+    ///
+    /// Events are delivered at most `max_latency` after capture (e.g. 200ms),
+    /// [`ConsoleHandle::flush`] waits until everything has been delivered.
+    /// Events arriving while the flusher is far behind are discarded and
+    /// counted in `sink_dropped_total` of `/api/stats`.
+    pub fn with_batch_sink<B: BatchSink + 'static>(
+        mut self,
+        sink: B,
+        max_latency: Duration,
+    ) -> Self {
+        self.sinks
+            .push(Arc::new(BufferedSink::new(sink, max_latency)));
+        self
+    }
+
+    /// Like [`with_batch_sink`](Self::with_batch_sink) for destinations that can be unreachable
+    ///
+    /// Batches that fail are spooled to `dir` (at most `max_bytes`, oldest
+    /// dropped first) and delivered in order once the destination accepts
+    /// batches again, so short outages don't lose events.
+    pub fn with_spooled_batch_sink<T: TryBatchSink + 'static>(
+        mut self,
+        sink: T,
+        dir: impl Into<PathBuf>,
+        max_bytes: u64,
+        max_latency: Duration,
+    ) -> Self {
+        let dir = dir.into();
+        match DiskSpool::open(&dir, max_bytes) {
+            Ok(spool) => {
+                let spooling = SpoolingSink::new(sink, spool);
+                self.sinks
+                    .push(Arc::new(BufferedSink::new(spooling, max_latency)));
+            }
+            Err(e) => {
+                tracing::error!("Failed to open spool directory {}: {}", dir.display(), e);
+            }
+        }
+        self
+    }
+
+    /// Add an alert rule, rules can also be managed via `/api/alerts/rules`
+    pub fn with_alert_rule(mut self, rule: AlertRule) -> Self {
+        self.alert_rules.push(rule);
+        self
+    }
+
+    /// Warn when `target` logs nothing for longer than `max_silence`
+    ///
+    /// Catches background tasks that died quietly, e.g. a heartbeat expected
+    /// every 60 seconds. Watches can also be managed via `/api/watchdog`.
+    pub fn with_silence_watch(mut self, target: &str, max_silence: Duration) -> Self {
+        self.target_watches.push(TargetWatch {
+            target: target.to_string(),
+            max_silence_secs: max_silence.as_secs().max(1),
+        });
+        self
+    }
+
+    /// Offer a filter preset to everyone using the UI
+    ///
+    /// Presets can also be managed via `/api/filters`, e.g. from the UI.
+    pub fn with_filter_preset(mut self, preset: FilterPreset) -> Self {
+        self.filter_presets.push(preset);
+        self
+    }
+
+    /// Warn when the console loses events under load
+    ///
+    /// Records a `visibility_degraded` lifecycle event, and posts to the
+    /// configured webhook, when the buffer eviction rate or the live stream
+    /// drop rate stays above its threshold for the configured period.
+    pub fn with_saturation_alert(mut self, config: SaturationConfig) -> Self {
+        self.saturation = Some(config);
+        self
+    }
+
+    /// Export span trees to an OTLP collector as their root spans close
+    ///
+    /// Spans are sent with the OTLP/HTTP JSON encoding, e.g. to
+    /// `http://localhost:4318/v1/traces`.
+    #[cfg(feature = "otlp")]
+    pub fn with_otlp_traces(mut self, config: OtlpTracesConfig) -> Self {
+        self.otlp_traces = Some(config);
+        self
+    }
+
+    /// Also push every captured event to a collector console
+    ///
+    /// The collector stores them with an `instance` field set to
+    /// `config.instance`, so one console can show and filter the events of
+    /// several services. Events are sent in batches and retried while the
+    /// collector is unreachable.
+    #[cfg(feature = "forwarder")]
+    pub fn with_forwarder(mut self, config: ForwarderConfig) -> Self {
+        self.forwarder = Some(config);
+        self
+    }
+
+    /// Listen for syslog messages (RFC 3164/5424) over UDP on the given address
+    pub fn with_syslog_udp(mut self, addr: SocketAddr) -> Self {
+        self.syslog_udp = Some(addr);
+        self
+    }
+
+    /// Listen for newline-framed syslog messages over TCP on the given address
+    pub fn with_syslog_tcp(mut self, addr: SocketAddr) -> Self {
+        self.syslog_tcp = Some(addr);
+        self
+    }
+
+    /// Shrink the buffer when the process gets close to its cgroup memory limit
+    ///
+    /// The capacity is halved whenever RSS exceeds 85% of the limit and grows
+    /// back once usage drops, each change is recorded as a lifecycle event.
+    pub fn with_adaptive_capacity(mut self) -> Self {
+        self.adaptive_capacity = true;
+        self
+    }
+
+    /// Like [`with_adaptive_capacity`](Self::with_adaptive_capacity) with an explicit limit in bytes
+    pub fn with_memory_limit(mut self, bytes: u64) -> Self {
+        self.adaptive_capacity = true;
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Expire events of `level` once they are older than `ttl`
+    ///
+    /// Keeps verbose levels short-lived while errors stay around, e.g. TRACE
+    /// for 10 minutes and ERROR for 24 hours. Levels without a TTL are only
+    /// evicted when the buffer is full.
+    pub fn with_level_ttl(mut self, level: &str, ttl: Duration) -> Self {
+        if !self.level_ttls.set(level, ttl) {
+            tracing::warn!("Ignoring TTL for unknown level '{}'", level);
+        }
+        self
+    }
+
+    /// Expire events by age and cap the number kept per level
+    ///
+    /// `max_age` applies to every level and shortens longer TTLs set with
+    /// `with_level_ttl`. Caps are applied as events are stored, so a TRACE
+    /// burst only evicts older TRACE events, never other levels:
+    ///
+    /// ```rust,no_run
+    /// use std::collections::HashMap;
+    /// use std::time::Duration;
+    /// use tracing::Level;
+    /// use tracing_web_console::{RetentionPolicy, TracingLayer};
+    ///
+    /// let layer = TracingLayer::builder("/tracing")
+    ///     .with_retention(RetentionPolicy {
+    ///         max_age: Some(Duration::from_secs(6 * 3600)),
+    ///         per_level_caps: HashMap::from([(Level::TRACE, 2_000), (Level::DEBUG, 2_000)]),
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn with_retention(mut self, policy: RetentionPolicy) -> Self {
+        if let Some(max_age) = policy.max_age {
+            self.level_ttls.set_max_age(max_age);
+        }
+        self.level_caps = policy.per_level_caps;
+        self
+    }
+
+    /// Replace events older than `after` with per-minute summaries
+    ///
+    /// Summaries keep the count of each target and level per minute with a
+    /// few example messages, at most `max_summaries` of them, dropping the
+    /// oldest minutes first. They are listed by `/api/summaries`.
+    pub fn with_compaction(mut self, after: Duration, max_summaries: usize) -> Self {
+        self.compaction = Some(CompactionConfig {
+            after,
+            max_summaries,
+        });
+        self
+    }
+
+    /// Periodically write the buffer as NDJSON to `dir`, keeping the newest `keep` files
+    ///
+    /// Not full persistence, but leaves a recent dump to look at after a crash.
+    pub fn with_snapshots(
+        mut self,
+        dir: impl Into<PathBuf>,
+        interval: Duration,
+        keep: usize,
+    ) -> Self {
+        self.snapshots = Some(SnapshotConfig {
+            dir: dir.into(),
+            // A zero interval would make tokio's interval panic
+            interval: interval.max(Duration::from_secs(1)),
+            keep: keep.max(1),
+        });
+        self
+    }
+
+    /// Write snapshots in a registered format instead of NDJSON, e.g. "csv"
+    pub fn with_snapshot_format(mut self, name: &str) -> Self {
+        self.snapshot_format = name.to_string();
+        self
+    }
+
+    /// Append events evicted from the full buffer to NDJSON segment files in
+    /// `dir`, using at most `max_bytes`
+    ///
+    /// Queries transparently read the spilled events back, reaching further
+    /// into the past than the buffer holds. The files only extend the buffer
+    /// of the running process and are removed on startup.
+    pub fn with_spill(mut self, dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        self.spill = Some((dir.into(), max_bytes));
+        self
+    }
+
+    /// Register an output format, selectable by name wherever events are
+    /// written out, e.g. `/api/ws?format=<name>` or snapshots
+    ///
+    /// Replaces a built-in format with the same name.
+    pub fn with_format(self, format: Arc<dyn Format>) -> Self {
+        self.formats.register(format);
+        self
+    }
+
+    /// Mirror the buffer into a memory-mapped ring file of `size` bytes
+    ///
+    /// Events in the file are restored on startup, so the hot buffer survives
+    /// restarts. The file format is documented in the README.
+    #[cfg(feature = "mmap")]
+    pub fn with_ring_file(mut self, path: impl Into<PathBuf>, size: usize) -> Self {
+        self.ring_file = Some((path.into(), size));
+        self
+    }
+
+    /// Persist events in a storage backend so they survive restarts
+    ///
+    /// The buffer is refilled from the backend on startup, older events are
+    /// queried with `history: true` on `/api/logs`.
+    pub fn with_storage_backend(mut self, backend: Arc<dyn StorageBackend>) -> Self {
+        self.storage_backend = Some(backend);
+        self
+    }
+
+    /// Persist events in an SQLite database at `path`
+    ///
+    /// If the database can't be opened, a warning is logged and events are
+    /// only kept in memory.
+    #[cfg(feature = "sqlite")]
+    pub fn with_sqlite(self, path: impl AsRef<std::path::Path>) -> Self {
+        let path = path.as_ref();
+        match crate::backend::sqlite::SqliteBackend::open(path) {
+            Ok(backend) => self.with_storage_backend(Arc::new(backend)),
+            Err(e) => {
+                tracing::warn!("Failed to open SQLite database {}: {}", path.display(), e);
+                self
+            }
+        }
+    }
+
+    /// Stream logs of the given Docker containers (by name or ID) into the console
+    #[cfg(feature = "docker")]
+    pub fn with_docker_containers(mut self, containers: &[&str]) -> Self {
+        self.docker_containers
+            .extend(containers.iter().map(|c| c.to_string()));
+        self
+    }
+
+    /// Fill the console with synthetic traffic of a simulated HTTP service
+    ///
+    /// Useful to try the console or test dashboards without an instrumented app.
+    #[cfg(feature = "generator")]
+    pub fn with_generator(mut self, config: crate::generator::GeneratorConfig) -> Self {
+        self.generator = Some(config);
+        self
+    }
+
+    /// Also export events and completed traces to an OpenTelemetry collector
+    ///
+    /// `url` is the collector's OTLP/HTTP base URL, e.g. `http://localhost:4318`.
+    /// Events are posted to `/v1/logs`, linked to the trace of the span they
+    /// were logged in, and span trees to `/v1/traces` unless
+    /// [`with_otlp_traces`](Self::with_otlp_traces) configures that export
+    /// separately. `service.name` is taken from `OTEL_SERVICE_NAME`.
+    #[cfg(feature = "otlp")]
+    pub fn with_otlp_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.otlp_endpoint = Some(url.into());
+        self
+    }
+
+    /// Filter of this console, capturing everything if it is invalid
+    fn console_filter(&self) -> TargetFilter {
+        TargetFilter::parse(&self.initial_filter).unwrap_or_else(|e| {
+            tracing::warn!(
+                "Invalid console filter '{}', capturing everything: {}",
+                self.initial_filter,
+                e
+            );
+            TargetFilter::default()
+        })
+    }
+
+    /// Build the TracingLayer, installing a global subscriber that feeds it
+    ///
+    /// If the application already set a global subscriber, the console only
+    /// receives events once [`capture_layer()`](crate::capture_layer) is
+    /// composed into it. Use [`build_layer`](Self::build_layer) to leave the
+    /// global subscriber alone.
     pub fn build(self) -> TracingLayer {
-        TracingLayer::with_capacity(&self.base_path, self.capacity)
+        // All consoles share one global capture layer, each with its own filter
+        let filter = self.console_filter();
+        let console = self.build_console(crate::subscriber::global_registry().clone(), filter);
+
+        // If another global subscriber is already set, the application may
+        // still compose `capture_layer()` into it shortly after
+        if !crate::subscriber::install_global() {
+            let storage = console.handle.storage.clone();
+            let warn_if_inactive = move || {
+                if !crate::subscriber::capture_active() {
+                    storage.push(capture_inactive_event());
+                }
+            };
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(async move {
+                        tokio::time::sleep(CAPTURE_GRACE_PERIOD).await;
+                        warn_if_inactive();
+                    });
+                }
+                Err(_) => warn_if_inactive(),
+            }
+        }
+        console
+    }
+
+    /// Build the console without touching the global subscriber
+    ///
+    /// Returns the capture layer feeding this console, filtered by
+    /// [`with_filter`](Self::with_filter), to compose into your own
+    /// subscriber stack next to the console itself:
+    ///
+    /// ```rust,no_run
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::util::SubscriberInitExt;
+    /// use tracing_web_console::TracingLayer;
+    ///
+    /// let (capture, console) = TracingLayer::builder("/tracing")
+    ///     .with_filter("info,my_app=debug")
+    ///     .build_layer();
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(capture)
+    ///     .init();
+    /// let app = axum::Router::new().merge(console.into_router());
+    /// ```
+    pub fn build_layer<S>(self) -> (impl Layer<S> + Send + Sync + 'static, TracingLayer)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let registry = CaptureRegistry::default();
+        let filter = self.console_filter();
+        let console = self.build_console(registry.clone(), filter);
+        (crate::subscriber::registry_layer(registry), console)
+    }
+
+    /// Set up storage, background tasks and routes, feeding the console
+    /// from `capture`
+    fn build_console(self, capture: CaptureRegistry, filter: TargetFilter) -> TracingLayer {
+        let base_path = self.base_path.as_str();
+
+        // Create storage for log events
+        let storage = LogStorage::with_capacity(self.capacity);
+
+        // Configure the ingest transform pipeline
+        for transform in self.custom_transforms {
+            storage.transforms().push_custom(transform);
+        }
+        storage.transforms().set_stages(self.transforms);
+        storage.set_source_url_template(self.source_url_template.clone());
+        storage.set_dedup_window(self.dedup_window);
+        storage.set_combine_writes(self.combined_writes);
+        storage.set_value_limits(ValueLimits {
+            max_message_len: self.max_message_len,
+            max_field_len: self.max_field_len,
+        });
+        storage.set_partition_by(self.partition_by.clone());
+        storage.set_level_caps(&self.level_caps);
+        for sink in self.sinks {
+            storage.add_sink(sink);
+        }
+
+        #[cfg(feature = "mmap")]
+        if let Some((path, size)) = &self.ring_file {
+            match crate::ring::MmapRing::open(path, *size) {
+                Ok(ring) => {
+                    storage.restore(ring.read_all());
+                    storage.add_sink(Arc::new(ring));
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to open ring file {}: {}", path.display(), e);
+                }
+            }
+        }
+
+        if let Some(backend) = &self.storage_backend {
+            // A ring file already restored the most recent events
+            if storage.is_empty() {
+                match crate::backend::load_recent(backend.as_ref(), self.capacity) {
+                    Ok(events) => storage.restore(events),
+                    Err(e) => tracing::warn!("Failed to load persisted events: {}", e),
+                }
+            }
+            storage.add_sink(Arc::new(BufferedSink::new(
+                BackendWriter(backend.clone()),
+                BACKEND_FLUSH_LATENCY,
+            )));
+        }
+
+        if let Some((dir, max_bytes)) = &self.spill {
+            match crate::spill::Spill::open(dir, *max_bytes) {
+                Ok(spill) => storage.set_spill(Some(spill)),
+                Err(e) => {
+                    tracing::error!("Failed to open spill directory {}: {}", dir.display(), e);
+                }
+            }
+        }
+
+        // Make sure the embedded frontend wasn't modified after the build
+        let mismatches = crate::frontend::verify_integrity();
+        if !mismatches.is_empty() {
+            tracing::error!(
+                "Embedded frontend assets failed integrity check: {}",
+                mismatches.join(", ")
+            );
+            storage.push(LogEvent::internal(
+                "ERROR",
+                "Embedded frontend assets failed integrity check",
+                HashMap::from([("files".to_string(), mismatches.join(", "))]),
+            ));
+        }
+
+        // Start optional ingestion sources, these need a running tokio runtime
+        if self.syslog_udp.is_some() || self.syslog_tcp.is_some() {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    if let Some(addr) = self.syslog_udp {
+                        let storage = storage.clone();
+                        handle.spawn(async move {
+                            if let Err(e) = syslog::run_udp(addr, storage).await {
+                                tracing::error!("Syslog UDP listener on {} failed: {}", addr, e);
+                            }
+                        });
+                    }
+                    if let Some(addr) = self.syslog_tcp {
+                        let storage = storage.clone();
+                        handle.spawn(async move {
+                            if let Err(e) = syslog::run_tcp(addr, storage).await {
+                                tracing::error!("Syslog TCP listener on {} failed: {}", addr, e);
+                            }
+                        });
+                    }
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, syslog listeners not started");
+                }
+            }
+        }
+
+        if let Some(capacity) = self.capture_queue {
+            storage.start_capture_queue(capacity);
+        }
+
+        if self.adaptive_capacity {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(crate::memory::run(storage.clone(), self.memory_limit));
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, adaptive capacity not started");
+                }
+            }
+        }
+
+        if let Some(config) = self.snapshots {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    let format = self.formats.get(&self.snapshot_format).unwrap_or_else(|| {
+                        tracing::warn!(
+                            "Unknown snapshot format '{}', using NDJSON",
+                            self.snapshot_format
+                        );
+                        Arc::new(crate::format::Ndjson)
+                    });
+                    handle.spawn(crate::snapshot::run(storage.clone(), config, format));
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, log snapshots not started");
+                }
+            }
+        }
+
+        if !self.level_ttls.is_empty() {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(crate::retention::run(storage.clone(), self.level_ttls));
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, retention not enforced");
+                }
+            }
+        }
+
+        if let Some(config) = self.compaction {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(crate::compaction::run(storage.clone(), config));
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, compaction not started");
+                }
+            }
+        }
+
+        if let Some(config) = self.saturation {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(crate::saturation::run(storage.clone(), config));
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, saturation alert not started");
+                }
+            }
+        }
+
+        #[cfg(feature = "otlp")]
+        let otlp_traces = self.otlp_traces.or_else(|| {
+            self.otlp_endpoint
+                .as_ref()
+                .map(|endpoint| OtlpTracesConfig {
+                    endpoint: format!("{}/v1/traces", endpoint.trim_end_matches('/')),
+                    ..Default::default()
+                })
+        });
+        #[cfg(feature = "otlp")]
+        if let Some(endpoint) = &self.otlp_endpoint {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(crate::otlp::run_logs(storage.clone(), endpoint.clone()));
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, OTLP log export not started");
+                }
+            }
+        }
+
+        #[cfg(feature = "otlp")]
+        if let Some(config) = otlp_traces {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(crate::otlp::run(storage.clone(), config));
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, OTLP trace export not started");
+                }
+            }
+        }
+
+        #[cfg(feature = "forwarder")]
+        if let Some(config) = self.forwarder {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(crate::forward::run(storage.clone(), config));
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, forwarding not started");
+                }
+            }
+        }
+
+        capture.register_ignoring(storage.clone(), filter, self.ignored_targets.clone());
+        // Unregistered once the router and every handle are dropped
+        let registration = Arc::new(capture.registration(storage.clone()));
+
+        // Create shared state
+        let mut logs_state = LogsState::new(storage.clone());
+        logs_state.read_only = self.read_only;
+        logs_state.access = AccessTokens::new(
+            self.access_tokens
+                .iter()
+                .map(|(token, role)| (token.as_str(), *role)),
+        );
+        logs_state.metrics = self.metrics;
+        logs_state.trust_forwarded_headers = self.trust_forwarded_headers;
+        logs_state.base_path = self
+            .external_base_url
+            .clone()
+            .unwrap_or_else(|| base_path.to_string());
+        logs_state.formats = self.formats.clone();
+        logs_state.backend = self.storage_backend.clone();
+        logs_state.capture = capture;
+        logs_state._registration = Some(registration.clone());
+
+        // Compile line patterns for ingested plain-text logs, skipping invalid ones
+        let line_patterns = self
+            .line_patterns
+            .iter()
+            .filter_map(|pattern| match LinePattern::new(pattern) {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    tracing::warn!("Ignoring invalid line pattern '{}': {}", pattern, e);
+                    None
+                }
+            })
+            .collect();
+        logs_state.line_parser.set_patterns(line_patterns);
+
+        for (source, secret) in &self.ingest_tokens {
+            logs_state.ingest_tokens.insert(source, secret);
+        }
+        if self.require_ingest_tokens {
+            logs_state.ingest_tokens.require();
+        }
+
+        let has_alert_rules = !self.alert_rules.is_empty();
+        for rule in self.alert_rules {
+            if let Err(e) = logs_state.alerts.upsert(rule) {
+                tracing::warn!("Ignoring invalid alert rule: {}", e);
+            }
+        }
+        // Always started since rules can be added via the API later
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(crate::alerts::run(
+                    storage.clone(),
+                    logs_state.alerts.clone(),
+                ));
+            }
+            Err(_) if has_alert_rules => {
+                tracing::warn!("No tokio runtime available, alert rules are not evaluated");
+            }
+            Err(_) => {}
+        }
+
+        for preset in self.filter_presets {
+            if let Err(e) = logs_state.presets.upsert(preset) {
+                tracing::warn!("Ignoring invalid filter preset: {}", e);
+            }
+        }
+
+        for watch in self.target_watches.iter().cloned() {
+            if let Err(e) = logs_state.watchdog.upsert(watch) {
+                tracing::warn!("Ignoring invalid silence watch: {}", e);
+            }
+        }
+        // Always started since watches can be added via the API later
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(crate::watchdog::run(
+                    storage.clone(),
+                    logs_state.watchdog.clone(),
+                ));
+            }
+            Err(_) if !self.target_watches.is_empty() => {
+                tracing::warn!("No tokio runtime available, silence watchdog not started");
+            }
+            Err(_) => {}
+        }
+
+        #[cfg(feature = "generator")]
+        if let Some(config) = self.generator {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(crate::generator::run(storage.clone(), config));
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, event generator not started");
+                }
+            }
+        }
+
+        #[cfg(feature = "docker")]
+        if !self.docker_containers.is_empty() {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    for container in self.docker_containers {
+                        handle.spawn(crate::ingest::docker::follow_container(
+                            container,
+                            storage.clone(),
+                            logs_state.line_parser.clone(),
+                        ));
+                    }
+                }
+                Err(_) => {
+                    tracing::warn!("No tokio runtime available, Docker log streaming not started");
+                }
+            }
+        }
+
+        let logs_state = Arc::new(logs_state);
+
+        // Handle for programmatic access from the host application
+        let handle = ConsoleHandle {
+            storage: storage.clone(),
+            line_parser: logs_state.line_parser.clone(),
+            _registration: registration,
+        };
+
+        // Create frontend state with the path browsers see
+        let frontend_state = crate::frontend::FrontendState {
+            base_path: Arc::new(logs_state.base_path.clone()),
+            storage: storage.clone(),
+            logs: logs_state.clone(),
+        };
+
+        // Create frontend router with its state
+        let frontend_router = Router::new()
+            .route("/", get(crate::frontend::serve_index))
+            .route("/assets/{*path}", get(crate::frontend::serve_static))
+            .route("/plain", get(crate::plain::serve_plain))
+            .with_state(frontend_state);
+
+        // Create the API router
+        let api_router = crate::api::create_api_router(logs_state);
+
+        // Merge frontend and API routers
+        let inner_router = frontend_router.merge(api_router);
+
+        // Add CORS middleware for development
+        // In production this allows all origins, which is fine for a debugging/monitoring tool
+        let cors = CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any);
+
+        // Nest everything under the base path and add CORS
+        let router = Router::new().nest(base_path, inner_router).layer(cors);
+
+        storage.push(LogEvent::lifecycle(
+            "started",
+            "Tracing console started",
+            HashMap::from([
+                ("base_path".to_string(), base_path.to_string()),
+                ("capacity".to_string(), self.capacity.to_string()),
+                ("version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+            ]),
+        ));
+
+        TracingLayer { router, handle }
     }
 }
 
@@ -181,9 +1255,46 @@ mod tests {
         assert_eq!(builder.initial_filter, "debug");
     }
 
+    #[test]
+    fn test_multiple_consoles_can_be_built() {
+        let _internal = TracingLayer::builder("/tracing/internal").build();
+        let _public = TracingLayer::builder("/tracing/public")
+            .with_filter("warn")
+            .read_only()
+            .build();
+    }
+
     #[test]
     fn test_builder_defaults_to_trace() {
         let builder = TracingLayerBuilder::new("/tracing");
         assert_eq!(builder.initial_filter, "trace");
     }
+
+    #[test]
+    fn test_build_layer_feeds_only_its_console() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let (capture, console) = TracingLayer::builder("/tracing")
+            .with_filter("warn,app::db=debug")
+            .build_layer();
+        let storage = console.handle().storage;
+        assert!(crate::subscriber::global_registry()
+            .filter_of(&storage)
+            .is_none());
+
+        let subscriber = tracing_subscriber::registry().with(capture);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app::http", "request");
+            tracing::debug!(target: "app::db", "query");
+            tracing::warn!(target: "app::http", "slow");
+        });
+
+        let filter = crate::storage::LogFilter {
+            target: Some("app".to_string()),
+            ..Default::default()
+        };
+        let (events, _) = storage.get_filtered(&filter, None, None);
+        let messages: Vec<_> = events.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["slow", "query"]);
+    }
 }
@@ -23,11 +23,84 @@
 //! }
 //! ```
 
+mod access;
+mod aggregate;
+mod alerts;
 mod api;
+mod backend;
+mod cancel;
+mod compaction;
+mod diagnostics;
+mod expr;
+mod filter;
+mod format;
+mod forward;
 mod frontend;
+#[cfg(feature = "generator")]
+mod generator;
+mod handle;
+mod id;
+mod ingest;
+mod intern;
 mod layer;
+mod limits;
+mod memory;
+mod metrics;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod partition;
+mod pending;
+mod plain;
+mod presets;
+mod query_cache;
+mod replay;
+mod request_id;
+mod retention;
+#[cfg(feature = "mmap")]
+mod ring;
+mod saturation;
+#[cfg(feature = "scripting")]
+mod script;
+mod sink;
+mod snapshot;
+mod span_index;
+mod spans;
+mod spill;
+mod spool;
 mod storage;
+mod stream_clients;
 mod subscriber;
+mod target_stats;
+mod target_trie;
+mod transform;
+mod watchdog;
 
-pub use layer::TracingLayer;
-pub use storage::LogEvent;
+pub use alerts::AlertRule;
+pub use api::error::{ApiError, ErrorBody};
+#[cfg(feature = "sqlite")]
+pub use backend::sqlite::SqliteBackend;
+pub use backend::StorageBackend;
+pub use expr::ExprError;
+pub use format::{Format, FormatRegistry};
+#[cfg(feature = "forwarder")]
+pub use forward::ForwarderConfig;
+#[cfg(feature = "generator")]
+pub use generator::GeneratorConfig;
+pub use handle::ConsoleHandle;
+pub use ingest::process::PipeOptions;
+pub use intern::Interned;
+pub use layer::{TracingLayer, TracingLayerBuilder};
+#[cfg(feature = "otlp")]
+pub use otlp::OtlpTracesConfig;
+pub use presets::FilterPreset;
+pub use request_id::{request_id, HttpTrace, HttpTraceLayer, RequestId, REQUEST_ID_HEADER};
+pub use retention::RetentionPolicy;
+pub use saturation::SaturationConfig;
+#[cfg(feature = "scripting")]
+pub use script::ScriptCondition;
+pub use sink::{BatchSink, EventSink, MirrorFormat, MirrorTarget};
+pub use spool::TryBatchSink;
+pub use storage::{LogEvent, Marker, SpanInfo};
+pub use subscriber::capture_layer;
+pub use transform::{EventTransform, TransformStage};
+pub use watchdog::TargetWatch;
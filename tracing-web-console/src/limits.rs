@@ -0,0 +1,166 @@
+//! Length limits for messages and field values
+//!
+//! A single event with a huge `Debug` body could otherwise take up much of
+//! the buffer's memory and stall the UI. Values over the limit are cut at a
+//! character boundary and end with a marker saying how much was left out,
+//! the marker counting towards the limit.
+
+use compact_str::CompactString;
+use std::fmt;
+
+/// Smallest limit, leaves room for the marker and some of the value
+pub(crate) const MIN_VALUE_LEN: usize = 64;
+
+/// Marker appended to a cut value
+fn marker(omitted: usize) -> String {
+    format!("… [{} bytes truncated]", omitted)
+}
+
+/// Largest index up to `max` that is a character boundary of `text`
+fn floor_char_boundary(text: &str, max: usize) -> usize {
+    let mut index = max.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Where to cut `text`, originally `total` bytes long, so that it fits into
+/// `max` bytes with the marker, and the number of bytes left out
+fn cut_point(text: &str, total: usize, max: usize) -> (usize, usize) {
+    // No more than `total` bytes are left out, so this marker is the longest
+    let room = max.saturating_sub(marker(total).len());
+    let end = floor_char_boundary(text, room);
+    (end, total - end)
+}
+
+/// Maximum lengths in bytes, `None` keeps values whole
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ValueLimits {
+    pub max_message_len: Option<usize>,
+    pub max_field_len: Option<usize>,
+}
+
+impl ValueLimits {
+    /// Limit of the field `name`, the message is recorded as field `message`
+    pub fn for_field(&self, name: &str) -> Option<usize> {
+        if name == "message" {
+            self.max_message_len
+        } else {
+            self.max_field_len
+        }
+    }
+
+    /// Limits keeping everything either of `self` and `other` keeps
+    pub fn loosest(self, other: Self) -> Self {
+        let loosest = |a: Option<usize>, b: Option<usize>| a.zip(b).map(|(a, b)| a.max(b));
+        Self {
+            max_message_len: loosest(self.max_message_len, other.max_message_len),
+            max_field_len: loosest(self.max_field_len, other.max_field_len),
+        }
+    }
+}
+
+/// Cut `text` to `max` bytes including the marker, returns whether it was cut
+pub(crate) fn truncate_string(text: &mut String, max: usize) -> bool {
+    if text.len() <= max {
+        return false;
+    }
+    let (end, omitted) = cut_point(text, text.len(), max);
+    text.truncate(end);
+    text.push_str(&marker(omitted));
+    true
+}
+
+/// Formatting target keeping at most `max` bytes, so huge `Debug` output is
+/// never held in full
+pub(crate) struct LimitedWriter {
+    text: CompactString,
+    max: usize,
+    omitted: usize,
+}
+
+impl LimitedWriter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            text: CompactString::default(),
+            max,
+            omitted: 0,
+        }
+    }
+
+    /// The text written, cut with the marker if it didn't fit
+    pub fn finish(mut self) -> (CompactString, bool) {
+        if self.omitted == 0 {
+            return (self.text, false);
+        }
+        let total = self.text.len() + self.omitted;
+        let (end, omitted) = cut_point(&self.text, total, self.max);
+        self.text.truncate(end);
+        self.text.push_str(&marker(omitted));
+        (self.text, true)
+    }
+}
+
+impl fmt::Write for LimitedWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = self.max - self.text.len();
+        if self.omitted == 0 && s.len() <= room {
+            self.text.push_str(s);
+        } else {
+            // Nothing is added after a cut, even if later pieces would fit
+            let end = if self.omitted == 0 {
+                floor_char_boundary(s, room)
+            } else {
+                0
+            };
+            self.text.push_str(&s[..end]);
+            self.omitted += s.len() - end;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    #[test]
+    fn test_values_are_cut_with_marker() {
+        let mut text = format!("é{}", "x".repeat(200));
+        assert!(truncate_string(&mut text, MIN_VALUE_LEN));
+        assert!(text.len() <= MIN_VALUE_LEN);
+        assert!(text.starts_with("éxx"));
+        let kept = text.find('…').unwrap();
+        assert!(text.ends_with(&format!("[{} bytes truncated]", 202 - kept)));
+        // Cutting again keeps the text as it is
+        let cut = text.clone();
+        assert!(!truncate_string(&mut text, MIN_VALUE_LEN));
+        assert_eq!(text, cut);
+
+        let mut writer = LimitedWriter::new(MIN_VALUE_LEN);
+        write!(writer, "{:?}", [0u8; 1000]).unwrap();
+        let (debug, truncated) = writer.finish();
+        assert!(truncated);
+        assert!(debug.len() <= MIN_VALUE_LEN);
+        assert!(debug.starts_with("[0, 0, "));
+
+        let mut writer = LimitedWriter::new(MIN_VALUE_LEN);
+        write!(writer, "{:?}", [1, 2, 3]).unwrap();
+        assert_eq!(writer.finish(), ("[1, 2, 3]".into(), false));
+
+        let limits = ValueLimits {
+            max_message_len: Some(100),
+            max_field_len: None,
+        };
+        assert_eq!(limits.for_field("message"), Some(100));
+        assert_eq!(limits.for_field("body"), None);
+        let strict = ValueLimits {
+            max_message_len: Some(64),
+            max_field_len: Some(64),
+        };
+        assert_eq!(limits.loosest(strict), limits);
+        assert_eq!(strict.loosest(strict), strict);
+    }
+}
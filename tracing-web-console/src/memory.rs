@@ -0,0 +1,276 @@
+//! Buffer capacity adapting to process memory pressure, and estimates of
+//! the memory held by stored events
+
+use crate::intern::Interned;
+use crate::storage::{LogEvent, LogStorage};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::time::Duration;
+
+/// How often memory usage is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Above this share of the limit the capacity is halved
+const HIGH_WATERMARK: f64 = 0.85;
+/// Below this share of the limit the capacity is doubled again
+const LOW_WATERMARK: f64 = 0.6;
+/// The buffer never shrinks below this many events
+const MIN_CAPACITY: usize = 100;
+
+/// Resident set size of this process in bytes (Linux only)
+fn current_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Memory limit of the cgroup this process runs in (v2 or v1)
+fn cgroup_limit() -> Option<u64> {
+    [
+        "/sys/fs/cgroup/memory.max",
+        "/sys/fs/cgroup/memory/memory.limit_in_bytes",
+    ]
+    .iter()
+    .filter_map(|path| std::fs::read_to_string(path).ok())
+    // "max" (v2) fails to parse, v1 reports a huge number when unlimited
+    .filter_map(|value| value.trim().parse::<u64>().ok())
+    .find(|limit| *limit < u64::MAX / 2)
+}
+
+/// Capacity to use for the given memory usage ratio, `None` keeps the current one
+fn next_capacity(current: usize, max: usize, usage: f64) -> Option<usize> {
+    if usage > HIGH_WATERMARK && current > MIN_CAPACITY.min(max) {
+        Some((current / 2).max(MIN_CAPACITY.min(max)))
+    } else if usage < LOW_WATERMARK && current < max {
+        Some(current.saturating_mul(2).min(max))
+    } else {
+        None
+    }
+}
+
+/// Watch memory usage and resize the buffer, forever
+///
+/// `limit` overrides the cgroup limit. Without either, or without a way to
+/// read the RSS, monitoring is disabled.
+pub(crate) async fn run(storage: LogStorage, limit: Option<u64>) {
+    let Some(limit) = limit.or_else(cgroup_limit) else {
+        tracing::warn!("No memory limit found, adaptive capacity disabled");
+        return;
+    };
+    if current_rss().is_none() {
+        tracing::warn!("Process memory usage unavailable, adaptive capacity disabled");
+        return;
+    }
+
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        let Some(rss) = current_rss() else {
+            continue;
+        };
+
+        let usage = rss as f64 / limit as f64;
+        let current = storage.effective_capacity();
+        let Some(capacity) = next_capacity(current, storage.capacity(), usage) else {
+            continue;
+        };
+        storage.set_effective_capacity(capacity);
+
+        let (level, message) = if capacity < current {
+            ("WARN", "Buffer capacity reduced due to memory pressure")
+        } else {
+            ("INFO", "Buffer capacity restored after memory pressure")
+        };
+        storage.push(LogEvent {
+            level: level.into(),
+            ..LogEvent::lifecycle(
+                "capacity_reconfigured",
+                message,
+                HashMap::from([
+                    ("reason".to_string(), "memory_pressure".to_string()),
+                    ("capacity".to_string(), capacity.to_string()),
+                    ("previous_capacity".to_string(), current.to_string()),
+                    ("rss_bytes".to_string(), rss.to_string()),
+                    ("limit_bytes".to_string(), limit.to_string()),
+                ]),
+            )
+        });
+    }
+}
+
+/// Heap bytes of a JSON value, not counting the value itself
+fn value_size(value: &Value) -> usize {
+    match value {
+        Value::String(text) => text.capacity(),
+        Value::Array(values) => {
+            values.capacity() * size_of::<Value>() + values.iter().map(value_size).sum::<usize>()
+        }
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| size_of::<(String, Value)>() + key.capacity() + value_size(value))
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// Heap bytes of a string map, including the table
+fn map_size<K, V>(
+    map: &HashMap<K, V>,
+    key_size: impl Fn(&K) -> usize,
+    value_size: impl Fn(&V) -> usize,
+) -> usize {
+    // One control byte per bucket on top of the entries
+    map.capacity() * (size_of::<(K, V)>() + 1)
+        + map
+            .iter()
+            .map(|(key, value)| key_size(key) + value_size(value))
+            .sum::<usize>()
+}
+
+/// Heap bytes of an interned string, held once for all events using it
+fn interned_size(value: &Interned) -> usize {
+    // Strong and weak count in front of the text
+    2 * size_of::<usize>() + value.len()
+}
+
+/// Approximate bytes an event holds, itself and everything it owns
+///
+/// Interned level, target and field names are shared and left out, see
+/// `MemoryEstimate`. Allocator overhead isn't known and left out as well,
+/// so the real usage is somewhat higher.
+pub(crate) fn estimated_size(event: &LogEvent) -> usize {
+    let optional = [
+        &event.module_path,
+        &event.file,
+        &event.source_url,
+        &event.thread_name,
+    ];
+    let span = event.span.as_ref().map_or(0, |span| {
+        size_of::<crate::storage::SpanInfo>()
+            + span.id.as_ref().map_or(0, String::capacity)
+            + span.name.capacity()
+            + map_size(&span.fields, String::capacity, String::capacity)
+            + span.ancestors.capacity() * size_of::<String>()
+            + span.ancestors.iter().map(String::capacity).sum::<usize>()
+            + map_size(&span.parent_fields, String::capacity, String::capacity)
+    });
+    size_of::<LogEvent>()
+        + event.id.capacity()
+        + event.message.capacity()
+        + optional
+            .into_iter()
+            .flatten()
+            .map(String::capacity)
+            .sum::<usize>()
+        + map_size(&event.fields, |_| 0, value_size)
+        + span
+}
+
+/// Estimated memory held by the stored events
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryUsage {
+    /// Approximate bytes of all stored events, including `shared_strings_bytes`
+    pub events_bytes: u64,
+    /// Average per stored event
+    pub bytes_per_event: u64,
+    /// Bytes of the targets, levels and field names, each held once and
+    /// shared by the events using it
+    pub shared_strings_bytes: u64,
+}
+
+/// Adds up the memory of events one at a time
+#[derive(Default)]
+pub(crate) struct MemoryEstimate {
+    events: u64,
+    bytes: u64,
+    shared: u64,
+    /// Addresses of the interned strings counted so far
+    seen: HashSet<usize>,
+}
+
+impl MemoryEstimate {
+    pub fn add(&mut self, event: &LogEvent) {
+        self.events += 1;
+        self.bytes += estimated_size(event) as u64;
+        let names = [&event.level, &event.target]
+            .into_iter()
+            .chain(event.fields.keys());
+        for name in names {
+            if self.seen.insert(name.as_ptr() as usize) {
+                self.shared += interned_size(name) as u64;
+            }
+        }
+    }
+
+    pub fn finish(self) -> MemoryUsage {
+        let bytes = self.bytes + self.shared;
+        MemoryUsage {
+            events_bytes: bytes,
+            bytes_per_event: bytes.checked_div(self.events).unwrap_or(0),
+            shared_strings_bytes: self.shared,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_capacity() {
+        // Shrink under pressure, but not below the minimum
+        assert_eq!(next_capacity(10_000, 10_000, 0.9), Some(5_000));
+        assert_eq!(next_capacity(150, 10_000, 0.9), Some(MIN_CAPACITY));
+        assert_eq!(next_capacity(MIN_CAPACITY, 10_000, 0.9), None);
+
+        // Grow back when pressure is gone, up to the configured capacity
+        assert_eq!(next_capacity(5_000, 10_000, 0.3), Some(10_000));
+        assert_eq!(next_capacity(10_000, 10_000, 0.3), None);
+
+        // Hold steady between the watermarks
+        assert_eq!(next_capacity(5_000, 10_000, 0.7), None);
+    }
+
+    #[test]
+    fn test_memory_estimate() {
+        let event = |message: &str| {
+            LogEvent::internal(
+                "INFO",
+                message,
+                HashMap::from([("order_id".to_string(), "42".to_string())]),
+            )
+        };
+        let small = estimated_size(&event("a"));
+        assert!(small > size_of::<LogEvent>());
+        assert_eq!(estimated_size(&event(&"a".repeat(1001))), small + 1000);
+
+        let mut estimate = MemoryEstimate::default();
+        estimate.add(&event("first"));
+        estimate.add(&event("second"));
+        let usage = estimate.finish();
+        assert!(usage.bytes_per_event >= small as u64);
+        // "INFO" and the target are counted once for both events, "order_id"
+        // isn't in the intern table so each event has its own copy
+        let shared = [
+            "INFO",
+            crate::storage::INTERNAL_TARGET,
+            "order_id",
+            "order_id",
+        ]
+        .iter()
+        .map(|name| 2 * size_of::<usize>() + name.len())
+        .sum::<usize>();
+        assert_eq!(usage.shared_strings_bytes, shared as u64);
+    }
+
+    #[test]
+    fn test_rss_is_readable_on_linux() {
+        if cfg!(target_os = "linux") {
+            assert!(current_rss().unwrap() > 0);
+        }
+    }
+}
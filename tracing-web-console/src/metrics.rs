@@ -0,0 +1,130 @@
+//! Prometheus text exposition of event counts and buffer state
+//!
+//! Lets existing alerting pick up error spikes without anyone watching the UI.
+
+use crate::storage::LogStorage;
+use std::fmt::Write;
+
+/// Prefix of every exported metric
+const NAMESPACE: &str = "tracing_web_console";
+
+/// Content type of the text exposition format
+pub(crate) const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Escape a label value, see the exposition format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Write the `# HELP` and `# TYPE` lines of a metric
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {}_{} {}", NAMESPACE, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", NAMESPACE, name, kind);
+}
+
+/// Current metrics of a storage in the Prometheus text format
+pub(crate) fn render(storage: &LogStorage) -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "events_total",
+        "counter",
+        "Events stored since start by level and target.",
+    );
+    for (target, level, count) in storage.event_totals() {
+        let _ = writeln!(
+            out,
+            "{}_events_total{{level=\"{}\",target=\"{}\"}} {}",
+            NAMESPACE,
+            level,
+            escape_label(&target),
+            count
+        );
+    }
+
+    let scalars = [
+        (
+            "evicted_events_total",
+            "counter",
+            "Events evicted from the full buffer since start.",
+            storage.evicted_total(),
+        ),
+        (
+            "dropped_events_total",
+            "counter",
+            "Events live stream clients missed by falling behind since start.",
+            storage.dropped_total(),
+        ),
+        (
+            "capture_dropped_events_total",
+            "counter",
+            "Captured events discarded because the capture queue was full.",
+            storage.capture_dropped_total(),
+        ),
+        (
+            "sink_dropped_events_total",
+            "counter",
+            "Events sinks discarded because their queue was full.",
+            storage.sink_dropped_total(),
+        ),
+        (
+            "websocket_clients",
+            "gauge",
+            "Connected live stream clients.",
+            storage.stream_clients().stats().len() as u64,
+        ),
+        (
+            "buffer_size",
+            "gauge",
+            "Events currently stored.",
+            storage.len() as u64,
+        ),
+        (
+            "buffer_capacity",
+            "gauge",
+            "Events the buffer can currently hold.",
+            storage.effective_capacity() as u64,
+        ),
+    ];
+    for (name, kind, help, value) in scalars {
+        header(&mut out, name, kind, help);
+        let _ = writeln!(out, "{}_{} {}", NAMESPACE, name, value);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LogEvent;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_render_counts_events() {
+        let storage = LogStorage::with_capacity(2);
+        for (level, target) in [("ERROR", "app"), ("ERROR", "app"), ("INFO", "db \"main\"")] {
+            storage.push(LogEvent {
+                target: target.into(),
+                ..LogEvent::internal(level, "event", HashMap::new())
+            });
+        }
+        let _client = storage.stream_clients().register("client");
+
+        let text = render(&storage);
+        assert!(
+            text.contains("tracing_web_console_events_total{level=\"ERROR\",target=\"app\"} 2\n")
+        );
+        assert!(text.contains(
+            "tracing_web_console_events_total{level=\"INFO\",target=\"db \\\"main\\\"\"} 1\n"
+        ));
+        assert!(text.contains("# TYPE tracing_web_console_events_total counter\n"));
+        assert!(text.contains("tracing_web_console_evicted_events_total 1\n"));
+        assert!(text.contains("tracing_web_console_websocket_clients 1\n"));
+        assert!(text.contains("tracing_web_console_buffer_size 2\n"));
+    }
+}
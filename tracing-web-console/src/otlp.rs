@@ -0,0 +1,451 @@
+//! Export of completed span trees as OTLP traces, and of events as OTLP logs
+//!
+//! When a root span closes, its tree is converted into OTLP spans and posted
+//! to a collector using the OTLP/HTTP JSON encoding, so the same
+//! instrumentation feeds both the console and a distributed tracing backend.
+//! The root's ULID becomes the trace ID, the random part of each span's ULID
+//! its span ID. Captured events can be exported as log records linked to
+//! these IDs too. Needs the `otlp` feature.
+
+use crate::spans::SpanNode;
+use crate::storage::{LogEvent, LogStorage};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Timeout for export requests
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for more completed trees before exporting a batch
+const BATCH_DELAY: Duration = Duration::from_secs(1);
+/// Most trees exported in one request
+const MAX_BATCH_TREES: usize = 128;
+/// `SPAN_KIND_INTERNAL`, spans don't say whether they are client or server side
+const SPAN_KIND_INTERNAL: u8 = 1;
+/// Most events exported in one request
+const MAX_BATCH_EVENTS: usize = 512;
+
+/// `service.name` from `OTEL_SERVICE_NAME`, as OpenTelemetry SDKs do
+fn default_service_name() -> String {
+    std::env::var("OTEL_SERVICE_NAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown_service".to_string())
+}
+
+/// Where and how completed span trees are exported
+#[derive(Debug, Clone)]
+pub struct OtlpTracesConfig {
+    /// OTLP/HTTP traces URL of the collector
+    pub endpoint: String,
+    /// `service.name` resource attribute of the exported spans, taken from
+    /// `OTEL_SERVICE_NAME` by default
+    pub service_name: String,
+    /// Extra request headers, e.g. for authentication
+    pub headers: HashMap<String, String>,
+}
+
+impl Default for OtlpTracesConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: default_service_name(),
+            headers: HashMap::new(),
+        }
+    }
+}
+
+/// OTLP `AnyValue` attribute with a string value
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// OTLP attribute of an event field, keeping numbers and booleans typed
+fn field_attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Bool(value) => json!({ "boolValue": value }),
+        // 64-bit integers are strings in the JSON encoding
+        Value::Number(number) if number.is_i64() || number.is_u64() => {
+            json!({ "intValue": number.to_string() })
+        }
+        Value::Number(number) => json!({ "doubleValue": number }),
+        Value::Array(items) => json!({
+            "arrayValue": {
+                "values": items
+                    .iter()
+                    .map(|item| json!({ "stringValue": crate::storage::field_text(item) }))
+                    .collect::<Vec<_>>(),
+            },
+        }),
+        other => json!({ "stringValue": crate::storage::field_text(other) }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+/// Nanoseconds since the Unix epoch, as the string the JSON encoding expects
+fn unix_nanos(timestamp: DateTime<Utc>) -> String {
+    let nanos = i128::from(timestamp.timestamp()) * 1_000_000_000
+        + i128::from(timestamp.timestamp_subsec_nanos());
+    nanos.max(0).to_string()
+}
+
+/// 64-bit span ID from the random part of a span's ULID
+fn span_id(id: &str) -> Option<String> {
+    crate::id::decode(id).map(|value| format!("{:016x}", value as u64))
+}
+
+/// Convert a span and its descendants
+///
+/// Spans still open when their root closed, e.g. detached tasks, are left out
+/// with their descendants.
+fn collect_spans(node: &SpanNode, trace_id: &str, parent: Option<&str>, out: &mut Vec<Value>) {
+    let span = &node.span;
+    let (Some(closed_at), Some(id)) = (span.closed_at, span_id(&span.id)) else {
+        return;
+    };
+
+    let mut fields: Vec<_> = span.fields.iter().collect();
+    fields.sort();
+    let mut attributes: Vec<Value> = fields
+        .into_iter()
+        .map(|(key, value)| attribute(key, value))
+        .collect();
+    attributes.push(attribute("code.namespace", &span.target));
+    attributes.push(attribute("level", &span.level));
+    attributes.push(json!({
+        "key": "busy_us",
+        "value": { "intValue": span.busy_us.to_string() },
+    }));
+
+    let mut converted = json!({
+        "traceId": trace_id,
+        "spanId": id,
+        "name": span.name,
+        "kind": SPAN_KIND_INTERNAL,
+        "startTimeUnixNano": unix_nanos(span.started_at),
+        "endTimeUnixNano": unix_nanos(closed_at),
+        "attributes": attributes,
+    });
+    if let Some(parent) = parent {
+        converted["parentSpanId"] = json!(parent);
+    }
+    out.push(converted);
+
+    for child in &node.children {
+        collect_spans(child, trace_id, Some(&id), out);
+    }
+}
+
+/// 128-bit trace ID from the ULID of a trace's root span
+fn trace_id(root_id: &str) -> Option<String> {
+    crate::id::decode(root_id).map(|value| format!("{:032x}", value))
+}
+
+/// Instrumentation scope of everything exported
+fn scope() -> Value {
+    json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+    })
+}
+
+/// OTLP `ExportTraceServiceRequest` with one trace per tree
+fn export_request(trees: &[SpanNode], service_name: &str) -> Value {
+    let mut spans = Vec::new();
+    for tree in trees {
+        if let Some(trace_id) = trace_id(&tree.span.id) {
+            collect_spans(tree, &trace_id, None, &mut spans);
+        }
+    }
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [attribute("service.name", service_name)] },
+            "scopeSpans": [{ "scope": scope(), "spans": spans }],
+        }],
+    })
+}
+
+/// OTLP severity number of a level, unknown levels are unspecified
+fn severity_number(level: &str) -> u8 {
+    match level.to_ascii_uppercase().as_str() {
+        "TRACE" => 1,
+        "DEBUG" => 5,
+        "INFO" => 9,
+        "WARN" => 13,
+        "ERROR" => 17,
+        _ => 0,
+    }
+}
+
+/// Convert an event into an OTLP log record
+///
+/// Events logged inside a recorded span are linked to its trace.
+fn log_record(event: &LogEvent, spans: &crate::spans::SpanStore) -> Value {
+    let mut fields: Vec<_> = event.fields.iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    let mut attributes: Vec<Value> = fields
+        .into_iter()
+        .map(|(key, value)| field_attribute(key, value))
+        .collect();
+    attributes.push(attribute("code.namespace", &event.target));
+    if let Some(file) = &event.file {
+        attributes.push(attribute("code.filepath", file));
+    }
+    if let Some(line) = event.line {
+        attributes.push(json!({
+            "key": "code.lineno",
+            "value": { "intValue": line.to_string() },
+        }));
+    }
+
+    let timestamp = unix_nanos(event.timestamp);
+    let mut record = json!({
+        "timeUnixNano": timestamp,
+        "observedTimeUnixNano": timestamp,
+        "severityNumber": severity_number(&event.level),
+        "severityText": event.level,
+        "body": { "stringValue": event.message },
+        "attributes": attributes,
+    });
+    let span = event.span.as_ref().and_then(|span| span.id.as_deref());
+    if let Some(span) = span {
+        let ids = spans
+            .root_id(span)
+            .and_then(|root| trace_id(&root))
+            .zip(span_id(span));
+        if let Some((trace_id, span_id)) = ids {
+            record["traceId"] = json!(trace_id);
+            record["spanId"] = json!(span_id);
+        }
+    }
+    record
+}
+
+/// OTLP `ExportLogsServiceRequest` of a batch of events
+fn export_logs_request(
+    events: &[LogEvent],
+    spans: &crate::spans::SpanStore,
+    service_name: &str,
+) -> Value {
+    let records: Vec<Value> = events
+        .iter()
+        .map(|event| log_record(event, spans))
+        .collect();
+    json!({
+        "resourceLogs": [{
+            "resource": { "attributes": [attribute("service.name", service_name)] },
+            "scopeLogs": [{ "scope": scope(), "logRecords": records }],
+        }],
+    })
+}
+
+/// Export stored events as OTLP logs to `{endpoint}/v1/logs`, forever
+///
+/// The console's own events are not exported.
+pub(crate) async fn run_logs(storage: LogStorage, endpoint: String) {
+    use futures::StreamExt;
+
+    let client = reqwest::Client::new();
+    let url = format!("{}/v1/logs", endpoint.trim_end_matches('/'));
+    let service_name = default_service_name();
+    let mut events = Box::pin(storage.event_stream());
+    let mut failing = false;
+
+    loop {
+        let mut batch = Vec::new();
+        match events.next().await {
+            Some(event) => batch.push((*event).clone()),
+            None => return,
+        }
+        let deadline = tokio::time::Instant::now() + BATCH_DELAY;
+        while batch.len() < MAX_BATCH_EVENTS {
+            match tokio::time::timeout_at(deadline, events.next()).await {
+                Ok(Some(event)) => batch.push((*event).clone()),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        batch.retain(|event| !crate::forward::is_own(event));
+        if batch.is_empty() {
+            continue;
+        }
+
+        let sent = client
+            .post(&url)
+            .timeout(EXPORT_TIMEOUT)
+            .json(&export_logs_request(&batch, storage.spans(), &service_name))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match sent {
+            Ok(_) => failing = false,
+            // Report once per outage, the batch is dropped
+            Err(e) if !failing => {
+                failing = true;
+                storage.diagnose(LogEvent::internal(
+                    "WARN",
+                    "Failed to export events to OTLP collector",
+                    HashMap::from([
+                        ("error".to_string(), e.to_string()),
+                        ("events".to_string(), batch.len().to_string()),
+                    ]),
+                ));
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Export span trees as their roots close, forever
+pub(crate) async fn run(storage: LogStorage, config: OtlpTracesConfig) {
+    let client = reqwest::Client::new();
+    let mut completed = storage.spans().subscribe_completed();
+
+    loop {
+        let mut ids = Vec::new();
+        let mut skipped = 0;
+        match completed.recv().await {
+            Ok(id) => ids.push(id),
+            Err(RecvError::Lagged(count)) => skipped += count,
+            Err(RecvError::Closed) => return,
+        }
+        let deadline = tokio::time::Instant::now() + BATCH_DELAY;
+        while ids.len() < MAX_BATCH_TREES {
+            match tokio::time::timeout_at(deadline, completed.recv()).await {
+                Ok(Ok(id)) => ids.push(id),
+                Ok(Err(RecvError::Lagged(count))) => skipped += count,
+                Ok(Err(RecvError::Closed)) | Err(_) => break,
+            }
+        }
+
+        if skipped > 0 {
+            storage.diagnose(LogEvent::internal(
+                "WARN",
+                "OTLP trace export fell behind, traces were skipped",
+                HashMap::from([("skipped".to_string(), skipped.to_string())]),
+            ));
+        }
+        let trees: Vec<SpanNode> = ids
+            .iter()
+            .filter_map(|id| storage.spans().tree(id))
+            .collect();
+        if trees.is_empty() {
+            continue;
+        }
+
+        let mut request = client
+            .post(&config.endpoint)
+            .timeout(EXPORT_TIMEOUT)
+            .json(&export_request(&trees, &config.service_name));
+        for (name, value) in &config.headers {
+            request = request.header(name, value);
+        }
+        let sent = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = sent {
+            storage.diagnose(LogEvent::internal(
+                "WARN",
+                "Failed to export spans to OTLP collector",
+                HashMap::from([
+                    ("error".to_string(), e.to_string()),
+                    ("traces".to_string(), trees.len().to_string()),
+                ]),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spans::SpanRecord;
+
+    fn span(id: &str, parent_id: Option<&str>, closed: bool) -> SpanRecord {
+        let started_at = DateTime::from_timestamp(1_700_000_000, 500).unwrap();
+        SpanRecord {
+            id: id.to_string(),
+            parent_id: parent_id.map(str::to_string),
+            name: "work".to_string(),
+            target: "app::db".to_string(),
+            level: "INFO".to_string(),
+            fields: HashMap::from([("table".to_string(), "orders".to_string())]),
+            started_at,
+            closed_at: closed.then(|| started_at + chrono::Duration::milliseconds(3)),
+            duration_us: closed.then_some(3_000),
+            busy_us: 1_200,
+            events: 0,
+        }
+    }
+
+    #[test]
+    fn test_tree_as_otlp_trace() {
+        let root = "01HZX3JQ8E4N2B7YV0C5T6W9AA";
+        let child = "01HZX3JQ8E4N2B7YV0C5T6W9AB";
+        let open = "01HZX3JQ8E4N2B7YV0C5T6W9AC";
+        let tree = SpanNode {
+            span: span(root, None, true),
+            children: vec![
+                SpanNode {
+                    span: span(child, Some(root), true),
+                    children: Vec::new(),
+                },
+                SpanNode {
+                    span: span(open, Some(root), false),
+                    children: Vec::new(),
+                },
+            ],
+        };
+
+        let request = export_request(&[tree], "orders-api");
+        let resource = &request["resourceSpans"][0];
+        assert_eq!(
+            resource["resource"]["attributes"][0]["value"]["stringValue"],
+            "orders-api"
+        );
+        let spans = resource["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 2);
+
+        let trace_id = format!("{:032x}", crate::id::decode(root).unwrap());
+        assert_eq!(spans[0]["traceId"], trace_id.as_str());
+        assert_eq!(spans[1]["traceId"], trace_id.as_str());
+        assert!(spans[0].get("parentSpanId").is_none());
+        assert_eq!(spans[1]["parentSpanId"], spans[0]["spanId"]);
+        assert_ne!(spans[0]["spanId"], spans[1]["spanId"]);
+        assert_eq!(spans[0]["startTimeUnixNano"], "1700000000000000500");
+        assert_eq!(spans[0]["endTimeUnixNano"], "1700000000003000500");
+        assert_eq!(spans[0]["attributes"][0]["key"], "table");
+    }
+
+    #[test]
+    fn test_event_as_log_record_in_trace() {
+        use crate::spans::SpanStore;
+        use crate::storage::SpanInfo;
+
+        let root = "01HZX3JQ8E4N2B7YV0C5T6W9AA";
+        let child = "01HZX3JQ8E4N2B7YV0C5T6W9AB";
+        let spans = SpanStore::default();
+        spans.open(span(root, None, false));
+        spans.open(span(child, Some(root), false));
+
+        let event = LogEvent {
+            target: "app::db".into(),
+            span: Some(SpanInfo {
+                id: Some(child.to_string()),
+                name: "work".to_string(),
+                fields: HashMap::new(),
+                ancestors: Vec::new(),
+                parent_fields: HashMap::new(),
+            }),
+            line: Some(42),
+            ..LogEvent::internal("WARN", "slow query", HashMap::new())
+        };
+        let request = export_logs_request(&[event], &spans, "orders-api");
+        let record = &request["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(record["severityNumber"], 13);
+        assert_eq!(record["body"]["stringValue"], "slow query");
+        assert_eq!(record["traceId"], trace_id(root).unwrap().as_str());
+        assert_eq!(record["spanId"], span_id(child).unwrap().as_str());
+    }
+}
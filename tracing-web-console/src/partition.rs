@@ -0,0 +1,163 @@
+//! Fair eviction across tenants sharing one buffer
+//!
+//! With a partition field such as `tenant_id`, events are grouped by its value
+//! (from the event's fields or its span's). A full buffer then evicts the
+//! oldest event of the partition holding the most events instead of the
+//! oldest event overall, so a noisy tenant only pushes out its own events.
+
+use crate::storage::{field_text, LogEvent};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+
+/// Value of the partition field of an event, from its fields or its span's
+pub(crate) fn partition_of<'a>(event: &'a LogEvent, field: &str) -> Option<Cow<'a, str>> {
+    event.fields.get(field).map(field_text).or_else(|| {
+        event
+            .span
+            .as_ref()
+            .and_then(|span| span.field(field))
+            .map(Cow::Borrowed)
+    })
+}
+
+/// Stored events of one partition
+#[derive(Debug, Clone, Serialize)]
+pub struct PartitionCount {
+    /// Value of the partition field, empty for events without one
+    pub partition: String,
+    pub events: usize,
+}
+
+/// Stored events per partition, kept in sync with the buffer
+#[derive(Debug, Default)]
+pub(crate) struct Partitions {
+    field: Option<String>,
+    /// Events without a value of the field are counted under ""
+    counts: HashMap<String, usize>,
+}
+
+impl Partitions {
+    /// Field events are partitioned by, `None` if partitioning is off
+    pub fn field(&self) -> Option<&str> {
+        self.field.as_deref()
+    }
+
+    /// Partition by `field`, counting the events already stored
+    pub fn set_field(&mut self, field: Option<String>, events: &VecDeque<LogEvent>) {
+        self.field = field;
+        self.rebuild(events);
+    }
+
+    fn key<'a>(&self, event: &'a LogEvent) -> Option<Cow<'a, str>> {
+        let field = self.field.as_deref()?;
+        Some(partition_of(event, field).unwrap_or(Cow::Borrowed("")))
+    }
+
+    /// Count a stored event
+    pub fn insert(&mut self, event: &LogEvent) {
+        if let Some(key) = self.key(event) {
+            *self.counts.entry(key.into_owned()).or_default() += 1;
+        }
+    }
+
+    /// Stop counting an event removed other than by `evict`
+    pub fn remove(&mut self, event: &LogEvent) {
+        let Some(key) = self.key(event) else {
+            return;
+        };
+        if let Some(count) = self.counts.get_mut(key.as_ref()) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(key.as_ref());
+            }
+        }
+    }
+
+    /// Recount after events were removed other than by `evict`
+    pub fn rebuild(&mut self, events: &VecDeque<LogEvent>) {
+        self.counts.clear();
+        if self.field.is_some() {
+            for event in events {
+                self.insert(event);
+            }
+        }
+    }
+
+    /// Remove the event making room for a new one: the oldest event of the
+    /// largest partition, or the oldest event without partitioning
+    pub fn evict(&mut self, events: &mut VecDeque<LogEvent>) -> Option<LogEvent> {
+        // Ties go to the smallest value, so eviction doesn't depend on hash order
+        let largest = self
+            .counts
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(partition, _)| partition.clone());
+        let index = largest
+            .and_then(|largest| {
+                events
+                    .iter()
+                    .position(|event| self.key(event).is_some_and(|key| key == largest.as_str()))
+            })
+            .unwrap_or(0);
+        let event = events.remove(index)?;
+        self.remove(&event);
+        Some(event)
+    }
+
+    /// Stored events per partition, largest first
+    pub fn counts(&self) -> Vec<PartitionCount> {
+        let mut counts: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(partition, events)| PartitionCount {
+                partition: partition.clone(),
+                events: *events,
+            })
+            .collect();
+        counts.sort_by(|a, b| {
+            b.events
+                .cmp(&a.events)
+                .then_with(|| a.partition.cmp(&b.partition))
+        });
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(tenant: &str) -> LogEvent {
+        LogEvent::internal(
+            "INFO",
+            "request",
+            HashMap::from([("tenant_id".to_string(), tenant.to_string())]),
+        )
+    }
+
+    #[test]
+    fn test_largest_partition_is_evicted() {
+        let mut events = VecDeque::new();
+        let mut partitions = Partitions::default();
+        partitions.set_field(Some("tenant_id".to_string()), &events);
+        for tenant in ["quiet", "noisy", "noisy", "noisy", "other"] {
+            let event = event(tenant);
+            partitions.insert(&event);
+            events.push_back(event);
+        }
+
+        let evicted = partitions.evict(&mut events).unwrap();
+        assert_eq!(evicted.fields["tenant_id"], "noisy");
+        assert_eq!(events[0].fields["tenant_id"], "quiet");
+        let counts = partitions.counts();
+        assert_eq!(counts[0].partition, "noisy");
+        assert_eq!(counts[0].events, 2);
+
+        // Without partitioning the oldest event goes
+        partitions.set_field(None, &events);
+        assert!(partitions.counts().is_empty());
+        let evicted = partitions.evict(&mut events).unwrap();
+        assert_eq!(evicted.fields["tenant_id"], "quiet");
+    }
+}
@@ -0,0 +1,215 @@
+//! Sharded queues of events waiting to be stored
+//!
+//! Storing an event needs the buffer's write lock. Under chatty tracing many
+//! threads would queue up on it, each storing a single event. Instead threads
+//! append to one of several queues, picked per thread, and whichever thread
+//! gets the lock stores the events of all queues in one go. The others find
+//! their event already stored without taking the lock, and wait until it was
+//! also passed to the sinks.
+
+use crate::storage::LogEvent;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::Thread;
+
+/// Number of queues, threads beyond that share them
+const SHARDS: usize = 16;
+
+/// A thread whose event may be stored by another thread
+pub(crate) struct Waiter {
+    /// Whether the sinks received the thread's last queued event
+    delivered: AtomicBool,
+    thread: Thread,
+}
+
+impl Waiter {
+    /// Mark the event as delivered and wake the thread
+    pub fn notify(&self) {
+        self.delivered.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+}
+
+/// Threads to notify once their events were passed to the sinks
+///
+/// Notifies them when dropped, so they are released even if storing the
+/// events or a sink panics.
+#[derive(Default)]
+pub(crate) struct Waiters(Vec<Arc<Waiter>>);
+
+impl Drop for Waiters {
+    fn drop(&mut self) {
+        for waiter in self.0.drain(..) {
+            waiter.notify();
+        }
+    }
+}
+
+thread_local! {
+    /// A thread has at most one event queued at a time, a push from within a
+    /// sink runs after its own event was taken
+    static WAITER: Arc<Waiter> = Arc::new(Waiter {
+        delivered: AtomicBool::new(true),
+        thread: std::thread::current(),
+    });
+}
+
+/// Queue of one group of threads
+#[derive(Default)]
+struct Shard {
+    events: Mutex<Vec<(LogEvent, Arc<Waiter>)>>,
+    /// Events appended so far, changed while holding `events`
+    enqueued: AtomicU64,
+    /// Events taken out so far, changed while holding `events`
+    taken: AtomicU64,
+}
+
+/// Position of an event in its queue, see `PendingShards::is_taken`
+pub(crate) struct Ticket {
+    shard: usize,
+    position: u64,
+    waiter: Arc<Waiter>,
+}
+
+impl Ticket {
+    /// Block until the event was passed to the sinks by whoever stored it
+    pub fn wait_delivered(&self) {
+        while !self.waiter.delivered.load(Ordering::Acquire) {
+            // Wakes up on `notify`, spurious wakeups check again
+            std::thread::park();
+        }
+    }
+}
+
+/// Queues of events waiting for the buffer's write lock
+pub(crate) struct PendingShards {
+    shards: Box<[Shard]>,
+}
+
+impl Default for PendingShards {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Shard::default()).collect(),
+        }
+    }
+}
+
+/// Queue used by the current thread, assigned round-robin on first use
+fn thread_shard() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static SHARD: usize = NEXT.fetch_add(1, Ordering::Relaxed) % SHARDS;
+    }
+    SHARD.with(|shard| *shard)
+}
+
+impl PendingShards {
+    /// Append an event to the current thread's queue
+    pub fn enqueue(&self, event: LogEvent) -> Ticket {
+        let waiter = WAITER.with(Arc::clone);
+        waiter.delivered.store(false, Ordering::Relaxed);
+        let index = thread_shard();
+        let shard = &self.shards[index];
+        let mut events = shard.events.lock();
+        events.push((event, waiter.clone()));
+        let position = shard.enqueued.fetch_add(1, Ordering::Relaxed) + 1;
+        Ticket {
+            shard: index,
+            position,
+            waiter,
+        }
+    }
+
+    /// Whether the event of `ticket` was taken out to be stored
+    pub fn is_taken(&self, ticket: &Ticket) -> bool {
+        self.shards[ticket.shard].taken.load(Ordering::Acquire) >= ticket.position
+    }
+
+    /// Take all waiting events, each thread's events in the order they were
+    /// appended, and the threads to notify once the sinks have them
+    ///
+    /// Called while holding the buffer's write lock, so events are taken in
+    /// the order they are stored.
+    pub fn take_all(&self, taken: &mut Vec<LogEvent>, waiters: &mut Waiters) {
+        for shard in self.shards.iter() {
+            let mut events = shard.events.lock();
+            if events.is_empty() {
+                continue;
+            }
+            for (event, waiter) in events.drain(..) {
+                taken.push(event);
+                waiters.0.push(waiter);
+            }
+            let enqueued = shard.enqueued.load(Ordering::Relaxed);
+            shard.taken.store(enqueued, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn event(message: &str) -> LogEvent {
+        LogEvent::internal("INFO", message, HashMap::new())
+    }
+
+    #[test]
+    fn test_tickets_are_taken_in_order() {
+        let pending = PendingShards::default();
+        let first = pending.enqueue(event("first"));
+        let second = pending.enqueue(event("second"));
+        assert!(!pending.is_taken(&first));
+
+        let mut taken = Vec::new();
+        let mut waiters = Waiters::default();
+        pending.take_all(&mut taken, &mut waiters);
+        assert!(pending.is_taken(&first) && pending.is_taken(&second));
+        let messages: Vec<_> = taken.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["first", "second"]);
+        assert_eq!(waiters.0.len(), 2);
+
+        let third = pending.enqueue(event("third"));
+        assert!(!pending.is_taken(&third));
+        taken.clear();
+        pending.take_all(&mut taken, &mut waiters);
+        assert_eq!(taken.len(), 1);
+        assert!(pending.is_taken(&third));
+    }
+
+    #[test]
+    fn test_waits_until_delivered() {
+        let pending = Arc::new(PendingShards::default());
+        let ticket = pending.enqueue(event("first"));
+        let combiner = {
+            let pending = pending.clone();
+            std::thread::spawn(move || {
+                let (mut taken, mut waiters) = (Vec::new(), Waiters::default());
+                pending.take_all(&mut taken, &mut waiters);
+                drop(waiters);
+                taken.len()
+            })
+        };
+        ticket.wait_delivered();
+        assert!(pending.is_taken(&ticket));
+        assert_eq!(combiner.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_waiters_are_released_on_panic() {
+        let pending = Arc::new(PendingShards::default());
+        let ticket = pending.enqueue(event("first"));
+        let combiner = {
+            let pending = pending.clone();
+            std::thread::spawn(move || {
+                let (mut taken, mut waiters) = (Vec::new(), Waiters::default());
+                pending.take_all(&mut taken, &mut waiters);
+                panic!("sink failed");
+            })
+        };
+        ticket.wait_delivered();
+        assert!(combiner.join().is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Server-rendered terminal-style log page that works without the JS bundle
+
+use crate::api::error::ApiError;
+use crate::cancel::CancelToken;
+use crate::frontend::FrontendState;
+use crate::storage::{field_text, LogFilter, SortOrder};
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use std::fmt::Write;
+
+/// Default number of events shown on the page
+const DEFAULT_LIMIT: usize = 200;
+/// Hard upper bound so the page stays renderable
+const MAX_LIMIT: usize = 2_000;
+/// Default auto-refresh interval in seconds
+const DEFAULT_REFRESH_SECS: u64 = 5;
+
+/// Query parameters for GET /plain
+#[derive(Debug, Default, Deserialize)]
+pub struct PlainQuery {
+    /// Minimum level (e.g. "info")
+    pub level: Option<String>,
+    /// Target filter (case-insensitive contains)
+    pub target: Option<String>,
+    /// Message search (case-insensitive contains)
+    pub search: Option<String>,
+    /// Number of events to show
+    pub limit: Option<usize>,
+    /// Auto-refresh interval in seconds, 0 disables refreshing
+    pub refresh: Option<u64>,
+}
+
+/// Terminal colors per level, matching the classic ANSI palette
+fn level_color(level: &str) -> &'static str {
+    match level {
+        "ERROR" => "#ff5555",
+        "WARN" => "#f1fa8c",
+        "INFO" => "#50fa7b",
+        "DEBUG" => "#8be9fd",
+        _ => "#bfbfbf",
+    }
+}
+
+/// Escape text for safe inclusion in HTML
+pub(crate) fn html_escape(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// GET /plain - Recent filtered events rendered as a terminal-like HTML page
+pub async fn serve_plain(
+    State(state): State<FrontendState>,
+    Query(query): Query<PlainQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if state.logs.access.is_enforced() && state.logs.access.role_of(&headers).is_none() {
+        return ApiError::Unauthorized("missing or invalid access token".to_string())
+            .into_response();
+    }
+    let filter = LogFilter {
+        global_level: query
+            .level
+            .as_ref()
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_uppercase()),
+        search: query.search.clone().filter(|s| !s.is_empty()),
+        target: query.target.clone().filter(|t| !t.is_empty()),
+        sort_order: SortOrder::NewestFirst,
+        ..Default::default()
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let refresh = query.refresh.unwrap_or(DEFAULT_REFRESH_SECS);
+
+    let storage = state.storage.clone();
+    let query =
+        move |cancel: &CancelToken| storage.query_cancellable(&filter, Some(limit), None, cancel);
+    let (events, total) = match crate::api::cancellable(query).await {
+        Ok(result) => (result.events, result.matched),
+        Err(response) => return response,
+    };
+
+    let mut rows = String::new();
+    // Oldest at the top, like a terminal
+    for event in events.iter().rev() {
+        let _ = write!(
+            rows,
+            "<span class=\"ts\">{}</span> <span style=\"color:{}\">{:>5}</span> <span class=\"target\">{}</span> {}",
+            event.timestamp.format("%H:%M:%S%.3f"),
+            level_color(&event.level),
+            html_escape(&event.level),
+            html_escape(&event.target),
+            html_escape(&event.message),
+        );
+        let mut fields: Vec<_> = event.fields.iter().collect();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in fields {
+            let _ = write!(
+                rows,
+                " <span class=\"field\">{}=</span>{}",
+                html_escape(key),
+                html_escape(&field_text(value))
+            );
+        }
+        rows.push('\n');
+    }
+
+    let refresh_tag = if refresh > 0 {
+        format!("<meta http-equiv=\"refresh\" content=\"{}\">", refresh)
+    } else {
+        String::new()
+    };
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    {refresh_tag}
+    <title>Tracing Console - Plain</title>
+    <style>
+        body {{ background: #1e1f29; color: #f8f8f2; font-family: ui-monospace, Menlo, Consolas, monospace; font-size: 13px; margin: 0; }}
+        header {{ padding: 0.5rem 1rem; background: #282a36; border-bottom: 1px solid #44475a; }}
+        form input, form select, form button {{ background: #1e1f29; color: #f8f8f2; border: 1px solid #44475a; font-family: inherit; padding: 0.2rem 0.4rem; }}
+        pre {{ margin: 0; padding: 0.5rem 1rem; white-space: pre-wrap; word-break: break-all; }}
+        .ts {{ color: #6272a4; }}
+        .target {{ color: #bd93f9; }}
+        .field {{ color: #ff79c6; }}
+        .meta {{ color: #6272a4; margin-left: 1rem; }}
+    </style>
+</head>
+<body>
+    <header>
+        <form method="get" action="{base}/plain">
+            <select name="level">
+                <option value="">all levels</option>
+                <option value="trace"{trace}>trace+</option>
+                <option value="debug"{debug}>debug+</option>
+                <option value="info"{info}>info+</option>
+                <option value="warn"{warn}>warn+</option>
+                <option value="error"{error}>error</option>
+            </select>
+            <input name="target" placeholder="target" value="{target}">
+            <input name="search" placeholder="search" value="{search}">
+            <input name="limit" type="number" min="1" max="{max_limit}" value="{limit}">
+            <input name="refresh" type="number" min="0" value="{refresh}" title="refresh seconds (0 = off)">
+            <button type="submit">apply</button>
+            <span class="meta">showing {shown} of {total} matching events</span>
+        </form>
+    </header>
+    <pre>{rows}</pre>
+</body>
+</html>
+"#,
+        refresh_tag = refresh_tag,
+        base = html_escape(&state.base_path),
+        trace = selected(query.level.as_deref(), "trace"),
+        debug = selected(query.level.as_deref(), "debug"),
+        info = selected(query.level.as_deref(), "info"),
+        warn = selected(query.level.as_deref(), "warn"),
+        error = selected(query.level.as_deref(), "error"),
+        target = html_escape(query.target.as_deref().unwrap_or("")),
+        search = html_escape(query.search.as_deref().unwrap_or("")),
+        max_limit = MAX_LIMIT,
+        limit = limit,
+        refresh = refresh,
+        shown = events.len(),
+        total = total,
+        rows = rows,
+    );
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(html))
+        .unwrap()
+}
+
+/// `selected` attribute for the level dropdown
+fn selected(current: Option<&str>, level: &str) -> &'static str {
+    if current.is_some_and(|c| c.eq_ignore_ascii_case(level)) {
+        " selected"
+    } else {
+        ""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_escape() {
+        assert_eq!(
+            html_escape("<script>alert(\"x\")</script> & 'y'"),
+            "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; &amp; &#39;y&#39;"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Named filter presets shared by everyone using the console
+
+use crate::expr::FilterExpr;
+use crate::storage::level_to_number;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A saved combination of filters, e.g. "payment errors"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterPreset {
+    /// Unique name shown in the UI
+    pub name: String,
+    /// Levels to show, all of them if empty
+    #[serde(default)]
+    pub levels: Vec<String>,
+    /// Minimum level per target prefix, e.g. `{"hyper": "WARN"}`
+    #[serde(default)]
+    pub target_levels: HashMap<String, String>,
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub search: Option<String>,
+    /// Filter expression such as `span:checkout AND level>=warn`
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+impl FilterPreset {
+    /// Preset with the given name that doesn't filter anything yet
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            levels: Vec::new(),
+            target_levels: HashMap::new(),
+            target: None,
+            search: None,
+            query: None,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        for level in self.levels.iter().chain(self.target_levels.values()) {
+            if level_to_number(level) == 0 {
+                return Err(format!("unknown level: {}", level));
+            }
+        }
+        if let Some(query) = self.query.as_deref() {
+            FilterExpr::parse(query).map_err(|e| format!("invalid query: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Presets in the order they were created
+#[derive(Clone, Default)]
+pub struct FilterPresets {
+    presets: Arc<RwLock<Vec<FilterPreset>>>,
+}
+
+impl FilterPresets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn list(&self) -> Vec<FilterPreset> {
+        self.presets.read().clone()
+    }
+
+    /// Add a preset or replace the one with the same name
+    pub fn upsert(&self, preset: FilterPreset) -> Result<FilterPreset, String> {
+        preset.validate()?;
+        let mut presets = self.presets.write();
+        match presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset.clone(),
+            None => presets.push(preset.clone()),
+        }
+        Ok(preset)
+    }
+
+    /// Delete a preset, returns whether it existed
+    pub fn remove(&self, name: &str) -> bool {
+        let mut presets = self.presets.write();
+        let before = presets.len();
+        presets.retain(|p| p.name != name);
+        presets.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_are_replaced_by_name() {
+        let presets = FilterPresets::new();
+        let mut errors = FilterPreset::new("payment errors");
+        errors.levels = vec!["ERROR".to_string()];
+        presets.upsert(errors.clone()).unwrap();
+        presets.upsert(FilterPreset::new("slow requests")).unwrap();
+
+        errors.target = Some("payments".to_string());
+        presets.upsert(errors.clone()).unwrap();
+        assert_eq!(presets.list()[0], errors);
+        assert_eq!(presets.list().len(), 2);
+
+        assert!(presets.remove("slow requests"));
+        assert!(!presets.remove("slow requests"));
+        assert_eq!(presets.list().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_presets_are_rejected() {
+        let presets = FilterPresets::new();
+        assert!(presets.upsert(FilterPreset::new(" ")).is_err());
+
+        let mut preset = FilterPreset::new("bad level");
+        preset.levels = vec!["loud".to_string()];
+        assert!(presets.upsert(preset).is_err());
+
+        let mut preset = FilterPreset::new("bad query");
+        preset.query = Some("level>=warn OR span:checkout".to_string());
+        assert!(presets.upsert(preset).is_err());
+        assert!(presets.list().is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+//! Cache of recent query results, invalidated whenever the buffer changes
+
+use crate::storage::{LogFilter, QueryResult};
+use parking_lot::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+/// Number of distinct queries remembered
+const MAX_ENTRIES: usize = 16;
+
+struct Entry {
+    key: u64,
+    generation: u64,
+    result: QueryResult,
+}
+
+/// Results of the most recent queries, keyed by filter and buffer generation
+///
+/// A UI polling the same filter while nothing is logged gets the cached
+/// result instead of a full buffer scan.
+#[derive(Default)]
+pub(crate) struct QueryCache {
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+impl QueryCache {
+    /// Hash a query, target levels are sorted so their order doesn't matter
+    pub fn key(filter: &LogFilter, limit: Option<usize>, offset: usize) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        filter.global_level.hash(&mut hasher);
+        let mut target_levels: Vec<_> = filter.target_levels.iter().collect();
+        target_levels.sort();
+        target_levels.hash(&mut hasher);
+        filter.search.hash(&mut hasher);
+        filter.target.hash(&mut hasher);
+        filter.sort_order.hash(&mut hasher);
+        filter.after_seq.hash(&mut hasher);
+        filter.before_seq.hash(&mut hasher);
+        filter.from.hash(&mut hasher);
+        filter.to.hash(&mut hasher);
+        filter.expr.as_ref().map(|e| e.source()).hash(&mut hasher);
+        filter.exclude_targets.hash(&mut hasher);
+        filter.exclude_search.hash(&mut hasher);
+        filter.exclude_levels.hash(&mut hasher);
+        filter.thread.hash(&mut hasher);
+        filter.partition.hash(&mut hasher);
+        limit.hash(&mut hasher);
+        offset.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Get a cached result computed at the given buffer generation
+    pub fn get(&self, key: u64, generation: u64) -> Option<QueryResult> {
+        self.entries
+            .lock()
+            .iter()
+            .find(|e| e.key == key && e.generation == generation)
+            .map(|e| e.result.clone())
+    }
+
+    /// Remember a result, evicting the oldest entry when full
+    pub fn insert(&self, key: u64, generation: u64, result: QueryResult) {
+        let mut entries = self.entries.lock();
+        entries.retain(|e| e.key != key);
+        if entries.len() >= MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(Entry {
+            key,
+            generation,
+            result,
+        });
+    }
+}
@@ -0,0 +1,97 @@
+//! Replay of stored events at their original pace
+//!
+//! A replay is prepared by `POST /api/replay` and streamed once over the
+//! WebSocket of `GET /api/replay/{id}/ws`, so an incident can be watched
+//! unfolding again with the live view's tools.
+
+use crate::storage::LogEvent;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Prepared replays not connected to within this time are dropped
+const UNCLAIMED_TTL: Duration = Duration::from_secs(5 * 60);
+/// Longest pause between two replayed events, quiet periods are shortened
+pub(crate) const MAX_GAP: Duration = Duration::from_secs(10);
+
+/// Events waiting to be streamed
+pub(crate) struct Replay {
+    /// Oldest first
+    pub events: Vec<LogEvent>,
+    /// Playback speed, 2.0 replays twice as fast
+    pub speed: f64,
+    created: Instant,
+}
+
+impl Replay {
+    pub fn new(events: Vec<LogEvent>, speed: f64) -> Self {
+        Self {
+            events,
+            speed,
+            created: Instant::now(),
+        }
+    }
+}
+
+/// Replays prepared but not yet streamed, by ID
+#[derive(Clone, Default)]
+pub(crate) struct Replays {
+    pending: Arc<Mutex<HashMap<String, Replay>>>,
+}
+
+impl Replays {
+    /// Store a replay, returns its ID
+    pub fn insert(&self, replay: Replay) -> String {
+        let id = crate::id::new_ulid();
+        let mut pending = self.pending.lock();
+        pending.retain(|_, r| r.created.elapsed() < UNCLAIMED_TTL);
+        pending.insert(id.clone(), replay);
+        id
+    }
+
+    /// Remove a replay to stream it, each replay streams once
+    pub fn take(&self, id: &str) -> Option<Replay> {
+        self.pending
+            .lock()
+            .remove(id)
+            .filter(|r| r.created.elapsed() < UNCLAIMED_TTL)
+    }
+}
+
+/// Pause before replaying `next` after `previous`
+pub(crate) fn delay(previous: &LogEvent, next: &LogEvent, speed: f64) -> Duration {
+    let gap = (next.timestamp - previous.timestamp)
+        .to_std()
+        .unwrap_or_default();
+    gap.div_f64(speed).min(MAX_GAP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_at(offset_ms: i64) -> LogEvent {
+        let mut event = LogEvent::internal("INFO", "tick", HashMap::new());
+        event.timestamp += chrono::Duration::milliseconds(offset_ms);
+        event
+    }
+
+    #[test]
+    fn test_delay_scales_and_caps() {
+        let (a, b) = (event_at(0), event_at(1_000));
+        assert_eq!(delay(&a, &b, 1.0), Duration::from_secs(1));
+        assert_eq!(delay(&a, &b, 4.0), Duration::from_millis(250));
+        // Out of order timestamps don't wait
+        assert_eq!(delay(&b, &a, 1.0), Duration::ZERO);
+        assert_eq!(delay(&a, &event_at(3_600_000), 1.0), MAX_GAP);
+    }
+
+    #[test]
+    fn test_replay_streams_once() {
+        let replays = Replays::default();
+        let id = replays.insert(Replay::new(vec![event_at(0)], 1.0));
+        assert_eq!(replays.take(&id).unwrap().events.len(), 1);
+        assert!(replays.take(&id).is_none());
+    }
+}
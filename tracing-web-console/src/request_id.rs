@@ -0,0 +1,287 @@
+//! Request ID and tracing middleware for the application's own routes
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use futures::future::BoxFuture;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{Layer, Service};
+use tracing::{Instrument, Span};
+
+/// Header carrying the request ID in both directions
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+/// Target of the request span, outside this crate so the capture filter keeps it
+const REQUEST_SPAN_TARGET: &str = "http";
+/// Longest incoming ID that is adopted, longer ones are replaced
+const MAX_ID_LEN: usize = 128;
+/// Route recorded for requests that didn't match any route
+const UNMATCHED_ROUTE: &str = "<unmatched>";
+
+/// ID of the current request, available as a request extension
+///
+/// ```rust,no_run
+/// use axum::Extension;
+/// use tracing_web_console::RequestId;
+///
+/// async fn handler(Extension(RequestId(id)): Extension<RequestId>) -> String {
+///     id
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Check that an incoming ID is safe to store and echo back
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+}
+
+/// Middleware assigning every request an ID
+///
+/// Adopts a valid incoming `X-Request-Id` header or generates a new ID, runs
+/// the request inside a span with a `request_id` field and echoes the ID in
+/// the response header. The field is promoted onto every event captured within
+/// the request, including events in nested spans, so it can be filtered on.
+///
+/// The span records the matched route template (`/api/users/{id}`) instead of
+/// the concrete path, so requests aggregate by endpoint and the number of
+/// distinct values stays bounded. Add the middleware with `Router::layer`,
+/// the route is only known after routing.
+///
+/// ```rust,no_run
+/// use axum::Router;
+///
+/// let app: Router = Router::new()
+///     // routes...
+///     .layer(axum::middleware::from_fn(tracing_web_console::request_id));
+/// ```
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let (span, id) = request_span(&mut request);
+    let mut response = next.run(request).instrument(span).await;
+    set_id_header(&mut response, &id);
+    response
+}
+
+/// Span of a request and its ID, which is added as a request extension
+fn request_span(request: &mut Request) -> (Span, String) {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(crate::id::new_ulid);
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE, MatchedPath::as_str);
+    let span = tracing::info_span!(
+        target: REQUEST_SPAN_TARGET,
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        route = %route,
+    );
+    request.extensions_mut().insert(RequestId(id.clone()));
+    (span, id)
+}
+
+fn set_id_header(response: &mut Response, id: &str) {
+    if let Ok(value) = HeaderValue::from_str(id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+}
+
+/// Tower layer tracing every request, created by `TracingLayer::http_trace_layer`
+///
+/// Does what the `request_id` middleware does and additionally records an
+/// event when the response is ready, with the concrete `path`, the `status`
+/// and the `latency_ms`. The event is logged at `ERROR` for server errors and
+/// at `INFO` otherwise. The path is a field of that event only, the request
+/// span keeps the route template so its fields stay low-cardinality.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpTraceLayer;
+
+impl<S> Layer<S> for HttpTraceLayer {
+    type Service = HttpTrace<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpTrace { inner }
+    }
+}
+
+/// Service created by `HttpTraceLayer`
+#[derive(Debug, Clone)]
+pub struct HttpTrace<S> {
+    inner: S,
+}
+
+impl<S> Service<Request> for HttpTrace<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let started = Instant::now();
+        let (span, id) = request_span(&mut request);
+        let path = request.uri().path().to_string();
+        // Call the service that was polled ready, keep a fresh clone for the next request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let future = span.in_scope(|| inner.call(request));
+
+        Box::pin(
+            async move {
+                let mut response = future.await?;
+                let status = response.status();
+                let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+                if status.is_server_error() {
+                    tracing::error!(
+                        target: REQUEST_SPAN_TARGET,
+                        path = %path,
+                        status = status.as_u16(),
+                        latency_ms,
+                        "request failed"
+                    );
+                } else {
+                    tracing::info!(
+                        target: REQUEST_SPAN_TARGET,
+                        path = %path,
+                        status = status.as_u16(),
+                        latency_ms,
+                        "request completed"
+                    );
+                }
+                set_id_header(&mut response, &id);
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::{Extension, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|Extension(RequestId(id)): Extension<RequestId>| async move { id }),
+            )
+            .route(
+                "/users/{id}",
+                get(|| async { tracing::info!(target: "app", "loading user") }),
+            )
+            .layer(axum::middleware::from_fn(request_id))
+    }
+
+    #[test]
+    fn test_is_valid_id() {
+        assert!(is_valid_id("5f0c6e1a-7b1d-4a4e-9d2c-3f1b2a9e8c7d"));
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("has space"));
+        assert!(!is_valid_id(&"a".repeat(MAX_ID_LEN + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_incoming_id_is_adopted() {
+        let request = Request::builder()
+            .uri("/")
+            .header("x-request-id", "abc-123")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()["x-request-id"], "abc-123");
+    }
+
+    #[tokio::test]
+    async fn test_span_records_route_template() {
+        use crate::storage::{LogFilter, LogStorage};
+        use crate::subscriber::LogCaptureLayer;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let request = Request::builder()
+            .uri("/users/42")
+            .body(Body::empty())
+            .unwrap();
+        app().oneshot(request).await.unwrap();
+
+        let (events, _) = storage.get_filtered(&LogFilter::default(), None, None);
+        let span = events[0].span.as_ref().unwrap();
+        assert_eq!(span.fields["route"], "/users/{id}");
+        assert!(!span.fields.contains_key("path"));
+    }
+
+    #[tokio::test]
+    async fn test_http_trace_records_completion() {
+        use crate::storage::{LogFilter, LogStorage};
+        use crate::subscriber::LogCaptureLayer;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route(
+                "/users/{id}",
+                get(|| async { tracing::info!(target: "app", "loading user") }),
+            )
+            .layer(HttpTraceLayer);
+        let request = Request::builder()
+            .uri("/users/42")
+            .header("x-request-id", "abc-123")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.headers()["x-request-id"], "abc-123");
+
+        let filter = LogFilter {
+            sort_order: crate::storage::SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let (events, _) = storage.get_filtered(&filter, None, None);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].fields["request_id"], "abc-123");
+        let completed = &events[1];
+        assert_eq!(completed.message, "request completed");
+        assert_eq!(completed.fields["path"], "/users/42");
+        assert_eq!(completed.fields["status"], 200);
+        assert!(completed.fields["latency_ms"].is_f64());
+        let span = completed.span.as_ref().unwrap();
+        assert_eq!(span.fields["route"], "/users/{id}");
+        assert_eq!(span.fields["method"], "GET");
+    }
+
+    #[tokio::test]
+    async fn test_missing_id_is_generated() {
+        let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        let id = response.headers()["x-request-id"].to_str().unwrap();
+        assert_eq!(id.len(), crate::id::ULID_LEN);
+    }
+}
@@ -0,0 +1,288 @@
+//! Expiry of events by age, and caps on the events kept per level
+//!
+//! Ages are checked by a background task, caps are applied as events are
+//! stored, see `LevelCaps`.
+
+use crate::storage::{level_to_number, LogEvent, LogStorage};
+use chrono::Utc;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tracing::Level;
+
+/// Bounds of the expiry check interval, derived from the shortest TTL
+const MIN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum age of events by level, levels without a TTL are only evicted by capacity
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LevelTtls {
+    /// TTL by `level_to_number`
+    ttls: HashMap<u8, Duration>,
+    /// Maximum age of all events, shortens longer TTLs
+    max_age: Option<Duration>,
+}
+
+impl LevelTtls {
+    /// Set the TTL of a level, returns false for unknown levels
+    pub fn set(&mut self, level: &str, ttl: Duration) -> bool {
+        match level_to_number(level) {
+            0 => false,
+            number => {
+                self.ttls.insert(number, ttl);
+                true
+            }
+        }
+    }
+
+    /// Expire events of all levels once they are older than `max_age`
+    pub fn set_max_age(&mut self, max_age: Duration) {
+        self.max_age = Some(max_age);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ttls.is_empty() && self.max_age.is_none()
+    }
+
+    /// TTL of a level by `level_to_number`
+    fn ttl(&self, level: u8) -> Option<Duration> {
+        match (self.ttls.get(&level), self.max_age) {
+            (Some(ttl), Some(max_age)) => Some((*ttl).min(max_age)),
+            (ttl, max_age) => ttl.copied().or(max_age),
+        }
+    }
+
+    /// Check often enough that events don't outlive their TTL by more than a tenth
+    fn check_interval(&self) -> Duration {
+        let shortest = self
+            .ttls
+            .values()
+            .chain(&self.max_age)
+            .min()
+            .copied()
+            .unwrap_or(MAX_CHECK_INTERVAL);
+        (shortest / 10).clamp(MIN_CHECK_INTERVAL, MAX_CHECK_INTERVAL)
+    }
+
+    /// Remove expired events, returns how many were removed
+    pub fn expire(&self, storage: &LogStorage) -> usize {
+        let now = Utc::now();
+        storage.retain(|event| {
+            let Some(ttl) = self.ttl(level_to_number(&event.level)) else {
+                return true;
+            };
+            match chrono::Duration::from_std(ttl) {
+                Ok(ttl) => now - event.timestamp <= ttl,
+                // A TTL too large for chrono never expires
+                Err(_) => true,
+            }
+        })
+    }
+}
+
+/// Limits on what the buffer keeps besides its capacity
+///
+/// Capping verbose levels leaves room for the events that matter, so a burst
+/// of TRACE output doesn't push the last ERROR out of the buffer.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Events older than this expire whatever their level, shortening longer
+    /// level TTLs
+    pub max_age: Option<Duration>,
+    /// Most events kept per level, storing an event of a level at its cap
+    /// evicts the oldest event of that level
+    pub per_level_caps: HashMap<Level, usize>,
+}
+
+/// Stored events per capped level, kept in sync with the buffer
+///
+/// Caps are applied while storing, like partition eviction: storing an event
+/// of a level at its cap evicts the oldest event of that level instead of the
+/// oldest event overall.
+#[derive(Debug, Default)]
+pub(crate) struct LevelCaps {
+    /// Cap by `level_to_number`
+    caps: HashMap<u8, usize>,
+    /// Stored events of the capped levels
+    counts: HashMap<u8, usize>,
+}
+
+impl LevelCaps {
+    /// Cap the given levels, removing the oldest stored events of levels
+    /// already over their cap, returns how many were removed
+    pub fn set(&mut self, caps: &HashMap<Level, usize>, events: &mut VecDeque<LogEvent>) -> usize {
+        self.caps = caps
+            .iter()
+            .map(|(level, cap)| (level_to_number(level.as_str()), (*cap).max(1)))
+            .collect();
+        self.rebuild(events);
+
+        let mut excess: HashMap<u8, usize> = self
+            .counts
+            .iter()
+            .filter(|(level, count)| **count > self.caps[*level])
+            .map(|(level, count)| (*level, count - self.caps[level]))
+            .collect();
+        if excess.is_empty() {
+            return 0;
+        }
+        let before = events.len();
+        events.retain(
+            |event| match excess.get_mut(&level_to_number(&event.level)) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            },
+        );
+        self.rebuild(events);
+        before - events.len()
+    }
+
+    /// Capped level of an event
+    fn capped(&self, event: &LogEvent) -> Option<u8> {
+        if self.caps.is_empty() {
+            return None;
+        }
+        let level = level_to_number(&event.level);
+        self.caps.contains_key(&level).then_some(level)
+    }
+
+    /// Count a stored event
+    pub fn insert(&mut self, event: &LogEvent) {
+        if let Some(level) = self.capped(event) {
+            *self.counts.entry(level).or_default() += 1;
+        }
+    }
+
+    /// Stop counting a removed event
+    pub fn remove(&mut self, event: &LogEvent) {
+        if let Some(level) = self.capped(event) {
+            if let Some(count) = self.counts.get_mut(&level) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Recount after events were removed other than by `evict`
+    pub fn rebuild(&mut self, events: &VecDeque<LogEvent>) {
+        self.counts.clear();
+        if !self.caps.is_empty() {
+            for event in events {
+                self.insert(event);
+            }
+        }
+    }
+
+    /// Remove the oldest event of the level of `event` if storing `event`
+    /// would exceed the level's cap
+    pub fn evict(&mut self, event: &LogEvent, events: &mut VecDeque<LogEvent>) -> Option<LogEvent> {
+        let level = self.capped(event)?;
+        if self.counts.get(&level).copied().unwrap_or(0) < self.caps[&level] {
+            return None;
+        }
+        let index = events
+            .iter()
+            .position(|stored| level_to_number(&stored.level) == level)?;
+        let evicted = events.remove(index)?;
+        self.remove(&evicted);
+        Some(evicted)
+    }
+}
+
+/// Expire events forever
+pub(crate) async fn run(storage: LogStorage, ttls: LevelTtls) {
+    let mut interval = tokio::time::interval(ttls.check_interval());
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        // A paused buffer is kept as evidence
+        if storage.is_paused() {
+            continue;
+        }
+        let storage = storage.clone();
+        let ttls = ttls.clone();
+        // Expiring rewrites the buffer under the write lock, keep it off the async workers
+        let _ = tokio::task::spawn_blocking(move || ttls.expire(&storage)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LogFilter;
+
+    fn event(level: &str, age: chrono::Duration) -> LogEvent {
+        LogEvent {
+            timestamp: Utc::now() - age,
+            ..LogEvent::internal(level, level, HashMap::new())
+        }
+    }
+
+    #[test]
+    fn test_expire_by_level() {
+        let storage = LogStorage::new();
+        storage.push(event("TRACE", chrono::Duration::minutes(11)));
+        storage.push(event("TRACE", chrono::Duration::minutes(1)));
+        storage.push(event("ERROR", chrono::Duration::hours(2)));
+        storage.push(event("INFO", chrono::Duration::days(30)));
+
+        let mut ttls = LevelTtls::default();
+        assert!(ttls.set("trace", Duration::from_secs(600)));
+        assert!(ttls.set("ERROR", Duration::from_secs(24 * 3600)));
+        assert!(!ttls.set("loud", Duration::from_secs(1)));
+
+        assert_eq!(ttls.expire(&storage), 1);
+        let (_, count) = storage.get_filtered(&LogFilter::default(), None, None);
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_max_age_shortens_level_ttls() {
+        let storage = LogStorage::new();
+        storage.push(event("ERROR", chrono::Duration::minutes(30)));
+        storage.push(event("ERROR", chrono::Duration::days(2)));
+        storage.push(event("TRACE", chrono::Duration::minutes(20)));
+
+        let mut ttls = LevelTtls::default();
+        ttls.set("ERROR", Duration::from_secs(7 * 24 * 3600));
+        ttls.set("TRACE", Duration::from_secs(600));
+        ttls.set_max_age(Duration::from_secs(24 * 3600));
+        assert_eq!(ttls.expire(&storage), 2);
+
+        let (events, _) = storage.get_filtered(&LogFilter::default(), None, None);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, "ERROR");
+    }
+
+    #[test]
+    fn test_level_caps_apply_while_storing() {
+        let storage = LogStorage::with_capacity(4);
+        storage.set_level_caps(&HashMap::from([(Level::TRACE, 2)]));
+        storage.push(event("ERROR", chrono::Duration::zero()));
+        for i in 0..4 {
+            storage.push(LogEvent {
+                message: format!("trace {}", i),
+                ..event("TRACE", chrono::Duration::zero())
+            });
+        }
+        storage.push(event("INFO", chrono::Duration::zero()));
+
+        // Without the cap the TRACE events would have pushed the ERROR out
+        let (events, _) = storage.get_filtered(&LogFilter::default(), None, None);
+        let messages: Vec<_> = events.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["INFO", "trace 3", "trace 2", "ERROR"]);
+    }
+
+    #[test]
+    fn test_check_interval() {
+        let mut ttls = LevelTtls::default();
+        ttls.set("TRACE", Duration::from_secs(600));
+        assert_eq!(ttls.check_interval(), Duration::from_secs(60));
+        ttls.set("DEBUG", Duration::from_secs(30));
+        assert_eq!(ttls.check_interval(), Duration::from_secs(3));
+        ttls.set_max_age(Duration::from_secs(20));
+        assert_eq!(ttls.check_interval(), Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,281 @@
+//! Memory-mapped ring file keeping the hot buffer across restarts
+//!
+//! File layout, all integers little-endian:
+//!
+//! ```text
+//! 0..8    magic "TWCRING1"
+//! 8..16   data capacity in bytes
+//! 16..24  head, total bytes ever written (monotonic)
+//! 24..32  tail, offset of the oldest record (monotonic)
+//! 32..64  reserved
+//! 64..    data region, records wrap around at the end
+//! ```
+//!
+//! A record is a u32 length followed by the event as JSON. Offsets are taken
+//! modulo the capacity, so external tools can read the records between tail
+//! and head while the process is running.
+
+use crate::sink::EventSink;
+use crate::storage::LogEvent;
+use memmap2::MmapMut;
+use parking_lot::Mutex;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"TWCRING1";
+const HEADER_LEN: usize = 64;
+const CAPACITY_OFFSET: usize = 8;
+const HEAD_OFFSET: usize = 16;
+const TAIL_OFFSET: usize = 24;
+/// Length prefix of each record
+const LEN_PREFIX: u64 = 4;
+/// Smallest useful ring, anything less can't hold a typical event
+const MIN_SIZE: usize = 4 * 1024;
+
+struct RingFile {
+    map: MmapMut,
+    capacity: u64,
+    head: u64,
+    tail: u64,
+}
+
+impl RingFile {
+    fn read_u64(&self, offset: usize) -> u64 {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&self.map[offset..offset + 8]);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn write_u64(&mut self, offset: usize, value: u64) {
+        self.map[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Copy bytes into the data region starting at a monotonic offset
+    fn write_at(&mut self, offset: u64, bytes: &[u8]) {
+        let capacity = self.capacity as usize;
+        let pos = (offset % self.capacity) as usize;
+        let first = bytes.len().min(capacity - pos);
+        self.map[HEADER_LEN + pos..HEADER_LEN + pos + first].copy_from_slice(&bytes[..first]);
+        if first < bytes.len() {
+            let rest = bytes.len() - first;
+            self.map[HEADER_LEN..HEADER_LEN + rest].copy_from_slice(&bytes[first..]);
+        }
+    }
+
+    /// Copy bytes out of the data region starting at a monotonic offset
+    fn read_at(&self, offset: u64, buf: &mut [u8]) {
+        let capacity = self.capacity as usize;
+        let pos = (offset % self.capacity) as usize;
+        let first = buf.len().min(capacity - pos);
+        buf[..first].copy_from_slice(&self.map[HEADER_LEN + pos..HEADER_LEN + pos + first]);
+        if first < buf.len() {
+            let rest = buf.len() - first;
+            buf[first..].copy_from_slice(&self.map[HEADER_LEN..HEADER_LEN + rest]);
+        }
+    }
+
+    fn record_len(&self, offset: u64) -> u64 {
+        let mut len = [0u8; LEN_PREFIX as usize];
+        self.read_at(offset, &mut len);
+        u32::from_le_bytes(len) as u64
+    }
+
+    /// Start over with an empty ring
+    fn reset(&mut self) {
+        self.map[..HEADER_LEN].fill(0);
+        self.map[..MAGIC.len()].copy_from_slice(MAGIC);
+        self.write_u64(CAPACITY_OFFSET, self.capacity);
+        self.head = 0;
+        self.tail = 0;
+    }
+
+    /// Append a record, records too large for the ring or the length prefix
+    /// are skipped
+    fn append(&mut self, payload: &[u8]) {
+        // A length that doesn't fit the prefix would corrupt the ring
+        let Ok(len) = u32::try_from(payload.len()) else {
+            return;
+        };
+        let total = LEN_PREFIX + len as u64;
+        if total > self.capacity {
+            return;
+        }
+
+        // Evict the oldest records until the new one fits
+        let mut tail = self.tail;
+        while self.head + total - tail > self.capacity {
+            let next = tail + LEN_PREFIX + self.record_len(tail);
+            if next > self.head {
+                // Corrupt length, drop everything
+                tail = self.head;
+                break;
+            }
+            tail = next;
+        }
+        // Persist the new tail before overwriting, so a crash mid-write never
+        // leaves the header pointing at a half overwritten record
+        if tail != self.tail {
+            self.tail = tail;
+            self.write_u64(TAIL_OFFSET, tail);
+        }
+
+        let head = self.head;
+        self.write_at(head, &len.to_le_bytes());
+        self.write_at(head + LEN_PREFIX, payload);
+        self.head = head + total;
+        self.write_u64(HEAD_OFFSET, self.head);
+    }
+}
+
+/// Fixed-size ring file mirroring every stored event
+///
+/// Writes only copy into the page cache, the OS persists them even if the
+/// process crashes.
+pub struct MmapRing {
+    file: Mutex<RingFile>,
+}
+
+impl MmapRing {
+    /// Open or create a ring file of `size` bytes
+    ///
+    /// An existing file with a different size or an invalid header is reset.
+    pub fn open(path: &Path, size: usize) -> io::Result<Self> {
+        if size < MIN_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("ring file size must be at least {} bytes", MIN_SIZE),
+            ));
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        file.set_len(size as u64)?;
+        // SAFETY: the file is only modified through this mapping. Other
+        // processes may read it, but concurrent writers are not supported.
+        let map = unsafe { MmapMut::map_mut(&file)? };
+
+        let mut ring = RingFile {
+            map,
+            capacity: (size - HEADER_LEN) as u64,
+            head: 0,
+            tail: 0,
+        };
+        let head = ring.read_u64(HEAD_OFFSET);
+        let tail = ring.read_u64(TAIL_OFFSET);
+        let valid = &ring.map[..MAGIC.len()] == MAGIC
+            && ring.read_u64(CAPACITY_OFFSET) == ring.capacity
+            && tail <= head
+            && head - tail <= ring.capacity;
+        if valid {
+            ring.head = head;
+            ring.tail = tail;
+        } else {
+            ring.reset();
+        }
+
+        Ok(Self {
+            file: Mutex::new(ring),
+        })
+    }
+
+    /// Read all events in the ring, oldest first
+    ///
+    /// Records that fail to parse are skipped.
+    pub fn read_all(&self) -> Vec<LogEvent> {
+        let ring = self.file.lock();
+        let mut events = Vec::new();
+        let mut offset = ring.tail;
+
+        while offset + LEN_PREFIX <= ring.head {
+            let len = ring.record_len(offset);
+            if offset + LEN_PREFIX + len > ring.head {
+                break;
+            }
+            let mut payload = vec![0u8; len as usize];
+            ring.read_at(offset + LEN_PREFIX, &mut payload);
+            if let Ok(event) = serde_json::from_slice(&payload) {
+                events.push(event);
+            }
+            offset += LEN_PREFIX + len;
+        }
+        events
+    }
+}
+
+impl EventSink for MmapRing {
+    fn write(&self, event: &LogEvent) {
+        if let Ok(payload) = serde_json::to_vec(event) {
+            self.file.lock().append(&payload);
+        }
+    }
+
+    fn flush(&self) {
+        if let Err(e) = self.file.lock().map.flush() {
+            tracing::warn!("Failed to flush ring file: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("twc-ring-{}", crate::id::new_ulid()))
+    }
+
+    fn event(message: &str) -> LogEvent {
+        LogEvent::internal("INFO", message, HashMap::new())
+    }
+
+    #[test]
+    fn test_events_survive_reopen() {
+        let path = temp_path();
+        {
+            let ring = MmapRing::open(&path, 64 * 1024).unwrap();
+            ring.write(&event("first"));
+            ring.write(&event("second"));
+        }
+
+        let ring = MmapRing::open(&path, 64 * 1024).unwrap();
+        let messages: Vec<_> = ring.read_all().into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, ["first", "second"]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_wraparound_keeps_newest() {
+        let path = temp_path();
+        let ring = MmapRing::open(&path, MIN_SIZE).unwrap();
+        for i in 0..200 {
+            ring.write(&event(&format!("event {}", i)));
+        }
+
+        let events = ring.read_all();
+        assert!(!events.is_empty());
+        assert!(events.len() < 200);
+        assert_eq!(events.last().unwrap().message, "event 199");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_resized_file_is_reset() {
+        let path = temp_path();
+        MmapRing::open(&path, 64 * 1024)
+            .unwrap()
+            .write(&event("old"));
+
+        let ring = MmapRing::open(&path, 32 * 1024).unwrap();
+        assert!(ring.read_all().is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
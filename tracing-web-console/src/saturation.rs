@@ -0,0 +1,330 @@
+//! Alerts when the console loses visibility under load
+//!
+//! A buffer turning over faster than anyone can look at it, or live stream
+//! clients falling behind, means events are lost exactly when traffic peaks.
+//! Both rates are checked every second, and a sustained excess is recorded as
+//! a lifecycle event and optionally posted to a webhook.
+
+use crate::storage::{LogEvent, LogStorage};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often the rates are measured
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Timeout for webhook requests
+#[cfg(feature = "webhooks")]
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Thresholds of the saturation alert
+#[derive(Debug, Clone)]
+pub struct SaturationConfig {
+    /// Events evicted per second from a full buffer
+    pub max_evictions_per_sec: f64,
+    /// Events per second live stream clients miss by falling behind
+    pub max_drops_per_sec: f64,
+    /// How long a rate has to stay above its threshold
+    pub sustained: Duration,
+    /// Generic HTTP or Slack incoming-webhook URL notified on changes
+    pub webhook_url: Option<String>,
+}
+
+impl Default for SaturationConfig {
+    fn default() -> Self {
+        Self {
+            max_evictions_per_sec: 1_000.0,
+            max_drops_per_sec: 10.0,
+            sustained: Duration::from_secs(30),
+            webhook_url: None,
+        }
+    }
+}
+
+/// Rate that can exceed its threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Metric {
+    Evictions,
+    Drops,
+}
+
+impl Metric {
+    fn description(self) -> &'static str {
+        match self {
+            Metric::Evictions => "buffer eviction rate",
+            Metric::Drops => "live stream drop rate",
+        }
+    }
+}
+
+/// A metric starting or ending a sustained excess
+#[derive(Debug, Clone, Serialize)]
+struct Transition {
+    metric: Metric,
+    /// Per second over the last check
+    rate: f64,
+    threshold: f64,
+    saturated: bool,
+}
+
+#[derive(Default)]
+struct MetricState {
+    last_total: u64,
+    /// Since when the rate is above the threshold
+    exceeded_since: Option<Instant>,
+    saturated: bool,
+}
+
+impl MetricState {
+    fn check(
+        &mut self,
+        metric: Metric,
+        total: u64,
+        elapsed: Duration,
+        threshold: f64,
+        sustained: Duration,
+        now: Instant,
+    ) -> Option<Transition> {
+        let rate = total.saturating_sub(self.last_total) as f64 / elapsed.as_secs_f64().max(0.001);
+        self.last_total = total;
+
+        if rate <= threshold {
+            self.exceeded_since = None;
+        } else if self.exceeded_since.is_none() {
+            self.exceeded_since = Some(now);
+        }
+        let saturated = self
+            .exceeded_since
+            .is_some_and(|since| now.duration_since(since) >= sustained);
+        if saturated == self.saturated {
+            return None;
+        }
+        self.saturated = saturated;
+        Some(Transition {
+            metric,
+            rate,
+            threshold,
+            saturated,
+        })
+    }
+}
+
+/// Rates of both metrics between checks
+struct Monitor {
+    config: SaturationConfig,
+    evictions: MetricState,
+    drops: MetricState,
+    last_check: Instant,
+}
+
+impl Monitor {
+    fn new(config: SaturationConfig, storage: &LogStorage, now: Instant) -> Self {
+        Self {
+            config,
+            evictions: MetricState {
+                last_total: storage.evicted_total(),
+                ..Default::default()
+            },
+            drops: MetricState {
+                last_total: storage.dropped_total(),
+                ..Default::default()
+            },
+            last_check: now,
+        }
+    }
+
+    fn check(&mut self, storage: &LogStorage, now: Instant) -> Vec<Transition> {
+        let elapsed = now.duration_since(self.last_check);
+        self.last_check = now;
+        let sustained = self.config.sustained;
+        [
+            self.evictions.check(
+                Metric::Evictions,
+                storage.evicted_total(),
+                elapsed,
+                self.config.max_evictions_per_sec,
+                sustained,
+                now,
+            ),
+            self.drops.check(
+                Metric::Drops,
+                storage.dropped_total(),
+                elapsed,
+                self.config.max_drops_per_sec,
+                sustained,
+                now,
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// Message describing a transition
+fn describe(transition: &Transition, sustained: Duration) -> String {
+    if transition.saturated {
+        format!(
+            "Console is losing events: {} at {:.0}/s for {}s (threshold {:.0}/s)",
+            transition.metric.description(),
+            transition.rate,
+            sustained.as_secs(),
+            transition.threshold
+        )
+    } else {
+        format!(
+            "Console recovered: {} back at {:.0}/s",
+            transition.metric.description(),
+            transition.rate
+        )
+    }
+}
+
+/// Lifecycle event recording a transition
+fn transition_event(transition: &Transition, sustained: Duration) -> LogEvent {
+    let metric = match transition.metric {
+        Metric::Evictions => "evictions",
+        Metric::Drops => "drops",
+    };
+    let fields = HashMap::from([
+        ("metric".to_string(), metric.to_string()),
+        ("rate".to_string(), format!("{:.1}", transition.rate)),
+        (
+            "threshold".to_string(),
+            format!("{:.1}", transition.threshold),
+        ),
+    ]);
+    let message = describe(transition, sustained);
+    if transition.saturated {
+        LogEvent {
+            level: "WARN".into(),
+            ..LogEvent::lifecycle("visibility_degraded", &message, fields)
+        }
+    } else {
+        LogEvent::lifecycle("visibility_restored", &message, fields)
+    }
+}
+
+/// Payload posted to the webhook, `text` makes it render in Slack as-is
+#[cfg(feature = "webhooks")]
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    text: String,
+    #[serde(flatten)]
+    transition: &'a Transition,
+}
+
+/// Measure eviction and drop rates forever
+///
+/// Transitions are only recorded as lifecycle events without the `webhooks`
+/// feature.
+pub(crate) async fn run(storage: LogStorage, config: SaturationConfig) {
+    #[cfg(not(feature = "webhooks"))]
+    if config.webhook_url.is_some() {
+        tracing::warn!("Saturation webhooks need the `webhooks` feature, none are sent");
+    }
+    #[cfg(feature = "webhooks")]
+    let client = reqwest::Client::new();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut monitor = Monitor::new(config.clone(), &storage, Instant::now());
+
+    loop {
+        interval.tick().await;
+        for transition in monitor.check(&storage, Instant::now()) {
+            storage.push(transition_event(&transition, config.sustained));
+
+            #[cfg(feature = "webhooks")]
+            if let Some(url) = &config.webhook_url {
+                let payload = WebhookPayload {
+                    text: describe(&transition, config.sustained),
+                    transition: &transition,
+                };
+                let sent = client
+                    .post(url)
+                    .timeout(WEBHOOK_TIMEOUT)
+                    .json(&payload)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                if let Err(e) = sent {
+                    storage.diagnose(LogEvent::internal(
+                        "WARN",
+                        "Failed to deliver saturation webhook",
+                        HashMap::from([("error".to_string(), e.to_string())]),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sustained_evictions_alert_once() {
+        let storage = LogStorage::with_capacity(10);
+        let config = SaturationConfig {
+            max_evictions_per_sec: 5.0,
+            sustained: Duration::from_secs(2),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let mut monitor = Monitor::new(config, &storage, start);
+        let push = |count: usize| {
+            for _ in 0..count {
+                storage.push(LogEvent::internal("INFO", "busy", HashMap::new()));
+            }
+        };
+
+        // Filling the buffer doesn't evict anything
+        push(10);
+        assert!(monitor
+            .check(&storage, start + Duration::from_secs(1))
+            .is_empty());
+
+        push(20);
+        assert!(monitor
+            .check(&storage, start + Duration::from_secs(2))
+            .is_empty());
+        push(20);
+        assert!(monitor
+            .check(&storage, start + Duration::from_secs(3))
+            .is_empty());
+        push(20);
+        let transitions = monitor.check(&storage, start + Duration::from_secs(4));
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].metric, Metric::Evictions);
+        assert!(transitions[0].saturated);
+        assert_eq!(transitions[0].rate, 20.0);
+
+        push(20);
+        assert!(monitor
+            .check(&storage, start + Duration::from_secs(5))
+            .is_empty());
+        let transitions = monitor.check(&storage, start + Duration::from_secs(6));
+        assert!(!transitions[0].saturated);
+        let event = transition_event(&transitions[0], Duration::from_secs(2));
+        assert_eq!(event.fields["lifecycle"], "visibility_restored");
+    }
+
+    #[test]
+    fn test_drops_are_measured() {
+        let storage = LogStorage::new();
+        let config = SaturationConfig {
+            sustained: Duration::ZERO,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let mut monitor = Monitor::new(config, &storage, start);
+        storage.record_dropped(100);
+
+        let transitions = monitor.check(&storage, start + Duration::from_secs(1));
+        assert_eq!(transitions[0].metric, Metric::Drops);
+        let event = transition_event(&transitions[0], Duration::ZERO);
+        assert_eq!(event.level, "WARN");
+        assert_eq!(event.fields["metric"], "drops");
+    }
+}
@@ -0,0 +1,245 @@
+//! Sandboxed Rhai scripts for transforms and rule conditions
+//!
+//! Only compiled with the `scripting` feature. Scripts see the event as an
+//! `event` object map with `level`, `target`, `message` and `fields` keys.
+
+use crate::storage::LogEvent;
+use parking_lot::Mutex;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+/// Upper bound on operations per script run, keeps runaway scripts from stalling capture
+const MAX_OPERATIONS: u64 = 10_000;
+/// Maximum number of compiled scripts kept in the cache
+const MAX_CACHED_SCRIPTS: usize = 64;
+
+/// Shared sandboxed engine (module imports disabled, resource limits applied)
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut engine = Engine::new();
+        engine.set_max_modules(0);
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_expr_depths(32, 32);
+        engine.set_max_string_size(64 * 1024);
+        engine.set_max_array_size(1024);
+        engine.set_max_map_size(1024);
+        engine
+    })
+}
+
+/// Compile a script, reusing a cached AST when the same source was seen before
+pub fn compile(source: &str) -> Result<Arc<AST>, String> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Arc<AST>>>> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+
+    if let Some(ast) = cache.lock().get(source) {
+        return Ok(ast.clone());
+    }
+
+    let ast = Arc::new(engine().compile(source).map_err(|e| e.to_string())?);
+
+    let mut cache = cache.lock();
+    if cache.len() >= MAX_CACHED_SCRIPTS {
+        cache.clear();
+    }
+    cache.insert(source.to_string(), ast.clone());
+    Ok(ast)
+}
+
+/// Script value of a field, numbers and booleans keep their type
+fn field_to_dynamic(value: &Value) -> Dynamic {
+    match value {
+        Value::Bool(value) => Dynamic::from(*value),
+        Value::Number(number) => match number.as_i64() {
+            Some(int) => Dynamic::from(int),
+            None => Dynamic::from(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(text) => Dynamic::from(text.clone()),
+        other => Dynamic::from(other.to_string()),
+    }
+}
+
+/// Field value of a script value
+fn dynamic_to_field(value: Dynamic) -> Value {
+    if let Ok(value) = value.as_bool() {
+        return value.into();
+    }
+    if let Ok(int) = value.as_int() {
+        return int.into();
+    }
+    if let Ok(float) = value.as_float() {
+        return float.into();
+    }
+    Value::String(value.to_string())
+}
+
+/// Convert an event into the object map exposed to scripts
+fn event_to_map(event: &LogEvent) -> Map {
+    let fields: Map = event
+        .fields
+        .iter()
+        .map(|(k, v)| (k.as_str().into(), field_to_dynamic(v)))
+        .collect();
+
+    let mut map = Map::new();
+    map.insert("level".into(), Dynamic::from(event.level.to_string()));
+    map.insert("target".into(), Dynamic::from(event.target.to_string()));
+    map.insert("message".into(), Dynamic::from(event.message.clone()));
+    map.insert("fields".into(), Dynamic::from_map(fields));
+    if let Some(span) = &event.span {
+        map.insert("span".into(), Dynamic::from(span.name.clone()));
+    }
+    map
+}
+
+/// Copy script modifications of the event map back onto the event
+fn apply_map(event: &mut LogEvent, map: Map) {
+    for (key, value) in map {
+        match key.as_str() {
+            "level" => {
+                if let Ok(level) = value.into_string() {
+                    event.level = crate::ingest::grok::normalize_level(&level).into();
+                }
+            }
+            "target" => {
+                if let Ok(target) = value.into_string() {
+                    event.target = target.into();
+                }
+            }
+            "message" => {
+                if let Ok(message) = value.into_string() {
+                    event.message = message;
+                }
+            }
+            "fields" => {
+                if let Some(fields) = value.try_cast::<Map>() {
+                    event.fields = fields
+                        .into_iter()
+                        .map(|(k, v)| (k.as_str().into(), dynamic_to_field(v)))
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Run a transform script against an event
+///
+/// The script may modify `event`; returning `false` drops the event. Scripts
+/// that fail at runtime pass the event through unchanged.
+pub fn run_transform(source: &str, mut event: LogEvent) -> Option<LogEvent> {
+    let Ok(ast) = compile(source) else {
+        return Some(event);
+    };
+
+    let mut scope = Scope::new();
+    scope.push("event", event_to_map(&event));
+
+    match engine().eval_ast_with_scope::<Dynamic>(&mut scope, &ast) {
+        Ok(result) if result.as_bool() == Ok(false) => None,
+        Ok(_) => {
+            if let Some(map) = scope.get_value::<Map>("event") {
+                apply_map(&mut event, map);
+            }
+            Some(event)
+        }
+        Err(_) => Some(event),
+    }
+}
+
+/// A boolean script condition, e.g. `event.level == "ERROR" && event.fields.amount > 100`
+///
+/// Intended for rule matching where cross-field logic is needed.
+#[derive(Clone)]
+pub struct ScriptCondition {
+    source: String,
+    ast: Arc<AST>,
+}
+
+impl ScriptCondition {
+    /// Compile a condition script
+    pub fn new(source: &str) -> Result<Self, String> {
+        Ok(Self {
+            source: source.to_string(),
+            ast: compile(source)?,
+        })
+    }
+
+    /// Source code of the condition
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluate the condition, treating errors and non-bool results as no match
+    pub fn matches(&self, event: &LogEvent) -> bool {
+        let mut scope = Scope::new();
+        scope.push("event", event_to_map(event));
+        engine()
+            .eval_ast_with_scope::<bool>(&mut scope, &self.ast)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn create_test_event(level: &str, message: &str) -> LogEvent {
+        LogEvent {
+            id: crate::id::new_ulid(),
+            seq: 0,
+            timestamp: Utc::now(),
+            level: level.into(),
+            target: "app".into(),
+            message: message.to_string(),
+            fields: HashMap::from([
+                ("user".into(), "alice".into()),
+                ("attempt".into(), 3.into()),
+            ]),
+            span: None,
+            module_path: None,
+            file: None,
+            line: None,
+            source_url: None,
+            thread_name: None,
+            thread_id: None,
+            repeat_count: None,
+            is_error: false,
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_transform_script_modifies_and_drops() {
+        let script = r#"
+            if event.message.contains("ping") { return false; }
+            event.fields.team = "payments";
+            true
+        "#;
+
+        assert!(run_transform(script, create_test_event("INFO", "ping")).is_none());
+
+        let event = run_transform(script, create_test_event("INFO", "charge")).unwrap();
+        assert_eq!(event.fields["team"], "payments");
+        assert_eq!(event.fields["user"], "alice");
+        assert_eq!(event.fields["attempt"], 3);
+    }
+
+    #[test]
+    fn test_condition() {
+        let condition =
+            ScriptCondition::new(r#"event.level == "ERROR" && event.fields.attempt > 2"#).unwrap();
+        assert!(condition.matches(&create_test_event("ERROR", "boom")));
+        assert!(!condition.matches(&create_test_event("INFO", "fine")));
+    }
+
+    #[test]
+    fn test_invalid_script_is_rejected() {
+        assert!(compile("let = ;").is_err());
+    }
+}
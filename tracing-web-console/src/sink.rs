@@ -0,0 +1,342 @@
+//! Sinks receiving every stored event in addition to the in-memory buffer
+
+use crate::format::{Compact, Format, Logfmt, Ndjson};
+use crate::storage::LogEvent;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Batches are delivered early once they reach this size
+const MAX_BATCH_SIZE: usize = 1_000;
+/// Events a buffered sink queues for its flusher by default
+const DEFAULT_BUFFER_CAPACITY: usize = 65_536;
+
+/// Destination that receives a copy of every captured event
+///
+/// Sinks run synchronously on the capture path after transforms have been
+/// applied, so implementations should be cheap or hand work off elsewhere.
+pub trait EventSink: Send + Sync {
+    /// Handle a single event
+    fn write(&self, event: &LogEvent);
+
+    /// Block until all events written so far have been delivered
+    ///
+    /// Only sinks that buffer need to implement this.
+    fn flush(&self) {}
+
+    /// Number of events discarded instead of delivered so far
+    ///
+    /// Only sinks that can drop events need to implement this.
+    fn dropped(&self) -> u64 {
+        0
+    }
+}
+
+/// Destination receiving events in batches, e.g. a file or a remote collector
+pub trait BatchSink: Send + Sync {
+    /// Deliver a non-empty batch of events, oldest first
+    fn write_batch(&self, events: &[LogEvent]);
+}
+
+enum BufferMessage {
+    Event(LogEvent),
+    Flush(mpsc::Sender<()>),
+}
+
+/// Sink handing events to a [`BatchSink`] on a background flusher thread
+///
+/// Events are delivered at most `max_latency` after they were captured, so
+/// slow destinations never block the capture path. Events arriving while the
+/// queue to the flusher is full are discarded and counted.
+pub struct BufferedSink {
+    tx: mpsc::SyncSender<BufferMessage>,
+    dropped: AtomicU64,
+}
+
+impl BufferedSink {
+    /// Queue up to `DEFAULT_BUFFER_CAPACITY` events for the flusher
+    pub fn new<B: BatchSink + 'static>(inner: B, max_latency: Duration) -> Self {
+        Self::with_capacity(inner, max_latency, DEFAULT_BUFFER_CAPACITY)
+    }
+
+    /// Queue up to `capacity` events for the flusher
+    pub fn with_capacity<B: BatchSink + 'static>(
+        inner: B,
+        max_latency: Duration,
+        capacity: usize,
+    ) -> Self {
+        let (tx, rx) = mpsc::sync_channel(capacity.max(1));
+        let spawned = std::thread::Builder::new()
+            .name("tracing-web-console-flusher".to_string())
+            .spawn(move || run_flusher(inner, rx, max_latency));
+        if let Err(e) = spawned {
+            tracing::error!("Failed to start sink flusher thread: {}", e);
+        }
+        Self {
+            tx,
+            dropped: AtomicU64::new(0),
+        }
+    }
+}
+
+impl EventSink for BufferedSink {
+    fn write(&self, event: &LogEvent) {
+        match self.tx.try_send(BufferMessage::Event(event.clone())) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            // The flusher thread is gone, nothing left to deliver to
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    fn flush(&self) {
+        let (done_tx, done_rx) = mpsc::channel();
+        // Waits for room in a full queue
+        if self.tx.send(BufferMessage::Flush(done_tx)).is_ok() {
+            // Messages are handled in order, so every earlier event is delivered first
+            let _ = done_rx.recv();
+        }
+    }
+
+    fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Collect events and deliver them when the oldest has waited `max_latency`
+fn run_flusher<B: BatchSink>(inner: B, rx: mpsc::Receiver<BufferMessage>, max_latency: Duration) {
+    let mut batch: Vec<LogEvent> = Vec::new();
+    let mut deadline: Option<Instant> = None;
+
+    let deliver = |batch: &mut Vec<LogEvent>| {
+        if !batch.is_empty() {
+            inner.write_batch(batch);
+            batch.clear();
+        }
+    };
+
+    loop {
+        let message = match deadline {
+            Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match message {
+            Ok(BufferMessage::Event(event)) => {
+                if batch.is_empty() {
+                    deadline = Some(Instant::now() + max_latency);
+                }
+                batch.push(event);
+                if batch.len() >= MAX_BATCH_SIZE {
+                    deliver(&mut batch);
+                    deadline = None;
+                }
+            }
+            Ok(BufferMessage::Flush(done)) => {
+                deliver(&mut batch);
+                deadline = None;
+                let _ = done.send(());
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                deliver(&mut batch);
+                deadline = None;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                deliver(&mut batch);
+                break;
+            }
+        }
+    }
+}
+
+/// Output format of the mirror sink
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MirrorFormat {
+    /// Single human readable line, similar to `tracing_subscriber::fmt().compact()`
+    #[default]
+    Compact,
+    /// One JSON object per line
+    Json,
+    /// `key=value` pairs
+    Logfmt,
+}
+
+impl MirrorFormat {
+    fn format(self) -> Arc<dyn Format> {
+        match self {
+            MirrorFormat::Compact => Arc::new(Compact),
+            MirrorFormat::Json => Arc::new(Ndjson),
+            MirrorFormat::Logfmt => Arc::new(Logfmt),
+        }
+    }
+}
+
+/// Stream the mirror sink writes to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MirrorTarget {
+    #[default]
+    Stderr,
+    Stdout,
+}
+
+/// Sink that prints events to stderr or stdout for platforms scraping process output
+pub struct MirrorSink {
+    format: Arc<dyn Format>,
+    target: MirrorTarget,
+}
+
+impl MirrorSink {
+    pub fn new(format: MirrorFormat, target: MirrorTarget) -> Self {
+        Self::with_format(format.format(), target)
+    }
+
+    /// Mirror in any registered format, binary formats are written as is
+    pub fn with_format(format: Arc<dyn Format>, target: MirrorTarget) -> Self {
+        Self { format, target }
+    }
+
+    /// Render an event as a single line (without trailing newline)
+    fn render(&self, event: &LogEvent) -> Vec<u8> {
+        self.format.encode_event(event).unwrap_or_default()
+    }
+}
+
+impl EventSink for MirrorSink {
+    fn write(&self, event: &LogEvent) {
+        let mut line = self.render(event);
+        line.push(b'\n');
+        // Ignore write errors, a closed stdout must never break capture
+        let _ = match self.target {
+            MirrorTarget::Stderr => std::io::stderr().lock().write_all(&line),
+            MirrorTarget::Stdout => std::io::stdout().lock().write_all(&line),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+
+    #[derive(Clone, Default)]
+    struct CollectingSink {
+        batches: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    impl BatchSink for CollectingSink {
+        fn write_batch(&self, events: &[LogEvent]) {
+            let messages = events.iter().map(|e| e.message.clone()).collect();
+            self.batches.lock().push(messages);
+        }
+    }
+
+    #[test]
+    fn test_buffered_sink_flush() {
+        let collected = CollectingSink::default();
+        let sink = BufferedSink::new(collected.clone(), Duration::from_secs(60));
+
+        for message in ["a", "b", "c"] {
+            sink.write(&LogEvent::internal("INFO", message, HashMap::new()));
+        }
+        sink.flush();
+
+        assert_eq!(*collected.batches.lock(), vec![vec!["a", "b", "c"]]);
+    }
+
+    #[test]
+    fn test_buffered_sink_delivers_within_latency() {
+        let collected = CollectingSink::default();
+        let sink = BufferedSink::new(collected.clone(), Duration::from_millis(20));
+
+        sink.write(&LogEvent::internal("INFO", "a", HashMap::new()));
+        for _ in 0..100 {
+            if !collected.batches.lock().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(collected.batches.lock().len(), 1);
+    }
+
+    /// Blocks in `write_batch` while `gate` is locked
+    struct BlockedSink {
+        gate: Arc<Mutex<()>>,
+        entered: Arc<AtomicU64>,
+    }
+
+    impl BatchSink for BlockedSink {
+        fn write_batch(&self, _events: &[LogEvent]) {
+            self.entered.fetch_add(1, Ordering::Release);
+            let _wait = self.gate.lock();
+        }
+    }
+
+    #[test]
+    fn test_buffered_sink_drops_when_full() {
+        let gate = Arc::new(Mutex::new(()));
+        let entered = Arc::new(AtomicU64::new(0));
+        let held = gate.lock();
+        let inner = BlockedSink {
+            gate: gate.clone(),
+            entered: entered.clone(),
+        };
+        let sink = BufferedSink::with_capacity(inner, Duration::ZERO, 2);
+
+        sink.write(&LogEvent::internal("INFO", "first", HashMap::new()));
+        while entered.load(Ordering::Acquire) == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        // The flusher is stuck in the destination, two more fit into the queue
+        for i in 0..10 {
+            sink.write(&LogEvent::internal("INFO", &i.to_string(), HashMap::new()));
+        }
+        assert_eq!(sink.dropped(), 8);
+
+        drop(held);
+        sink.flush();
+        assert_eq!(sink.dropped(), 8);
+    }
+
+    #[test]
+    fn test_compact_format() {
+        let event = LogEvent {
+            id: crate::id::new_ulid(),
+            seq: 0,
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            level: "INFO".into(),
+            target: "app::orders".into(),
+            message: "order created".to_string(),
+            fields: HashMap::from([
+                ("order_id".into(), "42".into()),
+                ("amount".into(), 9.99.into()),
+            ]),
+            span: None,
+            module_path: None,
+            file: None,
+            line: None,
+            source_url: None,
+            thread_name: None,
+            thread_id: None,
+            repeat_count: None,
+            is_error: false,
+            truncated: false,
+        };
+
+        let sink = MirrorSink::new(MirrorFormat::Compact, MirrorTarget::Stderr);
+        assert_eq!(
+            sink.render(&event),
+            b"2024-05-01T12:00:00.000000Z  INFO app::orders: order created amount=9.99 order_id=42"
+        );
+
+        let sink = MirrorSink::new(MirrorFormat::Json, MirrorTarget::Stdout);
+        let json: serde_json::Value = serde_json::from_slice(&sink.render(&event)).unwrap();
+        assert_eq!(json["message"], "order created");
+    }
+}
@@ -0,0 +1,275 @@
+//! Snapshots of the in-memory buffer
+//!
+//! Periodic snapshot files for crash recovery, NDJSON by default, and
+//! compressed bundles with metadata that can be downloaded and imported into
+//! another console.
+
+use crate::format::Format;
+use crate::storage::{LogEvent, LogStorage, Marker};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Prefix of snapshot file names, used to find old snapshots for rotation
+const FILE_PREFIX: &str = "snapshot-";
+/// Layout version of bundles, bumped on incompatible changes
+const BUNDLE_VERSION: u32 = 1;
+/// Largest decompressed bundle accepted for import
+const MAX_BUNDLE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Where and how often snapshots are written
+#[derive(Debug, Clone)]
+pub(crate) struct SnapshotConfig {
+    pub dir: PathBuf,
+    pub interval: Duration,
+    /// Number of snapshots to keep, older ones are deleted
+    pub keep: usize,
+}
+
+/// Write all buffered events to a new snapshot file, oldest event first
+///
+/// The file is written under a temporary name and renamed once complete, so a
+/// crash mid-write never leaves a truncated snapshot behind.
+pub(crate) fn write_snapshot(
+    storage: &LogStorage,
+    dir: &Path,
+    format: &dyn Format,
+) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+
+    // The timestamp sorts lexicographically, so file names order by age
+    let name = format!(
+        "{}{}.{}",
+        FILE_PREFIX,
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        format.extension()
+    );
+    let path = dir.join(&name);
+    let tmp_path = dir.join(format!(".{}.tmp", name));
+
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    let events = storage.matching(|_| true);
+    format.write_events(&mut writer, &mut events.iter())?;
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+
+    fs::rename(&tmp_path, &path)?;
+    Ok(path)
+}
+
+/// Delete all but the newest `keep` snapshots with `extension` in `dir`
+pub(crate) fn rotate(dir: &Path, keep: usize, extension: &str) -> std::io::Result<()> {
+    let mut snapshots: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(FILE_PREFIX) && n.ends_with(extension))
+        })
+        .collect();
+    snapshots.sort();
+
+    let excess = snapshots.len().saturating_sub(keep);
+    for path in snapshots.into_iter().take(excess) {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Where, when and how a bundle was taken
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleMetadata {
+    /// Unique ID, imported events are tagged with it
+    pub id: String,
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Version of the console that took the bundle
+    pub console_version: String,
+    /// Capture filter directives of the console, e.g. "info,my_app=debug"
+    #[serde(default)]
+    pub capture_filter: Option<String>,
+    /// Filters applied in the UI, stored as sent
+    #[serde(default)]
+    pub view: Option<Value>,
+    #[serde(default)]
+    pub note: Option<String>,
+    pub events: usize,
+}
+
+/// The buffer with its markers and metadata, written as gzip-compressed JSON
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotBundle {
+    pub metadata: BundleMetadata,
+    /// Oldest first
+    pub events: Vec<LogEvent>,
+    #[serde(default)]
+    pub markers: Vec<Marker>,
+}
+
+/// Name of the machine, if it can be found out
+pub(crate) fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+impl SnapshotBundle {
+    /// Bundle all buffered events and markers
+    pub(crate) fn capture(
+        storage: &LogStorage,
+        capture_filter: Option<String>,
+        view: Option<Value>,
+        note: Option<String>,
+    ) -> Self {
+        let events = storage.matching(|_| true);
+        Self {
+            metadata: BundleMetadata {
+                id: crate::id::new_ulid(),
+                version: BUNDLE_VERSION,
+                created_at: Utc::now(),
+                host: hostname(),
+                console_version: env!("CARGO_PKG_VERSION").to_string(),
+                capture_filter,
+                view,
+                note,
+                events: events.len(),
+            },
+            events,
+            markers: storage.markers_between(None, None),
+        }
+    }
+
+    /// Encode as gzip-compressed JSON
+    pub(crate) fn to_gzip(&self) -> io::Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()
+    }
+
+    /// Decode a bundle written by `to_gzip`
+    pub(crate) fn from_gzip(bytes: &[u8]) -> Result<Self, String> {
+        let mut json = Vec::new();
+        GzDecoder::new(bytes)
+            .take(MAX_BUNDLE_BYTES + 1)
+            .read_to_end(&mut json)
+            .map_err(|e| format!("not a gzip-compressed bundle: {}", e))?;
+        if json.len() as u64 > MAX_BUNDLE_BYTES {
+            return Err(format!(
+                "bundle exceeds {} bytes uncompressed",
+                MAX_BUNDLE_BYTES
+            ));
+        }
+        let bundle: Self =
+            serde_json::from_slice(&json).map_err(|e| format!("invalid bundle: {}", e))?;
+        if bundle.metadata.version > BUNDLE_VERSION {
+            return Err(format!(
+                "bundle version {} is newer than the supported version {}",
+                bundle.metadata.version, BUNDLE_VERSION
+            ));
+        }
+        Ok(bundle)
+    }
+}
+
+/// Write snapshots forever at the configured interval
+pub(crate) async fn run(storage: LogStorage, config: SnapshotConfig, format: Arc<dyn Format>) {
+    let mut interval = tokio::time::interval(config.interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first tick completes immediately, an empty startup snapshot is useless
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let storage = storage.clone();
+        let config = config.clone();
+        let format = format.clone();
+        // File IO and serializing the whole buffer must not block the runtime
+        let result = tokio::task::spawn_blocking(move || {
+            write_snapshot(&storage, &config.dir, format.as_ref())?;
+            rotate(&config.dir, config.keep, format.extension())
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!("Failed to write log snapshot: {}", e),
+            Err(e) => tracing::warn!("Log snapshot task failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Ndjson;
+    use crate::storage::LogEvent;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_snapshot_and_rotation() {
+        let dir = std::env::temp_dir().join(format!("twc-snapshot-{}", crate::id::new_ulid()));
+        let storage = LogStorage::new();
+        storage.push(LogEvent::internal("INFO", "first", HashMap::new()));
+        storage.push(LogEvent::internal("WARN", "second", HashMap::new()));
+
+        let path = write_snapshot(&storage, &dir, &Ndjson).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let events: Vec<LogEvent> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].message, "first");
+
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(2));
+            write_snapshot(&storage, &dir, &Ndjson).unwrap();
+        }
+        rotate(&dir, 2, Ndjson.extension()).unwrap();
+
+        let remaining = fs::read_dir(&dir).unwrap().count();
+        assert_eq!(remaining, 2);
+        assert!(!path.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let storage = LogStorage::new();
+        storage.push(LogEvent::internal("ERROR", "failed", HashMap::new()));
+        storage.add_marker(Marker {
+            id: "m1".to_string(),
+            timestamp: Utc::now(),
+            label: "v1.2.0".to_string(),
+            kind: Some("deploy".to_string()),
+        });
+
+        let view = serde_json::json!({"search": "failed"});
+        let bundle =
+            SnapshotBundle::capture(&storage, Some("info".to_string()), Some(view.clone()), None);
+        let bytes = bundle.to_gzip().unwrap();
+        let decoded = SnapshotBundle::from_gzip(&bytes).unwrap();
+        assert_eq!(decoded.metadata.id, bundle.metadata.id);
+        assert_eq!(decoded.metadata.events, 1);
+        assert_eq!(decoded.metadata.view, Some(view));
+        assert_eq!(decoded.events[0].message, "failed");
+        assert_eq!(decoded.markers[0].label, "v1.2.0");
+
+        assert!(SnapshotBundle::from_gzip(b"not gzip").is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Index of span field values answering `span.field=value` queries
+//!
+//! Request-scoped debugging usually starts from a span field such as
+//! `user_id=42`. Scanning the whole buffer for it gets slow with large
+//! capacities, so stored events are indexed by the fields of their spans.
+//!
+//! Fields with a value per request, like raw UUIDs, would grow the index with
+//! every event. The distinct values of each field are estimated, and fields
+//! exceeding `MAX_FIELD_CARDINALITY` are dropped from the index. They stay on
+//! the events and queries on them scan instead.
+
+use crate::storage::LogEvent;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// Maximum number of distinct field values indexed, beyond that new values
+/// are left out and queries on their field fall back to a scan
+const MAX_INDEXED_VALUES: usize = 100_000;
+/// Drop sequence numbers of evicted events every this many inserts
+const PRUNE_INTERVAL: u64 = 1024;
+/// Fields with more distinct values than this are no longer indexed
+pub(crate) const MAX_FIELD_CARDINALITY: u64 = 10_000;
+/// Registers per cardinality sketch, the estimate is within about 6.5%
+const SKETCH_REGISTERS: usize = 256;
+
+/// HyperLogLog estimate of the number of distinct values of a field
+struct CardinalitySketch {
+    registers: Box<[u8; SKETCH_REGISTERS]>,
+}
+
+impl CardinalitySketch {
+    fn new() -> Self {
+        Self {
+            registers: Box::new([0; SKETCH_REGISTERS]),
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        // The top 8 bits pick the register, the rest its leading zeros
+        let register = (hash >> 56) as usize;
+        let rank = ((hash << 8).leading_zeros().min(56) + 1) as u8;
+        let current = &mut self.registers[register];
+        *current = (*current).max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = SKETCH_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-(rank as i32)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+        let empty = self.registers.iter().filter(|&&rank| rank == 0).count();
+        // Small cardinalities are counted more precisely from empty registers
+        if estimate <= 2.5 * m && empty > 0 {
+            (m * (m / empty as f64).ln()) as u64
+        } else {
+            estimate as u64
+        }
+    }
+}
+
+/// Sequence numbers of events per span field and value
+#[derive(Default)]
+pub(crate) struct SpanFieldIndex {
+    /// Field name -> value -> ascending sequence numbers
+    postings: HashMap<String, HashMap<String, VecDeque<u64>>>,
+    /// Number of distinct (field, value) pairs
+    values: usize,
+    /// Fields with values left out because the index was full, with the
+    /// newest sequence number left out
+    incomplete: HashMap<String, u64>,
+    /// Estimated distinct values per field
+    sketches: HashMap<String, CardinalitySketch>,
+    /// Fields dropped from the index for having too many distinct values
+    high_cardinality: HashSet<String>,
+    inserts: u64,
+}
+
+impl SpanFieldIndex {
+    /// Index a stored event, `min_seq` is the oldest sequence number still
+    /// in the buffer
+    ///
+    /// Returns the fields and their estimated cardinality that were dropped
+    /// from the index because of this event.
+    pub fn insert(&mut self, event: &LogEvent, min_seq: u64) -> Vec<(String, u64)> {
+        let mut dropped = Vec::new();
+        let Some(span) = &event.span else {
+            return dropped;
+        };
+
+        for (name, value) in span.fields.iter().chain(&span.parent_fields) {
+            if self.high_cardinality.contains(name) {
+                continue;
+            }
+            let values = self.postings.entry(name.clone()).or_default();
+            if let Some(seqs) = values.get_mut(value) {
+                seqs.push_back(event.seq);
+                continue;
+            }
+
+            // Only values new to the index can raise the estimate
+            let sketch = self
+                .sketches
+                .entry(name.clone())
+                .or_insert_with(CardinalitySketch::new);
+            sketch.insert(value);
+            let cardinality = sketch.estimate();
+            if cardinality > MAX_FIELD_CARDINALITY {
+                if let Some(values) = self.postings.remove(name) {
+                    self.values -= values.len();
+                }
+                self.sketches.remove(name);
+                self.high_cardinality.insert(name.clone());
+                dropped.push((name.clone(), cardinality));
+            } else if self.values < MAX_INDEXED_VALUES {
+                values.insert(value.clone(), VecDeque::from([event.seq]));
+                self.values += 1;
+            } else {
+                self.incomplete.insert(name.clone(), event.seq);
+            }
+        }
+
+        self.inserts += 1;
+        if self.inserts % PRUNE_INTERVAL == 0 {
+            self.prune(min_seq);
+        }
+        dropped
+    }
+
+    /// Forget events older than `min_seq` and values no stored event has
+    fn prune(&mut self, min_seq: u64) {
+        for values in self.postings.values_mut() {
+            values.retain(|_, seqs| {
+                while seqs.front().is_some_and(|&seq| seq < min_seq) {
+                    seqs.pop_front();
+                }
+                !seqs.is_empty()
+            });
+        }
+        self.postings.retain(|_, values| !values.is_empty());
+        self.values = self.postings.values().map(HashMap::len).sum();
+        // Fields are complete again once the events left out are evicted
+        self.incomplete.retain(|_, newest| *newest >= min_seq);
+    }
+
+    /// Sequence numbers of events that may match all `terms`, ascending
+    ///
+    /// Returns the shortest posting list of the terms, or `None` if there are
+    /// no terms or one of them can't be answered from the index.
+    pub fn candidates(&self, terms: &[(&str, &str)], min_seq: u64) -> Option<Vec<u64>> {
+        let mut shortest: Option<&VecDeque<u64>> = None;
+        for (name, value) in terms {
+            let incomplete = self
+                .incomplete
+                .get(*name)
+                .is_some_and(|newest| *newest >= min_seq);
+            if incomplete || self.high_cardinality.contains(*name) {
+                return None;
+            }
+            let Some(seqs) = self
+                .postings
+                .get(*name)
+                .and_then(|values| values.get(*value))
+            else {
+                // Nothing stored has this value
+                return Some(Vec::new());
+            };
+            if !matches!(shortest, Some(s) if s.len() <= seqs.len()) {
+                shortest = Some(seqs);
+            }
+        }
+
+        let seqs = shortest?;
+        let start = seqs.partition_point(|&seq| seq < min_seq);
+        Some(seqs.range(start..).copied().collect())
+    }
+
+    /// Drop everything, e.g. after the storage was cleared
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SpanInfo;
+
+    fn event(seq: u64, user_id: &str, route: &str) -> LogEvent {
+        LogEvent {
+            seq,
+            span: Some(SpanInfo {
+                id: None,
+                name: "db_query".to_string(),
+                fields: HashMap::from([("user_id".to_string(), user_id.to_string())]),
+                ancestors: vec!["request".to_string()],
+                parent_fields: HashMap::from([("route".to_string(), route.to_string())]),
+            }),
+            ..LogEvent::internal("INFO", "query", HashMap::new())
+        }
+    }
+
+    #[test]
+    fn test_candidates_use_shortest_list() {
+        let mut index = SpanFieldIndex::default();
+        index.insert(&event(1, "42", "/orders"), 1);
+        index.insert(&event(2, "7", "/orders"), 1);
+        index.insert(&event(3, "42", "/users"), 1);
+
+        assert_eq!(index.candidates(&[("user_id", "42")], 1), Some(vec![1, 3]));
+        assert_eq!(
+            index.candidates(&[("route", "/orders"), ("user_id", "7")], 1),
+            Some(vec![2])
+        );
+        assert_eq!(index.candidates(&[("user_id", "42")], 2), Some(vec![3]));
+        assert_eq!(index.candidates(&[("user_id", "1")], 1), Some(vec![]));
+        assert_eq!(index.candidates(&[], 1), None);
+    }
+
+    #[test]
+    fn test_prune_drops_evicted_events() {
+        let mut index = SpanFieldIndex::default();
+        index.insert(&event(1, "42", "/orders"), 1);
+        index.insert(&event(2, "7", "/orders"), 1);
+        index.prune(2);
+
+        assert_eq!(index.values, 2);
+        assert!(index.postings["user_id"].get("42").is_none());
+    }
+
+    #[test]
+    fn test_field_is_complete_again_after_prune() {
+        let mut index = SpanFieldIndex::default();
+        index.insert(&event(1, "42", "/orders"), 1);
+        // As if the value of event 2 didn't fit into the full index
+        index.insert(&event(2, "42", "/orders"), 1);
+        index.incomplete.insert("user_id".to_string(), 2);
+        index.insert(&event(3, "42", "/orders"), 1);
+        assert_eq!(index.candidates(&[("user_id", "42")], 1), None);
+
+        index.prune(3);
+        assert!(index.incomplete.is_empty());
+        assert_eq!(index.candidates(&[("user_id", "42")], 3), Some(vec![3]));
+    }
+
+    #[test]
+    fn test_high_cardinality_field_is_dropped() {
+        let mut index = SpanFieldIndex::default();
+        let mut dropped = Vec::new();
+        for seq in 1..=20_000 {
+            let route = if seq % 2 == 0 { "/orders" } else { "/users" };
+            dropped.extend(index.insert(&event(seq, &format!("user-{}", seq), route), 1));
+        }
+
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].0, "user_id");
+        assert!(dropped[0].1 > MAX_FIELD_CARDINALITY);
+        assert_eq!(index.candidates(&[("user_id", "user-7")], 1), None);
+        assert_eq!(
+            index.candidates(&[("route", "/users")], 1).unwrap().len(),
+            10_000
+        );
+        assert_eq!(index.values, 2);
+    }
+
+    #[test]
+    fn test_cardinality_estimate() {
+        let mut sketch = CardinalitySketch::new();
+        for i in 0..5_000 {
+            sketch.insert(&i.to_string());
+            sketch.insert(&i.to_string());
+        }
+        let estimate = sketch.estimate() as f64;
+        assert!((estimate - 5_000.0).abs() < 5_000.0 * 0.2, "{}", estimate);
+    }
+}
@@ -0,0 +1,344 @@
+//! Span lifecycles and their hierarchy
+//!
+//! The capture layer records when spans are created, entered, exited and
+//! closed. Each span becomes a record with its parent, so requests can be
+//! shown as nested timelines.
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use tokio::sync::broadcast;
+
+/// Default number of spans kept, the oldest are dropped first
+const DEFAULT_MAX_SPANS: usize = 10_000;
+/// Closed root spans buffered for slow subscribers
+const COMPLETED_CAPACITY: usize = 1024;
+
+/// A span with its timing
+#[derive(Debug, Clone, Serialize)]
+pub struct SpanRecord {
+    /// ULID assigned when the span was created, ordered by creation
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    pub name: String,
+    pub target: String,
+    pub level: String,
+    pub fields: HashMap<String, String>,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the span is open
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<DateTime<Utc>>,
+    /// Time from creation to close in microseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_us: Option<u64>,
+    /// Time spent entered in microseconds, up to the last exit
+    pub busy_us: u64,
+    /// Events captured directly in the span, counted when it closes
+    pub events: u64,
+}
+
+/// A span with its descendants, children oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct SpanNode {
+    #[serde(flatten)]
+    pub span: SpanRecord,
+    pub children: Vec<SpanNode>,
+}
+
+/// Which spans a listing returns
+#[derive(Debug, Clone, Default)]
+pub struct SpanQuery {
+    /// Exact span name
+    pub name: Option<String>,
+    /// Target or modules below it
+    pub target: Option<String>,
+    /// Only children of this span
+    pub parent_id: Option<String>,
+    /// Only spans without a parent
+    pub roots: bool,
+    /// Only open (`Some(true)`) or closed (`Some(false)`) spans
+    pub open: Option<bool>,
+}
+
+impl SpanQuery {
+    fn matches(&self, span: &SpanRecord) -> bool {
+        if matches!(&self.name, Some(name) if *name != span.name) {
+            return false;
+        }
+        if let Some(prefix) = &self.target {
+            if span.target != *prefix && !span.target.starts_with(&format!("{}::", prefix)) {
+                return false;
+            }
+        }
+        if self.parent_id.is_some() && self.parent_id != span.parent_id {
+            return false;
+        }
+        if self.roots && span.parent_id.is_some() {
+            return false;
+        }
+        !matches!(self.open, Some(open) if open != span.closed_at.is_none())
+    }
+}
+
+/// Bounded store of span records, keyed by their ULID
+pub(crate) struct SpanStore {
+    spans: RwLock<BTreeMap<String, SpanRecord>>,
+    max_spans: usize,
+    /// IDs of root spans as they close
+    completed: broadcast::Sender<String>,
+}
+
+impl Default for SpanStore {
+    fn default() -> Self {
+        Self::with_max_spans(DEFAULT_MAX_SPANS)
+    }
+}
+
+impl SpanStore {
+    pub fn with_max_spans(max_spans: usize) -> Self {
+        Self {
+            spans: RwLock::new(BTreeMap::new()),
+            max_spans: max_spans.max(1),
+            completed: broadcast::channel(COMPLETED_CAPACITY).0,
+        }
+    }
+
+    /// Record a newly created span, dropping the oldest if full
+    pub fn open(&self, span: SpanRecord) {
+        let mut spans = self.spans.write();
+        spans.insert(span.id.clone(), span);
+        while spans.len() > self.max_spans {
+            spans.pop_first();
+        }
+    }
+
+    /// Record that a span closed, ignored if it was already dropped
+    ///
+    /// Closing a root span completes its tree, which is announced to
+    /// `subscribe_completed` receivers.
+    pub fn close(&self, id: &str, closed_at: DateTime<Utc>, busy_us: u64, events: u64) {
+        let root = match self.spans.write().get_mut(id) {
+            Some(span) => {
+                let duration = (closed_at - span.started_at)
+                    .num_microseconds()
+                    .unwrap_or(0);
+                span.closed_at = Some(closed_at);
+                span.duration_us = Some(duration.max(0) as u64);
+                span.busy_us = busy_us;
+                span.events = events;
+                span.parent_id.is_none()
+            }
+            None => false,
+        };
+        if root {
+            // Ignore if no receivers
+            let _ = self.completed.send(id.to_string());
+        }
+    }
+
+    /// ID of the root of the tree a span belongs to, itself if it has no parent
+    ///
+    /// Ancestors already dropped end the walk early.
+    pub fn root_id(&self, id: &str) -> Option<String> {
+        let spans = self.spans.read();
+        let mut span = spans.get(id)?;
+        while let Some(parent) = span.parent_id.as_ref().and_then(|p| spans.get(p)) {
+            span = parent;
+        }
+        Some(span.id.clone())
+    }
+
+    /// Receive the IDs of root spans as they close
+    pub fn subscribe_completed(&self) -> broadcast::Receiver<String> {
+        self.completed.subscribe()
+    }
+
+    /// Spans matching `query`, newest first
+    pub fn list(&self, query: &SpanQuery, limit: usize) -> Vec<SpanRecord> {
+        self.spans
+            .read()
+            .values()
+            .rev()
+            .filter(|span| query.matches(span))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Closed spans matching `query` that took at least `threshold_us`,
+    /// longest first
+    pub fn slowest(&self, query: &SpanQuery, threshold_us: u64, limit: usize) -> Vec<SpanRecord> {
+        let mut slow: Vec<_> = self
+            .spans
+            .read()
+            .values()
+            .filter(|span| span.duration_us.is_some_and(|d| d >= threshold_us))
+            .filter(|span| query.matches(span))
+            .cloned()
+            .collect();
+        // Ties keep the newest first
+        slow.sort_by(|a, b| {
+            b.duration_us
+                .cmp(&a.duration_us)
+                .then_with(|| b.id.cmp(&a.id))
+        });
+        slow.truncate(limit);
+        slow
+    }
+
+    /// A span with all stored descendants
+    pub fn tree(&self, id: &str) -> Option<SpanNode> {
+        let spans = self.spans.read();
+        let root = spans.get(id)?.clone();
+        let mut children: HashMap<&str, Vec<&SpanRecord>> = HashMap::new();
+        // Children were created after their parent, so only later keys qualify
+        let later = (Bound::Excluded(id), Bound::Unbounded);
+        for (_, span) in spans.range::<str, _>(later) {
+            if let Some(parent) = &span.parent_id {
+                children.entry(parent.as_str()).or_default().push(span);
+            }
+        }
+
+        fn build(span: SpanRecord, children: &HashMap<&str, Vec<&SpanRecord>>) -> SpanNode {
+            let nested = children
+                .get(span.id.as_str())
+                .map(|spans| {
+                    spans
+                        .iter()
+                        .map(|child| build((*child).clone(), children))
+                        .collect()
+                })
+                .unwrap_or_default();
+            SpanNode {
+                span,
+                children: nested,
+            }
+        }
+        Some(build(root, &children))
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.read().len()
+    }
+
+    pub fn clear(&self) {
+        self.spans.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(id: &str, parent_id: Option<&str>, name: &str) -> SpanRecord {
+        SpanRecord {
+            id: id.to_string(),
+            parent_id: parent_id.map(str::to_string),
+            name: name.to_string(),
+            target: "app::http".to_string(),
+            level: "INFO".to_string(),
+            fields: HashMap::new(),
+            started_at: Utc::now(),
+            closed_at: None,
+            duration_us: None,
+            busy_us: 0,
+            events: 0,
+        }
+    }
+
+    #[test]
+    fn test_tree_and_listing() {
+        let store = SpanStore::default();
+        store.open(span("01", None, "request"));
+        store.open(span("02", Some("01"), "db_query"));
+        store.open(span("03", Some("02"), "connect"));
+        store.open(span("04", Some("01"), "render"));
+        store.open(span("05", None, "request"));
+
+        let tree = store.tree("01").unwrap();
+        assert_eq!(tree.children.len(), 2);
+        assert_eq!(tree.children[0].span.name, "db_query");
+        assert_eq!(tree.children[0].children[0].span.name, "connect");
+        assert!(store.tree("99").is_none());
+
+        let roots = SpanQuery {
+            roots: true,
+            ..Default::default()
+        };
+        let listed: Vec<_> = store.list(&roots, 10).into_iter().map(|s| s.id).collect();
+        assert_eq!(listed, ["05", "01"]);
+
+        store.close("02", Utc::now(), 150, 3);
+        let open = SpanQuery {
+            open: Some(true),
+            parent_id: Some("01".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(store.list(&open, 10)[0].id, "04");
+        let closed = store.tree("02").unwrap().span;
+        assert_eq!(closed.busy_us, 150);
+        assert_eq!(closed.events, 3);
+        assert!(closed.duration_us.is_some());
+    }
+
+    #[test]
+    fn test_slowest_closed_spans() {
+        let store = SpanStore::default();
+        let started_at = Utc::now();
+        for (id, name, ms) in [
+            ("01", "request", 40),
+            ("02", "request", 250),
+            ("03", "db", 900),
+        ] {
+            store.open(SpanRecord {
+                started_at,
+                ..span(id, None, name)
+            });
+            store.close(id, started_at + chrono::Duration::milliseconds(ms), 0, 1);
+        }
+        store.open(span("04", None, "request"));
+
+        let all = SpanQuery::default();
+        let slow: Vec<_> = store
+            .slowest(&all, 100_000, 10)
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(slow, ["03", "02"]);
+        let requests = SpanQuery {
+            name: Some("request".to_string()),
+            ..Default::default()
+        };
+        let slow = store.slowest(&requests, 0, 10);
+        assert_eq!(slow.len(), 2);
+        assert_eq!(slow[0].duration_us, Some(250_000));
+        assert_eq!(store.slowest(&all, 0, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_oldest_spans_are_dropped() {
+        let store = SpanStore::with_max_spans(2);
+        store.open(span("01", None, "a"));
+        store.open(span("02", None, "b"));
+        store.open(span("03", None, "c"));
+        assert_eq!(store.len(), 2);
+        assert!(store.tree("01").is_none());
+        // Closing a dropped span is a no-op
+        store.close("01", Utc::now(), 0, 0);
+    }
+
+    #[test]
+    fn test_closed_roots_are_announced() {
+        let store = SpanStore::default();
+        let mut completed = store.subscribe_completed();
+        store.open(span("01", None, "request"));
+        store.open(span("02", Some("01"), "db_query"));
+        store.close("02", Utc::now(), 0, 0);
+        store.close("01", Utc::now(), 0, 0);
+        assert_eq!(completed.try_recv().unwrap(), "01");
+        assert!(completed.try_recv().is_err());
+    }
+}
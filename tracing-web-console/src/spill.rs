@@ -0,0 +1,359 @@
+//! Size-capped segment files holding events evicted from the buffer
+//!
+//! Segments are NDJSON files named `spill-<seq>.ndjson` after the sequence
+//! number of their first event, so names sort oldest first. Queries read them
+//! back to reach beyond the in-memory window. Sequence numbers restart with
+//! every run, so segments left over from a previous run are removed when the
+//! spill is opened. Other files in the directory are never touched.
+//!
+//! Evicted events are queued and written by a background thread, so storing
+//! events never waits for the disk.
+
+use crate::cancel::CancelToken;
+use crate::storage::LogEvent;
+use parking_lot::Mutex;
+use std::fs;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+/// Prefix of segment file names
+const SEGMENT_PREFIX: &str = "spill-";
+/// Extension of segment files
+const SEGMENT_EXTENSION: &str = "ndjson";
+/// Digits of the sequence number in segment file names
+const SEQ_DIGITS: usize = 20;
+/// The size limit is split across this many segments, the oldest segment is
+/// deleted as a whole once the limit is exceeded
+const SEGMENTS: u64 = 8;
+/// Lines read between checks for a cancelled query
+const CANCEL_CHECK_INTERVAL: usize = 4096;
+/// Queued events waiting for the writer, later ones are discarded and counted
+const MAX_QUEUED: usize = 65_536;
+/// How long the writer sleeps when nothing wakes it, it exits once the spill
+/// is dropped
+const WRITER_IDLE: Duration = Duration::from_secs(1);
+
+/// The segment events are currently appended to
+struct Segment {
+    path: PathBuf,
+    file: BufWriter<fs::File>,
+    len: u64,
+}
+
+/// Directory of segment files receiving evicted events
+pub(crate) struct Spill {
+    dir: PathBuf,
+    max_bytes: u64,
+    current: Mutex<Option<Segment>>,
+    /// Evicted events not written yet, see `queue`
+    queued: Mutex<Vec<LogEvent>>,
+    /// Events discarded because the queue was full
+    discarded: AtomicU64,
+    /// The background writer, woken by `queue`
+    writer: OnceLock<Thread>,
+}
+
+impl Spill {
+    /// Open a spill directory, removing segments of a previous run
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let spill = Self {
+            dir,
+            max_bytes: max_bytes.max(1),
+            current: Mutex::new(None),
+            queued: Mutex::new(Vec::new()),
+            discarded: AtomicU64::new(0),
+            writer: OnceLock::new(),
+        };
+        for (path, _) in spill.segments() {
+            fs::remove_file(path)?;
+        }
+        Ok(spill)
+    }
+
+    /// Segment files with their sizes, oldest first
+    fn segments(&self) -> Vec<(PathBuf, u64)> {
+        let mut segments: Vec<_> = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| is_segment(path))
+            .filter_map(|path| Some((path.clone(), fs::metadata(&path).ok()?.len())))
+            .collect();
+        segments.sort();
+        segments
+    }
+
+    /// Start the thread writing queued events, later calls are ignored
+    pub fn start_writer(self: &Arc<Self>) {
+        let spill = Arc::downgrade(self);
+        let handle = thread::Builder::new()
+            .name("log-spill".to_string())
+            .spawn(move || Self::run_writer(spill));
+        match handle {
+            Ok(handle) => {
+                let _ = self.writer.set(handle.thread().clone());
+            }
+            Err(e) => tracing::warn!("Failed to start the spill writer: {}", e),
+        }
+    }
+
+    fn run_writer(spill: Weak<Self>) {
+        loop {
+            thread::park_timeout(WRITER_IDLE);
+            let Some(spill) = spill.upgrade() else {
+                return;
+            };
+            if let Err(e) = spill.write_queued(&mut spill.current.lock()) {
+                tracing::warn!("Failed to spill evicted events: {}", e);
+            }
+            let discarded = spill.discarded.swap(0, Ordering::Relaxed);
+            if discarded > 0 {
+                tracing::warn!(
+                    "Discarded {} evicted events, the spill fell behind",
+                    discarded
+                );
+            }
+        }
+    }
+
+    /// Queue an evicted event for the background writer
+    ///
+    /// Without a running writer the queue is written by the next `scan`,
+    /// `retain` or `append`.
+    pub fn queue(&self, event: LogEvent) {
+        {
+            let mut queued = self.queued.lock();
+            if queued.len() >= MAX_QUEUED {
+                self.discarded.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            queued.push(event);
+        }
+        if let Some(writer) = self.writer.get() {
+            writer.unpark();
+        }
+    }
+
+    /// Write the queued events, called with `current` locked so they keep
+    /// their order
+    fn write_queued(&self, current: &mut Option<Segment>) -> io::Result<()> {
+        let queued = std::mem::take(&mut *self.queued.lock());
+        for event in &queued {
+            self.write(current, event)?;
+        }
+        Ok(())
+    }
+
+    /// Append an evicted event after the queued ones, starting a new segment
+    /// and deleting the oldest ones when the size limit is reached
+    pub fn append(&self, event: &LogEvent) -> io::Result<()> {
+        let mut current = self.current.lock();
+        self.write_queued(&mut current)?;
+        self.write(&mut current, event)
+    }
+
+    fn write(&self, current: &mut Option<Segment>, event: &LogEvent) -> io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        let line_len = line.len() as u64;
+
+        let segment_bytes = (self.max_bytes / SEGMENTS).max(1);
+        if current
+            .as_ref()
+            .is_none_or(|segment| segment.len > 0 && segment.len + line_len > segment_bytes)
+        {
+            if let Some(mut segment) = current.take() {
+                segment.file.flush()?;
+            }
+            let path = self.dir.join(format!(
+                "{SEGMENT_PREFIX}{:020}.{SEGMENT_EXTENSION}",
+                event.seq
+            ));
+            *current = Some(Segment {
+                file: BufWriter::new(fs::File::create(&path)?),
+                path,
+                len: 0,
+            });
+            self.enforce_limit(current.as_ref())?;
+        }
+
+        let segment = current.as_mut().expect("segment was just created");
+        segment.file.write_all(&line)?;
+        segment.len += line_len;
+        Ok(())
+    }
+
+    /// Delete the oldest segments beyond the size limit, never the current one
+    fn enforce_limit(&self, current: Option<&Segment>) -> io::Result<()> {
+        let segments = self.segments();
+        let mut total: u64 = segments.iter().map(|(_, len)| len).sum();
+        for (path, len) in segments {
+            if total <= self.max_bytes || current.is_some_and(|c| c.path == path) {
+                break;
+            }
+            fs::remove_file(&path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    /// Read every spilled event, oldest first
+    ///
+    /// Returns the number of events read, or `None` if cancelled.
+    pub fn scan(
+        &self,
+        cancel: &CancelToken,
+        mut f: impl FnMut(LogEvent),
+    ) -> io::Result<Option<usize>> {
+        {
+            let mut current = self.current.lock();
+            self.write_queued(&mut current)?;
+            if let Some(segment) = current.as_mut() {
+                segment.file.flush()?;
+            }
+        }
+        let mut read = 0;
+        for (path, _) in self.segments() {
+            for line in read_lines(&path)? {
+                if read % CANCEL_CHECK_INTERVAL == 0 && cancel.is_cancelled() {
+                    return Ok(None);
+                }
+                read += 1;
+                if let Ok(event) = serde_json::from_str(&line?) {
+                    f(event);
+                }
+            }
+        }
+        Ok(Some(read))
+    }
+
+    /// Remove every spilled event for which `keep` returns false
+    ///
+    /// Segments are rewritten, appending continues in a new segment. Returns
+    /// the number of removed events.
+    pub fn retain(&self, keep: impl Fn(&LogEvent) -> bool) -> io::Result<usize> {
+        let mut current = self.current.lock();
+        self.write_queued(&mut current)?;
+        if let Some(mut segment) = current.take() {
+            segment.file.flush()?;
+        }
+
+        let mut removed = 0;
+        for (path, _) in self.segments() {
+            let mut kept = Vec::new();
+            for line in read_lines(&path)? {
+                let line = line?;
+                match serde_json::from_str::<LogEvent>(&line) {
+                    Ok(event) if !keep(&event) => removed += 1,
+                    _ => {
+                        kept.extend_from_slice(line.as_bytes());
+                        kept.push(b'\n');
+                    }
+                }
+            }
+            if kept.is_empty() {
+                fs::remove_file(&path)?;
+            } else {
+                let tmp = path.with_extension("tmp");
+                fs::write(&tmp, &kept)?;
+                fs::rename(&tmp, &path)?;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Remove all spilled events
+    pub fn clear(&self) -> io::Result<()> {
+        let mut current = self.current.lock();
+        *current = None;
+        self.queued.lock().clear();
+        for (path, _) in self.segments() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `path` names a segment file created by a spill
+fn is_segment(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix(SEGMENT_PREFIX))
+        .and_then(|name| name.strip_suffix(SEGMENT_EXTENSION))
+        .and_then(|name| name.strip_suffix('.'))
+        .is_some_and(|seq| seq.len() == SEQ_DIGITS && seq.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn read_lines(path: &Path) -> io::Result<io::Lines<io::BufReader<fs::File>>> {
+    Ok(io::BufReader::new(fs::File::open(path)?).lines())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn event(seq: u64, message: &str) -> LogEvent {
+        LogEvent {
+            seq,
+            ..LogEvent::internal("INFO", message, HashMap::new())
+        }
+    }
+
+    fn messages(spill: &Spill) -> Vec<String> {
+        let mut messages = Vec::new();
+        spill
+            .scan(&CancelToken::default(), |e| messages.push(e.message))
+            .unwrap();
+        messages
+    }
+
+    #[test]
+    fn test_oldest_segments_are_dropped() {
+        let dir = std::env::temp_dir().join(format!("spill-test-{}", crate::id::new_ulid()));
+        let line_len = serde_json::to_vec(&event(1, "event 01")).unwrap().len() as u64 + 1;
+        // About two events per segment
+        let spill = Spill::open(&dir, line_len * 2 * SEGMENTS).unwrap();
+
+        for seq in 1..=20 {
+            spill
+                .append(&event(seq, &format!("event {:02}", seq)))
+                .unwrap();
+        }
+        let kept = messages(&spill);
+        assert!(kept.len() < 20);
+        assert_eq!(kept.last().unwrap(), "event 20");
+        let mut sorted = kept.clone();
+        sorted.sort();
+        assert_eq!(kept, sorted);
+
+        assert_eq!(spill.retain(|e| e.message != "event 20").unwrap(), 1);
+        assert_eq!(messages(&spill).last().unwrap(), "event 19");
+        spill.append(&event(21, "event 21")).unwrap();
+        assert_eq!(messages(&spill).last().unwrap(), "event 21");
+
+        // Queued events are written before reading without a writer thread
+        spill.queue(event(22, "event 22"));
+        assert_eq!(messages(&spill).last().unwrap(), "event 22");
+
+        spill.clear().unwrap();
+        assert!(messages(&spill).is_empty());
+
+        // Segments of a previous run are removed, other files are kept
+        spill.append(&event(23, "event 23")).unwrap();
+        drop(spill);
+        let other = dir.join("events.ndjson");
+        fs::write(&other, "{}\n").unwrap();
+        let reopened = Spill::open(&dir, 1024).unwrap();
+        assert!(messages(&reopened).is_empty());
+        assert!(other.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,293 @@
+//! Bounded on-disk queue for batches a remote destination couldn't accept
+
+use crate::sink::BatchSink;
+use crate::storage::LogEvent;
+use parking_lot::Mutex;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Extension of spooled batch files
+const SPOOL_EXTENSION: &str = "ndjson";
+/// Extension of batch files still being written
+const TMP_EXTENSION: &str = "tmp";
+/// Wait this long after a failed delivery before trying the destination again
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Destination whose delivery can fail, e.g. a remote collector
+///
+/// Wrapped in a spool via
+/// [`TracingLayerBuilder::with_spooled_batch_sink`](crate::TracingLayerBuilder::with_spooled_batch_sink),
+/// batches that fail are kept on disk and retried in order.
+pub trait TryBatchSink: Send + Sync {
+    /// Deliver a non-empty batch of events, oldest first
+    fn try_write_batch(&self, events: &[LogEvent]) -> io::Result<()>;
+}
+
+/// Directory of NDJSON batch files, named by a counter so they sort oldest first
+pub(crate) struct DiskSpool {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Number of the next batch file, continues after the files on disk
+    next: AtomicU64,
+}
+
+impl DiskSpool {
+    /// Open a spool directory, removing batches left half-written by a crash
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == TMP_EXTENSION) {
+                fs::remove_file(path)?;
+            }
+        }
+        let spool = Self {
+            dir,
+            max_bytes,
+            next: AtomicU64::new(0),
+        };
+        let next = spool
+            .files()
+            .last()
+            .map_or(0, |(path, _)| batch_number(path) + 1);
+        spool.next.store(next, Ordering::Relaxed);
+        Ok(spool)
+    }
+
+    /// Spooled batch files with their sizes, oldest first
+    fn files(&self) -> Vec<(PathBuf, u64)> {
+        let mut files: Vec<_> = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == SPOOL_EXTENSION))
+            .filter_map(|path| Some((path.clone(), fs::metadata(&path).ok()?.len())))
+            .collect();
+        files.sort_by_key(|(path, _)| batch_number(path));
+        files
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files().is_empty()
+    }
+
+    /// Append a batch, dropping the oldest batches beyond the size limit
+    ///
+    /// Returns the number of events dropped to make room.
+    pub fn push(&self, events: &[LogEvent]) -> io::Result<usize> {
+        let mut payload = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut payload, event)?;
+            payload.push(b'\n');
+        }
+
+        // Write to a temporary name first so readers never see partial batches
+        let name = format!("{:020}", self.next.fetch_add(1, Ordering::Relaxed));
+        let tmp = self.dir.join(format!("{}.{}", name, TMP_EXTENSION));
+        fs::File::create(&tmp)?.write_all(&payload)?;
+        fs::rename(&tmp, self.dir.join(format!("{}.{}", name, SPOOL_EXTENSION)))?;
+
+        let files = self.files();
+        let mut total: u64 = files.iter().map(|(_, len)| len).sum();
+        let mut dropped = 0;
+        for (path, len) in &files {
+            if total <= self.max_bytes {
+                break;
+            }
+            dropped += read_batch(path).map_or(0, |batch| batch.len());
+            fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(dropped)
+    }
+
+    /// Oldest spooled batch and its file, remove the file once delivered
+    pub fn oldest(&self) -> Option<(PathBuf, Vec<LogEvent>)> {
+        let (path, _) = self.files().into_iter().next()?;
+        match read_batch(&path) {
+            Ok(batch) => Some((path, batch)),
+            Err(e) => {
+                // Unreadable files would block the queue forever
+                tracing::warn!("Dropping unreadable spool file {}: {}", path.display(), e);
+                let _ = fs::remove_file(&path);
+                self.oldest()
+            }
+        }
+    }
+}
+
+/// Number of a batch file from its name, files of other names sort first
+fn batch_number(path: &Path) -> u64 {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Read a spooled batch, lines that fail to parse are skipped
+fn read_batch(path: &Path) -> io::Result<Vec<LogEvent>> {
+    let file = io::BufReader::new(fs::File::open(path)?);
+    let mut events = Vec::new();
+    for line in file.lines() {
+        if let Ok(event) = serde_json::from_str(&line?) {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+/// Batch sink spooling to disk while its destination is unreachable
+///
+/// Spooled batches are delivered oldest first before any new batch, so the
+/// destination still receives events in order after an outage.
+pub(crate) struct SpoolingSink<S> {
+    inner: S,
+    spool: DiskSpool,
+    /// When delivery last failed, new batches go straight to disk until
+    /// `RETRY_INTERVAL` has passed
+    failed_at: Mutex<Option<Instant>>,
+}
+
+impl<S: TryBatchSink> SpoolingSink<S> {
+    pub fn new(inner: S, spool: DiskSpool) -> Self {
+        Self {
+            inner,
+            spool,
+            failed_at: Mutex::new(None),
+        }
+    }
+
+    /// Deliver spooled batches, returns false if the destination is still down
+    fn drain(&self) -> bool {
+        while let Some((path, batch)) = self.spool.oldest() {
+            if !batch.is_empty() && self.inner.try_write_batch(&batch).is_err() {
+                return false;
+            }
+            let _ = fs::remove_file(&path);
+        }
+        true
+    }
+
+    fn spool(&self, events: &[LogEvent]) {
+        match self.spool.push(events) {
+            Ok(0) => {}
+            Ok(dropped) => tracing::warn!("Spool full, dropped {} oldest events", dropped),
+            Err(e) => tracing::warn!("Failed to spool {} events: {}", events.len(), e),
+        }
+    }
+}
+
+impl<S: TryBatchSink> BatchSink for SpoolingSink<S> {
+    fn write_batch(&self, events: &[LogEvent]) {
+        let mut failed_at = self.failed_at.lock();
+        if failed_at.is_some_and(|at| at.elapsed() < RETRY_INTERVAL) {
+            self.spool(events);
+            return;
+        }
+
+        let delivered = self.drain() && self.inner.try_write_batch(events).is_ok();
+        if delivered {
+            if failed_at.take().is_some() {
+                tracing::info!("Destination reachable again, spool drained");
+            }
+        } else {
+            if failed_at.is_none() {
+                tracing::warn!("Destination unreachable, spooling batches to disk");
+            }
+            *failed_at = Some(Instant::now());
+            self.spool(events);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct FlakySink {
+        down: Arc<AtomicBool>,
+        received: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TryBatchSink for FlakySink {
+        fn try_write_batch(&self, events: &[LogEvent]) -> io::Result<()> {
+            if self.down.load(Ordering::Relaxed) {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "down"));
+            }
+            let mut received = self.received.lock();
+            received.extend(events.iter().map(|e| e.message.clone()));
+            Ok(())
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("twc-spool-{}", crate::id::new_ulid()))
+    }
+
+    fn batch(message: &str) -> Vec<LogEvent> {
+        vec![LogEvent::internal("INFO", message, HashMap::new())]
+    }
+
+    #[test]
+    fn test_spooled_batches_are_delivered_in_order() {
+        let dir = temp_dir();
+        let sink = FlakySink::default();
+        let spooling = SpoolingSink::new(sink.clone(), DiskSpool::open(&dir, 1 << 20).unwrap());
+
+        sink.down.store(true, Ordering::Relaxed);
+        spooling.write_batch(&batch("first"));
+        spooling.write_batch(&batch("second"));
+        assert!(!spooling.spool.is_empty());
+
+        sink.down.store(false, Ordering::Relaxed);
+        // Skip the retry interval
+        *spooling.failed_at.lock() = None;
+        spooling.write_batch(&batch("third"));
+
+        assert_eq!(*sink.received.lock(), ["first", "second", "third"]);
+        assert!(spooling.spool.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_spool_drops_oldest_beyond_limit() {
+        let dir = temp_dir();
+        let spool = DiskSpool::open(&dir, 1).unwrap();
+        spool.push(&batch("old")).unwrap();
+        let dropped = spool.push(&batch("new")).unwrap();
+
+        // Both exceed one byte, the limit keeps dropping until empty
+        assert_eq!(dropped, 2);
+        assert!(spool.oldest().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reopened_spool_continues_in_order() {
+        let dir = temp_dir();
+        let spool = DiskSpool::open(&dir, 1 << 20).unwrap();
+        spool.push(&batch("first")).unwrap();
+        drop(spool);
+
+        // A batch interrupted while being written
+        fs::write(dir.join("00000000000000000001.tmp"), "{").unwrap();
+        let spool = DiskSpool::open(&dir, 1 << 20).unwrap();
+        assert!(!dir.join("00000000000000000001.tmp").exists());
+        spool.push(&batch("second")).unwrap();
+
+        let (path, events) = spool.oldest().unwrap();
+        assert_eq!(events[0].message, "first");
+        fs::remove_file(path).unwrap();
+        assert_eq!(spool.oldest().unwrap().1[0].message, "second");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,42 +1,212 @@
 //! Log storage with circular buffer implementation
 
 use chrono::{DateTime, Utc};
-use parking_lot::RwLock;
+use futures::Stream;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, OnceLock};
 use tokio::sync::broadcast;
 
+use crate::cancel::CancelToken;
+use crate::compaction::SummaryStore;
+use crate::diagnostics::Diagnostics;
+use crate::expr::FilterExpr;
+use crate::intern::Interned;
+use crate::limits::{truncate_string, ValueLimits, MIN_VALUE_LEN};
+use crate::partition::{partition_of, PartitionCount, Partitions};
+use crate::pending::{PendingShards, Waiters};
+use crate::query_cache::QueryCache;
+use crate::retention::LevelCaps;
+use crate::sink::EventSink;
+use crate::span_index::SpanFieldIndex;
+use crate::spans::SpanStore;
+use crate::spill::Spill;
+use crate::stream_clients::StreamClients;
+use crate::target_stats::{TargetOverview, TargetStats, TargetStatsResponse};
+use crate::target_trie::TargetTrie;
+use crate::transform::TransformPipeline;
+
 /// Maximum number of log events to store in memory
 const DEFAULT_MAX_EVENTS: usize = 10_000;
 /// Capacity of the broadcast channel for real-time log streaming
 const BROADCAST_CAPACITY: usize = 100;
+/// Maximum number of markers kept, the oldest are dropped first
+const MAX_MARKERS: usize = 1_000;
+/// Events scanned between checks for a cancelled query
+const CANCEL_CHECK_INTERVAL: usize = 4096;
+/// Times `push` tries the write lock before waiting for it, another thread
+/// holding it usually stores the event in the meantime
+const COMBINE_ATTEMPTS: usize = 4;
+/// Message handled by the capture queue's thread, see `start_capture_queue`
+enum CaptureMessage {
+    Event(LogEvent),
+    Flush(mpsc::Sender<()>),
+}
 
 /// A single log event captured by the subscriber
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEvent {
+    /// Unique, time-sortable identifier (ULID) usable as a permalink
+    #[serde(default)]
+    pub id: String,
+    /// Position in the buffer's history, assigned when stored
+    ///
+    /// Strictly increasing per storage but may skip values, use it with
+    /// `after_seq` to fetch only newer events.
+    #[serde(default)]
+    pub seq: u64,
     pub timestamp: DateTime<Utc>,
-    pub level: String,
-    pub target: String,
+    /// Level, target and field names are shared between events, see `Interned`
+    pub level: Interned,
+    pub target: Interned,
     pub message: String,
-    pub fields: HashMap<String, String>,
+    /// Field values as recorded, numbers and booleans keep their JSON type
+    pub fields: HashMap<Interned, Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub span: Option<SpanInfo>,
+    /// Module the event was logged from, e.g. `my_app::orders`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub line: Option<u32>,
+    /// Link to the source location, if a source URL template is configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    /// Name of the thread the event was logged on, e.g. `tokio-runtime-worker`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<u64>,
+    /// How often the event occurred in a row, set once a repeat was collapsed
+    /// into it by deduplication
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_count: Option<u64>,
+    /// Whether an error was recorded on the event, its causes are in the
+    /// `error.chain` field
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_error: bool,
+    /// Whether the message or a field value was cut to the configured
+    /// maximum length
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+/// Target used for events emitted by the console itself
+pub const INTERNAL_TARGET: &str = "tracing_web_console";
+/// Target for the console's own state changes (started, cleared, client connected, ...)
+pub const LIFECYCLE_TARGET: &str = "tracing_web_console::lifecycle";
+
+impl LogEvent {
+    /// Create an event describing the console's own state
+    ///
+    /// These are pushed straight into storage since the crate's own target is
+    /// excluded from capture to avoid recursion.
+    pub(crate) fn internal(level: &str, message: &str, fields: HashMap<String, String>) -> Self {
+        let fields = fields
+            .into_iter()
+            .map(|(key, value)| (Interned::from(key), Value::String(value)))
+            .collect();
+        Self {
+            id: crate::id::new_ulid(),
+            seq: 0,
+            timestamp: Utc::now(),
+            level: level.into(),
+            target: Interned::from_static(INTERNAL_TARGET),
+            message: message.to_string(),
+            fields,
+            span: None,
+            module_path: None,
+            file: None,
+            line: None,
+            source_url: None,
+            thread_name: None,
+            thread_id: None,
+            repeat_count: None,
+            is_error: false,
+            truncated: false,
+        }
+    }
+
+    /// Create a lifecycle event, `kind` is a stable identifier like "started"
+    pub(crate) fn lifecycle(
+        kind: &str,
+        message: &str,
+        mut fields: HashMap<String, String>,
+    ) -> Self {
+        fields.insert("lifecycle".to_string(), kind.to_string());
+        Self {
+            target: Interned::from_static(LIFECYCLE_TARGET),
+            ..Self::internal("INFO", message, fields)
+        }
+    }
+}
+
+/// Text of a field value as displayed and matched by filters
+///
+/// Strings are used as they are, other values in their JSON form.
+pub fn field_text(value: &Value) -> Cow<'_, str> {
+    match value {
+        Value::String(text) => Cow::Borrowed(text),
+        other => Cow::Owned(other.to_string()),
+    }
+}
+
+/// State of capture as returned by GET /api/capture
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureStatus {
+    pub paused: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused_at: Option<DateTime<Utc>>,
+    /// Events discarded since capture was paused
+    pub skipped: u64,
+}
+
+/// A point in time worth annotating, e.g. "v2.3.1 deployed"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub label: String,
+    /// Free-form category such as "deploy" or "config"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
 }
 
 /// Information about the span context
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpanInfo {
+    /// ID of the span's record, `None` for spans not captured as records
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub name: String,
     pub fields: HashMap<String, String>,
+    /// Names of the enclosing spans, outermost first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ancestors: Vec<String>,
+    /// Fields of the enclosing spans not set on this span, the closest span wins
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub parent_fields: HashMap<String, String>,
+}
+
+impl SpanInfo {
+    /// Value of a field on this span or any enclosing span
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(name)
+            .or_else(|| self.parent_fields.get(name))
+            .map(String::as_str)
+    }
 }
 
 /// Sort order for log queries
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub enum SortOrder {
     /// Newest logs first (default)
     #[default]
@@ -53,11 +223,263 @@ pub struct LogFilter {
     pub search: Option<String>,
     pub target: Option<String>,
     pub sort_order: SortOrder,
+    /// Only match events stored after this sequence number
+    pub after_seq: Option<u64>,
+    /// Only match events stored before this sequence number
+    pub before_seq: Option<u64>,
+    /// Only match events logged at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only match events logged at or before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Filter expression such as `span:create_order AND level>=debug`
+    pub expr: Option<FilterExpr>,
+    /// Hide events of these targets and their submodules
+    pub exclude_targets: Vec<String>,
+    /// Hide events whose message contains this (case-insensitive)
+    pub exclude_search: Option<String>,
+    /// Hide events of these levels
+    pub exclude_levels: Vec<String>,
+    /// Only match events logged on the thread with this name or ID
+    pub thread: Option<String>,
+    /// Only match events of this partition, as (field, value), events without
+    /// the field belong to the partition ""
+    pub partition: Option<(String, String)>,
+    /// `target_levels` as a prefix tree, built on the first match
+    pub(crate) level_trie: OnceLock<TargetTrie<u8>>,
+}
+
+impl LogFilter {
+    /// Whether the filter has no criteria and matches every event
+    pub(crate) fn is_unrestricted(&self) -> bool {
+        self.global_level.is_none()
+            && self.target_levels.is_empty()
+            && self.search.is_none()
+            && self.target.is_none()
+            && self.after_seq.is_none()
+            && self.before_seq.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+            && self.expr.is_none()
+            && self.thread.is_none()
+            && self.partition.is_none()
+            && !self.has_exclusions()
+    }
+
+    /// Whether any `exclude_*` criterion is set
+    pub(crate) fn has_exclusions(&self) -> bool {
+        !self.exclude_targets.is_empty()
+            || self.exclude_search.is_some()
+            || !self.exclude_levels.is_empty()
+    }
+
+    /// Check if an event is hidden by the `exclude_*` criteria
+    pub(crate) fn excludes(&self, event: &LogEvent) -> bool {
+        let excluded_target = self.exclude_targets.iter().any(|target| {
+            event
+                .target
+                .strip_prefix(target.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        });
+        excluded_target
+            || self
+                .exclude_levels
+                .iter()
+                .any(|level| level.eq_ignore_ascii_case(&event.level))
+            || self.exclude_search.as_ref().is_some_and(|search| {
+                event
+                    .message
+                    .to_lowercase()
+                    .contains(&search.to_lowercase())
+            })
+    }
+
+    /// Check if an event matches the filter criteria
+    pub(crate) fn matches(&self, event: &LogEvent) -> bool {
+        // Cheapest check first, time ranges usually exclude most events. The
+        // buffer is ordered by `seq`, not by time: ingested events keep their
+        // source timestamps and concurrent pushes interleave threads.
+        if self.from.is_some_and(|from| event.timestamp < from)
+            || self.to.is_some_and(|to| event.timestamp > to)
+        {
+            return false;
+        }
+
+        // Determine the required log level for this event's target
+        // Target filters take precedence over global level
+        // Use prefix matching: "my_crate" matches "my_crate::module::thing",
+        // the most specific (longest) target wins
+        let target_level = self
+            .level_trie
+            .get_or_init(|| {
+                self.target_levels
+                    .iter()
+                    .map(|(target, level)| (target, level_to_number(level)))
+                    .collect()
+            })
+            .longest_match(&event.target)
+            .copied();
+
+        // Target-specific level takes precedence, then fall back to global level
+        let required_level =
+            target_level.or_else(|| self.global_level.as_deref().map(level_to_number));
+
+        // Event level must be >= required level (higher severity)
+        if let Some(required_level_num) = required_level {
+            if level_to_number(&event.level) < required_level_num {
+                return false;
+            }
+        }
+
+        if let Some(after_seq) = self.after_seq {
+            if event.seq <= after_seq {
+                return false;
+            }
+        }
+
+        if let Some(before_seq) = self.before_seq {
+            if event.seq >= before_seq {
+                return false;
+            }
+        }
+
+        if let Some(expr) = &self.expr {
+            if !expr.matches(event) {
+                return false;
+            }
+        }
+
+        if self.excludes(event) {
+            return false;
+        }
+
+        if let Some((field, value)) = &self.partition {
+            if partition_of(event, field).unwrap_or_default() != value.as_str() {
+                return false;
+            }
+        }
+
+        if let Some(ref thread) = self.thread {
+            let by_name = event.thread_name.as_deref() == Some(thread.as_str());
+            let by_id = event.thread_id.is_some_and(|id| id.to_string() == *thread);
+            if !by_name && !by_id {
+                return false;
+            }
+        }
+
+        // Filter by target (case-insensitive contains)
+        if let Some(ref target_filter) = self.target {
+            if !event
+                .target
+                .to_lowercase()
+                .contains(&target_filter.to_lowercase())
+            {
+                return false;
+            }
+        }
+
+        // Filter by search term in message (case-insensitive contains)
+        if let Some(ref search) = self.search {
+            if !event
+                .message
+                .to_lowercase()
+                .contains(&search.to_lowercase())
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Spilled events matching `filter` that can appear on the requested page,
+/// oldest first, with the number of matching and of read events
+///
+/// `buffered` holds the sequence numbers of the matching events read from
+/// the buffer up to `last_seq`. The buffer's lock isn't held while reading,
+/// so events spilled since then are skipped, they were either read from the
+/// buffer already or stored after it was read. Returns `None` if cancelled.
+fn spilled_page(
+    spill: &Spill,
+    filter: &LogFilter,
+    limit: Option<usize>,
+    offset: usize,
+    buffered: &HashSet<u64>,
+    last_seq: u64,
+    cancel: &CancelToken,
+) -> Option<(VecDeque<LogEvent>, usize, usize)> {
+    let reach = offset.saturating_add(limit.unwrap_or(usize::MAX));
+    let keep = match filter.sort_order {
+        // Pages newest first start with the buffered events
+        SortOrder::NewestFirst => reach.saturating_sub(buffered.len()),
+        SortOrder::OldestFirst => reach,
+    };
+    let mut kept = VecDeque::new();
+    let mut matched = 0;
+    let read = spill.scan(cancel, |event| {
+        if event.seq > last_seq || buffered.contains(&event.seq) || !filter.matches(&event) {
+            return;
+        }
+        matched += 1;
+        match filter.sort_order {
+            SortOrder::NewestFirst if keep > 0 => {
+                if kept.len() == keep {
+                    kept.pop_front();
+                }
+                kept.push_back(event);
+            }
+            SortOrder::OldestFirst if kept.len() < keep => kept.push_back(event),
+            _ => {}
+        }
+    });
+    match read {
+        Ok(Some(read)) => Some((kept, matched, read)),
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!("Failed to read spilled events: {}", e);
+            Some((VecDeque::new(), 0, 0))
+        }
+    }
+}
+
+/// Result of a filtered query
+#[derive(Debug, Clone)]
+pub struct QueryResult {
+    /// The requested page of events
+    pub events: Vec<LogEvent>,
+    /// Number of events examined
+    pub scanned: usize,
+    /// Number of events matching the filter, before pagination
+    pub matched: usize,
+    /// Whether the limit cut off matching events after this page
+    pub truncated: bool,
+    /// Whether the result was served from the query cache
+    pub cached: bool,
+    /// Highest sequence number assigned so far, pass as `after_seq` to get newer events
+    pub last_seq: u64,
+}
+
+/// Fill `{file}`, `{line}` and `{module}` of a source URL template
+///
+/// Only files with a relative path, i.e. in the application's workspace, are
+/// linked. Dependencies are compiled from absolute paths outside the repository.
+pub(crate) fn source_url(template: &str, event: &LogEvent) -> Option<String> {
+    let file = event.file.as_deref()?;
+    if Path::new(file).is_absolute() || file.starts_with('/') {
+        return None;
+    }
+    let line = event.line.map(|line| line.to_string()).unwrap_or_default();
+    Some(
+        template
+            .replace("{file}", &file.replace('\\', "/"))
+            .replace("{line}", &line)
+            .replace("{module}", event.module_path.as_deref().unwrap_or_default()),
+    )
 }
 
 /// Convert log level string to numeric value for comparison
 /// Higher number = higher severity (ERROR > WARN > INFO > DEBUG > TRACE)
-fn level_to_number(level: &str) -> u8 {
+pub(crate) fn level_to_number(level: &str) -> u8 {
     match level.to_uppercase().as_str() {
         "ERROR" => 5,
         "WARN" => 4,
@@ -68,12 +490,86 @@ fn level_to_number(level: &str) -> u8 {
     }
 }
 
+/// Hash of what makes an event a repeat of another, see `collapse_repeat`
+///
+/// Fields are only counted, equal hashes are compared in full anyway.
+fn repeat_fingerprint(event: &LogEvent) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    event.level.hash(&mut hasher);
+    event.target.hash(&mut hasher);
+    event.message.hash(&mut hasher);
+    event.fields.len().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Binary search a stored event, the buffer is ordered by sequence number
+fn find_seq(events: &VecDeque<LogEvent>, seq: u64) -> Option<&LogEvent> {
+    let index = events.partition_point(|event| event.seq < seq);
+    events.get(index).filter(|event| event.seq == seq)
+}
+
 /// Thread-safe circular buffer for storing log events
 #[derive(Clone)]
 pub struct LogStorage {
     events: Arc<RwLock<VecDeque<LogEvent>>>,
-    max_events: usize,
+    /// Configured capacity, changeable at runtime
+    max_events: Arc<AtomicUsize>,
+    /// Capacity currently enforced, lowered under memory pressure
+    effective_capacity: Arc<AtomicUsize>,
     tx: broadcast::Sender<LogEvent>,
+    transforms: TransformPipeline,
+    sinks: Arc<RwLock<Vec<Arc<dyn EventSink>>>>,
+    markers: Arc<RwLock<VecDeque<Marker>>>,
+    /// Incremented on every change to `events`, while holding its write lock
+    generation: Arc<AtomicU64>,
+    query_cache: Arc<QueryCache>,
+    target_stats: Arc<TargetStats>,
+    /// Updated while holding the write lock of `events`
+    span_index: Arc<Mutex<SpanFieldIndex>>,
+    /// Events per partition, updated while holding the write lock of `events`
+    partitions: Arc<Mutex<Partitions>>,
+    /// Events per capped level, updated while holding the write lock of `events`
+    level_caps: Arc<Mutex<LevelCaps>>,
+    /// Per-minute summaries of compacted events
+    summaries: Arc<SummaryStore>,
+    /// Lifecycles of captured spans
+    spans: Arc<SpanStore>,
+    /// Events evicted to make room for new ones
+    evicted: Arc<AtomicU64>,
+    /// Events live stream clients missed because they fell behind
+    dropped: Arc<AtomicU64>,
+    /// The console's own diagnostic events, kept apart from captured ones
+    diagnostics: Arc<Diagnostics>,
+    /// Connected WebSocket clients with their send queues
+    stream_clients: Arc<StreamClients>,
+    /// Set while capture is paused, new events are discarded
+    paused: Arc<AtomicBool>,
+    /// When capture was paused
+    paused_at: Arc<Mutex<Option<DateTime<Utc>>>>,
+    /// Events discarded since capture was paused
+    skipped: Arc<AtomicU64>,
+    /// Template turning file and line into `source_url`, see `source_url`
+    source_url_template: Arc<RwLock<Option<String>>>,
+    /// Window in milliseconds within which repeats are collapsed, 0 disables it
+    dedup_window_ms: Arc<AtomicU64>,
+    /// Segment files receiving evicted events, read back by queries
+    spill: Arc<RwLock<Option<Arc<Spill>>>>,
+    /// Events waiting for the write lock of `events`
+    pending: Arc<PendingShards>,
+    /// Whether pushes store the waiting events of other threads, see `push`
+    combine_writes: Arc<AtomicBool>,
+    /// `repeat_fingerprint` of the newest stored event while deduplicating
+    newest_fingerprint: Arc<AtomicU64>,
+    /// Channel to the task storing captured events, see `start_capture_queue`
+    capture_queue: Arc<OnceLock<mpsc::SyncSender<CaptureMessage>>>,
+    /// Captured events discarded because the capture queue was full
+    capture_dropped: Arc<AtomicU64>,
+    /// Maximum message length in bytes, 0 keeps messages whole
+    max_message_len: Arc<AtomicUsize>,
+    /// Maximum length of text field values in bytes, 0 keeps them whole
+    max_field_len: Arc<AtomicUsize>,
 }
 
 impl LogStorage {
@@ -87,179 +583,1007 @@ impl LogStorage {
         let (tx, _) = broadcast::channel(BROADCAST_CAPACITY);
         Self {
             events: Arc::new(RwLock::new(VecDeque::with_capacity(max_events))),
-            max_events,
+            max_events: Arc::new(AtomicUsize::new(max_events)),
+            effective_capacity: Arc::new(AtomicUsize::new(max_events)),
             tx,
+            transforms: TransformPipeline::new(),
+            sinks: Arc::new(RwLock::new(Vec::new())),
+            markers: Arc::new(RwLock::new(VecDeque::new())),
+            generation: Arc::new(AtomicU64::new(0)),
+            query_cache: Arc::new(QueryCache::default()),
+            target_stats: Arc::new(TargetStats::default()),
+            span_index: Arc::new(Mutex::new(SpanFieldIndex::default())),
+            partitions: Arc::new(Mutex::new(Partitions::default())),
+            level_caps: Arc::new(Mutex::new(LevelCaps::default())),
+            summaries: Arc::new(SummaryStore::default()),
+            spans: Arc::new(SpanStore::default()),
+            evicted: Arc::new(AtomicU64::new(0)),
+            dropped: Arc::new(AtomicU64::new(0)),
+            diagnostics: Arc::new(Diagnostics::default()),
+            stream_clients: Arc::new(StreamClients::default()),
+            paused: Arc::new(AtomicBool::new(false)),
+            paused_at: Arc::new(Mutex::new(None)),
+            skipped: Arc::new(AtomicU64::new(0)),
+            source_url_template: Arc::new(RwLock::new(None)),
+            dedup_window_ms: Arc::new(AtomicU64::new(0)),
+            spill: Arc::new(RwLock::new(None)),
+            pending: Arc::new(PendingShards::default()),
+            combine_writes: Arc::new(AtomicBool::new(true)),
+            newest_fingerprint: Arc::new(AtomicU64::new(0)),
+            capture_queue: Arc::new(OnceLock::new()),
+            capture_dropped: Arc::new(AtomicU64::new(0)),
+            max_message_len: Arc::new(AtomicUsize::new(0)),
+            max_field_len: Arc::new(AtomicUsize::new(0)),
         }
     }
 
-    /// Add a new log event, removing oldest if at capacity
-    pub fn push(&self, event: LogEvent) {
-        let mut events = self.events.write();
-
-        if events.len() >= self.max_events {
-            events.pop_front();
-        }
+    /// Link stored events to their source, e.g.
+    /// `https://github.com/org/repo/blob/main/{file}#L{line}`
+    pub(crate) fn set_source_url_template(&self, template: Option<String>) {
+        *self.source_url_template.write() = template;
+    }
 
-        // Send to broadcast channel, ignore if no receivers
-        let _ = self.tx.send(event.clone());
+    /// Collapse an event into the newest stored one if it repeats it within
+    /// `window`, `None` stores every event
+    pub(crate) fn set_dedup_window(&self, window: Option<std::time::Duration>) {
+        let window_ms = window.map_or(0, |w| (w.as_millis() as u64).max(1));
+        self.dedup_window_ms.store(window_ms, Ordering::Relaxed);
+    }
 
-        events.push_back(event);
+    /// Cut messages and text field values to these lengths when storing
+    pub(crate) fn set_value_limits(&self, limits: ValueLimits) {
+        let limit = |len: Option<usize>| len.map_or(0, |len| len.max(MIN_VALUE_LEN));
+        self.max_message_len
+            .store(limit(limits.max_message_len), Ordering::Relaxed);
+        self.max_field_len
+            .store(limit(limits.max_field_len), Ordering::Relaxed);
     }
 
-    /// Subscribe to real-time log events
-    pub fn subscribe(&self) -> broadcast::Receiver<LogEvent> {
-        self.tx.subscribe()
+    /// Lengths messages and text field values are cut to
+    pub(crate) fn value_limits(&self) -> ValueLimits {
+        let limit = |len: usize| (len > 0).then_some(len);
+        ValueLimits {
+            max_message_len: limit(self.max_message_len.load(Ordering::Relaxed)),
+            max_field_len: limit(self.max_field_len.load(Ordering::Relaxed)),
+        }
     }
 
-    /// Get all log events matching the filter
-    pub fn get_filtered(
-        &self,
-        filter: &LogFilter,
-        limit: Option<usize>,
-        offset: Option<usize>,
-    ) -> (Vec<LogEvent>, usize) {
-        let events = self.events.read();
-        let offset = offset.unwrap_or(0);
+    /// Cut the message and text field values of an event over the limits
+    ///
+    /// Captured events were already cut while recording, this covers
+    /// ingested events and consoles with stricter limits than others
+    /// sharing the capture layer.
+    fn apply_value_limits(&self, event: &mut LogEvent) {
+        let limits = self.value_limits();
+        if let Some(max) = limits.max_message_len {
+            event.truncated |= truncate_string(&mut event.message, max);
+        }
+        if let Some(max) = limits.max_field_len {
+            for value in event.fields.values_mut() {
+                if let Value::String(text) = value {
+                    event.truncated |= truncate_string(text, max);
+                }
+            }
+        }
+    }
 
-        let filtered: Vec<LogEvent> = events
-            .iter()
-            .filter(|event| self.matches_filter(event, filter))
-            .cloned()
-            .collect();
+    /// Count `event` as a repeat of the newest stored event, if it has the same
+    /// level, target, message and fields and follows it within the window
+    ///
+    /// Most events aren't repeats, they are told apart by a hash of the
+    /// newest event without taking the lock. Events that became the newest
+    /// by removing later ones aren't hashed, repeats of them are stored.
+    fn collapse_repeat(&self, event: &LogEvent) -> bool {
+        let window_ms = self.dedup_window_ms.load(Ordering::Relaxed);
+        if window_ms == 0
+            || self.newest_fingerprint.load(Ordering::Acquire) != repeat_fingerprint(event)
+        {
+            return false;
+        }
+        let mut events = self.events.write();
+        let Some(last) = events.back_mut() else {
+            return false;
+        };
+        let elapsed_ms = (event.timestamp - last.timestamp).num_milliseconds();
+        if elapsed_ms > window_ms as i64
+            || last.level != event.level
+            || last.target != event.target
+            || last.message != event.message
+            || last.fields != event.fields
+        {
+            return false;
+        }
+        last.repeat_count = Some(last.repeat_count.unwrap_or(1) + 1);
+        // Cached query results still hold the previous count
+        self.generation.fetch_add(1, Ordering::Release);
+        true
+    }
 
-        let total_filtered = filtered.len();
+    /// Partition events by the value of `field`, `None` turns it off
+    ///
+    /// A full buffer then evicts the oldest event of the partition with the
+    /// most events, instead of the oldest event overall.
+    pub(crate) fn set_partition_by(&self, field: Option<String>) {
+        let events = self.events.write();
+        self.partitions.lock().set_field(field, &events);
+    }
 
-        // Apply sort order and pagination
-        let paginated: Vec<LogEvent> = match filter.sort_order {
-            SortOrder::NewestFirst => {
-                // Reverse to get newest first, then paginate
-                filtered
-                    .into_iter()
-                    .rev()
-                    .skip(offset)
-                    .take(limit.unwrap_or(usize::MAX))
-                    .collect()
-            }
-            SortOrder::OldestFirst => {
-                // Keep natural order (oldest first), then paginate
-                filtered
-                    .into_iter()
-                    .skip(offset)
-                    .take(limit.unwrap_or(usize::MAX))
-                    .collect()
-            }
-        };
+    /// Cap the number of stored events per level
+    ///
+    /// Storing an event of a level at its cap evicts the oldest event of that
+    /// level, even while the buffer has room. Events already over a cap are
+    /// removed right away.
+    pub(crate) fn set_level_caps(&self, caps: &HashMap<tracing::Level, usize>) {
+        let mut events = self.events.write();
+        if self.level_caps.lock().set(caps, &mut events) > 0 {
+            self.partitions.lock().rebuild(&events);
+            self.generation.fetch_add(1, Ordering::Release);
+        }
+    }
 
-        (paginated, total_filtered)
+    /// Field events are partitioned by, if any
+    pub(crate) fn partition_field(&self) -> Option<String> {
+        self.partitions.lock().field().map(str::to_string)
     }
 
-    /// Get all unique targets from stored events
-    pub fn get_targets(&self) -> Vec<String> {
-        let events = self.events.read();
-        let mut targets: Vec<String> = events
-            .iter()
-            .map(|e| e.target.clone())
-            .collect::<std::collections::HashSet<_>>()
-            .into_iter()
-            .collect();
+    /// Stored events per partition, largest first
+    pub(crate) fn partition_counts(&self) -> Vec<PartitionCount> {
+        self.partitions.lock().counts()
+    }
 
-        targets.sort();
-        targets
+    /// Append evicted events to `spill` instead of discarding them
+    pub(crate) fn set_spill(&self, spill: Option<Spill>) {
+        let spill = spill.map(Arc::new);
+        if let Some(spill) = &spill {
+            spill.start_writer();
+        }
+        *self.spill.write() = spill;
     }
 
-    /// Check if storage is empty
-    #[allow(dead_code)]
-    pub fn is_empty(&self) -> bool {
-        self.events.read().is_empty()
+    /// Whether evicted events are kept in spill files
+    pub(crate) fn is_spilling(&self) -> bool {
+        self.spill.read().is_some()
     }
 
-    /// Clear all stored events
-    #[allow(dead_code)]
-    pub fn clear(&self) {
-        self.events.write().clear();
+    /// Keep an evicted event in the spill files, if configured
+    ///
+    /// Only queues it, the spill's writer thread does the file I/O.
+    fn spill_evicted(spill: Option<&Spill>, event: LogEvent) {
+        if let Some(spill) = spill {
+            spill.queue(event);
+        }
     }
 
-    /// Check if an event matches the filter criteria
-    fn matches_filter(&self, event: &LogEvent, filter: &LogFilter) -> bool {
-        // Determine the required log level for this event's target
-        // Target filters take precedence over global level
-        // Use prefix matching: "my_crate" matches "my_crate::module::thing"
-        let target_level = filter
-            .target_levels
-            .iter()
-            .filter(|(target, _)| {
-                event.target == **target || event.target.starts_with(&format!("{}::", target))
-            })
-            // If multiple matches, use the most specific (longest) target
-            .max_by_key(|(target, _)| target.len())
-            .map(|(_, level)| level);
+    /// Whether both handles refer to the same storage
+    pub(crate) fn ptr_eq(&self, other: &LogStorage) -> bool {
+        Arc::ptr_eq(&self.events, &other.events)
+    }
 
-        // Target-specific level takes precedence, then fall back to global level
-        let required_level = target_level.or(filter.global_level.as_ref());
+    /// Get the ingest transform pipeline applied on every push
+    pub fn transforms(&self) -> &TransformPipeline {
+        &self.transforms
+    }
 
-        // If a level filter is specified, check if event level meets it
-        if let Some(level_str) = required_level {
-            let event_level_num = level_to_number(&event.level);
-            let required_level_num = level_to_number(level_str);
+    /// Register a sink receiving a copy of every stored event
+    pub fn add_sink(&self, sink: Arc<dyn EventSink>) {
+        self.sinks.write().push(sink);
+    }
 
-            // Event level must be >= required level (higher severity)
-            if event_level_num < required_level_num {
-                return false;
+    /// Add a new log event, removing oldest if at capacity
+    ///
+    /// The event is run through the transform pipeline first and may be dropped.
+    /// With deduplication enabled a repeat of the newest event only increments
+    /// its `repeat_count`, it's neither broadcast nor passed to sinks.
+    pub fn push(&self, event: LogEvent) {
+        if self.paused.load(Ordering::Acquire) {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let Some(mut event) = self.transforms.apply(event) else {
+            return;
+        };
+        if event.source_url.is_none() {
+            if let Some(template) = self.source_url_template.read().as_deref() {
+                event.source_url = source_url(template, &event);
             }
         }
+        self.apply_value_limits(&mut event);
+        if self.collapse_repeat(&event) {
+            self.target_stats.record(&event);
+            return;
+        }
 
-        // Filter by target (case-insensitive contains)
-        if let Some(ref target_filter) = filter.target {
-            if !event
-                .target
-                .to_lowercase()
-                .contains(&target_filter.to_lowercase())
-            {
-                return false;
+        let mut stored = Vec::new();
+        // Released when dropped, also if a sink panics
+        let mut waiters = Waiters::default();
+        let mut dropped_fields = Vec::new();
+        let ticket = if self.combine_writes.load(Ordering::Relaxed) {
+            // Whoever holds the write lock stores the events of all threads, so
+            // under contention most threads don't take it
+            let ticket = self.pending.enqueue(event);
+            let mut attempts = 0;
+            while !self.pending.is_taken(&ticket) {
+                let events = if attempts < COMBINE_ATTEMPTS {
+                    self.events.try_write()
+                } else {
+                    Some(self.events.write())
+                };
+                match events {
+                    Some(mut events) => {
+                        self.store_pending(
+                            &mut events,
+                            &mut stored,
+                            &mut waiters,
+                            &mut dropped_fields,
+                        );
+                    }
+                    None => {
+                        attempts += 1;
+                        std::thread::yield_now();
+                    }
+                }
             }
-        }
+            Some(ticket)
+        } else {
+            stored.push(event);
+            let mut events = self.events.write();
+            self.store_batch(&mut events, &mut stored, &mut dropped_fields);
+            None
+        };
 
-        // Filter by search term in message (case-insensitive contains)
-        if let Some(ref search) = filter.search {
-            if !event
-                .message
-                .to_lowercase()
-                .contains(&search.to_lowercase())
-            {
-                return false;
+        for event in &stored {
+            self.target_stats.record(event);
+            for sink in self.sinks.read().iter() {
+                sink.write(event);
             }
         }
+        // The sinks have the events, their threads may return
+        drop(waiters);
+        // Returns right away if this thread stored its own event
+        if let Some(ticket) = ticket {
+            ticket.wait_delivered();
+        }
 
-        true
+        for (field, cardinality) in dropped_fields {
+            self.push(LogEvent::lifecycle(
+                "field_cardinality_exceeded",
+                &format!(
+                    "Span field {} has about {} distinct values and is no longer indexed",
+                    field, cardinality
+                ),
+                HashMap::from([
+                    ("field".to_string(), field),
+                    ("cardinality".to_string(), cardinality.to_string()),
+                    (
+                        "max_cardinality".to_string(),
+                        crate::span_index::MAX_FIELD_CARDINALITY.to_string(),
+                    ),
+                ]),
+            ));
+        }
     }
-}
 
-impl Default for LogStorage {
-    fn default() -> Self {
-        Self::new()
+    /// Store the events waiting in `pending`, called with the write lock of
+    /// `events` held
+    ///
+    /// The stored events are added to `stored` for the work done after the
+    /// lock is released, as are span fields that stopped being indexed.
+    fn store_pending(
+        &self,
+        events: &mut VecDeque<LogEvent>,
+        stored: &mut Vec<LogEvent>,
+        waiters: &mut Waiters,
+        dropped_fields: &mut Vec<(String, u64)>,
+    ) {
+        let first = stored.len();
+        self.pending.take_all(stored, waiters);
+        if stored.len() == first {
+            return;
+        }
+        self.store_batch(events, &mut stored[first..], dropped_fields);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Assign sequence numbers to `batch` and store it, called with the
+    /// write lock of `events` held
+    fn store_batch(
+        &self,
+        events: &mut VecDeque<LogEvent>,
+        batch: &mut [LogEvent],
+        dropped_fields: &mut Vec<(String, u64)>,
+    ) {
+        let spill = self.spill.read().clone();
+        let capacity = self.effective_capacity.load(Ordering::Relaxed).max(1);
+        let mut partitions = self.partitions.lock();
+        let mut level_caps = self.level_caps.lock();
+        let mut span_index = self.span_index.lock();
+        for event in batch.iter_mut() {
+            // Sequence numbers are assigned under the lock so they follow buffer order
+            event.seq = self.generation.fetch_add(1, Ordering::Release) + 1;
 
-    fn create_test_event(level: &str, target: &str, message: &str) -> LogEvent {
-        LogEvent {
-            timestamp: Utc::now(),
-            level: level.to_string(),
-            target: target.to_string(),
-            message: message.to_string(),
-            fields: HashMap::new(),
-            span: None,
-            file: None,
-            line: None,
+            // A level at its cap makes room within the level first
+            if let Some(evicted) = level_caps.evict(event, events) {
+                partitions.remove(&evicted);
+                Self::spill_evicted(spill.as_deref(), evicted);
+                self.evicted.fetch_add(1, Ordering::Relaxed);
+            }
+            while events.len() >= capacity {
+                if let Some(evicted) = partitions.evict(events) {
+                    level_caps.remove(&evicted);
+                    Self::spill_evicted(spill.as_deref(), evicted);
+                }
+                self.evicted.fetch_add(1, Ordering::Relaxed);
+            }
+            partitions.insert(event);
+            level_caps.insert(event);
+
+            // Send to broadcast channel, ignore if no receivers
+            let _ = self.tx.send(event.clone());
+
+            let min_seq = events.front().map_or(event.seq, |oldest| oldest.seq);
+            dropped_fields.extend(span_index.insert(event, min_seq));
+            events.push_back(event.clone());
+        }
+        if self.dedup_window_ms.load(Ordering::Relaxed) > 0 {
+            if let Some(newest) = batch.last() {
+                self.newest_fingerprint
+                    .store(repeat_fingerprint(newest), Ordering::Release);
+            }
         }
     }
 
-    #[test]
-    fn test_circular_buffer() {
-        let storage = LogStorage::with_capacity(3);
+    /// Store each event under its own write lock instead of combining the
+    /// events of concurrent pushes, to compare both
+    pub(crate) fn set_combine_writes(&self, combine: bool) {
+        self.combine_writes.store(combine, Ordering::Relaxed);
+    }
+
+    /// Store captured events from a background thread instead of the
+    /// logging thread
+    ///
+    /// `capture` then only queues events in a bounded channel and never
+    /// waits, events arriving while it is full are discarded and counted.
+    /// Storing happens off the async workers since `push` may wait for the
+    /// write lock and for sinks. Later calls are ignored.
+    pub(crate) fn start_capture_queue(&self, capacity: usize) {
+        let Some(rx) = self.open_capture_queue(capacity) else {
+            return;
+        };
+        let storage = self.clone();
+        let spawned = std::thread::Builder::new()
+            .name("tracing-web-console-capture".to_string())
+            .spawn(move || storage.drain_capture_queue(rx));
+        if let Err(e) = spawned {
+            tracing::error!("Failed to start capture queue thread: {}", e);
+        }
+    }
+
+    /// Create the capture queue, `None` if it already exists
+    fn open_capture_queue(&self, capacity: usize) -> Option<mpsc::Receiver<CaptureMessage>> {
+        let (tx, rx) = mpsc::sync_channel(capacity.max(1));
+        self.capture_queue.set(tx).ok()?;
+        Some(rx)
+    }
+
+    /// Store queued events until the queue is closed
+    fn drain_capture_queue(&self, rx: mpsc::Receiver<CaptureMessage>) {
+        for message in rx {
+            match message {
+                CaptureMessage::Event(event) => self.push(event),
+                // Messages are handled in order, so every earlier event is stored
+                CaptureMessage::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+
+    /// Store an event captured by the tracing layer, through the capture
+    /// queue if one was started
+    pub(crate) fn capture(&self, event: LogEvent) {
+        let Some(queue) = self.capture_queue.get() else {
+            self.push(event);
+            return;
+        };
+        if queue.try_send(CaptureMessage::Event(event)).is_err() {
+            self.capture_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Number of captured events discarded because the capture queue was full
+    pub(crate) fn capture_dropped_total(&self) -> u64 {
+        self.capture_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of events sinks discarded instead of delivering, e.g. because a
+    /// batch sink's queue was full
+    pub(crate) fn sink_dropped_total(&self) -> u64 {
+        self.sinks.read().iter().map(|sink| sink.dropped()).sum()
+    }
+
+    /// Number of events evicted by `push` because the buffer was full
+    pub(crate) fn evicted_total(&self) -> u64 {
+        self.evicted.load(Ordering::Relaxed)
+    }
+
+    /// Record events a live stream client or the alert evaluation missed by
+    /// falling behind
+    pub(crate) fn record_dropped(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Number of events live stream clients missed so far
+    pub(crate) fn dropped_total(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Highest sequence number assigned so far
+    pub(crate) fn last_seq(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Configured maximum number of stored events
+    pub fn capacity(&self) -> usize {
+        self.max_events.load(Ordering::Relaxed)
+    }
+
+    /// Change the configured capacity, returns the number of evicted events
+    ///
+    /// The effective capacity is reset to the new value, a memory limit
+    /// lowers it again if needed. Shrinking evicts the oldest events right away.
+    pub fn set_capacity(&self, capacity: usize) -> usize {
+        let capacity = capacity.max(1);
+        let before = self.len();
+        self.max_events.store(capacity, Ordering::Relaxed);
+        self.set_effective_capacity(capacity);
+        before.saturating_sub(self.len())
+    }
+
+    /// Number of events currently kept, at most the configured capacity
+    pub fn effective_capacity(&self) -> usize {
+        self.effective_capacity.load(Ordering::Relaxed)
+    }
+
+    /// Change the number of events kept without changing the configured capacity
+    ///
+    /// The value is clamped to `1..=capacity()`, the oldest events are evicted
+    /// right away when shrinking.
+    pub fn set_effective_capacity(&self, capacity: usize) {
+        let capacity = capacity.clamp(1, self.capacity().max(1));
+        let spill = self.spill.read().clone();
+        let mut events = self.events.write();
+        self.effective_capacity.store(capacity, Ordering::Relaxed);
+        if events.len() > capacity {
+            let mut partitions = self.partitions.lock();
+            let mut level_caps = self.level_caps.lock();
+            while events.len() > capacity {
+                if let Some(evicted) = partitions.evict(&mut events) {
+                    level_caps.remove(&evicted);
+                    Self::spill_evicted(spill.as_deref(), evicted);
+                }
+            }
+            // Give the memory back, shrinking usually happens under memory pressure
+            events.shrink_to(capacity);
+            self.generation.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    /// Load previously stored events, e.g. from a ring file after a restart
+    ///
+    /// Unlike `push`, transforms, sinks and subscribers are skipped since the
+    /// events were already processed before they were persisted.
+    pub(crate) fn restore(&self, restored: Vec<LogEvent>) {
+        let mut events = self.events.write();
+        let mut span_index = self.span_index.lock();
+        let mut partitions = self.partitions.lock();
+        let mut level_caps = self.level_caps.lock();
+        for mut event in restored {
+            event.seq = self.generation.fetch_add(1, Ordering::Release) + 1;
+            if let Some(evicted) = level_caps.evict(&event, &mut events) {
+                partitions.remove(&evicted);
+            }
+            while events.len() >= self.effective_capacity.load(Ordering::Relaxed).max(1) {
+                if let Some(evicted) = partitions.evict(&mut events) {
+                    level_caps.remove(&evicted);
+                }
+            }
+            span_index.insert(&event, events.front().map_or(event.seq, |e| e.seq));
+            partitions.insert(&event);
+            level_caps.insert(&event);
+            events.push_back(event);
+        }
+        if let Some(newest) = events.back() {
+            self.newest_fingerprint
+                .store(repeat_fingerprint(newest), Ordering::Release);
+        }
+    }
+
+    /// Block until every sink has delivered the events pushed so far
+    ///
+    /// Events are queryable and passed to the sinks by the time `push`
+    /// returns, also when another thread stored them, this waits for events
+    /// still in the capture queue and for buffering sinks.
+    pub fn flush(&self) {
+        if let Some(queue) = self.capture_queue.get() {
+            let (done_tx, done_rx) = mpsc::channel();
+            // Waits for room in a full queue, fails only if the thread is gone
+            if queue.send(CaptureMessage::Flush(done_tx)).is_ok() {
+                let _ = done_rx.recv();
+            }
+        }
+        // Clone the list so sinks can take their time without holding the lock
+        let sinks: Vec<_> = self.sinks.read().clone();
+        for sink in sinks {
+            sink.flush();
+        }
+    }
+
+    /// Subscribe to real-time log events
+    pub fn subscribe(&self) -> broadcast::Receiver<LogEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Live events as a stream, starting with the next stored event
+    ///
+    /// A consumer falling behind skips the events it missed, they are counted
+    /// like those missed by live stream clients. The stream ends once every
+    /// handle of the storage is dropped.
+    pub fn event_stream(&self) -> impl Stream<Item = Arc<LogEvent>> + Send + 'static {
+        let dropped = self.dropped.clone();
+        futures::stream::unfold(self.subscribe(), move |mut rx| {
+            let dropped = dropped.clone();
+            async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(event) => return Some((Arc::new(event), rx)),
+                        Err(broadcast::error::RecvError::Lagged(count)) => {
+                            dropped.fetch_add(count, Ordering::Relaxed);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        })
+    }
+
+    /// Get all log events matching the filter
+    pub fn get_filtered(
+        &self,
+        filter: &LogFilter,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> (Vec<LogEvent>, usize) {
+        let result = self.query(filter, limit, offset);
+        (result.events, result.matched)
+    }
+
+    /// Get a page of log events matching the filter, with execution statistics
+    ///
+    /// Evicted events kept in spill files are included, older than all
+    /// buffered ones.
+    pub fn query(
+        &self,
+        filter: &LogFilter,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> QueryResult {
+        self.query_cancellable(filter, limit, offset, &CancelToken::default())
+            .expect("query without cancellation completes")
+    }
+
+    /// Like `query`, but gives up and releases the read lock once `cancel` is set
+    ///
+    /// The read lock is released before spill files are read, so pushes don't
+    /// wait for the disk. Returns `None` if the query was cancelled.
+    pub(crate) fn query_cancellable(
+        &self,
+        filter: &LogFilter,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        cancel: &CancelToken,
+    ) -> Option<QueryResult> {
+        let events = self.events.read();
+        let offset = offset.unwrap_or(0);
+
+        // The generation can't change while the read lock is held
+        let generation = self.generation.load(Ordering::Acquire);
+        let cache_key = QueryCache::key(filter, limit, offset);
+        if let Some(mut result) = self.query_cache.get(cache_key, generation) {
+            result.cached = true;
+            return Some(result);
+        }
+
+        // Span field clauses narrow the scan down to indexed candidates
+        let min_seq = events.front().map_or(0, |oldest| oldest.seq);
+        let candidates = filter.expr.as_ref().and_then(|expr| {
+            self.span_index
+                .lock()
+                .candidates(&expr.span_field_terms(), min_seq)
+        });
+
+        let mut filtered = Vec::new();
+        let scanned = match candidates {
+            Some(seqs) => {
+                for (i, seq) in seqs.iter().enumerate() {
+                    if i % CANCEL_CHECK_INTERVAL == 0 && cancel.is_cancelled() {
+                        return None;
+                    }
+                    match find_seq(&events, *seq) {
+                        Some(event) if filter.matches(event) => {
+                            filtered.push(event.clone());
+                        }
+                        _ => {}
+                    }
+                }
+                seqs.len()
+            }
+            None => {
+                for (i, event) in events.iter().enumerate() {
+                    if i % CANCEL_CHECK_INTERVAL == 0 && cancel.is_cancelled() {
+                        return None;
+                    }
+                    if filter.matches(event) {
+                        filtered.push(event.clone());
+                    }
+                }
+                events.len()
+            }
+        };
+        drop(events);
+
+        // Spilled events are older than every buffered one
+        let mut scanned = scanned;
+        let mut total_filtered = filtered.len();
+        if let Some(spill) = self.spill.read().clone() {
+            let buffered: HashSet<u64> = filtered.iter().map(|event| event.seq).collect();
+            let (spilled, matched, read) =
+                spilled_page(&spill, filter, limit, offset, &buffered, generation, cancel)?;
+            scanned += read;
+            total_filtered += matched;
+            filtered = spilled.into_iter().chain(filtered).collect();
+        }
+
+        // Apply sort order and pagination
+        let paginated: Vec<LogEvent> = match filter.sort_order {
+            SortOrder::NewestFirst => {
+                // Reverse to get newest first, then paginate
+                filtered
+                    .into_iter()
+                    .rev()
+                    .skip(offset)
+                    .take(limit.unwrap_or(usize::MAX))
+                    .collect()
+            }
+            SortOrder::OldestFirst => {
+                // Keep natural order (oldest first), then paginate
+                filtered
+                    .into_iter()
+                    .skip(offset)
+                    .take(limit.unwrap_or(usize::MAX))
+                    .collect()
+            }
+        };
+
+        let result = QueryResult {
+            truncated: offset.saturating_add(paginated.len()) < total_filtered,
+            events: paginated,
+            matched: total_filtered,
+            scanned,
+            cached: false,
+            last_seq: generation,
+        };
+        self.query_cache
+            .insert(cache_key, generation, result.clone());
+        Some(result)
+    }
+
+    /// Get all events matching a predicate, oldest first
+    pub fn matching<F: Fn(&LogEvent) -> bool>(&self, predicate: F) -> Vec<LogEvent> {
+        let events = self.events.read();
+        events
+            .iter()
+            .filter(|event| predicate(event))
+            .cloned()
+            .collect()
+    }
+
+    /// Visit every stored event without cloning, oldest first
+    ///
+    /// The storage is read-locked while `f` runs, so keep it cheap.
+    pub(crate) fn scan<F: FnMut(&LogEvent)>(&self, mut f: F) {
+        for event in self.events.read().iter() {
+            f(event);
+        }
+    }
+
+    /// Look up events by sequence number, skipping those no longer stored
+    pub(crate) fn get_by_seqs(&self, seqs: &[u64]) -> Vec<LogEvent> {
+        let events = self.events.read();
+        seqs.iter()
+            .filter_map(|seq| find_seq(&events, *seq))
+            .cloned()
+            .collect()
+    }
+
+    /// Look up a single log event by its ID
+    pub fn get_by_id(&self, id: &str) -> Option<LogEvent> {
+        let events = self.events.read();
+        // Recent events are the most likely to be requested, search from the back
+        events
+            .iter()
+            .rev()
+            .find(|event| event.id.eq_ignore_ascii_case(id))
+            .cloned()
+    }
+
+    /// Get all unique targets from stored events
+    pub fn get_targets(&self) -> Vec<String> {
+        let events = self.events.read();
+        let mut targets: Vec<String> = events
+            .iter()
+            .map(|e| e.target.clone())
+            .collect::<std::collections::HashSet<_>>()
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        targets.sort();
+        targets
+    }
+
+    /// Recent event counts of a target, `None` if it hasn't logged
+    ///
+    /// Covers the last hour regardless of how many events the buffer still holds.
+    pub fn target_stats(&self, target: &str) -> Option<TargetStatsResponse> {
+        self.target_stats.get(target, Utc::now())
+    }
+
+    /// Counts, activity and last error of every target, including evicted events
+    pub fn target_overview(&self) -> Vec<TargetOverview> {
+        self.target_stats.overview(Utc::now())
+    }
+
+    /// Events stored since start by target and level, including evicted ones
+    pub(crate) fn event_totals(&self) -> Vec<(String, &'static str, u64)> {
+        self.target_stats.totals()
+    }
+
+    /// Summaries of events removed by compaction
+    pub(crate) fn summaries(&self) -> &SummaryStore {
+        &self.summaries
+    }
+
+    /// Spans recorded by the capture layer
+    pub(crate) fn spans(&self) -> &SpanStore {
+        &self.spans
+    }
+
+    /// Record a diagnostic event of the console itself
+    ///
+    /// It goes to the separate diagnostics buffer, not to the captured events.
+    pub(crate) fn diagnose(&self, event: LogEvent) {
+        self.diagnostics.push(event);
+    }
+
+    /// The console's own diagnostic events
+    pub(crate) fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// Connected WebSocket clients
+    pub(crate) fn stream_clients(&self) -> &StreamClients {
+        &self.stream_clients
+    }
+
+    /// Record a marker, keeping markers ordered by timestamp
+    pub fn add_marker(&self, marker: Marker) {
+        let mut markers = self.markers.write();
+        // Markers usually arrive in order, but backdated ones are allowed
+        let index = markers.partition_point(|m| m.timestamp <= marker.timestamp);
+        markers.insert(index, marker);
+        if markers.len() > MAX_MARKERS {
+            markers.pop_front();
+        }
+    }
+
+    /// Get markers within an inclusive time range, oldest first
+    pub fn markers_between(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Vec<Marker> {
+        self.markers
+            .read()
+            .iter()
+            .filter(|m| from.is_none_or(|from| m.timestamp >= from))
+            .filter(|m| to.is_none_or(|to| m.timestamp <= to))
+            .cloned()
+            .collect()
+    }
+
+    /// Number of stored events
+    pub fn len(&self) -> usize {
+        self.events.read().len()
+    }
+
+    /// Check if storage is empty
+    pub fn is_empty(&self) -> bool {
+        self.events.read().is_empty()
+    }
+
+    /// Stop storing new events, so the buffer stays as it is
+    ///
+    /// Events captured while paused are discarded, they are neither stored,
+    /// streamed nor written to sinks. Retention and compaction leave the
+    /// buffer alone too. Returns false if capture was already paused.
+    pub(crate) fn pause(&self) -> bool {
+        let mut paused_at = self.paused_at.lock();
+        if paused_at.is_some() {
+            return false;
+        }
+        self.push(LogEvent::lifecycle(
+            "capture_paused",
+            "Capture paused",
+            HashMap::new(),
+        ));
+        *paused_at = Some(Utc::now());
+        self.skipped.store(0, Ordering::Relaxed);
+        self.paused.store(true, Ordering::Release);
+        true
+    }
+
+    /// Store new events again, returns false if capture wasn't paused
+    pub(crate) fn resume(&self) -> bool {
+        let mut paused_at = self.paused_at.lock();
+        let Some(since) = paused_at.take() else {
+            return false;
+        };
+        self.paused.store(false, Ordering::Release);
+        let skipped = self.skipped.load(Ordering::Relaxed);
+        self.push(LogEvent::lifecycle(
+            "capture_resumed",
+            &format!("Capture resumed, {} events were discarded", skipped),
+            HashMap::from([
+                ("skipped".to_string(), skipped.to_string()),
+                ("paused_at".to_string(), since.to_rfc3339()),
+            ]),
+        ));
+        true
+    }
+
+    /// Whether capture is paused
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Whether capture is paused, since when and what was discarded
+    pub(crate) fn capture_status(&self) -> CaptureStatus {
+        let paused_at = *self.paused_at.lock();
+        CaptureStatus {
+            paused: paused_at.is_some(),
+            paused_at,
+            skipped: if paused_at.is_some() {
+                self.skipped.load(Ordering::Relaxed)
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Clear all stored events
+    ///
+    /// A lifecycle event recording the clear is stored afterwards. Returns the
+    /// number of removed events.
+    pub fn clear(&self) -> usize {
+        let cleared = {
+            let mut events = self.events.write();
+            self.generation.fetch_add(1, Ordering::Release);
+            let cleared = events.len();
+            events.clear();
+            self.span_index.lock().clear();
+            self.partitions.lock().rebuild(&events);
+            self.level_caps.lock().rebuild(&events);
+            if let Some(spill) = self.spill.read().as_ref() {
+                if let Err(e) = spill.clear() {
+                    tracing::warn!("Failed to clear spilled events: {}", e);
+                }
+            }
+            cleared
+        };
+        self.summaries.clear();
+        self.spans.clear();
+        self.push(LogEvent::lifecycle(
+            "storage_cleared",
+            "Log storage cleared",
+            HashMap::from([("cleared".to_string(), cleared.to_string())]),
+        ));
+        cleared
+    }
+
+    /// Remove the stored events matching `filter`
+    ///
+    /// A filter without criteria clears everything like `clear`, otherwise
+    /// summaries and spans are kept. A lifecycle event recording the number
+    /// of removed events is stored afterwards.
+    pub fn clear_filtered(&self, filter: &LogFilter) -> usize {
+        if filter.is_unrestricted() {
+            return self.clear();
+        }
+        let mut cleared = self.retain(|event| !filter.matches(event));
+        if let Some(spill) = self.spill.read().clone() {
+            // Hold the buffer's lock so no events are spilled meanwhile
+            let _events = self.events.write();
+            match spill.retain(|event| !filter.matches(event)) {
+                Ok(0) => {}
+                Ok(removed) => {
+                    cleared += removed;
+                    self.generation.fetch_add(1, Ordering::Release);
+                }
+                Err(e) => tracing::warn!("Failed to clear spilled events: {}", e),
+            }
+        }
+        self.push(LogEvent::lifecycle(
+            "storage_cleared",
+            "Log storage cleared",
+            HashMap::from([
+                ("cleared".to_string(), cleared.to_string()),
+                ("filtered".to_string(), "true".to_string()),
+            ]),
+        ));
+        cleared
+    }
+
+    /// Remove every event for which `keep` returns false
+    ///
+    /// Returns the number of removed events.
+    pub(crate) fn retain<F: FnMut(&LogEvent) -> bool>(&self, keep: F) -> usize {
+        let mut events = self.events.write();
+        let before = events.len();
+        events.retain(keep);
+        let removed = before - events.len();
+        if removed > 0 {
+            self.partitions.lock().rebuild(&events);
+            self.level_caps.lock().rebuild(&events);
+            self.generation.fetch_add(1, Ordering::Release);
+        }
+        removed
+    }
+}
+
+impl Default for LogStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_event(level: &str, target: &str, message: &str) -> LogEvent {
+        LogEvent {
+            id: crate::id::new_ulid(),
+            seq: 0,
+            timestamp: Utc::now(),
+            level: level.into(),
+            target: target.into(),
+            message: message.to_string(),
+            fields: HashMap::new(),
+            span: None,
+            module_path: None,
+            file: None,
+            line: None,
+            source_url: None,
+            thread_name: None,
+            thread_id: None,
+            repeat_count: None,
+            is_error: false,
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_circular_buffer() {
+        let storage = LogStorage::with_capacity(3);
 
         storage.push(create_test_event("INFO", "test", "msg1"));
         storage.push(create_test_event("INFO", "test", "msg2"));
@@ -297,6 +1621,57 @@ mod tests {
         assert_eq!(filtered[0].level, "ERROR");
     }
 
+    #[test]
+    fn test_expr_filter_matches_ancestor_spans() {
+        let storage = LogStorage::new();
+
+        let mut nested = create_test_event("DEBUG", "db", "insert row");
+        nested.span = Some(SpanInfo {
+            id: None,
+            name: "db_query".to_string(),
+            fields: HashMap::new(),
+            ancestors: vec!["http".to_string(), "create_order".to_string()],
+            parent_fields: HashMap::new(),
+        });
+        storage.push(nested);
+        storage.push(create_test_event("DEBUG", "db", "outside span"));
+
+        let filter = LogFilter {
+            expr: Some(FilterExpr::parse("span:create_order level>=debug").unwrap()),
+            ..Default::default()
+        };
+        let (filtered, count) = storage.get_filtered(&filter, None, None);
+        assert_eq!(count, 1);
+        assert_eq!(filtered[0].message, "insert row");
+    }
+
+    #[test]
+    fn test_span_field_query_uses_index() {
+        let storage = LogStorage::with_capacity(100);
+        for i in 0..150 {
+            let mut event = create_test_event("INFO", "db", &format!("query {}", i));
+            event.span = Some(SpanInfo {
+                id: None,
+                name: "db_query".to_string(),
+                fields: HashMap::new(),
+                ancestors: vec!["request".to_string()],
+                parent_fields: HashMap::from([("user_id".to_string(), (i % 10).to_string())]),
+            });
+            storage.push(event);
+        }
+
+        let filter = LogFilter {
+            expr: Some(FilterExpr::parse("span.user_id=4").unwrap()),
+            sort_order: SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let result = storage.query(&filter, None, None);
+        // Only the events still in the buffer, without scanning the others
+        assert_eq!(result.matched, 10);
+        assert_eq!(result.scanned, 10);
+        assert_eq!(result.events[0].message, "query 54");
+    }
+
     #[test]
     fn test_search_filter() {
         let storage = LogStorage::new();
@@ -314,4 +1689,601 @@ mod tests {
         assert_eq!(count, 1);
         assert!(filtered[0].message.contains("hello"));
     }
+
+    #[test]
+    fn test_markers_are_ordered_and_ranged() {
+        let storage = LogStorage::new();
+        let now = Utc::now();
+        let marker = |label: &str, offset: i64| Marker {
+            id: crate::id::new_ulid(),
+            timestamp: now + chrono::Duration::seconds(offset),
+            label: label.to_string(),
+            kind: None,
+        };
+
+        storage.add_marker(marker("v2", 10));
+        storage.add_marker(marker("v1", 0));
+        storage.add_marker(marker("v3", 20));
+
+        let labels: Vec<_> = storage
+            .markers_between(None, None)
+            .into_iter()
+            .map(|m| m.label)
+            .collect();
+        assert_eq!(labels, ["v1", "v2", "v3"]);
+
+        let ranged = storage.markers_between(Some(now + chrono::Duration::seconds(5)), None);
+        assert_eq!(ranged.len(), 2);
+        assert_eq!(ranged[0].label, "v2");
+    }
+
+    #[test]
+    fn test_clear_records_lifecycle_event() {
+        let storage = LogStorage::new();
+        storage.push(create_test_event("INFO", "test", "msg1"));
+        storage.push(create_test_event("INFO", "test", "msg2"));
+
+        storage.clear();
+
+        let (events, count) = storage.get_filtered(&LogFilter::default(), None, None);
+        assert_eq!(count, 1);
+        assert_eq!(events[0].target, LIFECYCLE_TARGET);
+        assert_eq!(events[0].fields["lifecycle"], "storage_cleared");
+        assert_eq!(events[0].fields["cleared"], "2");
+    }
+
+    #[test]
+    fn test_repeats_are_collapsed_within_window() {
+        let storage = LogStorage::new();
+        storage.set_dedup_window(Some(std::time::Duration::from_secs(1)));
+        let mut rx = storage.subscribe();
+
+        let first = create_test_event("WARN", "app", "retrying");
+        storage.push(first.clone());
+        storage.push(LogEvent {
+            timestamp: first.timestamp + chrono::Duration::milliseconds(500),
+            ..first.clone()
+        });
+        storage.push(first.clone());
+        let mut other = first.clone();
+        other.fields.insert("attempt".into(), Value::from(2));
+        storage.push(other);
+        storage.push(LogEvent {
+            timestamp: first.timestamp + chrono::Duration::seconds(5),
+            ..first.clone()
+        });
+
+        let filter = LogFilter {
+            sort_order: SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let (events, total) = storage.get_filtered(&filter, None, None);
+        assert_eq!(total, 3);
+        assert_eq!(events[0].repeat_count, Some(3));
+        assert_eq!(events[1].repeat_count, None);
+        assert_eq!(events[2].repeat_count, None);
+
+        let mut broadcast = 0;
+        while rx.try_recv().is_ok() {
+            broadcast += 1;
+        }
+        assert_eq!(broadcast, 3);
+    }
+
+    #[test]
+    fn test_queries_read_spilled_events() {
+        let dir = std::env::temp_dir().join(format!("spill-storage-{}", crate::id::new_ulid()));
+        let storage = LogStorage::with_capacity(2);
+        storage.set_spill(Some(Spill::open(&dir, 1024 * 1024).unwrap()));
+        for i in 1..=5 {
+            storage.push(create_test_event("INFO", "app", &format!("event {}", i)));
+        }
+        assert_eq!(storage.len(), 2);
+
+        let mut filter = LogFilter::default();
+        let result = storage.query(&filter, Some(2), Some(1));
+        assert_eq!(result.matched, 5);
+        let messages: Vec<_> = result.events.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["event 4", "event 3"]);
+
+        filter.sort_order = SortOrder::OldestFirst;
+        let (events, _) = storage.get_filtered(&filter, Some(2), None);
+        assert_eq!(events[0].message, "event 1");
+
+        filter.search = Some("event 2".to_string());
+        assert_eq!(storage.clear_filtered(&filter), 1);
+        filter.search = None;
+        assert_eq!(storage.get_filtered(&filter, None, None).1, 5);
+
+        storage.clear();
+        assert_eq!(storage.get_filtered(&filter, None, None).1, 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_source_url() {
+        let template = "https://github.com/org/repo/blob/main/{file}#L{line}";
+        let mut event = create_test_event("INFO", "app", "msg");
+        assert_eq!(source_url(template, &event), None);
+
+        event.file = Some("src/orders.rs".to_string());
+        event.line = Some(42);
+        assert_eq!(
+            source_url(template, &event).unwrap(),
+            "https://github.com/org/repo/blob/main/src/orders.rs#L42"
+        );
+
+        event.file = Some("/home/me/.cargo/registry/src/hyper-1.0/src/lib.rs".to_string());
+        assert_eq!(source_url(template, &event), None);
+
+        let storage = LogStorage::new();
+        storage.set_source_url_template(Some(template.to_string()));
+        event.file = Some("src/main.rs".to_string());
+        storage.push(event);
+        let (events, _) = storage.get_filtered(&LogFilter::default(), None, None);
+        assert!(events[0]
+            .source_url
+            .as_ref()
+            .unwrap()
+            .ends_with("src/main.rs#L42"));
+    }
+
+    #[test]
+    fn test_exclusions_hide_events() {
+        let storage = LogStorage::new();
+        storage.push(create_test_event("DEBUG", "app::heartbeat", "beat"));
+        storage.push(create_test_event(
+            "INFO",
+            "app::heartbeat_monitor",
+            "checked",
+        ));
+        storage.push(create_test_event("TRACE", "app", "polling"));
+        storage.push(create_test_event("INFO", "app", "Health check ok"));
+        storage.push(create_test_event("ERROR", "app", "failed"));
+
+        let filter = LogFilter {
+            exclude_targets: vec!["app::heartbeat".to_string()],
+            exclude_search: Some("HEALTH".to_string()),
+            exclude_levels: vec!["trace".to_string()],
+            ..Default::default()
+        };
+        let (events, count) = storage.get_filtered(&filter, None, None);
+        assert_eq!(count, 2);
+        assert_eq!(events[0].message, "failed");
+        assert_eq!(events[1].message, "checked");
+    }
+
+    #[test]
+    fn test_clear_filtered_keeps_other_events() {
+        let storage = LogStorage::new();
+        storage.push(create_test_event("TRACE", "noisy", "tick"));
+        storage.push(create_test_event("TRACE", "noisy", "tock"));
+        storage.push(create_test_event("ERROR", "app", "failed"));
+
+        let filter = LogFilter {
+            target: Some("noisy".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(storage.clear_filtered(&filter), 2);
+
+        let (events, count) = storage.get_filtered(&LogFilter::default(), None, None);
+        assert_eq!(count, 2);
+        assert_eq!(events[0].fields["filtered"], "true");
+        assert_eq!(events[1].message, "failed");
+    }
+
+    #[test]
+    fn test_pause_keeps_buffer() {
+        let storage = LogStorage::with_capacity(4);
+        storage.push(create_test_event("ERROR", "test", "evidence"));
+        assert!(storage.pause());
+        assert!(!storage.pause());
+        for _ in 0..5 {
+            storage.push(create_test_event("INFO", "test", "noise"));
+        }
+
+        let status = storage.capture_status();
+        assert!(status.paused);
+        assert_eq!(status.skipped, 5);
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage.evicted_total(), 0);
+
+        assert!(storage.resume());
+        assert!(!storage.resume());
+        storage.push(create_test_event("INFO", "test", "after"));
+        let filter = LogFilter {
+            sort_order: SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let (events, _) = storage.get_filtered(&filter, None, None);
+        let messages: Vec<_> = events.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages[0], "evidence");
+        assert_eq!(events[1].fields["lifecycle"], "capture_paused");
+        assert_eq!(events[2].fields["skipped"], "5");
+        assert_eq!(messages[3], "after");
+        assert!(!storage.capture_status().paused);
+    }
+
+    #[test]
+    fn test_query_stats() {
+        let storage = LogStorage::new();
+        for i in 0..5 {
+            let level = if i % 2 == 0 { "ERROR" } else { "INFO" };
+            storage.push(create_test_event(level, "test", "msg"));
+        }
+
+        let filter = LogFilter {
+            global_level: Some("ERROR".to_string()),
+            ..Default::default()
+        };
+        let result = storage.query(&filter, Some(2), None);
+        assert_eq!(result.scanned, 5);
+        assert_eq!(result.matched, 3);
+        assert_eq!(result.events.len(), 2);
+        assert!(result.truncated);
+
+        let result = storage.query(&filter, Some(2), Some(2));
+        assert_eq!(result.events.len(), 1);
+        assert!(!result.truncated);
+    }
+
+    #[test]
+    fn test_query_cache_invalidated_by_push() {
+        let storage = LogStorage::new();
+        storage.push(create_test_event("INFO", "test", "msg1"));
+
+        let filter = LogFilter::default();
+        assert!(!storage.query(&filter, Some(10), None).cached);
+        let cached = storage.query(&filter, Some(10), None);
+        assert!(cached.cached);
+        assert_eq!(cached.matched, 1);
+
+        // A different page is a different query
+        assert!(!storage.query(&filter, Some(5), None).cached);
+
+        storage.push(create_test_event("INFO", "test", "msg2"));
+        let result = storage.query(&filter, Some(10), None);
+        assert!(!result.cached);
+        assert_eq!(result.matched, 2);
+    }
+
+    #[test]
+    fn test_after_seq_returns_only_newer_events() {
+        let storage = LogStorage::new();
+        storage.push(create_test_event("INFO", "test", "msg1"));
+        let first = storage.query(&LogFilter::default(), None, None);
+        assert_eq!(first.events[0].seq, first.last_seq);
+
+        storage.push(create_test_event("INFO", "test", "msg2"));
+        storage.push(create_test_event("INFO", "test", "msg3"));
+
+        let filter = LogFilter {
+            after_seq: Some(first.last_seq),
+            sort_order: SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let delta = storage.query(&filter, None, None);
+        assert_eq!(delta.matched, 2);
+        assert_eq!(delta.events[0].message, "msg2");
+        assert_eq!(delta.events[1].seq, delta.last_seq);
+    }
+
+    #[test]
+    fn test_before_seq_pages_are_stable() {
+        let storage = LogStorage::new();
+        for i in 1..=4 {
+            storage.push(create_test_event("INFO", "test", &format!("msg{}", i)));
+        }
+
+        let first = storage.query(&LogFilter::default(), Some(2), None);
+        assert_eq!(first.events[1].message, "msg3");
+
+        // A new event must not shift the next page like an offset would
+        storage.push(create_test_event("INFO", "test", "msg5"));
+        let filter = LogFilter {
+            before_seq: Some(first.events[1].seq),
+            ..Default::default()
+        };
+        let next = storage.query(&filter, Some(2), None);
+        let messages: Vec<_> = next.events.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["msg2", "msg1"]);
+        assert!(!next.truncated);
+    }
+
+    #[test]
+    fn test_cancelled_query_stops() {
+        let storage = LogStorage::new();
+        storage.push(create_test_event("INFO", "test", "msg"));
+
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let filter = LogFilter::default();
+        assert!(storage
+            .query_cancellable(&filter, None, None, &cancel)
+            .is_none());
+        assert_eq!(storage.query(&filter, None, None).matched, 1);
+    }
+
+    #[test]
+    fn test_effective_capacity_evicts_oldest() {
+        let storage = LogStorage::with_capacity(10);
+        for i in 0..5 {
+            storage.push(create_test_event("INFO", "test", &format!("msg{}", i)));
+        }
+
+        storage.set_effective_capacity(2);
+        let (events, count) = storage.get_filtered(&LogFilter::default(), None, None);
+        assert_eq!(count, 2);
+        assert_eq!(events[1].message, "msg3");
+
+        storage.push(create_test_event("INFO", "test", "msg5"));
+        assert_eq!(storage.get_filtered(&LogFilter::default(), None, None).1, 2);
+
+        // Never above the configured capacity
+        storage.set_effective_capacity(100);
+        assert_eq!(storage.effective_capacity(), 10);
+    }
+
+    #[test]
+    fn test_noisy_partition_evicts_only_itself() {
+        let storage = LogStorage::with_capacity(4);
+        storage.set_partition_by(Some("tenant_id".to_string()));
+        let tenant_event = |tenant: &str, message: &str| LogEvent {
+            fields: HashMap::from([("tenant_id".into(), Value::from(tenant))]),
+            ..create_test_event("INFO", "app", message)
+        };
+        storage.push(tenant_event("quiet", "quiet 1"));
+        for i in 0..10 {
+            storage.push(tenant_event("noisy", &format!("noisy {}", i)));
+        }
+
+        let quiet = LogFilter {
+            partition: Some(("tenant_id".to_string(), "quiet".to_string())),
+            ..Default::default()
+        };
+        assert_eq!(storage.query(&quiet, None, None).matched, 1);
+        assert_eq!(storage.len(), 4);
+        let counts = storage.partition_counts();
+        assert_eq!(
+            (counts[0].partition.as_str(), counts[0].events),
+            ("noisy", 3)
+        );
+
+        // Events stay ordered by sequence number
+        let (events, _) = storage.get_filtered(&LogFilter::default(), None, None);
+        assert!(events.windows(2).all(|w| w[0].seq > w[1].seq));
+    }
+
+    #[test]
+    fn test_concurrent_pushes_are_all_stored_in_order() {
+        let storage = LogStorage::with_capacity(100_000);
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    for i in 0..1000 {
+                        let event = create_test_event("INFO", "app", &format!("{}", i));
+                        storage.push(LogEvent {
+                            fields: HashMap::from([("thread".into(), Value::from(thread))]),
+                            ..event
+                        });
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(storage.len(), 8000);
+        assert_eq!(storage.last_seq(), 8000);
+        let oldest_first = LogFilter {
+            sort_order: SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let (events, _) = storage.get_filtered(&oldest_first, None, None);
+        assert!(events.windows(2).all(|w| w[0].seq < w[1].seq));
+        // Each thread's events keep the order they were pushed in
+        for thread in 0..8 {
+            let messages: Vec<u64> = events
+                .iter()
+                .filter(|e| e.fields["thread"] == thread)
+                .map(|e| e.message.parse().unwrap())
+                .collect();
+            assert_eq!(messages, (0..1000).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_sinks_have_the_event_when_push_returns() {
+        struct Seen(Mutex<std::collections::HashSet<String>>);
+        impl EventSink for Seen {
+            fn write(&self, event: &LogEvent) {
+                self.0.lock().insert(event.message.clone());
+            }
+        }
+
+        for combine in [true, false] {
+            let storage = LogStorage::with_capacity(100_000);
+            storage.set_combine_writes(combine);
+            let seen = Arc::new(Seen(Mutex::new(Default::default())));
+            storage.add_sink(seen.clone());
+            let threads: Vec<_> = (0..8)
+                .map(|thread| {
+                    let storage = storage.clone();
+                    let seen = seen.clone();
+                    std::thread::spawn(move || {
+                        for i in 0..500 {
+                            let message = format!("{} {}", thread, i);
+                            storage.push(create_test_event("INFO", "app", &message));
+                            assert!(seen.0.lock().contains(&message));
+                        }
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(seen.0.lock().len(), 4000);
+            assert_eq!(storage.last_seq(), 4000);
+        }
+    }
+
+    #[test]
+    fn test_capture_queue_drops_when_full() {
+        let storage = LogStorage::new();
+        // Nothing drains the queue until the thread is started below
+        let rx = storage.open_capture_queue(2).unwrap();
+        for i in 0..5 {
+            storage.capture(create_test_event("INFO", "app", &format!("msg{}", i)));
+        }
+        assert_eq!(storage.capture_dropped_total(), 3);
+        assert_eq!(storage.len(), 0);
+
+        let drain = storage.clone();
+        std::thread::spawn(move || drain.drain_capture_queue(rx));
+        // Flushing waits for the queued events
+        storage.flush();
+        let (events, _) = storage.get_filtered(&LogFilter::default(), None, None);
+        let messages: Vec<_> = events.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["msg1", "msg0"]);
+    }
+
+    #[test]
+    fn test_set_capacity_at_runtime() {
+        let storage = LogStorage::with_capacity(5);
+        for i in 0..5 {
+            storage.push(create_test_event("INFO", "test", &format!("msg{}", i)));
+        }
+
+        assert_eq!(storage.set_capacity(3), 2);
+        assert_eq!(storage.len(), 3);
+        assert_eq!(storage.capacity(), 3);
+
+        // Growing keeps everything and allows more events than before
+        assert_eq!(storage.set_capacity(10), 0);
+        assert_eq!(storage.effective_capacity(), 10);
+        for i in 0..5 {
+            storage.push(create_test_event("INFO", "test", &format!("more{}", i)));
+        }
+        assert_eq!(storage.len(), 8);
+    }
+
+    #[test]
+    fn test_get_by_id() {
+        let storage = LogStorage::new();
+
+        let event = create_test_event("INFO", "test", "find me");
+        let id = event.id.clone();
+        storage.push(event);
+        storage.push(create_test_event("INFO", "test", "other"));
+
+        let found = storage.get_by_id(&id).expect("event should be found");
+        assert_eq!(found.message, "find me");
+        assert!(storage.get_by_id("01ARZ3NDEKTSV4RRFFQ69G5FAV").is_none());
+    }
+
+    #[test]
+    fn test_pushed_values_are_cut_to_limits() {
+        let storage = LogStorage::new();
+        storage.set_value_limits(ValueLimits {
+            max_message_len: Some(10),
+            max_field_len: None,
+        });
+        // Limits are raised to the minimum
+        assert_eq!(storage.value_limits().max_message_len, Some(MIN_VALUE_LEN));
+
+        let mut event = create_test_event("INFO", "test", &"x".repeat(500));
+        event
+            .fields
+            .insert("body".into(), Value::String("y".repeat(500)));
+        storage.push(event);
+        storage.push(create_test_event("INFO", "test", "short"));
+
+        let (events, _) = storage.get_filtered(&LogFilter::default(), None, None);
+        let long = events.iter().find(|e| e.message.starts_with('x')).unwrap();
+        assert!(long.truncated);
+        assert!(long.message.len() <= MIN_VALUE_LEN);
+        assert!(long.message.ends_with("bytes truncated]"));
+        assert_eq!(long.fields["body"].as_str().unwrap().len(), 500);
+        let short = events.iter().find(|e| e.message == "short").unwrap();
+        assert!(!short.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_yields_new_events() {
+        use futures::StreamExt;
+
+        let storage = LogStorage::new();
+        storage.push(create_test_event("INFO", "test", "before"));
+        let mut events = Box::pin(storage.event_stream());
+
+        storage.push(create_test_event("INFO", "test", "first"));
+        storage.push(create_test_event("WARN", "test", "second"));
+        assert_eq!(events.next().await.unwrap().message, "first");
+        let second = events.next().await.unwrap();
+        assert_eq!(second.level, "WARN");
+        assert_eq!(second.message, "second");
+    }
+
+    #[test]
+    fn test_time_range_filter() {
+        let storage = LogStorage::new();
+        let start = Utc::now();
+        for minute in 0..10 {
+            storage.push(LogEvent {
+                timestamp: start + chrono::Duration::minutes(minute),
+                ..create_test_event("INFO", "test", &minute.to_string())
+            });
+        }
+
+        let filter = LogFilter {
+            from: Some(start + chrono::Duration::minutes(2)),
+            to: Some(start + chrono::Duration::minutes(5)),
+            sort_order: SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let result = storage.query(&filter, None, None);
+        let messages: Vec<_> = result.events.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["2", "3", "4", "5"]);
+
+        let open_ended = LogFilter {
+            from: Some(start + chrono::Duration::minutes(8)),
+            ..Default::default()
+        };
+        assert_eq!(storage.query(&open_ended, None, None).matched, 2);
+
+        let inverted = LogFilter {
+            from: Some(start + chrono::Duration::minutes(5)),
+            to: Some(start + chrono::Duration::minutes(2)),
+            ..Default::default()
+        };
+        assert_eq!(storage.query(&inverted, None, None).matched, 0);
+    }
+
+    #[test]
+    fn test_time_range_with_out_of_order_timestamps() {
+        // Ingested events keep their source timestamps, so the buffer isn't
+        // sorted by time
+        let storage = LogStorage::new();
+        let start = Utc::now();
+        for minute in [5, 1, 9, 3, 7, 2] {
+            storage.push(LogEvent {
+                timestamp: start + chrono::Duration::minutes(minute),
+                ..create_test_event("INFO", "test", &minute.to_string())
+            });
+        }
+
+        let filter = LogFilter {
+            from: Some(start + chrono::Duration::minutes(2)),
+            to: Some(start + chrono::Duration::minutes(5)),
+            sort_order: SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let result = storage.query(&filter, None, None);
+        let messages: Vec<_> = result.events.iter().map(|e| e.message.as_str()).collect();
+        // In buffer order
+        assert_eq!(messages, ["5", "3", "2"]);
+        assert_eq!(result.matched, 3);
+    }
 }
@@ -0,0 +1,130 @@
+//! Connected live stream clients and the events each of them missed
+//!
+//! Every WebSocket client has a bounded queue of messages waiting to be sent.
+//! A client that can't keep up, e.g. a backgrounded browser tab, loses events
+//! instead of holding the others back, and the counts here show which client
+//! is falling behind.
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Counters of one connected client
+pub(crate) struct ClientCounters {
+    connected_at: DateTime<Utc>,
+    /// Messages waiting in the client's send queue
+    queued: AtomicUsize,
+    /// Events the client missed since it connected
+    dropped: AtomicU64,
+}
+
+impl ClientCounters {
+    pub fn enqueued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dequeued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn dropped(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+/// A connected client as listed by GET /api/stats
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamClientStats {
+    pub id: String,
+    pub connected_at: DateTime<Utc>,
+    /// Messages waiting to be sent
+    pub queued: usize,
+    /// Events missed since connecting
+    pub dropped: u64,
+}
+
+/// Registry of connected clients
+#[derive(Default)]
+pub(crate) struct StreamClients {
+    clients: Arc<Mutex<BTreeMap<String, Arc<ClientCounters>>>>,
+}
+
+impl StreamClients {
+    /// Register a client until the returned registration is dropped
+    pub fn register(&self, id: &str) -> Registration {
+        let counters = Arc::new(ClientCounters {
+            connected_at: Utc::now(),
+            queued: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+        });
+        self.clients.lock().insert(id.to_string(), counters.clone());
+        Registration {
+            clients: self.clients.clone(),
+            id: id.to_string(),
+            counters,
+        }
+    }
+
+    /// Connected clients, oldest first
+    pub fn stats(&self) -> Vec<StreamClientStats> {
+        let mut stats: Vec<_> = self
+            .clients
+            .lock()
+            .iter()
+            .map(|(id, counters)| StreamClientStats {
+                id: id.clone(),
+                connected_at: counters.connected_at,
+                queued: counters.queued.load(Ordering::Relaxed),
+                dropped: counters.dropped.load(Ordering::Relaxed),
+            })
+            .collect();
+        stats.sort_by_key(|client| client.connected_at);
+        stats
+    }
+}
+
+/// A registered client, removed from the registry when dropped
+pub(crate) struct Registration {
+    clients: Arc<Mutex<BTreeMap<String, Arc<ClientCounters>>>>,
+    id: String,
+    counters: Arc<ClientCounters>,
+}
+
+impl Registration {
+    pub fn counters(&self) -> &Arc<ClientCounters> {
+        &self.counters
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.clients.lock().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clients_are_counted_while_registered() {
+        let clients = StreamClients::default();
+        let registration = clients.register("a");
+        registration.counters().enqueued();
+        registration.counters().enqueued();
+        registration.counters().dequeued();
+        registration.counters().dropped(5);
+
+        let stats = clients.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].id, "a");
+        assert_eq!(stats[0].queued, 1);
+        assert_eq!(stats[0].dropped, 5);
+
+        drop(registration);
+        assert!(clients.stats().is_empty());
+    }
+}
@@ -1,74 +1,560 @@
 //! Custom tracing subscriber that captures log events
 
-use crate::storage::{LogEvent, LogStorage, SpanInfo};
+use crate::filter::TargetFilter;
+use crate::intern::Interned;
+use crate::limits::{LimitedWriter, ValueLimits};
+use crate::spans::SpanRecord;
+use crate::storage::{field_text, LogEvent, LogStorage, SpanInfo, INTERNAL_TARGET};
 use chrono::Utc;
+use compact_str::{format_compact, CompactString, ToCompactString};
+use parking_lot::RwLock;
+use serde_json::Value;
+use smallvec::SmallVec;
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 use tracing::field::{Field, Visit};
-use tracing::{Level, Subscriber};
-use tracing_subscriber::layer::Context;
-use tracing_subscriber::registry::LookupSpan;
-use tracing_subscriber::Layer;
+use tracing::subscriber::Interest;
+use tracing::{Level, Metadata, Subscriber};
+use tracing_subscriber::filter::FilterExt;
+use tracing_subscriber::layer::{Context, Filter};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::{reload, EnvFilter, Layer};
+
+/// Number of fields stored inline before the visitor spills to the heap
+const INLINE_FIELDS: usize = 8;
+
+/// Field holding the messages of a recorded error and its sources
+pub(crate) const ERROR_CHAIN_FIELD: &str = "error.chain";
+
+/// A visited field value
+///
+/// Text stays inline until the event is stored, numbers, booleans and error
+/// chains are kept as JSON so they aren't turned into strings.
+#[derive(Debug, Clone)]
+enum FieldValue {
+    Text(CompactString),
+    Json(Value),
+}
+
+impl FieldValue {
+    fn to_json(&self) -> Value {
+        match self {
+            FieldValue::Text(text) => Value::String(text.to_string()),
+            FieldValue::Json(value) => value.clone(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            FieldValue::Text(text) => text.to_string(),
+            FieldValue::Json(value) => field_text(value).into_owned(),
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Text(text) => f.write_str(text),
+            FieldValue::Json(value) => f.write_str(&field_text(value)),
+        }
+    }
+}
 
 /// Visitor that collects fields from tracing events
+///
+/// Field names are `&'static str` from the callsite and short values are
+/// stored inline, so visiting typical events doesn't allocate. Storing the
+/// event still does: `to_map` builds its field map with a `String` per text
+/// value.
 struct FieldVisitor {
-    fields: HashMap<String, String>,
+    fields: SmallVec<[(&'static str, FieldValue); INLINE_FIELDS]>,
+    /// Whether a `dyn Error` was recorded
+    has_error: bool,
+    /// Lengths text values are cut to
+    limits: ValueLimits,
+    /// Whether a value was cut
+    truncated: bool,
 }
 
 impl FieldVisitor {
     fn new() -> Self {
+        Self::with_limits(ValueLimits::default())
+    }
+
+    fn with_limits(limits: ValueLimits) -> Self {
         Self {
-            fields: HashMap::new(),
+            fields: SmallVec::new(),
+            has_error: false,
+            limits,
+            truncated: false,
+        }
+    }
+
+    /// Text of a value, cut to the limit of the field
+    fn text(&mut self, name: &str, value: fmt::Arguments<'_>) -> CompactString {
+        let Some(max) = self.limits.for_field(name) else {
+            return format_compact!("{}", value);
+        };
+        let mut writer = LimitedWriter::new(max);
+        let _ = fmt::Write::write_fmt(&mut writer, value);
+        let (text, truncated) = writer.finish();
+        self.truncated |= truncated;
+        text
+    }
+
+    /// Set a field, replacing an earlier value with the same name
+    fn insert(&mut self, name: &'static str, value: FieldValue) {
+        match self.fields.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.fields.push((name, value)),
         }
     }
+
+    fn get(&self, name: &str) -> Option<&FieldValue> {
+        self.fields.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    /// Value of a text field, e.g. the target of a bridged `log` record
+    fn get_text(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            FieldValue::Text(text) => Some(text),
+            FieldValue::Json(_) => None,
+        }
+    }
+
+    fn remove(&mut self, name: &str) -> Option<FieldValue> {
+        let index = self.fields.iter().position(|(n, _)| *n == name)?;
+        Some(self.fields.remove(index).1)
+    }
+
+    /// Convert into the owned map stored on events
+    ///
+    /// Allocates the map and each text value, once per captured event.
+    fn to_map(&self) -> HashMap<Interned, Value> {
+        self.fields
+            .iter()
+            .map(|(k, v)| (Interned::from_static(k), v.to_json()))
+            .collect()
+    }
+
+    /// Convert into the text map stored on spans
+    fn to_text_map(&self) -> HashMap<String, String> {
+        self.fields
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_text()))
+            .collect()
+    }
 }
 
 impl Visit for FieldVisitor {
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        self.fields
-            .insert(field.name().to_string(), format!("{:?}", value));
+        let text = self.text(field.name(), format_args!("{:?}", value));
+        self.insert(field.name(), FieldValue::Text(text));
     }
 
     fn record_str(&mut self, field: &Field, value: &str) {
-        self.fields
-            .insert(field.name().to_string(), value.to_string());
+        let text = match self.limits.for_field(field.name()) {
+            Some(max) if value.len() > max => self.text(field.name(), format_args!("{}", value)),
+            _ => CompactString::from(value),
+        };
+        self.insert(field.name(), FieldValue::Text(text));
     }
 
     fn record_i64(&mut self, field: &Field, value: i64) {
-        self.fields
-            .insert(field.name().to_string(), value.to_string());
+        self.insert(field.name(), FieldValue::Json(value.into()));
     }
 
     fn record_u64(&mut self, field: &Field, value: u64) {
-        self.fields
-            .insert(field.name().to_string(), value.to_string());
+        self.insert(field.name(), FieldValue::Json(value.into()));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        // JSON has no NaN or infinity, keep their text instead of null
+        let value = match serde_json::Number::from_f64(value) {
+            Some(number) => FieldValue::Json(Value::Number(number)),
+            None => FieldValue::Text(value.to_compact_string()),
+        };
+        self.insert(field.name(), value);
     }
 
     fn record_bool(&mut self, field: &Field, value: bool) {
-        self.fields
-            .insert(field.name().to_string(), value.to_string());
+        self.insert(field.name(), FieldValue::Json(value.into()));
+    }
+
+    /// Errors keep their message in the field and the messages of the error
+    /// and all its sources, outermost first, in `error.chain`
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        let chain = std::iter::successors(Some(value), |error| error.source())
+            .map(|error| Value::String(self.text(field.name(), format_args!("{}", error)).into()))
+            .collect();
+        let text = self.text(field.name(), format_args!("{}", value));
+        self.insert(field.name(), FieldValue::Text(text));
+        self.insert(ERROR_CHAIN_FIELD, FieldValue::Json(Value::Array(chain)));
+        self.has_error = true;
     }
 }
 
+/// A console receiving captured events, with its own filter
+struct Registration {
+    storage: LogStorage,
+    filter: TargetFilter,
+    /// Targets never captured for this console, with their submodules
+    ignored: Vec<String>,
+}
+
+impl Registration {
+    fn wants(&self, target: &str, level: &Level) -> bool {
+        !self
+            .ignored
+            .iter()
+            .any(|ignored| is_within(target, ignored))
+            && self.filter.enabled(target, level)
+    }
+}
+
+/// Consoles fed by a capture layer
+///
+/// Several consoles can share one layer, each event is pushed to every
+/// console whose filter lets it through.
+#[derive(Clone, Default)]
+pub(crate) struct CaptureRegistry {
+    consoles: Arc<RwLock<Vec<Registration>>>,
+}
+
+impl CaptureRegistry {
+    /// Length limits keeping what every console keeps, each console cuts
+    /// further to its own limits when storing
+    fn value_limits(&self) -> ValueLimits {
+        self.consoles
+            .read()
+            .iter()
+            .map(|console| console.storage.value_limits())
+            .reduce(ValueLimits::loosest)
+            .unwrap_or_default()
+    }
+
+    /// Add a console to receive events passing `filter`
+    ///
+    /// The console ignores `DEFAULT_IGNORED_TARGETS`.
+    pub fn register(&self, storage: LogStorage, filter: TargetFilter) {
+        let ignored = DEFAULT_IGNORED_TARGETS
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        self.register_ignoring(storage, filter, ignored);
+    }
+
+    /// Add a console to receive events passing `filter`, except those of the
+    /// `ignored` targets
+    pub fn register_ignoring(
+        &self,
+        storage: LogStorage,
+        filter: TargetFilter,
+        ignored: Vec<String>,
+    ) {
+        self.consoles.write().push(Registration {
+            storage,
+            filter,
+            ignored,
+        });
+        // Callsites rejected earlier may be wanted by the new console. The lock
+        // must be released here, rebuilding calls back into `callsite_enabled`.
+        tracing::callsite::rebuild_interest_cache();
+    }
+
+    /// Remove a console, returns false if it isn't registered
+    pub fn unregister(&self, storage: &LogStorage) -> bool {
+        {
+            let mut consoles = self.consoles.write();
+            let before = consoles.len();
+            consoles.retain(|c| !c.storage.ptr_eq(storage));
+            if consoles.len() == before {
+                return false;
+            }
+        }
+        // Outside the lock, see `register`
+        tracing::callsite::rebuild_interest_cache();
+        true
+    }
+
+    /// Guard removing a registered console once dropped
+    pub fn registration(&self, storage: LogStorage) -> CaptureRegistration {
+        CaptureRegistration {
+            registry: self.clone(),
+            storage,
+        }
+    }
+
+    /// Filter of a registered console
+    pub fn filter_of(&self, storage: &LogStorage) -> Option<TargetFilter> {
+        self.consoles
+            .read()
+            .iter()
+            .find(|c| c.storage.ptr_eq(storage))
+            .map(|c| c.filter.clone())
+    }
+
+    /// Replace the filter of a registered console, returns false if it isn't registered
+    pub fn set_filter(&self, storage: &LogStorage, filter: TargetFilter) -> bool {
+        {
+            let mut consoles = self.consoles.write();
+            let Some(console) = consoles.iter_mut().find(|c| c.storage.ptr_eq(storage)) else {
+                return false;
+            };
+            console.filter = filter;
+        }
+        // Outside the lock, see `register`
+        tracing::callsite::rebuild_interest_cache();
+        true
+    }
+
+    /// Targets ignored by a registered console
+    pub fn ignored_targets_of(&self, storage: &LogStorage) -> Option<Vec<String>> {
+        self.consoles
+            .read()
+            .iter()
+            .find(|c| c.storage.ptr_eq(storage))
+            .map(|c| c.ignored.clone())
+    }
+
+    /// Replace the ignored targets of a registered console, returns false if
+    /// it isn't registered
+    pub fn set_ignored_targets(&self, storage: &LogStorage, ignored: Vec<String>) -> bool {
+        {
+            let mut consoles = self.consoles.write();
+            let Some(console) = consoles.iter_mut().find(|c| c.storage.ptr_eq(storage)) else {
+                return false;
+            };
+            console.ignored = ignored;
+        }
+        // Outside the lock, see `register`
+        tracing::callsite::rebuild_interest_cache();
+        true
+    }
+
+    /// Storages of the consoles wanting events with this target and level
+    ///
+    /// The handles are cloned so events are stored without holding the lock,
+    /// storing may log and come back here, e.g. a sink or a spill warning.
+    /// With a `register` waiting for the write lock, taking the fair read
+    /// lock again on the same thread would deadlock.
+    fn receivers(&self, target: &str, level: &Level) -> SmallVec<[LogStorage; 4]> {
+        self.consoles
+            .read()
+            .iter()
+            .filter(|c| c.wants(target, level))
+            .map(|c| c.storage.clone())
+            .collect()
+    }
+
+    /// Storages of all consoles, cloned like in `receivers`
+    fn storages(&self) -> SmallVec<[LogStorage; 4]> {
+        self.consoles
+            .read()
+            .iter()
+            .map(|c| c.storage.clone())
+            .collect()
+    }
+
+    /// Check whether the capture layer needs to see a span or event callsite
+    fn callsite_wanted(&self, metadata: &Metadata<'_>) -> bool {
+        // Spans are always needed, they provide context for captured events
+        !metadata.is_event()
+            || is_log_bridged(metadata)
+            || self.wants(metadata.target(), metadata.level())
+    }
+
+    /// Check whether any console wants events with this target and level
+    fn wants(&self, target: &str, level: &Level) -> bool {
+        if is_diagnostic(target, level) {
+            return true;
+        }
+        !is_own(target) && self.consoles.read().iter().any(|c| c.wants(target, level))
+    }
+}
+
+/// Removes a console from its registry when dropped
+///
+/// Shared by the console's router and handles, so a console nothing refers
+/// to anymore stops receiving events.
+pub(crate) struct CaptureRegistration {
+    registry: CaptureRegistry,
+    storage: LogStorage,
+}
+
+impl Drop for CaptureRegistration {
+    fn drop(&mut self) {
+        self.registry.unregister(&self.storage);
+    }
+}
+
+/// Events bridged from the `log` crate carry their real target in a field,
+/// so they can only be filtered after visiting
+fn is_log_bridged(metadata: &Metadata<'_>) -> bool {
+    metadata.fields().field("log.target").is_some()
+}
+
+/// Reject events no console wants before fields are visited
+///
+/// Works per layer: it only disables the capture layer, other layers of the
+/// subscriber still see every event.
+impl<S> Filter<S> for CaptureRegistry {
+    fn enabled(&self, metadata: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+        self.callsite_wanted(metadata)
+    }
+
+    fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.callsite_wanted(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+}
+
+/// Registry of the capture layer installed as part of the global subscriber
+pub(crate) fn global_registry() -> &'static CaptureRegistry {
+    static REGISTRY: OnceLock<CaptureRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(CaptureRegistry::default)
+}
+
+/// Filter applied to the capture layer only
+///
+/// Defaults to "trace" for all targets except:
+/// - this crate, only its diagnostics at "info" and above are kept apart
+/// - "log" target (noisy compatibility layer from log crate)
+pub(crate) fn capture_env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("trace,tracing_web_console=info,log=off"))
+}
+
+/// Handle replacing the environment filter of the global capture layer
+static ENV_FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Directives of the global capture layer's environment filter, `None` if
+/// the global subscriber isn't ours
+pub(crate) fn current_env_filter() -> Option<String> {
+    ENV_FILTER_HANDLE
+        .get()?
+        .with_current(|filter| filter.to_string())
+        .ok()
+}
+
+/// Replace the environment filter of the global capture layer
+///
+/// Applies to all consoles of the process, unlike their own filters.
+pub(crate) fn set_env_filter(directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::builder()
+        .parse(directives)
+        .map_err(|e| e.to_string())?;
+    let handle = ENV_FILTER_HANDLE
+        .get()
+        .ok_or("another global subscriber is installed")?;
+    handle.reload(filter).map_err(|e| e.to_string())
+}
+
+/// Whether `install_global` set the global subscriber
+static INSTALLED: OnceLock<bool> = OnceLock::new();
+/// Set once a layer feeding `global_registry()` was handed out or installed
+static CAPTURE_ATTACHED: AtomicBool = AtomicBool::new(false);
+
+/// Whether events reach the consoles: the console's own global subscriber is
+/// installed or a capture layer was composed into another subscriber
+pub(crate) fn capture_active() -> bool {
+    CAPTURE_ATTACHED.load(Ordering::Relaxed)
+}
+
+/// Whether the global subscriber is the one installed by the console
+pub(crate) fn installed_by_console() -> bool {
+    INSTALLED.get().copied().unwrap_or(false)
+}
+
+/// Layer feeding all consoles, for composing into your own subscriber
+///
+/// Consoles install a global subscriber of their own, which fails if the
+/// application already set one. In that case no events are captured until
+/// this layer is added to the application's subscriber:
+///
+/// ```rust,no_run
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use tracing_subscriber::util::SubscriberInitExt;
+///
+/// tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(tracing_web_console::capture_layer())
+///     .init();
+/// ```
+pub fn capture_layer<S>() -> impl Layer<S> + Send + Sync + 'static
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    CAPTURE_ATTACHED.store(true, Ordering::Relaxed);
+    LogCaptureLayer::with_registry(global_registry().clone())
+        .with_filter(capture_env_filter().and(global_registry().clone()))
+}
+
+/// Layer feeding only the consoles of `registry`, see `TracingLayerBuilder::build_layer`
+pub(crate) fn registry_layer<S>(registry: CaptureRegistry) -> impl Layer<S> + Send + Sync + 'static
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    CAPTURE_ATTACHED.store(true, Ordering::Relaxed);
+    LogCaptureLayer::with_registry(registry.clone()).with_filter(registry)
+}
+
+/// Install the global subscriber feeding `global_registry()`, at most once
+///
+/// Returns false if another global subscriber was already set.
+pub(crate) fn install_global() -> bool {
+    *INSTALLED.get_or_init(|| {
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::util::SubscriberInitExt;
+
+        // The filter is scoped to the capture layer, so it doesn't change the
+        // verbosity of other layers composed into the same subscriber
+        let (env_filter, handle) = reload::Layer::new(capture_env_filter());
+        let capture_layer = LogCaptureLayer::with_registry(global_registry().clone())
+            .with_filter(env_filter.and(global_registry().clone()));
+
+        let installed = tracing_subscriber::registry()
+            .with(capture_layer)
+            .try_init()
+            .is_ok();
+        if installed {
+            let _ = ENV_FILTER_HANDLE.set(handle);
+            CAPTURE_ATTACHED.store(true, Ordering::Relaxed);
+        }
+        installed
+    })
+}
+
 /// Custom layer that captures tracing events and stores them
 pub struct LogCaptureLayer {
-    storage: LogStorage,
+    registry: CaptureRegistry,
 }
 
 impl LogCaptureLayer {
-    /// Create a new log capture layer
+    /// Create a new log capture layer feeding a single storage
     pub fn new(storage: LogStorage) -> Self {
-        Self { storage }
+        let registry = CaptureRegistry::default();
+        registry.register(storage, TargetFilter::default());
+        Self::with_registry(registry)
     }
 
-    /// Extract the message from event fields
-    fn extract_message(event: &tracing::Event) -> String {
-        let mut visitor = FieldVisitor::new();
-        event.record(&mut visitor);
+    /// Create a capture layer feeding all consoles of a registry
+    pub(crate) fn with_registry(registry: CaptureRegistry) -> Self {
+        Self { registry }
+    }
 
+    /// Extract the message from the visited event fields
+    fn extract_message(visitor: &FieldVisitor) -> String {
         // Try to get the message field first
-        if let Some(message) = visitor.fields.get("message") {
-            return message.clone();
+        if let Some(message) = visitor.get("message") {
+            return message.to_text();
         }
 
         // If no message field, join all fields
@@ -81,7 +567,7 @@ impl LogCaptureLayer {
     }
 
     /// Convert tracing Level to string
-    fn level_to_string(level: &Level) -> String {
+    fn level_name(level: &Level) -> &'static str {
         match *level {
             Level::TRACE => "TRACE",
             Level::DEBUG => "DEBUG",
@@ -89,7 +575,6 @@ impl LogCaptureLayer {
             Level::WARN => "WARN",
             Level::ERROR => "ERROR",
         }
-        .to_string()
     }
 
     /// Extract span information from the current context
@@ -102,25 +587,109 @@ impl LogCaptureLayer {
 
         // Get span name and fields
         let name = span.name().to_string();
-        let mut fields = HashMap::new();
+        let fields = ext
+            .get::<SpanFields>()
+            .map(|cached| cached.0.clone())
+            .unwrap_or_default();
 
-        // Try to collect span fields
-        if let Some(field_visitor) = ext.get::<FieldVisitor>() {
-            fields = field_visitor.fields.clone();
+        // Ancestors outermost first, for filters matching any enclosing span,
+        // their fields closest first so inner spans shadow outer ones
+        let mut ancestors = Vec::new();
+        let mut parent_fields = HashMap::new();
+        for parent in span.scope().skip(1) {
+            ancestors.push(parent.name().to_string());
+            if let Some(cached) = parent.extensions().get::<SpanFields>() {
+                for (key, value) in &cached.0 {
+                    if !fields.contains_key(key) && !parent_fields.contains_key(key) {
+                        parent_fields.insert(key.clone(), value.clone());
+                    }
+                }
+            }
         }
+        ancestors.reverse();
 
-        Some(SpanInfo { name, fields })
+        Some(SpanInfo {
+            id: ext.get::<SpanTiming>().map(|timing| timing.id.clone()),
+            name,
+            fields,
+            ancestors,
+            parent_fields,
+        })
     }
 }
 
-/// Targets to filter out to avoid noise and recursive logging
-const FILTERED_TARGETS: &[&str] = &[
-    "log",                 // log crate compatibility layer
-    "tracing_web_console", // our own crate (avoid recursion)
-    "tungstenite",         // WebSocket library internals
-    "tokio_tungstenite",   // async WebSocket library internals
+/// Span fields copied onto every event inside the span, so they can be filtered on
+const PROMOTED_FIELDS: &[&str] = &["request_id"];
+
+/// Field holding the ID of the tokio task an event was logged from or a span
+/// was created in
+pub(crate) const TASK_ID_FIELD: &str = "task_id";
+
+/// Record the current tokio task, unless the field is already set explicitly
+fn record_task_id(visitor: &mut FieldVisitor) {
+    if visitor.get(TASK_ID_FIELD).is_some() {
+        return;
+    }
+    if let Some(id) = tokio::task::try_id() {
+        visitor.insert(TASK_ID_FIELD, FieldValue::Text(id.to_compact_string()));
+    }
+}
+
+/// Numeric ID of a thread, parsed from its debug output since
+/// `ThreadId::as_u64` is unstable
+fn thread_id(thread: &std::thread::Thread) -> Option<u64> {
+    format!("{:?}", thread.id())
+        .strip_prefix("ThreadId(")?
+        .strip_suffix(')')?
+        .parse()
+        .ok()
+}
+
+/// Fields of a span as text, kept in its extensions next to the
+/// `FieldVisitor` and rendered again only when the span records new values
+struct SpanFields(HashMap<String, String>);
+
+/// Timing of a span recorded in the consoles, kept in its extensions
+struct SpanTiming {
+    /// ID of the span's record
+    id: String,
+    /// Number of times the span is currently entered, e.g. by several threads
+    depth: u32,
+    /// When the span was entered while not entered before
+    entered_at: Option<Instant>,
+    /// Time spent entered up to the last exit
+    busy: Duration,
+    /// Events captured directly in the span
+    events: u64,
+}
+
+/// Targets ignored by consoles unless configured otherwise, to avoid noise
+pub(crate) const DEFAULT_IGNORED_TARGETS: &[&str] = &[
+    "log",               // log crate compatibility layer
+    "tungstenite",       // WebSocket library internals
+    "tokio_tungstenite", // async WebSocket library internals
 ];
 
+/// Our own crate, always ignored to avoid recursive logging
+pub(crate) const OWN_TARGET: &str = "tracing_web_console";
+
+/// Whether `target` is `module` or one of its submodules
+fn is_within(target: &str, module: &str) -> bool {
+    target
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Check whether a target belongs to this crate, which is never captured
+fn is_own(target: &str) -> bool {
+    is_within(target, OWN_TARGET)
+}
+
+/// Events of this crate worth keeping in the diagnostics buffer
+fn is_diagnostic(target: &str, level: &Level) -> bool {
+    *level <= Level::INFO && is_within(target, INTERNAL_TARGET)
+}
+
 impl<S> Layer<S> for LogCaptureLayer
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
@@ -129,49 +698,123 @@ where
         let metadata = event.metadata();
         let target = metadata.target();
 
+        // Skip unwanted events before visiting any fields, this also covers
+        // layers used without the registry filter
+        if !is_log_bridged(metadata) && !self.registry.wants(target, metadata.level()) {
+            return;
+        }
+
         // Extract all fields including the message
-        let mut visitor = FieldVisitor::new();
+        let mut visitor = FieldVisitor::with_limits(self.registry.value_limits());
         event.record(&mut visitor);
 
         // Determine the actual target - for events from the log crate bridge,
         // the real target is in the "log.target" field
         let actual_target = visitor
-            .fields
-            .get("log.target")
-            .cloned()
-            .unwrap_or_else(|| target.to_string());
+            .get_text("log.target")
+            .map_or_else(|| Interned::from_static(target), Interned::new);
 
-        // Filter out noisy targets (check actual target, not metadata target)
-        for filtered in FILTERED_TARGETS {
-            if actual_target == *filtered || actual_target.starts_with(&format!("{}::", filtered)) {
-                return;
+        // The console's own events go to the diagnostics buffers, capturing
+        // them as regular events could recurse
+        if is_diagnostic(&actual_target, metadata.level()) {
+            let message = Self::extract_message(&visitor);
+            visitor.remove("message");
+            let diagnostic = LogEvent {
+                target: actual_target,
+                level: Interned::from_static(Self::level_name(metadata.level())),
+                module_path: metadata.module_path().map(|s| s.to_string()),
+                file: metadata.file().map(|s| s.to_string()),
+                line: metadata.line(),
+                fields: visitor.to_map(),
+                ..LogEvent::internal("INFO", &message, HashMap::new())
+            };
+            for storage in self.registry.storages() {
+                storage.diagnose(diagnostic.clone());
             }
+            return;
+        }
+
+        // Filter out noisy targets (check actual target, not metadata target)
+        if is_own(&actual_target) {
+            return;
+        }
+
+        let receivers = self.registry.receivers(&actual_target, metadata.level());
+        if receivers.is_empty() {
+            return;
         }
 
         // Extract message separately
-        let message = Self::extract_message(event);
+        let message = Self::extract_message(&visitor);
 
         // Remove "message" and log crate fields from fields to avoid duplication/noise
-        visitor.fields.remove("message");
-        visitor.fields.remove("log.target");
-        visitor.fields.remove("log.module_path");
-        visitor.fields.remove("log.file");
-        visitor.fields.remove("log.line");
+        visitor.remove("message");
+        visitor.remove("log.target");
+
+        // Bridged `log` records carry their location in fields, the metadata
+        // points at the bridge
+        let module_path = visitor
+            .remove("log.module_path")
+            .map(|v| v.to_text())
+            .or_else(|| metadata.module_path().map(str::to_string));
+        let file = visitor
+            .remove("log.file")
+            .map(|v| v.to_text())
+            .or_else(|| metadata.file().map(str::to_string));
+        let line = visitor
+            .remove("log.line")
+            .and_then(|v| v.to_text().parse().ok())
+            .or_else(|| metadata.line());
+
+        // Promote correlation fields from the closest enclosing span
+        for name in PROMOTED_FIELDS {
+            if visitor.get(name).is_some() {
+                continue;
+            }
+            let value = ctx.event_scope(event).and_then(|mut scope| {
+                scope.find_map(|span| {
+                    let ext = span.extensions();
+                    ext.get::<FieldVisitor>()?.get(name).cloned()
+                })
+            });
+            if let Some(value) = value {
+                visitor.insert(name, value);
+            }
+        }
+        record_task_id(&mut visitor);
 
         // Create log event
+        let thread = std::thread::current();
         let log_event = LogEvent {
+            id: crate::id::new_ulid(),
+            seq: 0,
             timestamp: Utc::now(),
-            level: Self::level_to_string(metadata.level()),
+            level: Interned::from_static(Self::level_name(metadata.level())),
             target: actual_target,
             message,
-            fields: visitor.fields,
+            fields: visitor.to_map(),
             span: Self::extract_span_info(event, &ctx),
-            file: metadata.file().map(|s| s.to_string()),
-            line: metadata.line(),
+            module_path,
+            file,
+            line,
+            source_url: None,
+            thread_name: thread.name().map(str::to_string),
+            thread_id: thread_id(&thread),
+            repeat_count: None,
+            is_error: visitor.has_error,
+            truncated: visitor.truncated,
         };
 
-        // Store the event
-        self.storage.push(log_event);
+        if let Some(span) = ctx.event_span(event) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                timing.events += 1;
+            }
+        }
+
+        // Store the event in every interested console
+        for storage in receivers {
+            storage.capture(log_event.clone());
+        }
     }
 
     fn on_new_span(
@@ -184,9 +827,110 @@ where
         let span = ctx.span(id).expect("Span not found");
         let mut visitor = FieldVisitor::new();
         attrs.record(&mut visitor);
+        record_task_id(&mut visitor);
+
+        let fields = visitor.to_text_map();
+
+        let metadata = attrs.metadata();
+        let receivers = self.registry.receivers(metadata.target(), metadata.level());
+        if !is_own(metadata.target()) && !receivers.is_empty() {
+            // Spans filtered out are skipped, children attach to the closest recorded ancestor
+            let parent_id = span.scope().skip(1).find_map(|parent| {
+                let ext = parent.extensions();
+                ext.get::<SpanTiming>().map(|timing| timing.id.clone())
+            });
+            let record = SpanRecord {
+                id: crate::id::new_ulid(),
+                parent_id,
+                name: metadata.name().to_string(),
+                target: metadata.target().to_string(),
+                level: Self::level_name(metadata.level()).to_string(),
+                fields: fields.clone(),
+                started_at: Utc::now(),
+                closed_at: None,
+                duration_us: None,
+                busy_us: 0,
+                events: 0,
+            };
+            span.extensions_mut().insert(SpanTiming {
+                id: record.id.clone(),
+                depth: 0,
+                entered_at: None,
+                busy: Duration::ZERO,
+                events: 0,
+            });
+            for storage in receivers {
+                storage.spans().open(record.clone());
+            }
+        }
 
         let mut extensions = span.extensions_mut();
         extensions.insert(visitor);
+        extensions.insert(SpanFields(fields));
+    }
+
+    fn on_record(
+        &self,
+        id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        ctx: Context<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(visitor) = extensions.get_mut::<FieldVisitor>() else {
+            return;
+        };
+        values.record(visitor);
+        let fields = visitor.to_text_map();
+        extensions.insert(SpanFields(fields));
+    }
+
+    fn on_enter(&self, id: &tracing::span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>() {
+            timing.depth += 1;
+            if timing.depth == 1 {
+                timing.entered_at = Some(Instant::now());
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &tracing::span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>() {
+            timing.depth = timing.depth.saturating_sub(1);
+            if timing.depth == 0 {
+                if let Some(entered_at) = timing.entered_at.take() {
+                    timing.busy += entered_at.elapsed();
+                }
+            }
+        }
+    }
+
+    fn on_close(&self, id: tracing::span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(timing) = extensions.get::<SpanTiming>() else {
+            return;
+        };
+        let closed_at = Utc::now();
+        let busy_us = timing.busy.as_micros().min(u64::MAX as u128) as u64;
+        // Consoles that didn't record the span ignore the close
+        for storage in self.registry.storages() {
+            storage
+                .spans()
+                .close(&timing.id, closed_at, busy_us, timing.events);
+        }
     }
 }
 
@@ -196,22 +940,483 @@ mod tests {
 
     #[test]
     fn test_level_conversion() {
-        assert_eq!(LogCaptureLayer::level_to_string(&Level::TRACE), "TRACE");
-        assert_eq!(LogCaptureLayer::level_to_string(&Level::DEBUG), "DEBUG");
-        assert_eq!(LogCaptureLayer::level_to_string(&Level::INFO), "INFO");
-        assert_eq!(LogCaptureLayer::level_to_string(&Level::WARN), "WARN");
-        assert_eq!(LogCaptureLayer::level_to_string(&Level::ERROR), "ERROR");
+        assert_eq!(LogCaptureLayer::level_name(&Level::TRACE), "TRACE");
+        assert_eq!(LogCaptureLayer::level_name(&Level::DEBUG), "DEBUG");
+        assert_eq!(LogCaptureLayer::level_name(&Level::INFO), "INFO");
+        assert_eq!(LogCaptureLayer::level_name(&Level::WARN), "WARN");
+        assert_eq!(LogCaptureLayer::level_name(&Level::ERROR), "ERROR");
     }
 
     #[test]
     fn test_field_visitor() {
-        let visitor = FieldVisitor::new();
+        let mut visitor = FieldVisitor::new();
         assert_eq!(visitor.fields.len(), 0);
 
+        visitor.insert("user", FieldValue::Text(CompactString::from("alice")));
+        visitor.insert("user", FieldValue::Text(CompactString::from("bob")));
+        visitor.insert("status", FieldValue::Json(200u64.into()));
+        visitor.insert("ratio", FieldValue::Json(0.5.into()));
+        assert_eq!(visitor.fields.len(), 3);
+        assert!(!visitor.fields.spilled());
+        assert_eq!(visitor.get_text("user"), Some("bob"));
+        assert_eq!(visitor.get_text("status"), None);
+        assert_eq!(visitor.to_map()["status"], 200);
+        assert_eq!(visitor.to_text_map()["ratio"], "0.5");
+        assert_eq!(visitor.remove("status").unwrap().to_text(), "200");
+        assert_eq!(visitor.to_map()["user"], "bob");
+
         // FieldVisitor is tested implicitly through the subscriber integration tests
         // Direct testing requires complex tracing infrastructure setup
     }
 
+    #[test]
+    fn test_error_chain_is_recorded() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        #[derive(Debug)]
+        struct Failed(&'static str, Option<Box<Failed>>);
+
+        impl fmt::Display for Failed {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        impl std::error::Error for Failed {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                self.1.as_deref().map(|e| e as _)
+            }
+        }
+
+        let storage = LogStorage::new();
+        let registry = CaptureRegistry::default();
+        registry.register(storage.clone(), TargetFilter::default());
+        let subscriber =
+            tracing_subscriber::registry().with(LogCaptureLayer::with_registry(registry));
+        let error = Failed(
+            "request failed",
+            Some(Box::new(Failed("connection reset", None))),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let error: &(dyn std::error::Error + 'static) = &error;
+            tracing::error!(target: "app", error, "handler");
+            tracing::info!(target: "app", "fine");
+        });
+
+        let (events, _) = storage.get_filtered(&crate::storage::LogFilter::default(), None, None);
+        let failed = events.iter().find(|e| e.message == "handler").unwrap();
+        assert!(failed.is_error);
+        assert_eq!(failed.fields["error"], "request failed");
+        assert_eq!(
+            failed.fields[ERROR_CHAIN_FIELD],
+            serde_json::json!(["request failed", "connection reset"])
+        );
+        let fine = events.iter().find(|e| e.message == "fine").unwrap();
+        assert!(!fine.is_error);
+        assert!(!fine.fields.contains_key(ERROR_CHAIN_FIELD));
+    }
+
+    #[test]
+    fn test_long_values_are_cut_while_recording() {
+        use crate::limits::MIN_VALUE_LEN;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        storage.set_value_limits(ValueLimits {
+            max_message_len: None,
+            max_field_len: Some(MIN_VALUE_LEN),
+        });
+        let registry = CaptureRegistry::default();
+        registry.register(storage.clone(), TargetFilter::default());
+        let subscriber =
+            tracing_subscriber::registry().with(LogCaptureLayer::with_registry(registry));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app", body = ?[0u8; 1000], "upload");
+            tracing::info!(target: "app", body = "short", "small");
+        });
+
+        let (events, _) = storage.get_filtered(&crate::storage::LogFilter::default(), None, None);
+        let upload = events.iter().find(|e| e.message == "upload").unwrap();
+        assert!(upload.truncated);
+        let body = upload.fields["body"].as_str().unwrap();
+        assert!(body.len() <= MIN_VALUE_LEN);
+        assert!(body.ends_with("bytes truncated]"));
+        let small = events.iter().find(|e| e.message == "small").unwrap();
+        assert!(!small.truncated);
+        assert_eq!(small.fields["body"], "short");
+    }
+
+    #[test]
+    fn test_events_are_routed_by_console_filter() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let everything = LogStorage::new();
+        let errors_only = LogStorage::new();
+        let registry = CaptureRegistry::default();
+        registry.register(everything.clone(), TargetFilter::default());
+        registry.register(errors_only.clone(), TargetFilter::parse("error").unwrap());
+
+        let subscriber =
+            tracing_subscriber::registry().with(LogCaptureLayer::with_registry(registry));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app", "hello");
+            tracing::error!(target: "app", "boom");
+        });
+
+        let filter = crate::storage::LogFilter::default();
+        assert_eq!(everything.get_filtered(&filter, None, None).1, 2);
+        let (events, count) = errors_only.get_filtered(&filter, None, None);
+        assert_eq!(count, 1);
+        assert_eq!(events[0].message, "boom");
+    }
+
+    #[test]
+    fn test_span_fields_follow_recorded_values() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("request", user = "alice", status = 0);
+            let _outer = outer.enter();
+            let inner =
+                tracing::info_span!("query", table = "orders", status = tracing::field::Empty);
+            let _inner = inner.enter();
+            tracing::info!(target: "app", "before");
+            inner.record("status", 200);
+            outer.record("user", "bob");
+            tracing::info!(target: "app", "after");
+        });
+
+        let filter = crate::storage::LogFilter::default();
+        let (events, _) = storage.get_filtered(&filter, None, None);
+        let span = |message: &str| {
+            events
+                .iter()
+                .find(|e| e.message == message)
+                .and_then(|e| e.span.clone())
+                .unwrap()
+        };
+        let before = span("before");
+        assert_eq!(before.fields["table"], "orders");
+        assert!(!before.fields.contains_key("status"));
+        assert_eq!(before.parent_fields["user"], "alice");
+        assert_eq!(before.parent_fields["status"], "0");
+        let after = span("after");
+        assert_eq!(after.fields["status"], "200");
+        assert_eq!(after.parent_fields["user"], "bob");
+        // The span's own value shadows the enclosing span's
+        assert!(!after.parent_fields.contains_key("status"));
+        assert_eq!(after.ancestors, ["request"]);
+    }
+
+    #[test]
+    fn test_console_can_be_registered_while_storing() {
+        use crate::sink::EventSink;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        // A sink registering a console needs the registry's write lock while
+        // the event is being stored
+        struct Registering(CaptureRegistry);
+        impl EventSink for Registering {
+            fn write(&self, event: &LogEvent) {
+                if event.message == "first" {
+                    self.0.register(LogStorage::new(), TargetFilter::default());
+                }
+            }
+        }
+
+        let storage = LogStorage::new();
+        let registry = CaptureRegistry::default();
+        registry.register(storage.clone(), TargetFilter::default());
+        storage.add_sink(Arc::new(Registering(registry.clone())));
+        let subscriber =
+            tracing_subscriber::registry().with(LogCaptureLayer::with_registry(registry.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app", "first");
+            tracing::info!(target: "app", "second");
+        });
+
+        assert_eq!(registry.consoles.read().len(), 2);
+        let filter = crate::storage::LogFilter::default();
+        assert_eq!(storage.get_filtered(&filter, None, None).1, 2);
+    }
+
+    #[test]
+    fn test_capture_filter_does_not_affect_other_layers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        struct CountingLayer(Arc<AtomicUsize>);
+        impl<S: Subscriber> Layer<S> for CountingLayer {
+            fn on_event(&self, _event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let storage = LogStorage::new();
+        let seen = Arc::new(AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry()
+            .with(LogCaptureLayer::new(storage.clone()).with_filter(EnvFilter::new("info")))
+            .with(CountingLayer(seen.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!(target: "app", "verbose");
+            tracing::info!(target: "app", "hello");
+        });
+
+        let filter = crate::storage::LogFilter::default();
+        assert_eq!(storage.get_filtered(&filter, None, None).1, 1);
+        assert_eq!(seen.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_own_events_are_kept_as_diagnostics() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "tracing_web_console::api::logs", client = 7, "send failed");
+            tracing::debug!(target: "tracing_web_console::api::logs", "too verbose");
+            tracing::info!(target: "app", "captured");
+        });
+
+        assert_eq!(storage.len(), 1);
+        let diagnostics = storage.diagnostics().recent(10);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].level, "WARN");
+        assert_eq!(diagnostics[0].message, "send failed");
+        assert_eq!(diagnostics[0].fields["client"], 7);
+    }
+
+    #[test]
+    fn test_request_id_is_promoted_from_enclosing_span() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request", request_id = "abc");
+            let _request = request.enter();
+            let handler = tracing::info_span!("handler");
+            let _handler = handler.enter();
+            tracing::info!(target: "app", "inside");
+            tracing::info!(target: "app", request_id = "own", "explicit");
+        });
+
+        let filter = crate::storage::LogFilter {
+            sort_order: crate::storage::SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let (events, _) = storage.get_filtered(&filter, None, None);
+        assert_eq!(events[0].fields["request_id"], "abc");
+        assert_eq!(events[1].fields["request_id"], "own");
+    }
+
+    #[test]
+    fn test_captured_events_share_strings() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        let dispatch = tracing::Dispatch::new(subscriber);
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::warn!(target: "app::orders", order_id = 1, "created");
+        });
+        // A different callsite on another thread
+        std::thread::spawn(move || {
+            tracing::dispatcher::with_default(&dispatch, || {
+                tracing::warn!(target: "app::orders", order_id = 2, "shipped");
+            });
+        })
+        .join()
+        .unwrap();
+
+        let (events, _) = storage.get_filtered(&Default::default(), None, None);
+        let [second, first] = &events[..] else {
+            panic!("expected two events, got {}", events.len());
+        };
+        assert!(first.target.ptr_eq(&second.target));
+        assert!(first.level.ptr_eq(&second.level));
+        let key = |event: &LogEvent| event.fields.get_key_value("order_id").unwrap().0.clone();
+        assert!(key(first).ptr_eq(&key(second)));
+    }
+
+    #[tokio::test]
+    async fn test_task_id_is_captured() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let first = tokio::spawn(async { tracing::info!(target: "app", "first") });
+        let second = tokio::spawn(async {
+            let span = tracing::info_span!("reserve");
+            let _span = span.enter();
+            tracing::info!(target: "app", "second");
+        });
+        let ids = [first.id().to_string(), second.id().to_string()];
+        first.await.unwrap();
+        second.await.unwrap();
+
+        let filter = crate::storage::LogFilter {
+            sort_order: crate::storage::SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let (events, _) = storage.get_filtered(&filter, None, None);
+        assert_eq!(events[0].fields[TASK_ID_FIELD], ids[0]);
+        assert_eq!(events[1].fields[TASK_ID_FIELD], ids[1]);
+        assert_eq!(
+            events[1].span.as_ref().unwrap().fields[TASK_ID_FIELD],
+            ids[1]
+        );
+    }
+
+    #[test]
+    fn test_source_location_is_captured() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app", "located");
+            // What `tracing-log` records for a bridged `log` record
+            tracing::info!(
+                target: "log",
+                log.target = "legacy",
+                log.module_path = "legacy::db",
+                log.file = "src/db.rs",
+                log.line = 7u64,
+                "bridged"
+            );
+        });
+
+        let filter = crate::storage::LogFilter {
+            sort_order: crate::storage::SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        let (events, _) = storage.get_filtered(&filter, None, None);
+        assert_eq!(events[0].file.as_deref(), Some(file!()));
+        assert!(events[0].line.is_some());
+        assert_eq!(events[0].module_path.as_deref(), Some(module_path!()));
+
+        assert_eq!(events[1].target, "legacy");
+        assert_eq!(events[1].module_path.as_deref(), Some("legacy::db"));
+        assert_eq!(events[1].file.as_deref(), Some("src/db.rs"));
+        assert_eq!(events[1].line, Some(7));
+        assert!(!events[1].fields.contains_key("log.file"));
+    }
+
+    #[test]
+    fn test_thread_is_captured() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let storage = LogStorage::new();
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new(storage.clone()));
+        let dispatch = tracing::Dispatch::new(subscriber);
+        tracing::dispatcher::with_default(&dispatch, || tracing::info!(target: "app", "main"));
+        std::thread::Builder::new()
+            .name("blocking-1".to_string())
+            .spawn(move || {
+                tracing::dispatcher::with_default(
+                    &dispatch,
+                    || tracing::info!(target: "app", "blocking"),
+                )
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let mut filter = crate::storage::LogFilter {
+            thread: Some("blocking-1".to_string()),
+            ..Default::default()
+        };
+        let (events, _) = storage.get_filtered(&filter, None, None);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message, "blocking");
+        let id = events[0].thread_id.unwrap();
+
+        filter.thread = Some(id.to_string());
+        assert_eq!(storage.get_filtered(&filter, None, None).1, 1);
+        filter.thread = None;
+        let (events, _) = storage.get_filtered(&filter, None, None);
+        assert!(events
+            .iter()
+            .any(|e| e.thread_id.is_some_and(|other| other != id)));
+    }
+
+    #[test]
+    fn test_registry_wants() {
+        let registry = CaptureRegistry::default();
+        // Nothing is wanted without consoles
+        assert!(!registry.wants("app", &Level::ERROR));
+
+        registry.register(LogStorage::new(), TargetFilter::parse("info").unwrap());
+        assert!(registry.wants("app", &Level::INFO));
+        assert!(!registry.wants("app", &Level::DEBUG));
+        assert!(!registry.wants("tracing_web_console::api", &Level::ERROR));
+        assert!(!registry.wants("tungstenite::protocol", &Level::ERROR));
+    }
+
+    #[test]
+    fn test_set_console_filter() {
+        let registry = CaptureRegistry::default();
+        let storage = LogStorage::new();
+        registry.register(storage.clone(), TargetFilter::parse("info").unwrap());
+
+        assert!(registry.set_filter(&storage, TargetFilter::parse("warn,app=debug").unwrap()));
+        assert!(registry.wants("app::db", &Level::DEBUG));
+        assert!(!registry.wants("other", &Level::INFO));
+        assert_eq!(
+            registry.filter_of(&storage).unwrap().to_string(),
+            "warn,app=debug"
+        );
+
+        // Consoles are told apart by their storage, not by equal contents
+        assert!(!registry.set_filter(&LogStorage::new(), TargetFilter::default()));
+    }
+
+    #[test]
+    fn test_dropped_registration_unregisters() {
+        let registry = CaptureRegistry::default();
+        let storage = LogStorage::new();
+        registry.register(storage.clone(), TargetFilter::parse("info").unwrap());
+        let registration = Arc::new(registry.registration(storage.clone()));
+        let shared = registration.clone();
+
+        drop(registration);
+        assert!(registry.wants("app", &Level::INFO));
+        drop(shared);
+        assert!(!registry.wants("app", &Level::INFO));
+        assert!(registry.filter_of(&storage).is_none());
+        assert!(!registry.unregister(&storage));
+    }
+
+    #[test]
+    fn test_ignored_targets_are_per_console() {
+        let registry = CaptureRegistry::default();
+        let quiet = LogStorage::new();
+        registry.register_ignoring(
+            quiet.clone(),
+            TargetFilter::default(),
+            vec!["hyper".to_string()],
+        );
+        assert!(!registry.wants("hyper::proto", &Level::INFO));
+        assert!(registry.wants("hyper_util", &Level::INFO));
+        // Dropping the defaults captures them again, but never this crate
+        assert!(registry.wants("tungstenite", &Level::INFO));
+        assert!(!registry.wants("tracing_web_console::api", &Level::DEBUG));
+
+        let verbose = LogStorage::new();
+        registry.register(verbose.clone(), TargetFilter::default());
+        assert!(registry.wants("hyper::proto", &Level::INFO));
+
+        assert!(registry.set_ignored_targets(&verbose, vec!["hyper".to_string()]));
+        assert!(!registry.wants("hyper::proto", &Level::INFO));
+        assert_eq!(registry.ignored_targets_of(&quiet).unwrap(), ["hyper"]);
+    }
+
     #[test]
     fn test_log_capture_layer_creation() {
         let storage = LogStorage::new();
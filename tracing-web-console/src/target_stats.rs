@@ -0,0 +1,362 @@
+//! Per-target event counts in rolling time windows
+//!
+//! Counts are kept independently of the buffer, so they still describe the
+//! last hour after the events themselves were evicted.
+
+use crate::storage::{level_to_number, LogEvent};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Width of a counting bucket, windows are accurate to this
+const BUCKET_SECS: i64 = 10;
+/// Buckets older than this are dropped
+const MAX_AGE_SECS: i64 = 3600;
+/// Windows reported by the stats endpoint
+const WINDOWS: &[(&str, i64)] = &[("1m", 60), ("5m", 300), ("15m", 900), ("1h", 3600)];
+/// New targets beyond this are not tracked
+const MAX_TARGETS: usize = 10_000;
+/// Window `events_per_minute` of the target overview is averaged over
+const OVERVIEW_RATE_SECS: i64 = 300;
+/// Level names by `level_to_number() - 1`
+const LEVELS: [&str; 5] = ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
+
+struct Bucket {
+    /// Bucket start divided by `BUCKET_SECS`
+    index: i64,
+    counts: [u64; LEVELS.len()],
+}
+
+#[derive(Default)]
+struct TargetEntry {
+    /// Non-empty buckets, oldest first
+    buckets: VecDeque<Bucket>,
+    /// Events per level since start, never reset
+    totals: [u64; LEVELS.len()],
+    first_seen: Option<DateTime<Utc>>,
+    last_seen: Option<DateTime<Utc>>,
+    last_error: Option<LastError>,
+}
+
+/// Most recent ERROR event of a target
+#[derive(Debug, Clone, Serialize)]
+pub struct LastError {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub message: String,
+}
+
+/// Counts of a target within one window
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowStats {
+    /// Window name, e.g. "5m"
+    pub window: String,
+    pub seconds: i64,
+    pub total: u64,
+    /// Counts per level, levels without events are omitted
+    pub by_level: BTreeMap<String, u64>,
+    /// Events per second
+    pub rate: f64,
+    /// Share of ERROR events, 0.0 to 1.0
+    pub error_share: f64,
+}
+
+/// Recent behavior of a single target
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetStatsResponse {
+    pub target: String,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub last_error: Option<LastError>,
+    pub windows: Vec<WindowStats>,
+}
+
+/// Counts and activity of a target since start, for overviews of all targets
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetOverview {
+    pub target: String,
+    /// Events since start
+    pub total: u64,
+    /// Events since start per level, levels without events are omitted
+    pub by_level: BTreeMap<String, u64>,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Average over the last 5 minutes
+    pub events_per_minute: f64,
+    pub last_error: Option<LastError>,
+}
+
+/// Rolling per-target, per-level counters
+#[derive(Default)]
+pub(crate) struct TargetStats {
+    targets: Mutex<HashMap<String, TargetEntry>>,
+}
+
+impl TargetStats {
+    /// Count an event in its target's bucket for the event timestamp
+    pub fn record(&self, event: &LogEvent) {
+        let level = level_to_number(&event.level);
+        if level == 0 {
+            return;
+        }
+
+        let mut targets = self.targets.lock();
+        if !targets.contains_key(event.target.as_str()) && targets.len() >= MAX_TARGETS {
+            return;
+        }
+        let entry = targets.entry(event.target.to_string()).or_default();
+
+        let index = event.timestamp.timestamp().div_euclid(BUCKET_SECS);
+        // Events usually arrive in order, search from the newest bucket
+        let position = entry.buckets.iter().rposition(|b| b.index <= index);
+        let bucket = match position {
+            Some(i) if entry.buckets[i].index == index => &mut entry.buckets[i],
+            _ => {
+                let at = position.map_or(0, |i| i + 1);
+                entry.buckets.insert(
+                    at,
+                    Bucket {
+                        index,
+                        counts: [0; LEVELS.len()],
+                    },
+                );
+                &mut entry.buckets[at]
+            }
+        };
+        bucket.counts[level as usize - 1] += 1;
+        entry.totals[level as usize - 1] += 1;
+
+        let oldest = index - MAX_AGE_SECS / BUCKET_SECS;
+        while entry.buckets.front().is_some_and(|b| b.index <= oldest) {
+            entry.buckets.pop_front();
+        }
+
+        if !matches!(entry.first_seen, Some(seen) if seen <= event.timestamp) {
+            entry.first_seen = Some(event.timestamp);
+        }
+        if !matches!(entry.last_seen, Some(seen) if seen > event.timestamp) {
+            entry.last_seen = Some(event.timestamp);
+        }
+        let newer_error = !matches!(&entry.last_error, Some(e) if e.timestamp > event.timestamp);
+        if event.level == "ERROR" && newer_error {
+            entry.last_error = Some(LastError {
+                id: event.id.clone(),
+                timestamp: event.timestamp,
+                message: event.message.clone(),
+            });
+        }
+    }
+
+    /// Stats of a target as of `now`, `None` if it never logged
+    pub fn get(&self, target: &str, now: DateTime<Utc>) -> Option<TargetStatsResponse> {
+        let targets = self.targets.lock();
+        let entry = targets.get(target)?;
+        let now_index = now.timestamp().div_euclid(BUCKET_SECS);
+
+        let windows = WINDOWS
+            .iter()
+            .map(|(name, seconds)| {
+                let first = now_index - seconds / BUCKET_SECS;
+                let mut counts = [0u64; LEVELS.len()];
+                for bucket in entry
+                    .buckets
+                    .iter()
+                    .filter(|b| b.index > first && b.index <= now_index)
+                {
+                    for (total, count) in counts.iter_mut().zip(bucket.counts) {
+                        *total += count;
+                    }
+                }
+
+                let total: u64 = counts.iter().sum();
+                let errors = counts[LEVELS.len() - 1];
+                WindowStats {
+                    window: name.to_string(),
+                    seconds: *seconds,
+                    total,
+                    by_level: LEVELS
+                        .iter()
+                        .zip(counts)
+                        .filter(|(_, count)| *count > 0)
+                        .map(|(level, count)| (level.to_string(), count))
+                        .collect(),
+                    rate: total as f64 / *seconds as f64,
+                    error_share: if total == 0 {
+                        0.0
+                    } else {
+                        errors as f64 / total as f64
+                    },
+                }
+            })
+            .collect();
+
+        Some(TargetStatsResponse {
+            target: target.to_string(),
+            last_seen: entry.last_seen,
+            last_error: entry.last_error.clone(),
+            windows,
+        })
+    }
+
+    /// Overview of every target as of `now`, sorted by target
+    pub fn overview(&self, now: DateTime<Utc>) -> Vec<TargetOverview> {
+        let targets = self.targets.lock();
+        let now_index = now.timestamp().div_euclid(BUCKET_SECS);
+        let first = now_index - OVERVIEW_RATE_SECS / BUCKET_SECS;
+        let mut overview: Vec<_> = targets
+            .iter()
+            .map(|(target, entry)| {
+                let recent: u64 = entry
+                    .buckets
+                    .iter()
+                    .filter(|b| b.index > first && b.index <= now_index)
+                    .flat_map(|b| b.counts)
+                    .sum();
+                TargetOverview {
+                    target: target.clone(),
+                    total: entry.totals.iter().sum(),
+                    by_level: LEVELS
+                        .iter()
+                        .zip(entry.totals)
+                        .filter(|(_, count)| *count > 0)
+                        .map(|(level, count)| (level.to_string(), count))
+                        .collect(),
+                    first_seen: entry.first_seen,
+                    last_seen: entry.last_seen,
+                    events_per_minute: recent as f64 * 60.0 / OVERVIEW_RATE_SECS as f64,
+                    last_error: entry.last_error.clone(),
+                }
+            })
+            .collect();
+        overview.sort_by(|a, b| a.target.cmp(&b.target));
+        overview
+    }
+
+    /// Events counted since start by target and level, levels without events
+    /// are omitted
+    pub fn totals(&self) -> Vec<(String, &'static str, u64)> {
+        let targets = self.targets.lock();
+        let mut totals: Vec<_> = targets
+            .iter()
+            .flat_map(|(target, entry)| {
+                LEVELS
+                    .iter()
+                    .zip(entry.totals)
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(level, count)| (target.clone(), *level, count))
+            })
+            .collect();
+        totals.sort();
+        totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn event(level: &str, target: &str, timestamp: DateTime<Utc>) -> LogEvent {
+        LogEvent {
+            target: target.into(),
+            timestamp,
+            ..LogEvent::internal(level, &format!("{} event", level), HashMap::new())
+        }
+    }
+
+    #[test]
+    fn test_windows_count_by_level() {
+        let stats = TargetStats::default();
+        let now = Utc::now();
+
+        stats.record(&event("INFO", "app", now));
+        stats.record(&event("ERROR", "app", now - Duration::seconds(5)));
+        stats.record(&event("INFO", "app", now - Duration::minutes(10)));
+        stats.record(&event("WARN", "other", now));
+
+        let result = stats.get("app", now).unwrap();
+        let one_minute = &result.windows[0];
+        assert_eq!(one_minute.window, "1m");
+        assert_eq!(one_minute.total, 2);
+        assert_eq!(one_minute.by_level.get("ERROR"), Some(&1));
+        assert_eq!(one_minute.error_share, 0.5);
+
+        let fifteen_minutes = &result.windows[2];
+        assert_eq!(fifteen_minutes.total, 3);
+        assert_eq!(fifteen_minutes.rate, 3.0 / 900.0);
+
+        assert_eq!(result.last_error.unwrap().message, "ERROR event");
+        assert_eq!(result.last_seen, Some(now));
+        assert!(stats.get("missing", now).is_none());
+    }
+
+    #[test]
+    fn test_totals_outlive_windows() {
+        let stats = TargetStats::default();
+        let now = Utc::now();
+
+        stats.record(&event("ERROR", "app", now - Duration::hours(2)));
+        stats.record(&event("ERROR", "app", now));
+        stats.record(&event("INFO", "app", now));
+
+        assert_eq!(
+            stats.totals(),
+            vec![
+                ("app".to_string(), "ERROR", 2),
+                ("app".to_string(), "INFO", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overview_of_all_targets() {
+        let stats = TargetStats::default();
+        let now = Utc::now();
+
+        stats.record(&event("INFO", "app", now - Duration::hours(2)));
+        stats.record(&event("ERROR", "app", now - Duration::minutes(1)));
+        stats.record(&event("INFO", "app", now));
+        stats.record(&event("WARN", "db", now));
+
+        let overview = stats.overview(now);
+        assert_eq!(overview.len(), 2);
+        let app = &overview[0];
+        assert_eq!(app.target, "app");
+        assert_eq!(app.total, 3);
+        assert_eq!(app.by_level.get("INFO"), Some(&2));
+        assert_eq!(app.first_seen, Some(now - Duration::hours(2)));
+        assert_eq!(app.last_seen, Some(now));
+        assert_eq!(app.events_per_minute, 2.0 / 5.0);
+        assert!(app.last_error.is_some());
+        assert!(overview[1].last_error.is_none());
+    }
+
+    #[test]
+    fn test_old_buckets_are_dropped() {
+        let stats = TargetStats::default();
+        let now = Utc::now();
+
+        stats.record(&event("INFO", "app", now - Duration::hours(2)));
+        stats.record(&event("INFO", "app", now));
+
+        let targets = stats.targets.lock();
+        assert_eq!(targets["app"].buckets.len(), 1);
+    }
+
+    #[test]
+    fn test_out_of_order_events_keep_buckets_sorted() {
+        let stats = TargetStats::default();
+        let now = Utc::now();
+
+        stats.record(&event("INFO", "app", now));
+        stats.record(&event("INFO", "app", now - Duration::minutes(3)));
+        stats.record(&event("INFO", "app", now - Duration::minutes(1)));
+
+        let targets = stats.targets.lock();
+        let indexes: Vec<_> = targets["app"].buckets.iter().map(|b| b.index).collect();
+        let mut sorted = indexes.clone();
+        sorted.sort();
+        assert_eq!(indexes, sorted);
+        assert_eq!(indexes.len(), 3);
+    }
+}
@@ -0,0 +1,105 @@
+//! Prefix tree of targets for per-target directives
+//!
+//! A directive for `my_app` applies to `my_app::orders::db` too, and the most
+//! specific directive wins. Walking the `::` segments of a target finds that
+//! directive in time proportional to the target's length, regardless of how
+//! many directives there are.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+struct Node<V> {
+    value: Option<V>,
+    children: HashMap<String, Node<V>>,
+}
+
+impl<V> Default for Node<V> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            children: HashMap::new(),
+        }
+    }
+}
+
+/// Values by target, looked up by the longest matching module prefix
+#[derive(Debug, Clone)]
+pub(crate) struct TargetTrie<V> {
+    root: Node<V>,
+}
+
+impl<V> Default for TargetTrie<V> {
+    fn default() -> Self {
+        Self {
+            root: Node::default(),
+        }
+    }
+}
+
+impl<V> TargetTrie<V> {
+    /// Set the value of a target, replacing an earlier one
+    pub fn insert(&mut self, target: &str, value: V) {
+        let mut node = &mut self.root;
+        for segment in target.split("::") {
+            node = node.children.entry(segment.to_string()).or_default();
+        }
+        node.value = Some(value);
+    }
+
+    /// Value of the longest target that is `target` or one of its parent modules
+    pub fn longest_match(&self, target: &str) -> Option<&V> {
+        let mut node = &self.root;
+        let mut found = None;
+        for segment in target.split("::") {
+            match node.children.get(segment) {
+                Some(child) => node = child,
+                None => break,
+            }
+            found = node.value.as_ref().or(found);
+        }
+        found
+    }
+}
+
+impl<V, T: AsRef<str>> FromIterator<(T, V)> for TargetTrie<V> {
+    fn from_iter<I: IntoIterator<Item = (T, V)>>(iter: I) -> Self {
+        let mut trie = Self::default();
+        for (target, value) in iter {
+            trie.insert(target.as_ref(), value);
+        }
+        trie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_match() {
+        let trie: TargetTrie<&str> = [
+            ("my_app", "debug"),
+            ("my_app::noisy", "off"),
+            ("hyper", "warn"),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(trie.longest_match("my_app"), Some(&"debug"));
+        assert_eq!(trie.longest_match("my_app::orders::db"), Some(&"debug"));
+        assert_eq!(trie.longest_match("my_app::noisy::deep"), Some(&"off"));
+        // Prefixes only match whole segments
+        assert_eq!(trie.longest_match("my_application"), None);
+        assert_eq!(trie.longest_match("my_app::noisy2"), Some(&"debug"));
+        assert_eq!(trie.longest_match("other"), None);
+    }
+
+    #[test]
+    fn test_insert_replaces() {
+        let mut trie = TargetTrie::default();
+        trie.insert("app::db", 1);
+        trie.insert("app::db", 2);
+        assert_eq!(trie.longest_match("app::db::pool"), Some(&2));
+        assert_eq!(trie.longest_match("app"), None);
+    }
+}
@@ -0,0 +1,288 @@
+//! Ingest-time transformation pipeline applied before events reach storage
+
+use crate::storage::LogEvent;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Replacement value used by the redact stage when none is configured
+const DEFAULT_REDACTION: &str = "[REDACTED]";
+
+/// A single stage of the ingest pipeline
+///
+/// Returning `None` drops the event, it will not be stored or broadcast.
+pub trait EventTransform: Send + Sync {
+    /// Human readable name shown by `GET /api/transforms`
+    fn name(&self) -> &str;
+
+    /// Transform the event, or return `None` to drop it
+    fn apply(&self, event: LogEvent) -> Option<LogEvent>;
+}
+
+/// Built-in transform stages that can be configured at runtime via the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformStage {
+    /// Add static fields to every event (existing fields are kept)
+    Enrich { fields: HashMap<String, String> },
+    /// Replace the values of the given event and span fields
+    Redact {
+        fields: Vec<String>,
+        #[serde(default)]
+        replacement: Option<String>,
+    },
+    /// Rename an event field
+    RenameField { from: String, to: String },
+    /// Drop events matching all of the given conditions
+    Drop {
+        /// Target prefix, "my_crate" matches "my_crate::module"
+        #[serde(default)]
+        target: Option<String>,
+        /// Drop events at or below this severity (e.g. "DEBUG" drops DEBUG and TRACE)
+        #[serde(default)]
+        max_level: Option<String>,
+        /// Case-insensitive substring of the message
+        #[serde(default)]
+        message_contains: Option<String>,
+    },
+    /// Run a sandboxed Rhai script which may modify `event` or return `false` to drop it
+    #[cfg(feature = "scripting")]
+    Script { source: String },
+}
+
+impl EventTransform for TransformStage {
+    fn name(&self) -> &str {
+        match self {
+            TransformStage::Enrich { .. } => "enrich",
+            TransformStage::Redact { .. } => "redact",
+            TransformStage::RenameField { .. } => "rename_field",
+            TransformStage::Drop { .. } => "drop",
+            #[cfg(feature = "scripting")]
+            TransformStage::Script { .. } => "script",
+        }
+    }
+
+    fn apply(&self, mut event: LogEvent) -> Option<LogEvent> {
+        match self {
+            TransformStage::Enrich { fields } => {
+                for (key, value) in fields {
+                    event
+                        .fields
+                        .entry(key.into())
+                        .or_insert_with(|| value.clone().into());
+                }
+                Some(event)
+            }
+            TransformStage::Redact {
+                fields,
+                replacement,
+            } => {
+                let replacement = replacement.as_deref().unwrap_or(DEFAULT_REDACTION);
+                for field in fields {
+                    if let Some(value) = event.fields.get_mut(field.as_str()) {
+                        *value = replacement.into();
+                    }
+                    if let Some(value) = event
+                        .span
+                        .as_mut()
+                        .and_then(|span| span.fields.get_mut(field))
+                    {
+                        *value = replacement.to_string();
+                    }
+                }
+                Some(event)
+            }
+            TransformStage::RenameField { from, to } => {
+                if let Some(value) = event.fields.remove(from.as_str()) {
+                    event.fields.insert(to.into(), value);
+                }
+                Some(event)
+            }
+            TransformStage::Drop {
+                target,
+                max_level,
+                message_contains,
+            } => {
+                let target_matches = target.as_ref().is_none_or(|t| {
+                    event.target == *t || event.target.starts_with(&format!("{}::", t))
+                });
+                let level_matches = max_level.as_ref().is_none_or(|l| {
+                    crate::storage::level_to_number(&event.level)
+                        <= crate::storage::level_to_number(l)
+                });
+                let message_matches = message_contains
+                    .as_ref()
+                    .is_none_or(|m| event.message.to_lowercase().contains(&m.to_lowercase()));
+
+                if target_matches && level_matches && message_matches {
+                    None
+                } else {
+                    Some(event)
+                }
+            }
+            #[cfg(feature = "scripting")]
+            TransformStage::Script { source } => crate::script::run_transform(source, event),
+        }
+    }
+}
+
+impl TransformStage {
+    /// Check that the stage is well-formed before it is installed
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            TransformStage::RenameField { from, to } if from.is_empty() || to.is_empty() => {
+                Err("rename_field requires non-empty 'from' and 'to'".to_string())
+            }
+            #[cfg(feature = "scripting")]
+            TransformStage::Script { source } => crate::script::compile(source).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Ordered chain of transforms shared between the capture layer and the API
+///
+/// Custom transforms registered in code always run first, followed by the
+/// built-in stages which can be replaced at runtime.
+#[derive(Clone, Default)]
+pub struct TransformPipeline {
+    custom: Arc<RwLock<Vec<Arc<dyn EventTransform>>>>,
+    stages: Arc<RwLock<Vec<TransformStage>>>,
+}
+
+impl TransformPipeline {
+    /// Create an empty pipeline that passes every event through unchanged
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a custom transform implemented in code
+    pub fn push_custom(&self, transform: Arc<dyn EventTransform>) {
+        self.custom.write().push(transform);
+    }
+
+    /// Names of the custom transforms, in order
+    pub fn custom_names(&self) -> Vec<String> {
+        self.custom
+            .read()
+            .iter()
+            .map(|t| t.name().to_string())
+            .collect()
+    }
+
+    /// Get the currently configured built-in stages
+    pub fn stages(&self) -> Vec<TransformStage> {
+        self.stages.read().clone()
+    }
+
+    /// Replace all built-in stages
+    pub fn set_stages(&self, stages: Vec<TransformStage>) {
+        *self.stages.write() = stages;
+    }
+
+    /// Run an event through all stages, returning `None` if it was dropped
+    pub fn apply(&self, event: LogEvent) -> Option<LogEvent> {
+        let custom = self.custom.read();
+        let stages = self.stages.read();
+
+        // Fast path: nothing configured
+        if custom.is_empty() && stages.is_empty() {
+            return Some(event);
+        }
+
+        let event = custom
+            .iter()
+            .try_fold(event, |event, transform| transform.apply(event))?;
+
+        stages
+            .iter()
+            .try_fold(event, |event, stage| stage.apply(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn create_test_event(level: &str, target: &str, message: &str) -> LogEvent {
+        let mut fields = HashMap::new();
+        fields.insert("password".into(), "hunter2".into());
+        fields.insert("user".into(), "alice".into());
+        LogEvent {
+            id: crate::id::new_ulid(),
+            seq: 0,
+            timestamp: Utc::now(),
+            level: level.into(),
+            target: target.into(),
+            message: message.to_string(),
+            fields,
+            span: None,
+            module_path: None,
+            file: None,
+            line: None,
+            source_url: None,
+            thread_name: None,
+            thread_id: None,
+            repeat_count: None,
+            is_error: false,
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_pipeline_passthrough() {
+        let pipeline = TransformPipeline::new();
+        let event = pipeline
+            .apply(create_test_event("INFO", "app", "hello"))
+            .unwrap();
+        assert_eq!(event.message, "hello");
+    }
+
+    #[test]
+    fn test_redact_rename_enrich() {
+        let pipeline = TransformPipeline::new();
+        pipeline.set_stages(vec![
+            TransformStage::Redact {
+                fields: vec!["password".to_string()],
+                replacement: None,
+            },
+            TransformStage::RenameField {
+                from: "user".to_string(),
+                to: "user_name".to_string(),
+            },
+            TransformStage::Enrich {
+                fields: HashMap::from([("env".to_string(), "dev".to_string())]),
+            },
+        ]);
+
+        let event = pipeline
+            .apply(create_test_event("INFO", "app", "login"))
+            .unwrap();
+        assert_eq!(event.fields["password"], DEFAULT_REDACTION);
+        assert_eq!(event.fields["user_name"], "alice");
+        assert!(!event.fields.contains_key("user"));
+        assert_eq!(event.fields["env"], "dev");
+    }
+
+    #[test]
+    fn test_drop_stage() {
+        let pipeline = TransformPipeline::new();
+        pipeline.set_stages(vec![TransformStage::Drop {
+            target: Some("noisy".to_string()),
+            max_level: Some("DEBUG".to_string()),
+            message_contains: None,
+        }]);
+
+        assert!(pipeline
+            .apply(create_test_event("TRACE", "noisy::heartbeat", "tick"))
+            .is_none());
+        assert!(pipeline
+            .apply(create_test_event("WARN", "noisy::heartbeat", "late"))
+            .is_some());
+        assert!(pipeline
+            .apply(create_test_event("TRACE", "app", "tick"))
+            .is_some());
+    }
+}
@@ -0,0 +1,237 @@
+//! Watchdog raising a warning when an expected target stops logging
+
+use crate::storage::{LogEvent, LogStorage};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often watched targets are checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Expectation that a target logs at least once every `max_silence_secs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetWatch {
+    /// Exact target, e.g. "orders::heartbeat"
+    pub target: String,
+    pub max_silence_secs: u64,
+}
+
+impl TargetWatch {
+    fn validate(&self) -> Result<(), String> {
+        if self.target.trim().is_empty() {
+            return Err("target must not be empty".to_string());
+        }
+        if self.max_silence_secs == 0 {
+            return Err("max_silence_secs must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Current state of a watch
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchStatus {
+    #[serde(flatten)]
+    pub watch: TargetWatch,
+    /// Last event of the target, `None` if it hasn't logged since the watch started
+    pub last_seen: Option<DateTime<Utc>>,
+    pub silent: bool,
+}
+
+struct WatchState {
+    watch: TargetWatch,
+    /// Silence is counted from here when the target never logged
+    registered_at: DateTime<Utc>,
+    silent: bool,
+}
+
+/// Watched targets, checked by a background task
+#[derive(Clone, Default)]
+pub struct Watchdog {
+    watches: Arc<RwLock<Vec<WatchState>>>,
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the watch of a target
+    pub fn upsert(&self, watch: TargetWatch) -> Result<TargetWatch, String> {
+        watch.validate()?;
+        let mut watches = self.watches.write();
+        match watches.iter_mut().find(|w| w.watch.target == watch.target) {
+            Some(existing) => existing.watch = watch.clone(),
+            None => watches.push(WatchState {
+                watch: watch.clone(),
+                registered_at: Utc::now(),
+                silent: false,
+            }),
+        }
+        Ok(watch)
+    }
+
+    /// Stop watching a target, returns whether it was watched
+    pub fn remove(&self, target: &str) -> bool {
+        let mut watches = self.watches.write();
+        let before = watches.len();
+        watches.retain(|w| w.watch.target != target);
+        watches.len() != before
+    }
+
+    /// All watches with their current state
+    pub fn statuses(&self, storage: &LogStorage) -> Vec<WatchStatus> {
+        self.watches
+            .read()
+            .iter()
+            .map(|state| WatchStatus {
+                watch: state.watch.clone(),
+                last_seen: last_seen(storage, &state.watch.target),
+                silent: state.silent,
+            })
+            .collect()
+    }
+
+    /// Update watch states as of `now`, returns events for targets that went
+    /// silent or resumed
+    fn check(&self, storage: &LogStorage, now: DateTime<Utc>) -> Vec<LogEvent> {
+        let mut events = Vec::new();
+        for state in self.watches.write().iter_mut() {
+            let target = &state.watch.target;
+            let last_seen = last_seen(storage, target);
+            let since = last_seen.unwrap_or(state.registered_at);
+            let silent_secs = (now - since).num_seconds().max(0) as u64;
+            let silent = silent_secs > state.watch.max_silence_secs;
+            if silent == state.silent {
+                continue;
+            }
+            state.silent = silent;
+
+            let mut fields = HashMap::from([
+                ("watched_target".to_string(), target.clone()),
+                (
+                    "max_silence_secs".to_string(),
+                    state.watch.max_silence_secs.to_string(),
+                ),
+            ]);
+            if let Some(last_seen) = last_seen {
+                fields.insert("last_seen".to_string(), last_seen.to_rfc3339());
+            }
+
+            events.push(if silent {
+                fields.insert("silent_secs".to_string(), silent_secs.to_string());
+                LogEvent {
+                    level: "WARN".into(),
+                    ..LogEvent::lifecycle(
+                        "target_silent",
+                        &format!(
+                            "Target {} has not logged for {}s (expected every {}s)",
+                            target, silent_secs, state.watch.max_silence_secs
+                        ),
+                        fields,
+                    )
+                }
+            } else {
+                LogEvent::lifecycle(
+                    "target_resumed",
+                    &format!("Target {} is logging again", target),
+                    fields,
+                )
+            });
+        }
+        events
+    }
+}
+
+/// Last event of a target, from the rolling stats so evicted events count too
+fn last_seen(storage: &LogStorage, target: &str) -> Option<DateTime<Utc>> {
+    storage
+        .target_stats(target)
+        .and_then(|stats| stats.last_seen)
+}
+
+/// Check watched targets forever
+pub(crate) async fn run(storage: LogStorage, watchdog: Watchdog) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        for event in watchdog.check(&storage, Utc::now()) {
+            storage.push(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(timestamp: DateTime<Utc>) -> LogEvent {
+        LogEvent {
+            target: "orders::heartbeat".into(),
+            timestamp,
+            ..LogEvent::internal("INFO", "tick", HashMap::new())
+        }
+    }
+
+    #[test]
+    fn test_silent_target_warns_once_and_resumes() {
+        let storage = LogStorage::new();
+        let watchdog = Watchdog::new();
+        watchdog
+            .upsert(TargetWatch {
+                target: "orders::heartbeat".to_string(),
+                max_silence_secs: 60,
+            })
+            .unwrap();
+
+        let now = Utc::now();
+        storage.push(heartbeat(now - chrono::Duration::seconds(30)));
+        assert!(watchdog.check(&storage, now).is_empty());
+
+        let later = now + chrono::Duration::seconds(60);
+        let events = watchdog.check(&storage, later);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, "WARN");
+        assert_eq!(events[0].fields["lifecycle"], "target_silent");
+        // Still silent, no repeated warning
+        assert!(watchdog.check(&storage, later).is_empty());
+        assert!(watchdog.statuses(&storage)[0].silent);
+
+        storage.push(heartbeat(later));
+        let events = watchdog.check(&storage, later);
+        assert_eq!(events[0].fields["lifecycle"], "target_resumed");
+        assert!(!watchdog.statuses(&storage)[0].silent);
+    }
+
+    #[test]
+    fn test_never_seen_target_counts_from_registration() {
+        let storage = LogStorage::new();
+        let watchdog = Watchdog::new();
+        watchdog
+            .upsert(TargetWatch {
+                target: "order_processor".to_string(),
+                max_silence_secs: 10,
+            })
+            .unwrap();
+
+        assert!(watchdog.check(&storage, Utc::now()).is_empty());
+        let events = watchdog.check(&storage, Utc::now() + chrono::Duration::seconds(11));
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].fields.contains_key("last_seen"));
+    }
+
+    #[test]
+    fn test_invalid_watch() {
+        let watchdog = Watchdog::new();
+        let watch = TargetWatch {
+            target: "app".to_string(),
+            max_silence_secs: 0,
+        };
+        assert!(watchdog.upsert(watch).is_err());
+    }
+}